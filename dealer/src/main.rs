@@ -1,119 +1,392 @@
-use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
 
 use argh::FromArgs;
+use matcher::{
+    circuits, with_num_bits, DEFAULT_MAX_PREFERENCE_VALUE, DEFAULT_NUM_BITS,
+    DEFAULT_STATISTICAL_SECURITY,
+};
 use mpc::{
-    fields::{Mersenne127, Mersenne61},
-    spdz::{PrecomputedSpdzData, SpdzShare},
-    MpcField, MpcShare,
+    estimator::ResourceUsage,
+    fields::{self, FieldType, Goldilocks, Mersenne127, Mersenne31, Mersenne61},
+    spdz::{
+        self, ConsumptionRecord, MascotCounts, PrecomputedSpdzData, PrecomputedSpdzHeader,
+        SeededShareGenerator, SeededSpdzData, SpdzBatch, SpdzDataKind, SpdzSeed, SpdzShare,
+    },
+    transport::{self, NetworkConfig, TransportError},
+    MpcField,
 };
 use rand::{prelude::StdRng, Rng, SeedableRng};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-/// Field type for preprocessing.
-enum FieldType {
-    Mersenne61,
-    Mersenne127,
+/// SPDZ offline preprocessing tool.
+#[derive(FromArgs)]
+struct Options {
+    #[argh(subcommand)]
+    command: Command,
 }
 
-impl FromStr for FieldType {
-    type Err = &'static str;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "61" => Ok(FieldType::Mersenne61),
-            "127" => Ok(FieldType::Mersenne127),
-            _ => Err("Unsupported field type. Available options: 61, 127"),
-        }
-    }
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Generate(GenerateOptions),
+    Serve(ServeOptions),
+    Verify(VerifyOptions),
+    Inspect(InspectOptions),
 }
 
-/// SPDZ offline preprocessing tool.
+/// Generate data for all parties as a trusted dealer, or for this party together with its peers using MASCOT.
 #[derive(FromArgs)]
-struct Options {
-    /// number of parties participating in protocol
+#[argh(subcommand, name = "generate")]
+struct GenerateOptions {
+    /// number of parties participating in protocol (trusted dealer only, MASCOT takes it from network configuration)
     #[argh(option)]
-    parties: usize,
+    parties: Option<usize>,
 
     /// output path pattern ('#' is replaced with party ID)
     #[argh(option)]
     output: String,
 
-    /// target field
-    #[argh(option, default = "FieldType::Mersenne127")]
-    field: FieldType,
+    /// write data in streaming format, which matcher reads incrementally instead of loading it into memory
+    #[argh(switch)]
+    streaming: bool,
+
+    /// derive shares of parties other than 0 from seeds, so that only party 0 needs full data (with corrections)
+    #[argh(switch)]
+    compressed: bool,
+
+    /// target field (default 127), overrides network configuration of MASCOT
+    #[argh(option)]
+    field: Option<FieldType>,
+
+    /// number of independent MAC keys (1-4), more keys compensate for weaker security of small fields
+    #[argh(option, default = "1")]
+    mac_keys: usize,
 
     /// number of beaver triples to be generated
     #[argh(option)]
-    beaver_triples: usize,
+    beaver_triples: Option<usize>,
+
+    /// number of square pairs (a, a^2) to be generated
+    #[argh(option)]
+    square_pairs: Option<usize>,
+
+    /// number of inverse pairs (r, r^-1) to be generated
+    #[argh(option)]
+    inverse_pairs: Option<usize>,
 
     /// number of random bits to be generated
     #[argh(option)]
-    random_bits: usize,
+    random_bits: Option<usize>,
+
+    /// number of random bit masks of given width to be generated, as 'width:count' (can be repeated)
+    #[argh(option)]
+    bit_masks: Vec<BitMaskCount>,
 
     /// number of input masks to be generated for each party
     #[argh(option)]
+    input_masks: Option<usize>,
+
+    /// number of output masks (for private openings) to be generated for each party
+    #[argh(option)]
+    output_masks: Option<usize>,
+
+    /// generate exactly the amounts needed by matcher for preference vectors of given length
+    /// (explicitly specified amounts take precedence)
+    #[argh(option)]
+    estimate_for_matching: Option<usize>,
+
+    /// number of bits for field-embedded integers used by matcher (for estimation)
+    #[argh(option, default = "DEFAULT_NUM_BITS")]
+    matching_bits: usize,
+
+    /// maximum value of preference vector element used by matcher (for estimation)
+    #[argh(option, default = "DEFAULT_MAX_PREFERENCE_VALUE")]
+    matching_max_preference: u64,
+
+    /// statistical security in bits used by matcher (for estimation)
+    #[argh(option, default = "DEFAULT_STATISTICAL_SECURITY")]
+    matching_statistical_security: usize,
+
+    #[argh(subcommand)]
+    protocol: Option<Protocol>,
+}
+
+/// Protocol generating the data instead of a trusted dealer.
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Protocol {
+    Mascot(MascotOptions),
+}
+
+/// Generate data for this party together with its peers using MASCOT protocol, so that nobody learns the MAC key.
+/// All parties must request the same amounts of data with the same number of MAC keys.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "mascot")]
+struct MascotOptions {
+    /// path to network configuration file
+    #[argh(option)]
+    config: String,
+
+    /// current party ID
+    #[argh(option)]
+    id: usize,
+
+    /// path to private TLS key
+    #[argh(option)]
+    private_key: String,
+
+    /// path to TLS certificate of this party (needed if network configuration pins it by fingerprint)
+    #[argh(option)]
+    certificate: Option<String>,
+}
+
+/// Serve fresh data to parties over network as a trusted dealer, one session after another.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "serve")]
+struct ServeOptions {
+    /// path to network configuration file (dealer address and certificate are taken from it)
+    #[argh(option)]
+    config: String,
+
+    /// path to private TLS key of dealer
+    #[argh(option)]
+    private_key: String,
+
+    /// path to TLS certificate of dealer (needed if network configuration pins it by fingerprint)
+    #[argh(option)]
+    certificate: Option<String>,
+
+    /// target field, overrides network configuration
+    #[argh(option)]
+    field: Option<FieldType>,
+
+    /// number of independent MAC keys (1-4), must match the number expected by parties
+    #[argh(option, default = "1")]
+    mac_keys: usize,
+
+    /// number of beaver triples, square pairs, inverse pairs or random bits generated for each request
+    #[argh(option, default = "10000")]
+    batch_size: usize,
+
+    /// number of bit masks generated for each request (0 makes parties assemble bit masks from random bits)
+    #[argh(option, default = "1000")]
+    bit_masks_batch_size: usize,
+
+    /// number of input or output masks generated for each request
+    #[argh(option, default = "100")]
+    input_masks_batch_size: usize,
+}
+
+/// Check that precomputed data of all parties is consistent by opening all shared values.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "verify")]
+struct VerifyOptions {
+    /// input path pattern ('#' is replaced with party ID)
+    #[argh(option)]
+    input: String,
+}
+
+/// Print description of precomputed data of a single party and amounts of data remaining for future runs.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "inspect")]
+struct InspectOptions {
+    /// input path
+    #[argh(option)]
+    input: String,
+}
+
+/// Number of random bit masks of a single width.
+struct BitMaskCount {
+    width: usize,
+    count: usize,
+}
+
+impl FromStr for BitMaskCount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, count) = s
+            .split_once(':')
+            .ok_or_else(|| "Expected 'width:count'".to_string())?;
+        Ok(Self {
+            width: width.parse().map_err(|_| "Invalid bit mask width")?,
+            count: count.parse().map_err(|_| "Invalid number of bit masks")?,
+        })
+    }
+}
+
+/// Amounts of data to be generated.
+struct DataCounts {
+    beaver_triples: usize,
+    square_pairs: usize,
+    inverse_pairs: usize,
+    random_bits: usize,
+    /// Number of bit masks of each width.
+    bit_masks: BTreeMap<usize, usize>,
     input_masks: usize,
+    output_masks: usize,
 }
 
+/// Shares of a single value for all parties.
+type Sharing<T, const K: usize> = Vec<SpdzShare<T, K>>;
+
 /// Generator of random SPDZ sharings.
-struct ShareGenerator<T, R> {
+struct ShareGenerator<T, R, const K: usize> {
     num_parties: usize,
-    auth_key: T,
+    auth_key: [T; K],
     rng: R,
+    /// Seeds of all parties, if shares of parties other than 0 are derived from them (seed of party 0 is unused).
+    seeds: Option<Vec<SpdzSeed>>,
+    seeded_shares: HashMap<(usize, SpdzDataKind), SeededShareGenerator<T, K>>,
 }
 
-impl<T, R> ShareGenerator<T, R>
+impl<T, R, const K: usize> ShareGenerator<T, R, K>
 where
     T: MpcField,
     R: Rng,
 {
-    /// Generate random sharing of given value.
-    fn share(&mut self, value: T) -> Vec<SpdzShare<T>> {
-        let mut shares: Vec<_> = (1..self.num_parties)
-            .map(|_| SpdzShare {
-                value: T::random(&mut self.rng),
-                mac: T::random(&mut self.rng),
-            })
-            .collect();
-        let sum = shares.iter().fold(SpdzShare::zero(), |acc, &x| acc + x);
-        shares.push(SpdzShare {
-            value: value - sum.value,
-            mac: value * self.auth_key - sum.mac,
-        });
+    /// Generate random sharing of given value. Share of party 0 corrects random shares of other parties.
+    fn share(&mut self, value: T, kind: SpdzDataKind) -> Vec<SpdzShare<T, K>> {
+        let mut shares = vec![SpdzShare::from_plain(value, self.auth_key, 0)];
+        for party_id in 1..self.num_parties {
+            let share = match &self.seeds {
+                Some(seeds) => self
+                    .seeded_shares
+                    .entry((party_id, kind))
+                    .or_insert_with(|| SeededShareGenerator::new(&seeds[party_id], kind))
+                    .next()
+                    .unwrap(),
+                None => SpdzShare {
+                    value: T::random(&mut self.rng),
+                    mac: random_array(&mut self.rng),
+                },
+            };
+            shares[0] -= share;
+            shares.push(share);
+        }
         shares
     }
 
     /// Generate random sharing of random value.
-    fn share_random(&mut self) -> (Vec<SpdzShare<T>>, T) {
+    fn share_random(&mut self, kind: SpdzDataKind) -> (Vec<SpdzShare<T, K>>, T) {
         let value = T::random(&mut self.rng);
-        (self.share(value), value)
+        (self.share(value, kind), value)
     }
 
     /// Generate random sharing of random bit.
-    fn share_random_bit(&mut self) -> (Vec<SpdzShare<T>>, T) {
+    fn share_random_bit(&mut self) -> (Vec<SpdzShare<T, K>>, T) {
         let value = T::from(self.rng.gen_range(0..=1));
-        (self.share(value), value)
+        (self.share(value, SpdzDataKind::RandomBits), value)
+    }
+
+    /// Generate random sharing of random `T::SAFE_BITS`-bit integer, its remainder modulo 2^width
+    /// and its first `width` bits.
+    fn share_random_bit_mask(
+        &mut self,
+        width: usize,
+    ) -> (Sharing<T, K>, Sharing<T, K>, Vec<Sharing<T, K>>) {
+        let kind = SpdzDataKind::BitMasks(width);
+        let bits: Vec<_> = (0..T::SAFE_BITS)
+            .map(|_| T::from(self.rng.gen_range(0..=1)))
+            .collect();
+        let compose = |bits: &[T]| {
+            bits.iter()
+                .rev()
+                .fold(T::zero(), |acc, &x| acc.double() + x)
+        };
+        let mask = self.share(compose(&bits), kind);
+        let low_part = self.share(compose(&bits[..width]), kind);
+        let low_bits = bits[..width].iter().map(|&x| self.share(x, kind)).collect();
+        (mask, low_part, low_bits)
+    }
+
+    /// Returns true if shares of given party are stored in its data (and not derived from seed).
+    fn stores_shares(&self, party_id: usize) -> bool {
+        self.seeds.is_none() || party_id == 0
     }
 
     /// Generate beaver triples and add them to precomputed data table.
-    fn fill_beaver_triples(&mut self, data: &mut [PrecomputedSpdzData<T>], count: usize) {
+    fn fill_beaver_triples(&mut self, data: &mut [PrecomputedSpdzData<T, K>], count: usize) {
         for _ in 0..count {
-            let (shares_a, a) = self.share_random();
-            let (shares_b, b) = self.share_random();
-            let shares_ab = self.share(a * b);
+            let (shares_a, a) = self.share_random(SpdzDataKind::BeaverTriples);
+            let (shares_b, b) = self.share_random(SpdzDataKind::BeaverTriples);
+            let shares_ab = self.share(a * b, SpdzDataKind::BeaverTriples);
             for (i, party_data) in data.iter_mut().enumerate() {
-                party_data
-                    .beaver_triples
-                    .push((shares_a[i], shares_b[i], shares_ab[i]));
+                if self.stores_shares(i) {
+                    party_data
+                        .beaver_triples
+                        .push((shares_a[i], shares_b[i], shares_ab[i]));
+                }
+            }
+        }
+    }
+
+    /// Generate square pairs and add them to precomputed data table.
+    fn fill_square_pairs(&mut self, data: &mut [PrecomputedSpdzData<T, K>], count: usize) {
+        for _ in 0..count {
+            let (shares_a, a) = self.share_random(SpdzDataKind::SquarePairs);
+            let shares_a_squared = self.share(a.square(), SpdzDataKind::SquarePairs);
+            for (i, party_data) in data.iter_mut().enumerate() {
+                if self.stores_shares(i) {
+                    party_data
+                        .square_pairs
+                        .push((shares_a[i], shares_a_squared[i]));
+                }
+            }
+        }
+    }
+
+    /// Generate inverse pairs and add them to precomputed data table.
+    fn fill_inverse_pairs(&mut self, data: &mut [PrecomputedSpdzData<T, K>], count: usize) {
+        for _ in 0..count {
+            let (r, r_inverse) = fields::random_inverse_pair(&mut self.rng);
+            let shares_r = self.share(r, SpdzDataKind::InversePairs);
+            let shares_r_inverse = self.share(r_inverse, SpdzDataKind::InversePairs);
+            for (i, party_data) in data.iter_mut().enumerate() {
+                if self.stores_shares(i) {
+                    party_data
+                        .inverse_pairs
+                        .push((shares_r[i], shares_r_inverse[i]));
+                }
             }
         }
     }
 
     /// Generate random bits and add them to precomputed data table.
-    fn fill_random_bits(&mut self, data: &mut [PrecomputedSpdzData<T>], count: usize) {
+    fn fill_random_bits(&mut self, data: &mut [PrecomputedSpdzData<T, K>], count: usize) {
         for _ in 0..count {
             let (shares, _) = self.share_random_bit();
             for (i, party_data) in data.iter_mut().enumerate() {
-                party_data.random_bits.push(shares[i]);
+                if self.stores_shares(i) {
+                    party_data.random_bits.push(shares[i]);
+                }
+            }
+        }
+    }
+
+    /// Generate bit masks of given width and add them to precomputed data table.
+    fn fill_bit_masks(
+        &mut self,
+        data: &mut [PrecomputedSpdzData<T, K>],
+        width: usize,
+        count: usize,
+    ) {
+        for _ in 0..count {
+            let (mask, low_part, low_bits) = self.share_random_bit_mask(width);
+            for (i, party_data) in data.iter_mut().enumerate() {
+                if self.stores_shares(i) {
+                    party_data.bit_masks.entry(width).or_default().push((
+                        mask[i],
+                        low_part[i],
+                        low_bits.iter().map(|bit| bit[i]).collect(),
+                    ));
+                }
             }
         }
     }
@@ -121,77 +394,778 @@ where
     /// Generate input masks for given party and add them to precomputed data table.
     fn fill_input_masks_for(
         &mut self,
-        data: &mut [PrecomputedSpdzData<T>],
+        data: &mut [PrecomputedSpdzData<T, K>],
         party_id: usize,
         count: usize,
     ) {
         for _ in 0..count {
-            let (shares, plain) = self.share_random();
+            let (shares, plain) = self.share_random(SpdzDataKind::InputMasks(party_id));
             for (i, party_data) in data.iter_mut().enumerate() {
-                party_data.input_masks[party_id].push(shares[i]);
+                if self.stores_shares(i) {
+                    party_data.input_masks[party_id].push(shares[i]);
+                }
             }
             data[party_id].input_masks_plain.push(plain);
         }
     }
+
+    /// Generate output masks for given party and add them to precomputed data table.
+    fn fill_output_masks_for(
+        &mut self,
+        data: &mut [PrecomputedSpdzData<T, K>],
+        party_id: usize,
+        count: usize,
+    ) {
+        for _ in 0..count {
+            let (shares, plain) = self.share_random(SpdzDataKind::OutputMasks(party_id));
+            for (i, party_data) in data.iter_mut().enumerate() {
+                if self.stores_shares(i) {
+                    party_data.output_masks[party_id].push(shares[i]);
+                }
+            }
+            data[party_id].output_masks_plain.push(plain);
+        }
+    }
+}
+
+/// Generate array of random field elements.
+fn random_array<T: MpcField, const K: usize>(rng: &mut impl Rng) -> [T; K] {
+    let mut array = [T::zero(); K];
+    for x in &mut array {
+        *x = T::random(&mut *rng);
+    }
+    array
+}
+
+/// Determine amounts of data to be generated.
+fn data_counts<T: MpcField>(num_parties: usize, options: &GenerateOptions) -> DataCounts {
+    let estimate = options.estimate_for_matching.map(|num_dims| {
+        if let Err(err) = matcher::check_parameters::<T>(
+            options.matching_bits,
+            options.matching_statistical_security,
+            num_parties,
+            num_dims,
+            options.matching_max_preference,
+        ) {
+            panic!("{}", err);
+        }
+
+        println!("Estimating resources for matching with {num_dims}-element preference vectors...");
+        with_num_bits!(options.matching_bits, N => {
+            futures::executor::block_on(circuits::estimate_matching_resources::<T, N>(
+                num_parties,
+                num_dims,
+                options.matching_max_preference,
+            ))
+        })
+    });
+
+    let bit_masks = if options.bit_masks.is_empty() {
+        estimate
+            .as_ref()
+            .map(|x| x.num_bit_masks.clone())
+            .unwrap_or_default()
+    } else {
+        options
+            .bit_masks
+            .iter()
+            .map(|x| (x.width, x.count))
+            .collect()
+    };
+    if bit_masks.keys().any(|&width| width > T::SAFE_BITS) {
+        panic!("Bit mask width cannot exceed {} bits", T::SAFE_BITS);
+    }
+
+    let count_or_estimate = |count: Option<usize>, estimate_fn: fn(&ResourceUsage) -> usize| {
+        count
+            .or_else(|| estimate.as_ref().map(estimate_fn))
+            .expect("Specify amounts of data explicitly or use --estimate-for-matching")
+    };
+
+    DataCounts {
+        beaver_triples: count_or_estimate(options.beaver_triples, |x| x.num_beaver_triples),
+        square_pairs: count_or_estimate(options.square_pairs, |x| x.num_square_pairs),
+        inverse_pairs: count_or_estimate(options.inverse_pairs, |x| x.num_inverse_pairs),
+        random_bits: count_or_estimate(options.random_bits, |x| x.num_random_bits),
+        bit_masks,
+        input_masks: count_or_estimate(options.input_masks, |x| {
+            x.num_input_masks.iter().copied().max().unwrap_or(0)
+        }),
+        output_masks: count_or_estimate(options.output_masks, |x| {
+            x.num_output_masks.iter().copied().max().unwrap_or(0)
+        }),
+    }
 }
 
-/// Generate authorizaton key and sharings.
-fn generate<T, R>(mut rng: R, options: &Options) -> Vec<PrecomputedSpdzData<T>>
+/// Generate authorization keys of new session. Returns empty data of each party and generator of sharings,
+/// which derives shares of parties other than 0 from seeds if data is compressed.
+fn setup<T, R, const K: usize>(
+    mut rng: R,
+    num_parties: usize,
+    compressed: bool,
+) -> (Vec<PrecomputedSpdzData<T, K>>, ShareGenerator<T, R, K>)
 where
-    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+    T: MpcField,
     R: Rng,
 {
-    let mut data: Vec<PrecomputedSpdzData<T>> = (0..options.parties)
+    let session_id = rng.gen();
+    let data: Vec<PrecomputedSpdzData<T, K>> = (0..num_parties)
         .map(|id| PrecomputedSpdzData {
-            num_parties: options.parties,
+            num_parties,
             party_id: id,
-            auth_key: T::random(&mut rng),
-            input_masks: (0..options.parties).map(|_| Vec::new()).collect(),
+            session_id,
+            auth_key: random_array(&mut rng),
+            input_masks: (0..num_parties).map(|_| Vec::new()).collect(),
+            output_masks: (0..num_parties).map(|_| Vec::new()).collect(),
             ..Default::default()
         })
         .collect();
 
-    let auth_key = data.iter().fold(T::zero(), |acc, x| acc + x.auth_key);
+    let mut auth_key = [T::zero(); K];
+    for party_data in &data {
+        for (key, key_share) in auth_key.iter_mut().zip(party_data.auth_key) {
+            *key += key_share;
+        }
+    }
 
-    let mut share_gen = ShareGenerator {
-        num_parties: options.parties,
+    let seeds = compressed.then(|| (0..num_parties).map(|_| rng.gen()).collect());
+    let share_gen = ShareGenerator {
+        num_parties,
         auth_key,
         rng,
+        seeds,
+        seeded_shares: HashMap::new(),
     };
+    (data, share_gen)
+}
+
+/// Generate authorizaton keys and sharings. Returns seeds of parties if data is compressed.
+fn generate<T, R, const K: usize>(
+    rng: R,
+    num_parties: usize,
+    counts: &DataCounts,
+    compressed: bool,
+) -> (Vec<PrecomputedSpdzData<T, K>>, Option<Vec<SpdzSeed>>)
+where
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+    R: Rng,
+{
+    let (mut data, mut share_gen) = setup(rng, num_parties, compressed);
 
-    println!("Generating {} beaver triples...", options.beaver_triples);
-    share_gen.fill_beaver_triples(&mut data, options.beaver_triples);
+    println!("Generating {} beaver triples...", counts.beaver_triples);
+    share_gen.fill_beaver_triples(&mut data, counts.beaver_triples);
 
-    println!("Generating {} random bits...", options.random_bits);
-    share_gen.fill_random_bits(&mut data, options.random_bits);
+    println!("Generating {} square pairs...", counts.square_pairs);
+    share_gen.fill_square_pairs(&mut data, counts.square_pairs);
 
-    println!("Generating {} input masks...", options.input_masks);
-    for party_id in 0..options.parties {
-        share_gen.fill_input_masks_for(&mut data, party_id, options.input_masks);
+    println!("Generating {} inverse pairs...", counts.inverse_pairs);
+    share_gen.fill_inverse_pairs(&mut data, counts.inverse_pairs);
+
+    println!("Generating {} random bits...", counts.random_bits);
+    share_gen.fill_random_bits(&mut data, counts.random_bits);
+
+    for (&width, &count) in &counts.bit_masks {
+        println!("Generating {count} bit masks of width {width}...");
+        share_gen.fill_bit_masks(&mut data, width, count);
     }
 
-    data
+    println!("Generating {} input masks...", counts.input_masks);
+    for party_id in 0..num_parties {
+        share_gen.fill_input_masks_for(&mut data, party_id, counts.input_masks);
+    }
+
+    println!("Generating {} output masks...", counts.output_masks);
+    for party_id in 0..num_parties {
+        share_gen.fill_output_masks_for(&mut data, party_id, counts.output_masks);
+    }
+
+    (data, share_gen.seeds)
 }
 
-fn run<T>(options: Options)
+/// Save precomputed data of a single party in selected format.
+fn save<T, const K: usize>(data: &PrecomputedSpdzData<T, K>, path: String, streaming: bool)
 where
     T: MpcField + Serialize + for<'a> Deserialize<'a>,
 {
-    println!("Generating data for {} parties", options.parties);
+    if streaming {
+        data.save_file_streaming(path).unwrap();
+    } else {
+        data.save_file(path).unwrap();
+    }
+}
+
+fn run_generate<T, const K: usize>(options: GenerateOptions)
+where
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+{
+    let num_parties = options
+        .parties
+        .expect("Specify number of parties with --parties");
+    let counts = data_counts::<T>(num_parties, &options);
+
+    println!("Generating data for {num_parties} parties with {K} MAC keys");
     let rng = StdRng::from_entropy();
-    let data = generate::<T, _>(rng, &options);
+    let (data, seeds) = generate::<T, _, K>(rng, num_parties, &counts, options.compressed);
 
     println!("Saving...");
     for (id, party_data) in data.into_iter().enumerate() {
         let output_path = options.output.replace("#", &format!("{id}"));
-        party_data.save_file(output_path).unwrap();
+        match &seeds {
+            Some(seeds) if id != 0 => {
+                let seeded_data = SeededSpdzData {
+                    num_parties: party_data.num_parties,
+                    party_id: id,
+                    session_id: party_data.session_id,
+                    auth_key: party_data.auth_key,
+                    seed: seeds[id],
+                    num_beaver_triples: counts.beaver_triples,
+                    num_square_pairs: counts.square_pairs,
+                    num_inverse_pairs: counts.inverse_pairs,
+                    num_random_bits: counts.random_bits,
+                    num_bit_masks: counts.bit_masks.clone(),
+                    num_input_masks: vec![counts.input_masks; num_parties],
+                    input_masks_plain: party_data.input_masks_plain,
+                    num_output_masks: vec![counts.output_masks; num_parties],
+                    output_masks_plain: party_data.output_masks_plain,
+                };
+                seeded_data.save_file(output_path).unwrap();
+            }
+            _ => save(&party_data, output_path, options.streaming),
+        }
     }
 }
 
-fn main() {
-    let options: Options = argh::from_env();
-    match options.field {
-        FieldType::Mersenne61 => run::<Mersenne61>(options),
-        FieldType::Mersenne127 => run::<Mersenne127>(options),
+fn run_generate_with_mac_keys<T>(options: GenerateOptions)
+where
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+{
+    match options.mac_keys {
+        1 => run_generate::<T, 1>(options),
+        2 => run_generate::<T, 2>(options),
+        3 => run_generate::<T, 3>(options),
+        4 => run_generate::<T, 4>(options),
+        _ => panic!("Unsupported number of MAC keys"),
+    }
+}
+
+async fn run_mascot<T, const K: usize>(
+    options: GenerateOptions,
+    mascot: MascotOptions,
+    config: NetworkConfig,
+) where
+    T: MpcField + Serialize + DeserializeOwned + Unpin,
+{
+    let num_parties = config.parties.len();
+    if options
+        .parties
+        .is_some_and(|parties| parties != num_parties)
+    {
+        panic!("Number of parties doesn't match network configuration");
+    }
+    if options.compressed {
+        panic!("Compressed data can be generated only by a trusted dealer");
+    }
+    let counts = data_counts::<T>(num_parties, &options);
+
+    let private_key = transport::load_private_key(mascot.private_key).expect("Invalid private key");
+
+    println!("Waiting for peers...");
+    let connection = transport::connect_multiparty(&config, private_key, mascot.id)
+        .await
+        .expect("Multiparty connection failed");
+
+    println!(
+        "Generating data for {} parties with {} MAC keys: {} beaver triples, {} square pairs, {} inverse pairs, \
+         {} random bits, {} bit masks, {} input masks, {} output masks...",
+        num_parties,
+        K,
+        counts.beaver_triples,
+        counts.square_pairs,
+        counts.inverse_pairs,
+        counts.random_bits,
+        counts.bit_masks.values().sum::<usize>(),
+        counts.input_masks,
+        counts.output_masks
+    );
+    let mascot_counts = MascotCounts {
+        beaver_triples: counts.beaver_triples,
+        square_pairs: counts.square_pairs,
+        inverse_pairs: counts.inverse_pairs,
+        random_bits: counts.random_bits,
+        bit_masks: counts.bit_masks.clone(),
+        input_masks: counts.input_masks,
+        output_masks: counts.output_masks,
+    };
+    let data: PrecomputedSpdzData<T, K> = spdz::generate_with_mascot(connection, mascot_counts)
+        .await
+        .expect("MASCOT protocol failed");
+
+    // Random bits (and bit masks assembled from them) are generated from random squares and inverse pairs
+    // from random products, so a few of them can be lost. All parties see the same shortfall and fail together.
+    let mut shortfalls = Vec::new();
+    if data.random_bits.len() < counts.random_bits {
+        shortfalls.push(format!(
+            "{} of {} random bits",
+            data.random_bits.len(),
+            counts.random_bits
+        ));
+    }
+    for (width, &count) in &counts.bit_masks {
+        let generated = data.bit_masks.get(width).map_or(0, |masks| masks.len());
+        if generated < count {
+            shortfalls.push(format!("{generated} of {count} bit masks of width {width}"));
+        }
+    }
+    if data.inverse_pairs.len() < counts.inverse_pairs {
+        shortfalls.push(format!(
+            "{} of {} inverse pairs",
+            data.inverse_pairs.len(),
+            counts.inverse_pairs
+        ));
+    }
+    if !shortfalls.is_empty() {
+        println!("Generated only {}, run MASCOT again", shortfalls.join(", "));
+        std::process::exit(1);
+    }
+
+    println!("Saving...");
+    let output_path = options.output.replace("#", &format!("{}", mascot.id));
+    save(&data, output_path, options.streaming);
+}
+
+async fn run_mascot_with_mac_keys<T>(
+    options: GenerateOptions,
+    mascot: MascotOptions,
+    config: NetworkConfig,
+) where
+    T: MpcField + Serialize + DeserializeOwned + Unpin,
+{
+    match options.mac_keys {
+        1 => run_mascot::<T, 1>(options, mascot, config).await,
+        2 => run_mascot::<T, 2>(options, mascot, config).await,
+        3 => run_mascot::<T, 3>(options, mascot, config).await,
+        4 => run_mascot::<T, 4>(options, mascot, config).await,
+        _ => panic!("Unsupported number of MAC keys"),
+    }
+}
+
+/// Generate batch of values of given kind for all parties.
+fn generate_batch<T, R, const K: usize>(
+    share_gen: &mut ShareGenerator<T, R, K>,
+    empty_data: &[PrecomputedSpdzData<T, K>],
+    options: &ServeOptions,
+    kind: SpdzDataKind,
+) -> Vec<SpdzBatch<T, K>>
+where
+    T: MpcField,
+    R: Rng,
+{
+    let mut data = empty_data.to_vec();
+    match kind {
+        SpdzDataKind::BeaverTriples => {
+            share_gen.fill_beaver_triples(&mut data, options.batch_size);
+            data.into_iter()
+                .map(|x| SpdzBatch::BeaverTriples(x.beaver_triples))
+                .collect()
+        }
+        SpdzDataKind::SquarePairs => {
+            share_gen.fill_square_pairs(&mut data, options.batch_size);
+            data.into_iter()
+                .map(|x| SpdzBatch::SquarePairs(x.square_pairs))
+                .collect()
+        }
+        SpdzDataKind::InversePairs => {
+            share_gen.fill_inverse_pairs(&mut data, options.batch_size);
+            data.into_iter()
+                .map(|x| SpdzBatch::InversePairs(x.inverse_pairs))
+                .collect()
+        }
+        SpdzDataKind::RandomBits => {
+            share_gen.fill_random_bits(&mut data, options.batch_size);
+            data.into_iter()
+                .map(|x| SpdzBatch::RandomBits(x.random_bits))
+                .collect()
+        }
+        SpdzDataKind::BitMasks(width) => {
+            share_gen.fill_bit_masks(&mut data, width, options.bit_masks_batch_size);
+            data.into_iter()
+                .map(|mut x| SpdzBatch::BitMasks {
+                    width,
+                    masks: x.bit_masks.remove(&width).unwrap_or_default(),
+                })
+                .collect()
+        }
+        SpdzDataKind::InputMasks(party_id) => {
+            share_gen.fill_input_masks_for(&mut data, party_id, options.input_masks_batch_size);
+            data.into_iter()
+                .map(|mut x| SpdzBatch::InputMasks {
+                    party_id,
+                    masks: std::mem::take(&mut x.input_masks[party_id]),
+                    plain: x.input_masks_plain,
+                })
+                .collect()
+        }
+        SpdzDataKind::OutputMasks(party_id) => {
+            share_gen.fill_output_masks_for(&mut data, party_id, options.input_masks_batch_size);
+            data.into_iter()
+                .map(|mut x| SpdzBatch::OutputMasks {
+                    party_id,
+                    masks: std::mem::take(&mut x.output_masks[party_id]),
+                    plain: x.output_masks_plain,
+                })
+                .collect()
+        }
+    }
+}
+
+/// Delay before accepting parties again after the first failure, doubled after each following one.
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Maximum delay before accepting parties again.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Returns true if dealer service fails to accept parties because of an error, which isn't caused by them.
+fn is_setup_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::AddrInUse
+            | io::ErrorKind::AddrNotAvailable
+            | io::ErrorKind::PermissionDenied
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::InvalidData
+            | io::ErrorKind::NotFound
+    )
+}
+
+async fn run_serve<T, const K: usize>(options: ServeOptions, config: NetworkConfig)
+where
+    T: MpcField + Serialize + DeserializeOwned + Unpin,
+{
+    let num_parties = config.parties.len();
+    let private_key =
+        transport::load_private_key(&options.private_key).expect("Invalid private key");
+
+    let mut retry_delay = MIN_RETRY_DELAY;
+    loop {
+        println!("Waiting for {num_parties} parties...");
+        let channels = match transport::accept_dealer_clients(&config, private_key.clone()).await {
+            Ok(channels) => channels,
+            Err(err) => {
+                println!("Failed to accept parties: {err}");
+                match TransportError::from_io_error(&err) {
+                    Some(err) => {
+                        println!("Failing party: {}", config.describe_party(err.peer_id()))
+                    }
+                    // Address can't be bound or configuration is invalid, retrying won't help.
+                    None if is_setup_error(&err) => std::process::exit(1),
+                    None => {}
+                }
+                println!("Retrying in {} s", retry_delay.as_secs());
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                continue;
+            }
+        };
+        retry_delay = MIN_RETRY_DELAY;
+
+        let (empty_data, mut share_gen) =
+            setup::<T, _, K>(StdRng::from_entropy(), num_parties, false);
+        let session_id = empty_data[0].session_id;
+        let auth_keys = empty_data.iter().map(|x| x.auth_key).collect();
+
+        println!("Serving data for {num_parties} parties with {K} MAC keys...");
+        let result = spdz::serve_spdz_dealer(channels, session_id, auth_keys, |kind| {
+            generate_batch(&mut share_gen, &empty_data, &options, kind)
+        })
+        .await;
+
+        match result {
+            Ok(()) => println!("All parties disconnected, session finished"),
+            Err(err) => {
+                println!("Session aborted: {err}");
+                println!("Failing party: {}", config.describe_party(err.peer_id()));
+            }
+        }
+    }
+}
+
+async fn run_serve_with_mac_keys<T>(options: ServeOptions, config: NetworkConfig)
+where
+    T: MpcField + Serialize + DeserializeOwned + Unpin,
+{
+    match options.mac_keys {
+        1 => run_serve::<T, 1>(options, config).await,
+        2 => run_serve::<T, 2>(options, config).await,
+        3 => run_serve::<T, 3>(options, config).await,
+        4 => run_serve::<T, 4>(options, config).await,
+        _ => panic!("Unsupported number of MAC keys"),
+    }
+}
+
+fn run_verify<T, const K: usize>(options: VerifyOptions, num_parties: usize)
+where
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+{
+    println!("Loading data of {num_parties} parties with {K} MAC keys...");
+    let data: Vec<PrecomputedSpdzData<T, K>> = (0..num_parties)
+        .map(|id| {
+            let input_path = options.input.replace("#", &format!("{id}"));
+            PrecomputedSpdzData::load_file(&input_path)
+                .unwrap_or_else(|err| panic!("Failed to load {input_path}: {err}"))
+        })
+        .collect();
+
+    println!("Verifying...");
+    if let Err(err) = spdz::verify_precomputed_data(&data) {
+        println!("Data is inconsistent: {err}");
+        std::process::exit(1);
+    }
+    println!(
+        "Data is consistent: {} beaver triples, {} square pairs, {} inverse pairs, {} random bits, \
+         {} bit masks, {} input masks, {} output masks",
+        data[0].beaver_triples.len(),
+        data[0].square_pairs.len(),
+        data[0].inverse_pairs.len(),
+        data[0].random_bits.len(),
+        data[0].bit_masks.values().map(Vec::len).sum::<usize>(),
+        data[0].input_masks.iter().map(Vec::len).sum::<usize>(),
+        data[0].output_masks.iter().map(Vec::len).sum::<usize>()
+    );
+}
+
+fn run_verify_with_mac_keys<T>(options: VerifyOptions, header: PrecomputedSpdzHeader)
+where
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+{
+    match header.num_mac_keys {
+        1 => run_verify::<T, 1>(options, header.num_parties),
+        2 => run_verify::<T, 2>(options, header.num_parties),
+        3 => run_verify::<T, 3>(options, header.num_parties),
+        4 => run_verify::<T, 4>(options, header.num_parties),
+        _ => panic!("Unsupported number of MAC keys"),
+    }
+}
+
+fn run_inspect<T, const K: usize>(options: InspectOptions, header: PrecomputedSpdzHeader)
+where
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+{
+    let data =
+        PrecomputedSpdzData::<T, K>::load_file(&options.input).expect("Invalid precomputed data");
+    // Record of replaced data is ignored, as it is by matcher.
+    let record = ConsumptionRecord::load(&options.input)
+        .expect("Invalid consumption record")
+        .filter(|record| record.session_id == header.session_id);
+
+    println!("Format: {:?} (version {})", header.format, header.version);
+    println!("Field: {}, MAC keys: {}", header.field, K);
+    println!("Party: {} of {}", header.party_id, header.num_parties);
+    println!(
+        "Session: {}",
+        header
+            .session_id
+            .iter()
+            .map(|x| format!("{x:02x}"))
+            .collect::<String>()
+    );
+    if matches!(
+        record,
+        Some(ConsumptionRecord {
+            in_progress: true,
+            ..
+        })
+    ) {
+        println!("Data was used by an interrupted run and must be regenerated");
+    }
+    if matches!(record, Some(ConsumptionRecord { aborted: true, .. })) {
+        println!("Last run aborted, parties continue after the furthest position of all of them");
+    }
+
+    let cursor = record.map(|record| record.cursor).unwrap_or_default();
+    let print_amount = |name: &str, total: usize, consumed: usize| {
+        println!(
+            "{name}: {total} ({} remaining)",
+            total.saturating_sub(consumed)
+        );
+    };
+    print_amount(
+        "Beaver triples",
+        data.beaver_triples.len(),
+        cursor.beaver_triples,
+    );
+    print_amount("Square pairs", data.square_pairs.len(), cursor.square_pairs);
+    print_amount(
+        "Inverse pairs",
+        data.inverse_pairs.len(),
+        cursor.inverse_pairs,
+    );
+    print_amount("Random bits", data.random_bits.len(), cursor.random_bits);
+    for (width, masks) in &data.bit_masks {
+        let consumed = cursor.bit_masks.get(width).copied().unwrap_or(0);
+        print_amount(
+            &format!("Bit masks of width {width}"),
+            masks.len(),
+            consumed,
+        );
+    }
+    for (id, masks) in data.input_masks.iter().enumerate() {
+        let consumed = cursor.input_masks.get(id).copied().unwrap_or(0);
+        print_amount(&format!("Input masks of party {id}"), masks.len(), consumed);
+    }
+    for (id, masks) in data.output_masks.iter().enumerate() {
+        let consumed = cursor.output_masks.get(id).copied().unwrap_or(0);
+        print_amount(
+            &format!("Output masks of party {id}"),
+            masks.len(),
+            consumed,
+        );
+    }
+}
+
+fn run_inspect_with_mac_keys<T>(options: InspectOptions, header: PrecomputedSpdzHeader)
+where
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+{
+    match header.num_mac_keys {
+        1 => run_inspect::<T, 1>(options, header),
+        2 => run_inspect::<T, 2>(options, header),
+        3 => run_inspect::<T, 3>(options, header),
+        4 => run_inspect::<T, 4>(options, header),
+        _ => panic!("Unsupported number of MAC keys"),
+    }
+}
+
+/// Parse command line arguments. Options without a subcommand are passed to `generate`,
+/// which keeps invocations of older versions of the tool working.
+fn parse_options() -> Options {
+    let args: Vec<String> = std::env::args().collect();
+    let cmd = Path::new(&args[0])
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&args[0]);
+    let mut command_args: Vec<&str> = args[1..].iter().map(String::as_str).collect();
+    if command_args
+        .first()
+        .is_some_and(|arg| arg.starts_with('-') && *arg != "--help")
+    {
+        command_args.insert(0, "generate");
+    }
+
+    Options::from_args(&[cmd], &command_args).unwrap_or_else(|early_exit| {
+        std::process::exit(match early_exit.status {
+            Ok(()) => {
+                println!("{}", early_exit.output);
+                0
+            }
+            Err(()) => {
+                eprintln!(
+                    "{}\nRun {} --help for more information.",
+                    early_exit.output, cmd
+                );
+                1
+            }
+        })
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let options = parse_options();
+    match options.command {
+        Command::Generate(mut options) => match options.protocol.take() {
+            None => match options.field.unwrap_or(FieldType::Mersenne127) {
+                FieldType::Mersenne31 => run_generate_with_mac_keys::<Mersenne31>(options),
+                FieldType::Mersenne61 => run_generate_with_mac_keys::<Mersenne61>(options),
+                FieldType::Mersenne127 => run_generate_with_mac_keys::<Mersenne127>(options),
+                FieldType::Goldilocks => run_generate_with_mac_keys::<Goldilocks>(options),
+                FieldType::Ring64 => panic!("SPDZ preprocessing requires a prime field"),
+            },
+            Some(Protocol::Mascot(mascot)) => {
+                let mut config = NetworkConfig::load(&mascot.config).expect("Invalid config");
+                if mascot.id >= config.parties.len() {
+                    panic!(
+                        "Invalid party ID {}, network configuration has {} parties",
+                        mascot.id,
+                        config.parties.len()
+                    );
+                }
+                if let Some(path) = &mascot.certificate {
+                    let certificate =
+                        transport::load_certificate(path).expect("Invalid certificate");
+                    config.parties[mascot.id]
+                        .set_certificate(certificate)
+                        .expect("Certificate doesn't match network configuration");
+                }
+                let field = options
+                    .field
+                    .or(config.field)
+                    .unwrap_or(FieldType::Mersenne127);
+                match field {
+                    FieldType::Mersenne31 => {
+                        run_mascot_with_mac_keys::<Mersenne31>(options, mascot, config).await
+                    }
+                    FieldType::Mersenne61 => {
+                        run_mascot_with_mac_keys::<Mersenne61>(options, mascot, config).await
+                    }
+                    FieldType::Mersenne127 => {
+                        run_mascot_with_mac_keys::<Mersenne127>(options, mascot, config).await
+                    }
+                    FieldType::Goldilocks => {
+                        run_mascot_with_mac_keys::<Goldilocks>(options, mascot, config).await
+                    }
+                    FieldType::Ring64 => panic!("SPDZ preprocessing requires a prime field"),
+                }
+            }
+        },
+        Command::Serve(options) => {
+            let mut config = NetworkConfig::load(&options.config).expect("Invalid config");
+            if let (Some(path), Some(dealer)) = (&options.certificate, &mut config.dealer) {
+                let certificate = transport::load_certificate(path).expect("Invalid certificate");
+                dealer
+                    .set_certificate(certificate)
+                    .expect("Certificate doesn't match network configuration");
+            }
+            let field = options
+                .field
+                .or(config.field)
+                .unwrap_or(FieldType::Mersenne127);
+            match field {
+                FieldType::Mersenne31 => {
+                    run_serve_with_mac_keys::<Mersenne31>(options, config).await
+                }
+                FieldType::Mersenne61 => {
+                    run_serve_with_mac_keys::<Mersenne61>(options, config).await
+                }
+                FieldType::Mersenne127 => {
+                    run_serve_with_mac_keys::<Mersenne127>(options, config).await
+                }
+                FieldType::Goldilocks => {
+                    run_serve_with_mac_keys::<Goldilocks>(options, config).await
+                }
+                FieldType::Ring64 => panic!("SPDZ preprocessing requires a prime field"),
+            }
+        }
+        Command::Verify(options) => {
+            // Field and layout are taken from data of party 0, data of other parties must match it.
+            let header = PrecomputedSpdzHeader::load_file(options.input.replace("#", "0"))
+                .expect("Invalid precomputed data");
+            match header.field {
+                FieldType::Mersenne31 => run_verify_with_mac_keys::<Mersenne31>(options, header),
+                FieldType::Mersenne61 => run_verify_with_mac_keys::<Mersenne61>(options, header),
+                FieldType::Mersenne127 => run_verify_with_mac_keys::<Mersenne127>(options, header),
+                FieldType::Goldilocks => run_verify_with_mac_keys::<Goldilocks>(options, header),
+                FieldType::Ring64 => panic!("SPDZ preprocessing requires a prime field"),
+            }
+        }
+        Command::Inspect(options) => {
+            let header =
+                PrecomputedSpdzHeader::load_file(&options.input).expect("Invalid precomputed data");
+            match header.field {
+                FieldType::Mersenne31 => run_inspect_with_mac_keys::<Mersenne31>(options, header),
+                FieldType::Mersenne61 => run_inspect_with_mac_keys::<Mersenne61>(options, header),
+                FieldType::Mersenne127 => run_inspect_with_mac_keys::<Mersenne127>(options, header),
+                FieldType::Goldilocks => run_inspect_with_mac_keys::<Goldilocks>(options, header),
+                FieldType::Ring64 => panic!("SPDZ preprocessing requires a prime field"),
+            }
+        }
     }
 }
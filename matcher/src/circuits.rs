@@ -1,9 +1,10 @@
-use std::{fmt, iter};
+use std::fmt;
 
 use mpc::{
     circuits::{join_circuits_all, IntShare, WrappedShare},
+    estimator::{self, ResourceUsage},
     executor::{self, MpcExecution, MpcExecutionError, MpcExecutionStats},
-    ff::Field,
+    spdz::SpdzConfig,
     MpcEngine, MpcField,
 };
 use mpc_flow::FlowError;
@@ -47,95 +48,116 @@ pub type PreferenceVec = Vec<u64>;
 /// Given preferences of 2N parties, find matching between parties 0, ..., n-1 and parties n, ..., 2n-1,
 /// such that total cost is minimum possible. Total cost is sum of costs of individual pairs.
 /// Cost of pair is square of L2 distance between preference vectors.
-/// Returns index of party matched to current party.
+/// Returns index of party matched to current party and engine after successful execution.
 pub async fn compute_private_matching<Engine, Error, const N: usize>(
     engine: Engine,
     preferences: PreferenceVec,
     max_preference_value: u64,
-) -> Result<(usize, MpcExecutionStats), MatchingError<Error>>
+) -> Result<(usize, MpcExecutionStats, Engine), MatchingError<Error>>
 where
     Engine: 'static + Send + MpcEngine<Error = Error>,
     Error: 'static + Send,
 {
-    if engine.num_parties() % 2 != 0 {
+    if !engine.num_parties().is_multiple_of(2) {
         panic!("Protocol requires even number of parties")
     }
 
     let num_parties = engine.num_parties();
-    let party_id = engine.party_id();
+    let inputs: Vec<_> = preferences.into_iter().map(Engine::Field::from).collect();
 
-    // Random field element that is used to hide our output from circuit.
-    let output_mask = Engine::Field::random(&mut rand::thread_rng());
-
-    // Input to circuit is concatenation of [output_mask] and preference vector.
-    let inputs: Vec<_> = iter::once(output_mask)
-        .chain(preferences.into_iter().map(Engine::Field::from))
-        .collect();
-
-    let (circuit_output, stats) =
+    let (circuit_output, stats, engine) =
         executor::run_circuit_in_background(engine, inputs, move |ctx, inputs| {
             Box::pin(matching_circuit::<_, N>(ctx, inputs, max_preference_value))
         })
         .await?;
 
-    let outputs = circuit_output?;
-
-    // Extract our output and "decrypt" it by subtracting mask.
-    let output = (outputs[party_id] - output_mask).truncated() as usize;
+    let output = circuit_output?.truncated() as usize;
 
     if output < num_parties {
-        Ok((output as usize, stats))
+        Ok((output, stats, engine))
     } else {
         Err(MatchingError::InvalidOutput)
     }
 }
 
-/// Circuit used by `compute_private_matching`. Computes matching and returns masked outputs of all parties.
+/// Compute exact amount of resources consumed by `compute_private_matching`,
+/// given number of parties and length of preference vectors. Rounds are counted for default SPDZ configuration.
+pub async fn estimate_matching_resources<T: MpcField, const N: usize>(
+    num_parties: usize,
+    num_dims: usize,
+    max_preference_value: u64,
+) -> ResourceUsage {
+    if !num_parties.is_multiple_of(2) {
+        panic!("Protocol requires even number of parties")
+    }
+
+    let input_counts = vec![vec![num_dims; num_parties]];
+    let (output, usage) = estimator::estimate_resources::<T, _, _>(
+        0,
+        input_counts,
+        SpdzConfig::default(),
+        move |ctx, inputs| Box::pin(matching_circuit::<_, N>(ctx, inputs, max_preference_value)),
+    )
+    .await
+    .expect("Resource estimator failed");
+
+    output.expect("Matching circuit failed");
+    usage
+}
+
+/// Circuit used by `compute_private_matching`. Computes matching and returns output of current party.
 async fn matching_circuit<E: MpcEngine, const N: usize>(
     ctx: &MpcExecution<E>,
     inputs: Vec<Vec<E::Share>>,
     max_preference_value: u64,
-) -> Result<Vec<E::Field>, MatchingError<E::Error>> {
+) -> Result<E::Field, MatchingError<E::Error>> {
     if !inputs.iter().all(|x| x.len() == inputs[0].len()) {
         return Err(MatchingError::InputLengthMismatch);
     }
 
     let max_preference_value = IntShare::from_plain(ctx, max_preference_value as i64);
 
-    // The first input of each party is its output mask.
-    let output_masks = inputs.iter().map(|vec| vec[0]);
-
-    // The rest of inputs form preference vectors.
-    let preferences: Vec<_> = join_circuits_all(inputs.iter().map(|vec| {
-        join_circuits_all(vec[1..].iter().map(|&x| async move {
-            IntShare::<_, N>::wrap_safe(ctx, x)
-                .await
-                .clamp(ctx, IntShare::zero(), max_preference_value)
-                .await
-        }))
-    }))
-    .await;
+    let preferences: Vec<_> = ctx
+        .profile(
+            "clamp inputs",
+            join_circuits_all(inputs.iter().map(|vec| {
+                join_circuits_all(vec.iter().map(|&x| async move {
+                    IntShare::<_, N>::wrap_safe(ctx, x)
+                        .await
+                        .clamp(ctx, IntShare::zero(), max_preference_value)
+                        .await
+                }))
+            })),
+        )
+        .await;
 
     let first_right_index = preferences.len() / 2;
     let left_preferences = &preferences[..first_right_index];
     let right_preferences = &preferences[first_right_index..];
 
-    let costs = get_cost_matrix(ctx, left_preferences, right_preferences).await;
-
-    let (left_matches, right_matches) =
-        mpc_flow::min_cost_bipartite_matching(ctx, costs.view()).await?;
+    let costs = ctx
+        .profile(
+            "cost matrix",
+            get_cost_matrix(ctx, left_preferences, right_preferences),
+        )
+        .await;
+
+    let (left_matches, right_matches) = ctx
+        .profile(
+            "min cost matching",
+            mpc_flow::min_cost_bipartite_matching(ctx, costs.view()),
+        )
+        .await?;
 
     let matches = left_matches
         .into_iter()
         .map(|x| x + IntShare::from_plain(ctx, first_right_index as i64))
-        .chain(right_matches);
-
-    let masked_matches = matches
-        .zip(output_masks)
-        .map(|(value, mask)| value.raw() + mask);
+        .chain(right_matches)
+        .map(|x| x.raw())
+        .collect();
 
     ctx.ensure_integrity();
-    Ok(join_circuits_all(masked_matches.map(|x| ctx.open_unchecked(x))).await)
+    Ok(ctx.profile("output", ctx.open_to_each(matches)).await)
 }
 
 /// Compute matrix of costs for each possible pair.
@@ -159,11 +181,40 @@ async fn compare_preferences<E: MpcEngine, const N: usize>(
     left: &[IntShare<E::Share, N>],
     right: &[IntShare<E::Share, N>],
 ) -> IntShare<E::Share, N> {
-    join_circuits_all(left.iter().zip(right).map(|(&x, &y)| {
-        let delta = x - y;
-        delta.mul(ctx, delta)
-    }))
-    .await
-    .into_iter()
-    .fold(IntShare::zero(), |acc, x| acc + x)
+    join_circuits_all(left.iter().zip(right).map(|(&x, &y)| (x - y).square(ctx)))
+        .await
+        .into_iter()
+        .fold(IntShare::zero(), |acc, x| acc + x)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+    use mpc::{fields::Ring64, spdz::SpdzConfig, spdz2k::testing::mock_spdz2k_engines};
+
+    use crate::check_parameters;
+
+    use super::compute_private_matching;
+
+    #[tokio::test]
+    async fn test_private_matching_spdz2k() {
+        // Matching (0, 3), (1, 2) costs 2 + 2, while matching (0, 2), (1, 3) costs 162 + 162.
+        let preferences = vec![vec![0, 0], vec![10, 10], vec![9, 9], vec![1, 1]];
+        let max_preference_value = 10;
+        check_parameters::<Ring64>(16, 40, preferences.len(), 2, max_preference_value).unwrap();
+
+        let engines = mock_spdz2k_engines(preferences.len(), SpdzConfig::default());
+        let results = future::join_all(engines.into_iter().zip(preferences).map(
+            |(engine, preferences)| {
+                compute_private_matching::<_, _, 16>(engine, preferences, max_preference_value)
+            },
+        ))
+        .await;
+
+        let matches: Vec<_> = results
+            .into_iter()
+            .map(|result| result.map_err(|_| "Matching failed").unwrap().0)
+            .collect();
+        assert_eq!(matches, vec![3, 2, 1, 0]);
+    }
 }
@@ -0,0 +1,82 @@
+use mpc::MpcField;
+
+pub mod circuits;
+
+/// Default number of bits for field-embedded integers.
+pub const DEFAULT_NUM_BITS: usize = 32;
+
+/// Supported numbers of bits for field-embedded integers (see `with_num_bits`).
+pub const SUPPORTED_NUM_BITS: [usize; 4] = [16, 24, 32, 48];
+
+/// Default maximum value of preference vector element. Minimum is 0.
+pub const DEFAULT_MAX_PREFERENCE_VALUE: u64 = 100;
+
+/// Evaluate expression with constant `$bits` set to number of bits chosen at runtime.
+/// Panics if number of bits is not in `SUPPORTED_NUM_BITS`.
+#[macro_export]
+macro_rules! with_num_bits {
+    ($num_bits:expr, $bits:ident => $body:expr) => {
+        match $num_bits {
+            16 => {
+                const $bits: usize = 16;
+                $body
+            }
+            24 => {
+                const $bits: usize = 24;
+                $body
+            }
+            32 => {
+                const $bits: usize = 32;
+                $body
+            }
+            48 => {
+                const $bits: usize = 48;
+                $body
+            }
+            _ => panic!("Unsupported number of bits"),
+        }
+    };
+}
+
+/// Default statistical security parameter: masked values leak with probability at most 2^-40.
+pub const DEFAULT_STATISTICAL_SECURITY: usize = 40;
+
+/// Check if matching of given number of parties with preference vectors of given length can be computed
+/// using given field and number of bits for integers. Values compared in MPC are masked by random
+/// `T::SAFE_BITS`-bit integers, so `num_bits + statistical_security` must fit into them,
+/// and path costs computed by min-cost flow (sums of squared distances) must fit into `num_bits`-bit integers.
+pub fn check_parameters<T: MpcField>(
+    num_bits: usize,
+    statistical_security: usize,
+    num_parties: usize,
+    num_dims: usize,
+    max_preference_value: u64,
+) -> Result<(), String> {
+    if !SUPPORTED_NUM_BITS.contains(&num_bits) {
+        return Err(format!(
+            "Unsupported number of bits. Available options: {:?}",
+            SUPPORTED_NUM_BITS
+        ));
+    }
+    if num_bits + statistical_security > T::SAFE_BITS {
+        return Err(format!(
+            "Field {} supports integers with at most {} bits with statistical security of {} bits",
+            T::FIELD_TYPE,
+            T::SAFE_BITS.saturating_sub(statistical_security),
+            statistical_security
+        ));
+    }
+
+    // Costs are signed integers, path in flow network visits every party at most once.
+    let max_cost = u128::from(max_preference_value)
+        .pow(2)
+        .saturating_mul(num_dims as u128)
+        .saturating_mul(num_parties as u128);
+    if max_cost >= 1 << (num_bits - 1) {
+        return Err(format!(
+            "Maximum preference value {} is too large for {}-bit integers with {} parties and {}-element preference vectors",
+            max_preference_value, num_bits, num_parties, num_dims
+        ));
+    }
+    Ok(())
+}
@@ -1,22 +1,21 @@
-mod circuits;
-
-use std::time::Instant;
+use std::{fmt, time::Instant};
 
 use argh::FromArgs;
+use matcher::{
+    circuits::{self, MatchingError},
+    with_num_bits, DEFAULT_MAX_PREFERENCE_VALUE, DEFAULT_NUM_BITS, DEFAULT_STATISTICAL_SECURITY,
+};
 use mpc::{
-    fields::Mersenne127,
-    spdz::{PrecomputedSpdzDealer, SpdzEngine},
-    transport::{self, NetworkConfig},
+    executor::MpcExecutionError,
+    fields::{FieldType, Goldilocks, Mersenne127, Mersenne31, Mersenne61},
+    spdz::{
+        DigestType, NetworkSpdzDealer, OpeningStrategy, PrecomputedSpdzDealer,
+        PrecomputedSpdzHeader, SpdzConfig, SpdzDealer, SpdzEngine, SpdzShare,
+    },
+    transport::{self, NetworkConfig, TransportError},
+    MpcContext, MpcField,
 };
-
-/// Field for matching computation.
-type Fp = Mersenne127;
-
-/// Number of bits for field-embedded integers.
-const NUM_BITS: usize = 32;
-
-/// Maximum value of preference vector element. Minimum is 0.
-const MAX_PREFERENCE_VALUE: u64 = 100;
+use serde::{de::DeserializeOwned, Serialize};
 
 /// MPC dating app for gigachads.
 #[derive(FromArgs, Debug)]
@@ -33,18 +32,157 @@ struct Options {
     #[argh(option)]
     private_key: String,
 
-    /// path to precomputed data for SPDZ protocol
+    /// path to TLS certificate of this party (needed if network configuration pins it by fingerprint)
     #[argh(option)]
-    precomp: String,
+    certificate: Option<String>,
+
+    /// path to precomputed data for SPDZ protocol (if omitted, data is received from dealer service in config)
+    #[argh(option)]
+    precomp: Option<String>,
+
+    /// number of MAC keys used by dealer service (ignored with precomputed data)
+    #[argh(option, default = "1")]
+    mac_keys: usize,
 
     /// preference vector
     #[argh(option)]
     preferences: String,
+
+    /// field used for computation (31, 61, 127 or goldilocks), overrides network configuration
+    #[argh(option)]
+    field: Option<FieldType>,
+
+    /// number of bits for field-embedded integers
+    #[argh(option, default = "DEFAULT_NUM_BITS")]
+    bits: usize,
+
+    /// maximum value of preference vector element
+    #[argh(option, default = "DEFAULT_MAX_PREFERENCE_VALUE")]
+    max_preference: u64,
+
+    /// statistical security in bits, bounds number of bits for integers (lower it only for small fields)
+    #[argh(option, default = "DEFAULT_STATISTICAL_SECURITY")]
+    statistical_security: usize,
+
+    /// routing of openings (king, rotating, partitioned or all-to-all), must be the same for all parties
+    #[argh(option, default = "OpeningStrategy::SingleKing")]
+    opening: OpeningStrategy,
+
+    /// number of collected openings that triggers integrity checking (lower values save memory)
+    #[argh(option, default = "SpdzConfig::default().batch_check_threshold")]
+    batch_check_threshold: usize,
+
+    /// maximum number of openings checked at once using a single random polynomial
+    #[argh(option, default = "SpdzConfig::default().max_batch_check_size")]
+    max_batch_check_size: usize,
+
+    /// check integrity every given number of rounds (useful for latency-sensitive deployments)
+    #[argh(option)]
+    check_every_rounds: Option<usize>,
+
+    /// hashing function used by SPDZ (sha3, sha256 or blake2), must be the same for all parties
+    #[argh(option, default = "DigestType::Sha3_256")]
+    digest: DigestType,
+
+    /// sign broadcasts and identify parties that sent inconsistent messages when integrity checks fail
+    #[argh(switch)]
+    identifiable_abort: bool,
 }
 
 #[tokio::main]
 async fn main() {
     let options: Options = argh::from_env();
+    let mut config = NetworkConfig::load(&options.config).expect("Invalid config");
+    if options.id >= config.parties.len() {
+        panic!(
+            "Invalid party ID {}, network configuration has {} parties",
+            options.id,
+            config.parties.len()
+        );
+    }
+    if let Some(path) = &options.certificate {
+        let certificate = transport::load_certificate(path).expect("Invalid certificate");
+        config.parties[options.id]
+            .set_certificate(certificate)
+            .expect("Certificate doesn't match network configuration");
+    }
+
+    let field = options
+        .field
+        .or(config.field)
+        .unwrap_or(FieldType::Mersenne127);
+
+    let num_mac_keys = match &options.precomp {
+        Some(path) => {
+            let header =
+                PrecomputedSpdzHeader::load_file(path).expect("Invalid precomputed SPDZ data");
+
+            if header.field != field {
+                panic!(
+                    "Precomputed SPDZ data was generated for field {}, but field {} was selected",
+                    header.field, field
+                );
+            }
+
+            check_party(header.num_parties, header.party_id, &options, &config);
+            header.num_mac_keys
+        }
+        None if config.dealer.is_some() => options.mac_keys,
+        None => panic!("Specify precomputed SPDZ data or configure dealer service"),
+    };
+
+    // Multiple MAC keys are supported only for smaller fields, to limit number of instantiated circuits.
+    match (field, num_mac_keys) {
+        (FieldType::Mersenne31, 1) => run_with_params::<Mersenne31, 1>(options, config).await,
+        (FieldType::Mersenne31, 2) => run_with_params::<Mersenne31, 2>(options, config).await,
+        (FieldType::Mersenne31, 3) => run_with_params::<Mersenne31, 3>(options, config).await,
+        (FieldType::Mersenne31, 4) => run_with_params::<Mersenne31, 4>(options, config).await,
+        (FieldType::Goldilocks, 1) => run_with_params::<Goldilocks, 1>(options, config).await,
+        (FieldType::Goldilocks, 2) => run_with_params::<Goldilocks, 2>(options, config).await,
+        (FieldType::Mersenne61, 1) => run_with_params::<Mersenne61, 1>(options, config).await,
+        (FieldType::Mersenne61, 2) => run_with_params::<Mersenne61, 2>(options, config).await,
+        (FieldType::Mersenne127, 1) => run_with_params::<Mersenne127, 1>(options, config).await,
+        (field, num_mac_keys) => panic!(
+            "Field {} with {} MAC keys is not supported",
+            field, num_mac_keys
+        ),
+    }
+}
+
+/// Check that precomputed data was generated for this party.
+fn check_party(num_parties: usize, party_id: usize, options: &Options, config: &NetworkConfig) {
+    if num_parties != config.parties.len() || party_id != options.id {
+        panic!(
+            "Precomputed SPDZ data was generated for party {} of {}, but this is party {} of {}",
+            party_id,
+            num_parties,
+            options.id,
+            config.parties.len()
+        );
+    }
+}
+
+async fn run_with_params<T, const K: usize>(options: Options, config: NetworkConfig)
+where
+    T: MpcField + Serialize + DeserializeOwned + Unpin,
+{
+    if let Err(err) = matcher::check_parameters::<T>(
+        options.bits,
+        options.statistical_security,
+        config.parties.len(),
+        options.preferences.split(',').count(),
+        options.max_preference,
+    ) {
+        panic!("{}", err);
+    }
+
+    with_num_bits!(options.bits, N => run::<T, K, N>(options, config).await)
+}
+
+async fn run<T, const K: usize, const N: usize>(options: Options, config: NetworkConfig)
+where
+    T: MpcField + Serialize + DeserializeOwned + Unpin,
+{
     let party_id = options.id;
 
     let preferences: Vec<u64> = options
@@ -54,14 +192,48 @@ async fn main() {
         .collect::<Result<_, _>>()
         .expect("Invalid preferences vector");
 
-    let config = NetworkConfig::load(options.config).expect("Invalid config");
+    match &options.precomp {
+        Some(path) => {
+            let dealer: PrecomputedSpdzDealer<T, K> =
+                PrecomputedSpdzDealer::from_file(path).expect("Invalid precomputed SPDZ data");
+            let dealer = compute::<T, _, K, N>(options, config, preferences, dealer).await;
+            dealer.close().unwrap_or_else(|err| {
+                exit_with_error("Failed to save consumption of precomputed data", err, None)
+            });
+        }
+        None => {
+            let private_key =
+                transport::load_private_key(&options.private_key).expect("Invalid private key");
+
+            println!("Connecting to dealer service...");
+            let dealer: NetworkSpdzDealer<T, K> =
+                NetworkSpdzDealer::connect(config.clone(), private_key, party_id)
+                    .await
+                    .unwrap_or_else(|err| {
+                        exit_with_error("Dealer service connection failed", err, None)
+                    });
+            check_party(dealer.num_parties(), dealer.party_id(), &options, &config);
+            compute::<T, _, K, N>(options, config, preferences, dealer).await;
+        }
+    }
+}
+
+/// Compute matching and print results. Returns dealer after successful computation.
+async fn compute<T, D, const K: usize, const N: usize>(
+    options: Options,
+    config: NetworkConfig,
+    preferences: Vec<u64>,
+    dealer: D,
+) -> D
+where
+    T: MpcField + Serialize + DeserializeOwned + Unpin,
+    D: SpdzDealer<K, Field = T, Share = SpdzShare<T, K>> + Send + 'static,
+{
+    let party_id = options.id;
 
     let private_key =
         transport::load_private_key(options.private_key).expect("Invalid private key");
 
-    let dealer =
-        PrecomputedSpdzDealer::from_file(options.precomp).expect("Invalid precomputed SPDZ data");
-
     let group_id = if party_id < config.parties.len() / 2 {
         1
     } else {
@@ -72,20 +244,45 @@ async fn main() {
 
     let connection = transport::connect_multiparty(&config, private_key, party_id)
         .await
-        .expect("Multiparty connection failed");
+        .unwrap_or_else(|err| {
+            let failing_party = TransportError::from_io_error(&err).map(|err| err.peer_id());
+            exit_with_error(
+                "Multiparty connection failed",
+                err,
+                failing_party.map(|id| config.describe_party(id)),
+            )
+        });
 
     println!("All peers connected, computing matching...");
 
     let start_time = Instant::now();
-    let engine: SpdzEngine<Fp, _, _> = SpdzEngine::new(dealer, connection);
+    let spdz_config = SpdzConfig {
+        batch_check_threshold: options.batch_check_threshold,
+        max_batch_check_size: options.max_batch_check_size,
+        check_every_rounds: options.check_every_rounds,
+        digest: options.digest,
+        opening_strategy: options.opening,
+        identifiable_abort: options.identifiable_abort,
+    };
+    let engine: SpdzEngine<T, _, _, K> = SpdzEngine::new(dealer, connection, spdz_config);
 
-    let (our_match, execution_stats) = circuits::compute_private_matching::<_, _, NUM_BITS>(
-        engine,
-        preferences,
-        MAX_PREFERENCE_VALUE,
-    )
-    .await
-    .expect("MPC computation failed");
+    let (our_match, execution_stats, engine) =
+        circuits::compute_private_matching::<_, _, N>(engine, preferences, options.max_preference)
+            .await
+            .unwrap_or_else(|err| {
+                let failing_party = match &err {
+                    MatchingError::Execution(MpcExecutionError::Engine(err)) => err.party_id(),
+                    MatchingError::Execution(MpcExecutionError::UnexpectedNumberOfInputs(id)) => {
+                        Some(*id)
+                    }
+                    _ => None,
+                };
+                exit_with_error(
+                    "MPC computation failed",
+                    err,
+                    failing_party.map(|id| config.describe_party(id)),
+                )
+            });
 
     let end_time = Instant::now();
     let computation_time = end_time - start_time;
@@ -98,4 +295,34 @@ async fn main() {
         execution_stats.num_rounds,
         execution_stats.num_integrity_checks,
     );
+
+    for (label, stats) in &execution_stats.labels {
+        println!(
+            "  {}: time: {:.1}s, openings: {}, rounds: {}, triples: {}, square pairs: {}, inverse pairs: {}, \
+             random bits: {}, bit masks: {}, input masks: {}, output masks: {}",
+            label,
+            stats.time.as_secs_f64(),
+            stats.num_openings,
+            stats.num_rounds,
+            stats.num_beaver_triples,
+            stats.num_square_pairs,
+            stats.num_inverse_pairs,
+            stats.num_random_bits,
+            stats.num_bit_masks,
+            stats.num_input_masks,
+            stats.num_output_masks,
+        );
+    }
+
+    engine.into_dealer()
+}
+
+/// Print error that prevents computing matching (e.g. a peer that stopped responding) and exit.
+/// Failing party is described with its name and address from network configuration, if known.
+fn exit_with_error(context: &str, err: impl fmt::Display, failing_party: Option<String>) -> ! {
+    println!("{context}: {err}");
+    if let Some(party) = failing_party {
+        println!("Failing party: {party}");
+    }
+    std::process::exit(1)
 }
@@ -1,8 +1,10 @@
-use crate::{executor::MpcExecution, join_circuits, MpcDealer, MpcEngine};
+use ff::Field;
+
+use crate::{executor::MpcExecution, join_circuits, MpcEngine};
 
 /// Multiply two shared values.
 pub async fn mul<E: MpcEngine>(ctx: &MpcExecution<E>, x: E::Share, y: E::Share) -> E::Share {
-    let (mask_for_x, mask_for_y, mask_for_xy) = ctx.engine().dealer().next_beaver_triple();
+    let (mask_for_x, mask_for_y, mask_for_xy) = ctx.beaver_triple();
     let (masked_x, masked_y) = join_circuits!(
         ctx.open_unchecked(x - mask_for_x),
         ctx.open_unchecked(y - mask_for_y),
@@ -10,6 +12,36 @@ pub async fn mul<E: MpcEngine>(ctx: &MpcExecution<E>, x: E::Share, y: E::Share)
     mask_for_xy + mask_for_y * masked_x + mask_for_x * masked_y + ctx.plain(masked_x * masked_y)
 }
 
+/// Square shared value. Uses a square pair, so it needs a single opening instead of two,
+/// or a Beaver triple if dealer has no square pairs.
+pub async fn square<E: MpcEngine>(ctx: &MpcExecution<E>, x: E::Share) -> E::Share {
+    let (mask, mask_squared) = match ctx.square_pair() {
+        Some(pair) => pair,
+        None => return mul(ctx, x, x).await,
+    };
+    let masked_x = ctx.open_unchecked(x - mask).await;
+    mask_squared + mask * masked_x.double() + ctx.plain(masked_x.square())
+}
+
+/// Multiplicative inverse of shared value. Input is multiplied by the nonzero part r of an inverse pair
+/// and the product is opened, so that x^-1 = (x * r)^-1 * r. Returns zero for zero input (and reveals it).
+///
+/// If dealer has no inverse pairs, a Beaver triple (a, b, ab) is used instead: opening x - a gives x * b
+/// without another multiplication, and x^-1 = (x * b)^-1 * b. Besides revealing whether x is zero, this
+/// fallback returns zero for nonzero x with probability 1/|F| that b is zero, as b is not guaranteed to be nonzero.
+pub async fn inverse<E: MpcEngine>(ctx: &MpcExecution<E>, x: E::Share) -> E::Share {
+    let (mask, masked_x) = match ctx.inverse_pair() {
+        Some((mask, _)) => (mask, ctx.open_unchecked(mul(ctx, x, mask).await).await),
+        None => {
+            let (a, b, ab) = ctx.beaver_triple();
+            let masked_x = ctx.open_unchecked(x - a).await;
+            (b, ctx.open_unchecked(ab + b * masked_x).await)
+        }
+    };
+    let masked_inverse = Option::from(masked_x.invert()).unwrap_or_else(E::Field::zero);
+    mask * masked_inverse
+}
+
 #[cfg(test)]
 mod tests {
     use crate::circuits::{testing::*, *};
@@ -27,4 +59,31 @@ mod tests {
         })
         .await;
     }
+
+    #[tokio::test]
+    async fn test_square() {
+        test_circuit(|ctx| {
+            Box::pin(async {
+                let a = PlainShare(1337.into());
+                let result = square(ctx, a).await;
+                assert_eq!(result.0, (1337 * 1337).into());
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_inverse() {
+        test_circuit(|ctx| {
+            Box::pin(async {
+                let a = PlainShare(1337.into());
+                let result = inverse(ctx, a).await;
+                assert_eq!(result.0 * MockField::from(1337), 1.into());
+
+                let zero = PlainShare(0.into());
+                assert_eq!(inverse(ctx, zero).await.0, 0.into());
+            })
+        })
+        .await;
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{circuits::mul, executor::MpcExecution, join_circuits, MpcEngine, MpcField, MpcShare};
+use crate::{circuits::mul, executor::MpcExecution, join_circuits, MpcEngine, MpcShare};
 
 use super::{fold_tree, BitShare, WrappedShare};
 
@@ -9,41 +9,34 @@ pub async fn bitwise_compare<E: MpcEngine>(
     lhs: u64,
     rhs: &[BitShare<E::Share>],
 ) -> (BitShare<E::Share>, BitShare<E::Share>) {
-    // Given bit sequences L and R, let us define f(L, R) to be a pair (cmp, neq) such that
-    // a) if L < R, then cmp = -1, neq = 1;
-    // b) if L > R, then cmp = 1, neq = 1;
-    // c) if L = R, then cmp = 0, neq = 0.
+    // Given bit sequences L and R, let us define f(L, R) to be a pair ([L < R], [L > R]).
     // The algorithm is based on observation that f(AB, CD) can be computed
     // from f(A, C) and f(B, D) using 2 multiplications and 1 communication round.
     // This enables us to compute the result in log_2(bits) rounds in binary-tree fashion.
+    // No division is needed, so it works also over rings.
 
-    // 1. Map individual bits into pairs (cmp, neq).
+    // 1. Map individual bits into pairs ([lhs_i < rhs_i], [lhs_i > rhs_i]).
     let base_cases = rhs.iter().enumerate().map(|(i, rhs_bit)| {
         let lhs_bit = (lhs >> i) & 1;
         if lhs_bit == 0 {
-            (-rhs_bit.raw(), rhs_bit.raw())
+            (rhs_bit.raw(), E::Share::zero())
         } else {
-            let not_rhs_bit = rhs_bit.not(ctx);
-            (not_rhs_bit.raw(), not_rhs_bit.raw())
+            (E::Share::zero(), rhs_bit.not(ctx).raw())
         }
     });
 
-    // 2. Fold the sequence of pairs.
-    let (cmp, neq) = fold_tree(
+    // 2. Fold the sequence of pairs. Less significant part decides only if more significant parts are equal.
+    let (is_less, is_greater) = fold_tree(
         base_cases,
         (E::Share::zero(), E::Share::zero()),
-        |lhs, rhs| async move {
-            let (a, b) = join_circuits!(mul(ctx, lhs.0, rhs.1), mul(ctx, lhs.1, rhs.1));
-            (lhs.0 + rhs.0 - a, lhs.1 + rhs.1 - b)
+        |low, high| async move {
+            let high_neq = high.0 + high.1;
+            let (a, b) = join_circuits!(mul(ctx, low.0, high_neq), mul(ctx, low.1, high_neq));
+            (low.0 + high.0 - a, low.1 + high.1 - b)
         },
     )
     .await;
-
-    // 3. Convert aggregated pair (cmp, neq) into sharings of [lhs < rhs] and [lhs > rhs].
-    let scale = E::Field::power_of_two_inverse(1);
-    let is_less = BitShare::wrap((neq - cmp) * scale);
-    let is_greater = BitShare::wrap((neq + cmp) * scale);
-    (is_less, is_greater)
+    (BitShare::wrap(is_less), BitShare::wrap(is_greater))
 }
 
 /// Check equality of plaintext unsigned integer with a hidden integer, provided sharings of its individual bits.
@@ -1,6 +1,6 @@
 use ff::Field;
 
-use crate::{executor::MpcExecution, MpcDealer, MpcEngine, MpcShare};
+use crate::{executor::MpcExecution, MpcEngine, MpcShare};
 
 use super::{mul, WrappedShare};
 
@@ -54,7 +54,7 @@ impl<T: MpcShare> BitShare<T> {
     where
         E: MpcEngine<Share = T>,
     {
-        Self::wrap(ctx.engine().dealer().next_uint(1))
+        Self::wrap(ctx.random_uint(1))
     }
 
     /// Open share. Requires communication.
@@ -3,9 +3,9 @@ use std::{
     ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
-use crate::{executor::MpcExecution, join_circuits, MpcDealer, MpcEngine, MpcField, MpcShare};
+use crate::{executor::MpcExecution, join_circuits, MpcEngine, MpcField, MpcShare};
 
-use super::{bitwise_compare, bitwise_equal, mul, BitShare, WrappedShare};
+use super::{bitwise_compare, bitwise_equal, mul, square, BitShare, WrappedShare};
 
 /// Share of N-bit signed integer embedded in a prime field, where 2 <= N <= min(Field::SAFE_BITS-1, 64).
 /// Valid values are from range [-2^(N-1); 2^(N-1)-1] and are supported by all operations,
@@ -90,7 +90,7 @@ impl<T: MpcShare, const N: usize> IntShare<T, N> {
         E: MpcEngine<Share = T>,
     {
         let shift = ctx.plain(E::Field::power_of_two(N - 1));
-        Self::wrap(ctx.engine().dealer().next_uint(N) - shift)
+        Self::wrap(ctx.random_uint(N) - shift)
     }
 
     /// Sharing of number from sharing of its bit decomposition.
@@ -124,6 +124,14 @@ impl<T: MpcShare, const N: usize> IntShare<T, N> {
         Self::wrap(mul(ctx, self.0, rhs.0).await)
     }
 
+    /// Square integer share.
+    pub async fn square<E>(self, ctx: &MpcExecution<E>) -> Self
+    where
+        E: MpcEngine<Share = T>,
+    {
+        Self::wrap(square(ctx, self.0).await)
+    }
+
     /// Remainder modulo 2^k for k <= N. Result is given in range [0;2^k).
     /// This operation supports values in a larger range, namely `[-2^N+1; 2^N-1]`.
     /// This method is guaranteed to return k-bit integer even for invalid inputs.
@@ -132,32 +140,10 @@ impl<T: MpcShare, const N: usize> IntShare<T, N> {
     where
         E: MpcEngine<Share = T>,
     {
-        if k > N {
-            panic!("Too large k.");
-        }
-
-        // Adapted Mod2M algorithm from "Improved Primitives for Secure Multiparty Integer Computation"
-        // (https://citeseerx.ist.psu.edu/viewdoc/download?doi=10.1.1.220.9499&rep=rep1&type=pdf)
-
-        // Normalized value is in range [1; 2^(N+1)-1]. We only need the first k <= N bits.
-        let normalized_value = self.raw() + ctx.plain(E::Field::power_of_two(N));
-
-        let (mask, low, low_bits) = random_bit_mask(ctx, k);
-        let masked_value = normalized_value + mask;
-
-        // Check integrity of all computations so far, so attacker cannot compromise privacy.
-        ctx.ensure_integrity();
-
-        let masked_value = ctx.open_unchecked(masked_value).await;
-        let mut masked_value = masked_value.truncated();
-        if k < 64 {
-            masked_value %= 1 << k;
-        }
-
-        let (masked_less, _) = bitwise_compare(ctx, masked_value, &low_bits).await;
+        let (_, masked_low, _, low, masked_less) = self.open_masked(ctx, k).await;
         let correction = masked_less.raw() * T::Field::power_of_two(k);
 
-        Self::wrap(ctx.plain(masked_value.into()) - low + correction)
+        Self::wrap(ctx.plain(masked_low.into()) - low + correction)
     }
 
     /// Floor division of N-bit integer by 2^k.
@@ -168,8 +154,13 @@ impl<T: MpcShare, const N: usize> IntShare<T, N> {
         E: MpcEngine<Share = T>,
     {
         let k = cmp::min(k, N);
-        let remainder = self.mod_power_of_two(ctx, k).await;
-        Self::wrap((self.raw() - remainder.raw()) * T::Field::power_of_two_inverse(k))
+        let (masked_value, masked_low, high, _, masked_less) = self.open_masked(ctx, k).await;
+
+        // Quotient of normalized value is the quotient of masked value minus the high part of mask,
+        // minus the borrow from the low bits. Division of opened value is exact, so it works also over rings.
+        let masked_high = (masked_value - E::Field::from(masked_low)).exact_div_power_of_two(k);
+        let quotient = ctx.plain(masked_high) - high - masked_less.raw();
+        Self::wrap(quotient - ctx.plain(E::Field::power_of_two(N - k)))
     }
 
     /// Test if value is less than zero.
@@ -263,6 +254,43 @@ impl<T: MpcShare, const N: usize> IntShare<T, N> {
         (self - rhs).equal_zero(ctx).await
     }
 
+    /// Mask normalized value with random bit mask split at k-th bit and open it, for k <= N.
+    /// Returns opened masked value, its remainder modulo 2^k, sharings of the mask divided by 2^k
+    /// and of the mask modulo 2^k, and sharing of the borrow bit [masked value mod 2^k < mask mod 2^k].
+    async fn open_masked<E>(
+        self,
+        ctx: &MpcExecution<E>,
+        k: usize,
+    ) -> (E::Field, u64, T, T, BitShare<T>)
+    where
+        E: MpcEngine<Share = T>,
+    {
+        if k > N {
+            panic!("Too large k.");
+        }
+
+        // Adapted Mod2M algorithm from "Improved Primitives for Secure Multiparty Integer Computation"
+        // (https://citeseerx.ist.psu.edu/viewdoc/download?doi=10.1.1.220.9499&rep=rep1&type=pdf)
+
+        // Normalized value is in range [1; 2^(N+1)-1]. We only need the first k <= N bits.
+        let normalized_value = self.raw() + ctx.plain(E::Field::power_of_two(N));
+
+        let (high, low, low_bits) = random_split_bit_mask(ctx, k);
+        let masked_value = normalized_value + high * T::Field::power_of_two(k) + low;
+
+        // Check integrity of all computations so far, so attacker cannot compromise privacy.
+        ctx.ensure_integrity();
+
+        let masked_value = ctx.open_unchecked(masked_value).await;
+        let mut masked_low = masked_value.truncated();
+        if k < 64 {
+            masked_low %= 1 << k;
+        }
+
+        let (masked_less, _) = bitwise_compare(ctx, masked_low, &low_bits).await;
+        (masked_value, masked_low, high, low, masked_less)
+    }
+
     /// Returns max(low, min(high, self)).
     /// Warning: guarantees only statistical privacy with `Field::SAFE_BITS - N - 1` bits, input cannot be overflown.
     pub async fn clamp<E>(self, ctx: &MpcExecution<E>, low: Self, high: Self) -> Self
@@ -360,16 +388,30 @@ fn bits_to_raw_share<T: MpcShare>(bits: &[BitShare<T>]) -> T {
 
 /// Function PRandM from "Improved Primitives for Secure Multiparty Integer Computation".
 /// Returns sharing of a random integer X, sharing of X mod 2^k and separate sharings of the first k bits.
-/// The integer X has Field::SAFE_BITS bits and is provided by dealer.
+/// The integer X has Field::SAFE_BITS bits and is provided by dealer as a single preprocessed tuple.
 fn random_bit_mask<E: MpcEngine>(
     ctx: &MpcExecution<E>,
     k: usize,
 ) -> (E::Share, E::Share, Vec<BitShare<E::Share>>) {
-    let high_part = ctx.engine().dealer().next_uint(E::Field::SAFE_BITS - k);
-    let low_bits: Vec<_> = (0..k).map(|_| BitShare::random(ctx)).collect();
-    let low_part = bits_to_raw_share(&low_bits);
-    let mask = high_part * E::Field::power_of_two(k) + low_part;
-    (mask, low_part, low_bits)
+    let (mask, low_part, low_bits) = ctx.random_bit_mask(k);
+    (
+        mask,
+        low_part,
+        low_bits.into_iter().map(BitShare::wrap).collect(),
+    )
+}
+
+/// The same as `random_bit_mask`, but returns sharing of X div 2^k instead of X.
+fn random_split_bit_mask<E: MpcEngine>(
+    ctx: &MpcExecution<E>,
+    k: usize,
+) -> (E::Share, E::Share, Vec<BitShare<E::Share>>) {
+    let (high_part, low_part, low_bits) = ctx.random_split_bit_mask(k);
+    (
+        high_part,
+        low_part,
+        low_bits.into_iter().map(BitShare::wrap).collect(),
+    )
 }
 
 #[cfg(test)]
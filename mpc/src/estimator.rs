@@ -0,0 +1,514 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+};
+
+use async_trait::async_trait;
+use rand::{prelude::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    executor::{self, MpcExecution, MpcExecutionError},
+    fields::random_inverse_pair,
+    plaintext::PlainShare,
+    spdz::{OpeningStrategy, SpdzConfig},
+    MpcContext, MpcDealer, MpcEngine, MpcField,
+};
+
+/// Amounts of preprocessed data and communication consumed by MPC computation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub num_beaver_triples: usize,
+    pub num_square_pairs: usize,
+    pub num_inverse_pairs: usize,
+    pub num_random_bits: usize,
+    /// Number of random bit masks of each width.
+    pub num_bit_masks: BTreeMap<usize, usize>,
+    pub num_input_masks: Vec<usize>,
+    pub num_output_masks: Vec<usize>,
+    pub num_openings: usize,
+    /// Communication rounds of SPDZ protocol, including input, commitment and integrity-check rounds.
+    pub num_rounds: usize,
+}
+
+/// Error of resource estimation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EstimatorError {
+    /// Circuit processes a different number of inputs of party than given to estimator.
+    UnexpectedNumberOfInputs(usize),
+}
+
+impl fmt::Display for EstimatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::UnexpectedNumberOfInputs(id) => {
+                write!(f, "Unexpected number of inputs of party {}", id)
+            }
+        }
+    }
+}
+
+/// Mock MPC engine that computes result in plain on a single node (like `PlainMpcEngine`)
+/// and counts resources that would be consumed by SPDZ protocol with given configuration.
+/// Inputs of other parties are zeros.
+pub struct ResourceEstimator<T> {
+    _phantom: PhantomData<T>,
+    rng: SmallRng,
+    num_parties: usize,
+    party_id: usize,
+    config: SpdzConfig,
+    /// Numbers of inputs of all parties in each remaining call of `process_inputs`.
+    input_counts: VecDeque<Vec<usize>>,
+    /// Whether startup handshake with other parties was done.
+    started: bool,
+    num_unchecked_openings: usize,
+    rounds_since_check: usize,
+    usage: ResourceUsage,
+}
+
+impl<T> ResourceEstimator<T> {
+    /// Create new instance. Input counts hold numbers of inputs of all parties in each call of `process_inputs`,
+    /// i.e. inputs passed to `run_circuit` first and inputs requested in each round of circuit after them.
+    pub fn new(
+        num_parties: usize,
+        party_id: usize,
+        input_counts: Vec<Vec<usize>>,
+        config: SpdzConfig,
+    ) -> Self {
+        assert!(
+            input_counts
+                .iter()
+                .all(|counts| counts.len() == num_parties),
+            "Input counts must be given for all parties"
+        );
+        Self {
+            _phantom: PhantomData,
+            rng: SmallRng::from_entropy(),
+            num_parties,
+            party_id,
+            config,
+            input_counts: input_counts.into(),
+            started: false,
+            num_unchecked_openings: 0,
+            rounds_since_check: 0,
+            usage: ResourceUsage {
+                num_input_masks: vec![0; num_parties],
+                num_output_masks: vec![0; num_parties],
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Resources consumed so far.
+    pub fn usage(&self) -> &ResourceUsage {
+        &self.usage
+    }
+}
+
+impl<T> ResourceEstimator<T> {
+    /// Count startup handshake (and exchange of verification keys in identifiable-abort mode)
+    /// done by SPDZ engine with dealer of precomputed data before the first operation.
+    fn start(&mut self) {
+        if !self.started {
+            self.started = true;
+            self.usage.num_rounds += 1 + if self.config.identifiable_abort { 2 } else { 0 };
+        }
+    }
+
+    /// Count rounds of opening given number of values, followed by integrity check if it is due.
+    fn count_opening(&mut self, count: usize) {
+        self.start();
+        self.usage.num_openings += count;
+        self.usage.num_rounds += match self.config.opening_strategy {
+            OpeningStrategy::AllToAll => 1,
+            _ => 2,
+        };
+        self.num_unchecked_openings += count;
+        self.rounds_since_check += 1;
+
+        let check_forced = matches!(self.config.check_every_rounds,
+            Some(rounds) if self.rounds_since_check >= rounds);
+        if check_forced || self.num_unchecked_openings >= self.config.batch_check_threshold {
+            self.count_integrity_check();
+        }
+    }
+
+    /// Count rounds of integrity check: commitments to random point and to check values for each batch,
+    /// followed by state hash check.
+    fn count_integrity_check(&mut self) {
+        self.start();
+        let num_batches = self
+            .num_unchecked_openings
+            .div_ceil(self.config.max_batch_check_size);
+        self.usage.num_rounds += 4 * num_batches + self.state_hash_check_rounds();
+        self.num_unchecked_openings = 0;
+        self.rounds_since_check = 0;
+    }
+
+    /// Rounds of state hash check, which is followed by exchange of abort flags in identifiable-abort mode.
+    fn state_hash_check_rounds(&self) -> usize {
+        1 + usize::from(self.config.identifiable_abort)
+    }
+}
+
+impl<T: MpcField> ResourceEstimator<T> {
+    /// Random unsigned integer with given number of bits.
+    fn random_uint(&mut self, bits: usize) -> T {
+        (0..bits).fold(T::zero(), |acc, _| {
+            acc.double() + T::from(self.rng.gen_range(0..=1))
+        })
+    }
+}
+
+impl<T: MpcField> MpcContext for ResourceEstimator<T> {
+    type Field = T;
+    type Share = PlainShare<T>;
+
+    fn num_parties(&self) -> usize {
+        self.num_parties
+    }
+
+    fn party_id(&self) -> usize {
+        self.party_id
+    }
+}
+
+#[async_trait(?Send)]
+impl<T: MpcField> MpcEngine for ResourceEstimator<T> {
+    type Dealer = Self;
+    type Error = EstimatorError;
+
+    fn dealer(&mut self) -> &mut Self::Dealer {
+        self
+    }
+
+    async fn process_inputs(
+        &mut self,
+        inputs: Vec<Self::Field>,
+    ) -> Result<Vec<Vec<Self::Share>>, EstimatorError> {
+        let counts = match self.input_counts.pop_front() {
+            Some(counts) if counts[self.party_id] == inputs.len() => counts,
+            _ => return Err(EstimatorError::UnexpectedNumberOfInputs(self.party_id)),
+        };
+        // Masked inputs are broadcast and followed by state hash check.
+        self.start();
+        self.usage.num_rounds += 1 + self.state_hash_check_rounds();
+
+        Ok(counts
+            .into_iter()
+            .enumerate()
+            .map(|(id, count)| {
+                self.usage.num_input_masks[id] += count;
+                if id == self.party_id {
+                    inputs.iter().copied().map(PlainShare).collect()
+                } else {
+                    vec![PlainShare(T::zero()); count]
+                }
+            })
+            .collect())
+    }
+
+    async fn process_openings_unchecked(
+        &mut self,
+        requests: Vec<Self::Share>,
+    ) -> Result<Vec<Self::Field>, EstimatorError> {
+        self.count_opening(requests.len());
+        Ok(requests.iter().map(|r| r.0).collect())
+    }
+
+    async fn process_private_openings_unchecked(
+        &mut self,
+        requests: Vec<Self::Share>,
+        private_requests: Vec<(usize, Self::Share)>,
+    ) -> Result<(Vec<Self::Field>, Vec<Option<Self::Field>>), EstimatorError> {
+        // SPDZ masks each privately opened value with an output mask of its recipient
+        // and opens it together with partial openings.
+        for &(id, _) in &private_requests {
+            self.usage.num_output_masks[id] += 1;
+        }
+        self.count_opening(requests.len() + private_requests.len());
+        Ok((
+            requests.iter().map(|r| r.0).collect(),
+            private_requests
+                .iter()
+                .map(|&(id, r)| if id == self.party_id { Some(r.0) } else { None })
+                .collect(),
+        ))
+    }
+
+    async fn check_integrity(&mut self) -> Result<(), Self::Error> {
+        self.count_integrity_check();
+        Ok(())
+    }
+}
+
+impl<T: MpcField> MpcDealer for ResourceEstimator<T> {
+    fn share_plain(&self, x: Self::Field) -> Self::Share {
+        PlainShare(x)
+    }
+
+    fn next_beaver_triple(&mut self) -> (Self::Share, Self::Share, Self::Share) {
+        self.usage.num_beaver_triples += 1;
+        let a = Self::Field::random(&mut self.rng);
+        let b = Self::Field::random(&mut self.rng);
+        (PlainShare(a), PlainShare(b), PlainShare(a * b))
+    }
+
+    fn next_square_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        self.usage.num_square_pairs += 1;
+        let a = Self::Field::random(&mut self.rng);
+        Some((PlainShare(a), PlainShare(a.square())))
+    }
+
+    fn next_inverse_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        self.usage.num_inverse_pairs += 1;
+        let (r, r_inverse) = random_inverse_pair(&mut self.rng);
+        Some((PlainShare(r), PlainShare(r_inverse)))
+    }
+
+    fn next_uint(&mut self, bits: usize) -> Self::Share {
+        self.usage.num_random_bits += bits;
+        PlainShare(self.random_uint(bits))
+    }
+
+    fn next_bit_mask(&mut self, k: usize) -> (Self::Share, Self::Share, Vec<Self::Share>) {
+        *self.usage.num_bit_masks.entry(k).or_default() += 1;
+        let high_part = self.random_uint(Self::Field::SAFE_BITS - k);
+        let low_bits: Vec<_> = (0..k).map(|_| self.random_uint(1)).collect();
+        let low_part = low_bits
+            .iter()
+            .rev()
+            .fold(Self::Field::zero(), |acc, &x| acc.double() + x);
+        let mask = high_part * Self::Field::power_of_two(k) + low_part;
+        (
+            PlainShare(mask),
+            PlainShare(low_part),
+            low_bits.into_iter().map(PlainShare).collect(),
+        )
+    }
+
+    fn is_exhausted(&self) -> bool {
+        false
+    }
+}
+
+/// Execute async circuit using resource estimator, given numbers of inputs of all parties in each input round
+/// (see `ResourceEstimator::new`). Inputs of all parties are zeros.
+/// Returns output of circuit and resources consumed by it.
+pub async fn estimate_resources<T, F, R>(
+    party_id: usize,
+    input_counts: Vec<Vec<usize>>,
+    config: SpdzConfig,
+    circuit_fn: F,
+) -> Result<(R, ResourceUsage), MpcExecutionError<EstimatorError>>
+where
+    T: MpcField,
+    F: FnOnce(
+        &'_ MpcExecution<ResourceEstimator<T>>,
+        Vec<Vec<PlainShare<T>>>,
+    ) -> Pin<Box<dyn Future<Output = R> + '_>>,
+{
+    let num_parties = input_counts.first().map_or(0, |counts| counts.len());
+    let inputs = vec![T::zero(); input_counts.first().map_or(0, |counts| counts[party_id])];
+    let engine = ResourceEstimator::new(num_parties, party_id, input_counts, config);
+    let (output, _, engine) = executor::run_circuit(engine, &inputs, circuit_fn).await?;
+    Ok((output, engine.usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use futures::future;
+
+    use crate::{
+        circuits::{self, BitShare},
+        executor::{self, MpcExecution, MpcExecutionError},
+        fields::Mersenne127,
+        spdz::{
+            OpeningStrategy, PrecomputedSpdzData, PrecomputedSpdzDealer, SpdzConfig, SpdzCursor,
+            SpdzDealer, SpdzEngine,
+        },
+        transport, MpcEngine,
+    };
+
+    use super::{estimate_resources, EstimatorError, ResourceUsage};
+
+    /// Vector of default values, e.g. zero sharings.
+    fn zeros<V: Clone + Default>(count: usize) -> Vec<V> {
+        vec![V::default(); count]
+    }
+
+    /// Numbers of inputs of parties passed to circuit and requested during it.
+    fn input_counts() -> Vec<Vec<usize>> {
+        vec![vec![1, 0, 2], vec![0, 1, 0]]
+    }
+
+    /// Circuit using all kinds of data, where parties provide different numbers of inputs.
+    async fn circuit<E: MpcEngine>(
+        ctx: &MpcExecution<E>,
+        inputs: Vec<Vec<E::Share>>,
+    ) -> Option<E::Field> {
+        let product = circuits::mul(ctx, inputs[0][0], inputs[2][1]).await;
+        let party_id = ctx.engine().party_id();
+        let extra = match party_id {
+            1 => ctx.input_own(vec![5u64.into()]).await,
+            _ => ctx.input_from(1, 1).await,
+        };
+        let _ = BitShare::random(ctx);
+        let _ = ctx.square_pair();
+        let _ = ctx.random_bit_mask(4);
+        // Partial and private openings in the same round are merged.
+        let (_, output) = futures::join!(
+            ctx.open_unchecked(inputs[2][0] + extra[0]),
+            ctx.open_to(0, product)
+        );
+        output
+    }
+
+    #[tokio::test]
+    async fn test_estimate_resources() {
+        let (output, usage) = estimate_resources::<Mersenne127, _, _>(
+            1,
+            input_counts(),
+            SpdzConfig::default(),
+            |ctx, inputs| Box::pin(circuit(ctx, inputs)),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(output, None);
+        assert_eq!(
+            usage,
+            ResourceUsage {
+                num_beaver_triples: 1,
+                num_square_pairs: 1,
+                num_inverse_pairs: 0,
+                num_random_bits: 1,
+                num_bit_masks: BTreeMap::from([(4, 1)]),
+                num_input_masks: vec![1, 1, 2],
+                num_output_masks: vec![1, 0, 0],
+                num_openings: 4,
+                // Startup, two input rounds, two openings and final integrity check.
+                num_rounds: 1 + 2 * 2 + 2 * 2 + 5,
+            }
+        );
+
+        // Party 1 provides one input during the circuit, while party 0 is expected to provide another one.
+        let estimate_with_counts = |counts| {
+            estimate_resources::<Mersenne127, _, _>(
+                1,
+                counts,
+                SpdzConfig::default(),
+                |ctx, inputs| Box::pin(circuit(ctx, inputs)),
+            )
+        };
+        let result = estimate_with_counts(vec![vec![1, 0, 2], vec![0, 2, 0]]).await;
+        assert_eq!(
+            result.err(),
+            Some(MpcExecutionError::Engine(
+                EstimatorError::UnexpectedNumberOfInputs(1)
+            ))
+        );
+        let result = estimate_with_counts(vec![vec![1, 0, 2], vec![1, 1, 0]]).await;
+        assert_eq!(
+            result.err(),
+            Some(MpcExecutionError::UnexpectedNumberOfInputs(0))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_estimate_matches_spdz() {
+        let configs = [
+            SpdzConfig::default(),
+            SpdzConfig {
+                check_every_rounds: Some(1),
+                opening_strategy: OpeningStrategy::AllToAll,
+                identifiable_abort: true,
+                ..Default::default()
+            },
+            SpdzConfig {
+                batch_check_threshold: 1,
+                max_batch_check_size: 1,
+                opening_strategy: OpeningStrategy::PartitionedKings,
+                ..Default::default()
+            },
+        ];
+        for config in configs {
+            let (_, usage) = estimate_resources::<Mersenne127, _, _>(
+                0,
+                input_counts(),
+                config,
+                |ctx, inputs| Box::pin(circuit(ctx, inputs)),
+            )
+            .await
+            .unwrap();
+
+            // Zero sharings are valid for any MAC key, so data of the estimated size is easy to provide.
+            let channels = transport::memory_multiparty_channels(3);
+            let results = future::join_all(channels.into_iter().enumerate().map(
+                |(party_id, transport)| {
+                    let data = PrecomputedSpdzData::<Mersenne127> {
+                        num_parties: 3,
+                        party_id,
+                        beaver_triples: zeros(usage.num_beaver_triples),
+                        square_pairs: zeros(usage.num_square_pairs),
+                        inverse_pairs: zeros(usage.num_inverse_pairs),
+                        random_bits: zeros(usage.num_random_bits),
+                        bit_masks: usage
+                            .num_bit_masks
+                            .iter()
+                            .map(|(&width, &count)| {
+                                let mask = Default::default();
+                                (width, vec![(mask, mask, vec![mask; width]); count])
+                            })
+                            .collect(),
+                        input_masks: usage
+                            .num_input_masks
+                            .iter()
+                            .map(|&count| zeros(count))
+                            .collect(),
+                        input_masks_plain: zeros(usage.num_input_masks[party_id]),
+                        output_masks: usage
+                            .num_output_masks
+                            .iter()
+                            .map(|&count| zeros(count))
+                            .collect(),
+                        output_masks_plain: zeros(usage.num_output_masks[party_id]),
+                        ..Default::default()
+                    };
+                    let dealer = PrecomputedSpdzDealer::new(data);
+                    let engine: SpdzEngine<_, _, _> = SpdzEngine::new(dealer, transport, config);
+                    let inputs = vec![2u64.into(); input_counts()[0][party_id]];
+                    async move {
+                        let (_, _, engine) =
+                            executor::run_circuit(engine, &inputs, |ctx, inputs| {
+                                Box::pin(circuit(ctx, inputs))
+                            })
+                            .await
+                            .unwrap();
+                        let num_rounds = engine.num_rounds();
+                        (engine.into_dealer().cursor().unwrap(), num_rounds)
+                    }
+                },
+            ))
+            .await;
+
+            let expected_cursor = SpdzCursor {
+                beaver_triples: usage.num_beaver_triples,
+                square_pairs: usage.num_square_pairs,
+                inverse_pairs: usage.num_inverse_pairs,
+                random_bits: usage.num_random_bits,
+                bit_masks: usage.num_bit_masks.clone(),
+                input_masks: usage.num_input_masks.clone(),
+                output_masks: usage.num_output_masks.clone(),
+            };
+            for (cursor, num_rounds) in results {
+                assert_eq!(cursor, expected_cursor);
+                assert_eq!(num_rounds, usage.num_rounds, "{:?}", config);
+            }
+        }
+    }
+}
@@ -1,22 +1,28 @@
 use std::{
     cell::{Cell, RefCell, RefMut},
+    collections::{BTreeMap, BTreeSet},
     fmt,
     future::Future,
     mem,
     pin::Pin,
     task::Poll,
     thread,
+    time::{Duration, Instant},
 };
 
 use tokio::sync::oneshot;
 
-use crate::{MpcDealer, MpcEngine, MpcShare};
+use crate::{circuits::join_circuits_all, MpcDealer, MpcEngine, MpcShare};
+
+/// Request for secret inputs: (owner ID, number of inputs, values if owned by current party).
+type InputRequest<T> = (usize, usize, Vec<T>);
 
 /// Error during MPC circuit execution.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum MpcExecutionError<T> {
     Engine(T),
     DealerExhausted,
+    UnexpectedNumberOfInputs(usize),
 }
 
 impl<T: fmt::Display> fmt::Display for MpcExecutionError<T> {
@@ -24,6 +30,9 @@ impl<T: fmt::Display> fmt::Display for MpcExecutionError<T> {
         match *self {
             Self::Engine(ref err) => err.fmt(f),
             Self::DealerExhausted => write!(f, "Dealer exhausted"),
+            Self::UnexpectedNumberOfInputs(id) => {
+                write!(f, "Received unexpected number of inputs from {}", id)
+            }
         }
     }
 }
@@ -35,20 +44,46 @@ impl<T> From<T> for MpcExecutionError<T> {
 }
 
 /// Statistics collected during MPC execution.
-#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct MpcExecutionStats {
     pub num_openings: usize,
     pub num_rounds: usize,
     pub num_integrity_checks: usize,
+    pub labels: BTreeMap<&'static str, MpcLabelStats>,
+}
+
+/// Statistics collected for circuit code profiled under given label (see `MpcExecution::profile`).
+/// Nested labels are inclusive, i.e. work done under inner label counts towards outer labels too.
+/// Rounds are counted whenever labelled code waits for communication.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MpcLabelStats {
+    pub num_openings: usize,
+    pub num_rounds: usize,
+    pub num_beaver_triples: usize,
+    pub num_square_pairs: usize,
+    pub num_inverse_pairs: usize,
+    pub num_random_bits: usize,
+    pub num_bit_masks: usize,
+    /// Input masks of all parties consumed by inputs provided during circuit execution.
+    pub num_input_masks: usize,
+    /// Output masks consumed by private openings.
+    pub num_output_masks: usize,
+    pub time: Duration,
 }
 
 /// MPC async circuit execution context.
 pub struct MpcExecution<Engine: MpcEngine> {
-    engine: RefCell<Engine>,
+    /// Engine is taken out by executor while it communicates, so that no borrow is held across await points.
+    engine: RefCell<Option<Engine>>,
     open_buffer: RoundCommandBuffer<Engine::Share, Engine::Field>,
+    private_open_buffer: RoundCommandBuffer<(usize, Engine::Share), Option<Engine::Field>>,
+    input_buffer: RoundCommandBuffer<InputRequest<Engine::Field>, Vec<Engine::Share>>,
     force_integrity_check: Cell<bool>,
     cached_one: Engine::Share,
     cached_two: Engine::Share,
+    label_stack: RefCell<Vec<&'static str>>,
+    label_stats: RefCell<BTreeMap<&'static str, MpcLabelStats>>,
+    round_labels: RefCell<BTreeSet<&'static str>>,
 }
 
 impl<Engine: MpcEngine> MpcExecution<Engine> {
@@ -56,25 +91,194 @@ impl<Engine: MpcEngine> MpcExecution<Engine> {
     fn new(mut engine: Engine) -> Self {
         let one = engine.dealer().share_plain(ff::Field::one());
         MpcExecution {
-            engine: RefCell::new(engine),
+            engine: RefCell::new(Some(engine)),
             open_buffer: RoundCommandBuffer::new(),
+            private_open_buffer: RoundCommandBuffer::new(),
+            input_buffer: RoundCommandBuffer::new(),
             force_integrity_check: Cell::new(false),
             cached_one: one,
             cached_two: one.double(),
+            label_stack: RefCell::new(Vec::new()),
+            label_stats: RefCell::new(BTreeMap::new()),
+            round_labels: RefCell::new(BTreeSet::new()),
         }
     }
 
     /// Get underlying MPC engine.
     pub fn engine(&self) -> RefMut<Engine> {
-        self.engine.borrow_mut()
+        RefMut::map(self.engine.borrow_mut(), |engine| {
+            engine.as_mut().expect("Engine is used by executor")
+        })
+    }
+
+    /// Take underlying MPC engine for communication, while circuit is not being polled.
+    fn take_engine(&self) -> Engine {
+        self.engine
+            .take()
+            .expect("Engine is already used by executor")
+    }
+
+    /// Return MPC engine taken by `take_engine`.
+    fn put_engine(&self, engine: Engine) {
+        self.engine.replace(Some(engine));
     }
 
     /// Open provided share. Requires communication.
     /// Warning: Integrity checks may be deferred (like in SPDZ protocol). Use with care.
     pub async fn open_unchecked(&self, input: Engine::Share) -> Engine::Field {
+        self.record_communication(1);
         self.open_buffer.queue(input).await
     }
 
+    /// Open provided share only to party with given ID. Requires communication.
+    /// Returns opened value to the recipient and `None` to other parties.
+    /// Opened value is authenticated, but checks may be deferred until the end of circuit. Use with care.
+    pub async fn open_to(&self, party_id: usize, input: Engine::Share) -> Option<Engine::Field> {
+        self.record_usage(|stats| stats.num_output_masks += 1);
+        self.record_communication(1);
+        self.private_open_buffer.queue((party_id, input)).await
+    }
+
+    /// Open i-th share only to party with ID i. Requires communication.
+    /// Returns value opened to current party.
+    /// Opened values are authenticated, but checks may be deferred until the end of circuit. Use with care.
+    pub async fn open_to_each(&self, inputs: Vec<Engine::Share>) -> Engine::Field {
+        let (num_parties, party_id) = {
+            let engine = self.engine();
+            (engine.num_parties(), engine.party_id())
+        };
+        if inputs.len() != num_parties {
+            panic!("Expected exactly one share for each party");
+        }
+        let outputs = join_circuits_all(
+            inputs
+                .into_iter()
+                .enumerate()
+                .map(|(id, input)| self.open_to(id, input)),
+        )
+        .await;
+        outputs[party_id].expect("Value was not opened to its recipient")
+    }
+
+    /// Provide new secret inputs of current party during circuit execution. Requires communication.
+    /// Other parties are expected to call `input_from` with matching count at the same point of circuit.
+    pub async fn input_own(&self, values: Vec<Engine::Field>) -> Vec<Engine::Share> {
+        let party_id = self.engine().party_id();
+        self.record_usage(|stats| stats.num_input_masks += values.len());
+        self.record_communication(0);
+        self.input_buffer
+            .queue((party_id, values.len(), values))
+            .await
+    }
+
+    /// Receive sharings of `count` new secret inputs of party with given ID. Requires communication.
+    /// Panics if called with ID of current party, use `input_own` instead.
+    pub async fn input_from(&self, party_id: usize, count: usize) -> Vec<Engine::Share> {
+        let (num_parties, own_id) = {
+            let engine = self.engine();
+            (engine.num_parties(), engine.party_id())
+        };
+        if party_id == own_id {
+            panic!("Own inputs must be provided using input_own");
+        }
+        if party_id >= num_parties {
+            panic!("Invalid party ID");
+        }
+        self.record_usage(|stats| stats.num_input_masks += count);
+        self.record_communication(0);
+        self.input_buffer.queue((party_id, count, Vec::new())).await
+    }
+
+    /// Sharing of next Beaver triple provided by dealer.
+    pub fn beaver_triple(&self) -> (Engine::Share, Engine::Share, Engine::Share) {
+        self.record_usage(|stats| stats.num_beaver_triples += 1);
+        self.engine().dealer().next_beaver_triple()
+    }
+
+    /// Sharing of next square pair (a, a^2) provided by dealer, if it has square pairs.
+    pub fn square_pair(&self) -> Option<(Engine::Share, Engine::Share)> {
+        let pair = self.engine().dealer().next_square_pair();
+        if pair.is_some() {
+            self.record_usage(|stats| stats.num_square_pairs += 1);
+        }
+        pair
+    }
+
+    /// Sharing of next inverse pair (r, r^-1) provided by dealer, if it has inverse pairs.
+    pub fn inverse_pair(&self) -> Option<(Engine::Share, Engine::Share)> {
+        let pair = self.engine().dealer().next_inverse_pair();
+        if pair.is_some() {
+            self.record_usage(|stats| stats.num_inverse_pairs += 1);
+        }
+        pair
+    }
+
+    /// Sharing of random unsigned integer with given number of bits provided by dealer.
+    pub fn random_uint(&self, bits: usize) -> Engine::Share {
+        self.record_usage(|stats| stats.num_random_bits += bits);
+        self.engine().dealer().next_uint(bits)
+    }
+
+    /// Sharing of random `Field::SAFE_BITS`-bit integer, its remainder modulo 2^k and separate sharings
+    /// of its first k bits provided by dealer.
+    pub fn random_bit_mask(&self, k: usize) -> (Engine::Share, Engine::Share, Vec<Engine::Share>) {
+        self.record_usage(|stats| stats.num_bit_masks += 1);
+        self.engine().dealer().next_bit_mask(k)
+    }
+
+    /// The same as `random_bit_mask`, but returns sharing of the integer divided by 2^k instead of the integer.
+    pub fn random_split_bit_mask(
+        &self,
+        k: usize,
+    ) -> (Engine::Share, Engine::Share, Vec<Engine::Share>) {
+        self.record_usage(|stats| stats.num_bit_masks += 1);
+        self.engine().dealer().next_split_bit_mask(k)
+    }
+
+    /// Run future under given label. Resources consumed and time spent by the future
+    /// are reported in `MpcExecutionStats::labels`.
+    pub async fn profile<F: Future>(&self, label: &'static str, future: F) -> F::Output {
+        let start_time = Instant::now();
+        futures::pin_mut!(future);
+
+        let output = futures::future::poll_fn(|cx| {
+            self.label_stack.borrow_mut().push(label);
+            let poll = future.as_mut().poll(cx);
+            self.label_stack.borrow_mut().pop();
+            poll
+        })
+        .await;
+
+        self.label_stats.borrow_mut().entry(label).or_default().time += start_time.elapsed();
+        output
+    }
+
+    /// Update statistics of all labels that are currently active.
+    fn record_usage(&self, update: impl Fn(&mut MpcLabelStats)) {
+        let stack = self.label_stack.borrow();
+        let mut label_stats = self.label_stats.borrow_mut();
+        for (i, &label) in stack.iter().enumerate() {
+            if !stack[..i].contains(&label) {
+                update(label_stats.entry(label).or_default());
+            }
+        }
+    }
+
+    /// Mark currently active labels as waiting for communication in this round.
+    fn record_communication(&self, num_openings: usize) {
+        self.record_usage(|stats| stats.num_openings += num_openings);
+        let stack = self.label_stack.borrow();
+        self.round_labels.borrow_mut().extend(stack.iter().copied());
+    }
+
+    /// Add rounds executed in last iteration to labels that were waiting for communication.
+    fn record_rounds(&self, num_rounds: usize) {
+        let mut label_stats = self.label_stats.borrow_mut();
+        for label in mem::take(&mut *self.round_labels.borrow_mut()) {
+            label_stats.entry(label).or_default().num_rounds += num_rounds;
+        }
+    }
+
     /// Ensure integrity of everything computed so far.
     /// The check will be executed at the beginning of next round.
     pub fn ensure_integrity(&self) {
@@ -97,12 +301,13 @@ impl<Engine: MpcEngine> MpcExecution<Engine> {
     }
 }
 
-/// Execute async circuit.
+/// Execute async circuit. Returns engine after successful execution,
+/// so it can be reused for further computations on shares returned from circuit.
 pub async fn run_circuit<Engine, F, T>(
     mut engine: Engine,
     inputs: &[Engine::Field],
     circuit_fn: F,
-) -> Result<(T, MpcExecutionStats), MpcExecutionError<Engine::Error>>
+) -> Result<(T, MpcExecutionStats, Engine), MpcExecutionError<Engine::Error>>
 where
     Engine: MpcEngine,
     F: FnOnce(
@@ -118,43 +323,133 @@ where
     let mut future = circuit_fn(&ctx, input_shares);
     let mut stats = MpcExecutionStats::default();
 
-    loop {
+    let (outputs, engine) = loop {
         let poll = futures::poll!(future.as_mut());
-        if ctx.engine().dealer().is_exhausted() {
+        let mut engine = ctx.take_engine();
+        if engine.dealer().is_exhausted() {
             return Err(MpcExecutionError::DealerExhausted);
         }
 
         if let Poll::Ready(outputs) = poll {
             stats.num_integrity_checks += 1;
-            ctx.engine().check_integrity().await?;
-            return Ok((outputs, stats));
+            engine.check_integrity().await?;
+            break (outputs, engine);
         }
 
         if ctx.force_integrity_check.get() {
             stats.num_integrity_checks += 1;
-            ctx.engine().check_integrity().await?;
+            engine.check_integrity().await?;
             ctx.force_integrity_check.set(false);
         }
 
+        let rounds_before = stats.num_rounds;
         let requests = ctx.open_buffer.take_requests();
-        if requests.is_empty() {
+        let private_requests = ctx.private_open_buffer.take_requests();
+        let input_requests = ctx.input_buffer.take_requests();
+        if requests.is_empty() && private_requests.is_empty() && input_requests.is_empty() {
             panic!("Circuit didn't make progress");
         }
 
-        stats.num_openings += requests.len();
-        stats.num_rounds += 1;
+        // Partial and private openings are processed together in a single round.
+        if !private_requests.is_empty() {
+            stats.num_openings += requests.len() + private_requests.len();
+            stats.num_rounds += 1;
+
+            let (responses, private_responses) = engine
+                .process_private_openings_unchecked(requests, private_requests)
+                .await?;
+            if !responses.is_empty() {
+                ctx.open_buffer.resolve_all(responses);
+            }
+            ctx.private_open_buffer.resolve_all(private_responses);
+        } else if !requests.is_empty() {
+            stats.num_openings += requests.len();
+            stats.num_rounds += 1;
+
+            let responses = engine.process_openings_unchecked(requests).await?;
+            ctx.open_buffer.resolve_all(responses);
+        }
+
+        if !input_requests.is_empty() {
+            stats.num_rounds += 1;
+
+            let own_inputs = input_requests
+                .iter()
+                .flat_map(|(_, _, values)| values.iter().copied())
+                .collect();
+            let shares = engine.process_inputs(own_inputs).await?;
+            ctx.input_buffer
+                .resolve_all(split_inputs(&input_requests, shares)?);
+        }
 
-        let responses = ctx.engine().process_openings_unchecked(requests).await?;
-        ctx.open_buffer.resolve_all(responses);
+        ctx.put_engine(engine);
+        ctx.record_rounds(stats.num_rounds - rounds_before);
+    };
+
+    drop(future);
+    stats.labels = ctx.label_stats.take();
+    Ok((outputs, stats, engine))
+}
+
+/// Split input shares returned by engine between input requests issued in one round.
+fn split_inputs<T, S, E>(
+    requests: &[InputRequest<T>],
+    shares: Vec<Vec<S>>,
+) -> Result<Vec<Vec<S>>, MpcExecutionError<E>> {
+    let mut expected_counts = vec![0; shares.len()];
+    for &(party_id, count, _) in requests {
+        expected_counts[party_id] += count;
     }
+
+    for (party_id, party_shares) in shares.iter().enumerate() {
+        if party_shares.len() != expected_counts[party_id] {
+            return Err(MpcExecutionError::UnexpectedNumberOfInputs(party_id));
+        }
+    }
+
+    let mut shares: Vec<_> = shares.into_iter().map(|x| x.into_iter()).collect();
+    Ok(requests
+        .iter()
+        .map(|&(party_id, count, _)| shares[party_id].by_ref().take(count).collect())
+        .collect())
+}
+
+/// Execute the same async circuit for all parties in-process, e.g. in tests.
+/// Engine and inputs of party `i` are at index `i`. Results are returned once all parties finish,
+/// so engines of parties that finished early keep their connections open.
+pub async fn run_circuit_for_all_parties<Engine, F, T>(
+    engines: Vec<Engine>,
+    inputs: &[Vec<Engine::Field>],
+    circuit_fn: F,
+) -> Vec<Result<(T, MpcExecutionStats, Engine), MpcExecutionError<Engine::Error>>>
+where
+    Engine: MpcEngine,
+    F: Copy
+        + Fn(
+            &'_ MpcExecution<Engine>,
+            Vec<Vec<Engine::Share>>,
+        ) -> Pin<Box<dyn Future<Output = T> + '_>>,
+{
+    assert_eq!(
+        engines.len(),
+        inputs.len(),
+        "Inputs missing for some parties"
+    );
+    futures::future::join_all(
+        engines
+            .into_iter()
+            .zip(inputs)
+            .map(|(engine, inputs)| run_circuit(engine, inputs, circuit_fn)),
+    )
+    .await
 }
 
-/// Execute async circuit on a dedicated thread.
+/// Execute async circuit on a dedicated thread. Returns engine after successful execution.
 pub async fn run_circuit_in_background<Engine, Error, F, T>(
     engine: Engine,
     inputs: Vec<Engine::Field>,
     circuit_fn: F,
-) -> Result<(T, MpcExecutionStats), MpcExecutionError<Engine::Error>>
+) -> Result<(T, MpcExecutionStats, Engine), MpcExecutionError<Engine::Error>>
 where
     Engine: 'static + Send + MpcEngine<Error = Error>,
     Error: 'static + Send,
@@ -168,7 +463,9 @@ where
 {
     let (sender, receiver) = oneshot::channel();
     thread::spawn(move || {
+        // Timers are used by transport timeouts.
         let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
             .build()
             .unwrap();
         let future = run_circuit(engine, &inputs, circuit_fn);
@@ -249,3 +546,44 @@ impl<T, S> RoundCommandBuffer<T, S> {
         self.first_unpolled_response.set(0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::circuits::{self, testing::*};
+
+    use super::run_circuit;
+
+    #[tokio::test]
+    async fn test_profile() {
+        let (output, stats, _) = run_circuit(MockEngine::new(), &[], |ctx, _| {
+            Box::pin(async move {
+                let x = ctx.plain(3.into());
+                ctx.profile("outer", async {
+                    let y = ctx.profile("mul", circuits::mul(ctx, x, x)).await;
+                    let z = ctx.input_own(vec![2.into(), 5.into()]).await;
+                    ctx.open_to(0, y + z[1]).await
+                })
+                .await
+            })
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(output, Some(14.into()));
+        assert_eq!(stats.num_rounds, 3);
+        assert_eq!(stats.labels.len(), 2);
+
+        let outer = stats.labels["outer"];
+        assert_eq!(outer.num_openings, 3);
+        assert_eq!(outer.num_rounds, 3);
+        assert_eq!(outer.num_beaver_triples, 1);
+        assert_eq!(outer.num_input_masks, 2);
+        assert_eq!(outer.num_output_masks, 1);
+
+        let mul = stats.labels["mul"];
+        assert_eq!(mul.num_openings, 2);
+        assert_eq!(mul.num_rounds, 1);
+        assert_eq!(mul.num_beaver_triples, 1);
+        assert!(mul.time <= outer.time);
+    }
+}
@@ -1,3 +1,52 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Identifier of field implemented in this module.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldType {
+    Mersenne31,
+    Mersenne61,
+    Mersenne127,
+    Goldilocks,
+    Ring64,
+}
+
+impl FromStr for FieldType {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "31" => Ok(FieldType::Mersenne31),
+            "61" => Ok(FieldType::Mersenne61),
+            "127" => Ok(FieldType::Mersenne127),
+            "goldilocks" => Ok(FieldType::Goldilocks),
+            _ => Err("Unsupported field type. Available options: 31, 61, 127, goldilocks"),
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Mersenne31 => write!(f, "31"),
+            Self::Mersenne61 => write!(f, "61"),
+            Self::Mersenne127 => write!(f, "127"),
+            Self::Goldilocks => write!(f, "goldilocks"),
+            Self::Ring64 => write!(f, "ring64"),
+        }
+    }
+}
+
+/// Random nonzero field element and its inverse.
+pub fn random_inverse_pair<T: ff::Field>(mut rng: impl rand::RngCore) -> (T, T) {
+    loop {
+        let r = T::random(&mut rng);
+        if let Some(r_inverse) = Option::from(r.invert()) {
+            return (r, r_inverse);
+        }
+    }
+}
+
 /// Precomputed powers of two and their inverses.
 struct PowersOfTwo<T, const N: usize> {
     pub powers: [T; N],
@@ -17,13 +66,100 @@ impl<T: ff::Field, const N: usize> PowersOfTwo<T, N> {
     }
 }
 
+mod mersenne_31 {
+    use ff::PrimeField;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::MpcField;
+
+    use super::{FieldType, PowersOfTwo};
+
+    /// Finite field mod 2^31-1.
+    #[derive(PrimeField)]
+    #[PrimeFieldModulus = "2147483647"]
+    #[PrimeFieldGenerator = "7"]
+    #[PrimeFieldReprEndianness = "little"]
+    pub struct Mersenne31([u64; 1]);
+
+    #[static_init::dynamic]
+    static POWERS_OF_TWO: PowersOfTwo<Mersenne31, { Mersenne31::SAFE_BITS + 1 }> =
+        PowersOfTwo::precompute();
+
+    impl MpcField for Mersenne31 {
+        const FIELD_TYPE: FieldType = FieldType::Mersenne31;
+        const SAFE_BITS: usize = 30;
+
+        fn power_of_two(k: usize) -> Self {
+            POWERS_OF_TWO.powers[k]
+        }
+
+        fn power_of_two_inverse(k: usize) -> Self {
+            POWERS_OF_TWO.inverses[k]
+        }
+
+        fn truncated(&self) -> u64 {
+            // ff::PrimeField stores value multiplied by constant.
+            // We can invert it by multiplying with element that has representation [1].
+            const R2_INV: Mersenne31 = Mersenne31([1]);
+            (*self * R2_INV).0[0]
+        }
+    }
+
+    impl Serialize for Mersenne31 {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.to_repr().0.serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Mersenne31 {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = Mersenne31Repr(Deserialize::deserialize(deserializer)?);
+            Self::from_repr_vartime(repr)
+                .ok_or_else(|| serde::de::Error::custom("Invalid field element"))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::MpcField;
+
+        use super::Mersenne31;
+
+        #[test]
+        fn test_serialization() {
+            let value = Mersenne31::from(1234567890);
+            let encoded = bincode::serialize(&value).unwrap();
+            let decoded = bincode::deserialize(&encoded).unwrap();
+            assert_eq!(value, decoded);
+        }
+
+        #[test]
+        fn test_truncation() {
+            let int_value = 1234567890;
+            let field_value = Mersenne31::from(int_value);
+            let trunc_value = field_value.truncated();
+            assert_eq!(trunc_value, int_value);
+        }
+
+        #[test]
+        fn test_powers_of_two() {
+            let value = Mersenne31::power_of_two(Mersenne31::SAFE_BITS);
+            assert_eq!(value.truncated(), 1 << 30);
+            assert_eq!(
+                value * Mersenne31::power_of_two_inverse(30),
+                Mersenne31::from(1)
+            );
+        }
+    }
+}
+
 mod mersenne_61 {
     use ff::PrimeField;
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
     use crate::MpcField;
 
-    use super::PowersOfTwo;
+    use super::{FieldType, PowersOfTwo};
 
     /// Finite field mod 2^61-1.
     #[derive(PrimeField)]
@@ -37,6 +173,7 @@ mod mersenne_61 {
         PowersOfTwo::precompute();
 
     impl MpcField for Mersenne61 {
+        const FIELD_TYPE: FieldType = FieldType::Mersenne61;
         const SAFE_BITS: usize = 60;
 
         fn power_of_two(k: usize) -> Self {
@@ -99,7 +236,7 @@ mod mersenne_127 {
 
     use crate::MpcField;
 
-    use super::PowersOfTwo;
+    use super::{FieldType, PowersOfTwo};
 
     /// Finite field mod 2^127-1.
     #[derive(PrimeField)]
@@ -113,6 +250,7 @@ mod mersenne_127 {
         PowersOfTwo::precompute();
 
     impl MpcField for Mersenne127 {
+        const FIELD_TYPE: FieldType = FieldType::Mersenne127;
         const SAFE_BITS: usize = 126;
 
         fn power_of_two(k: usize) -> Self {
@@ -171,5 +309,314 @@ mod mersenne_127 {
     }
 }
 
+mod goldilocks {
+    use ff::PrimeField;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::MpcField;
+
+    use super::{FieldType, PowersOfTwo};
+
+    /// Finite field mod 2^64-2^32+1.
+    #[derive(PrimeField)]
+    #[PrimeFieldModulus = "18446744069414584321"]
+    #[PrimeFieldGenerator = "7"]
+    #[PrimeFieldReprEndianness = "little"]
+    pub struct Goldilocks([u64; 2]);
+
+    #[static_init::dynamic]
+    static POWERS_OF_TWO: PowersOfTwo<Goldilocks, { Goldilocks::SAFE_BITS + 1 }> =
+        PowersOfTwo::precompute();
+
+    impl MpcField for Goldilocks {
+        const FIELD_TYPE: FieldType = FieldType::Goldilocks;
+        const SAFE_BITS: usize = 62;
+
+        fn power_of_two(k: usize) -> Self {
+            POWERS_OF_TWO.powers[k]
+        }
+
+        fn power_of_two_inverse(k: usize) -> Self {
+            POWERS_OF_TWO.inverses[k]
+        }
+
+        fn truncated(&self) -> u64 {
+            // ff::PrimeField stores value multiplied by constant.
+            // We can invert it by multiplying with element that has representation [1, 0].
+            // Modulus is smaller than 2^64, so the lower limb contains the whole value.
+            const R2_INV: Goldilocks = Goldilocks([1, 0]);
+            (*self * R2_INV).0[0]
+        }
+    }
+
+    impl Serialize for Goldilocks {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.to_repr().0.serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Goldilocks {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = GoldilocksRepr(Deserialize::deserialize(deserializer)?);
+            Self::from_repr_vartime(repr)
+                .ok_or_else(|| serde::de::Error::custom("Invalid field element"))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::MpcField;
+
+        use super::Goldilocks;
+
+        #[test]
+        fn test_serialization() {
+            let value = Goldilocks::from(12345678901234567890);
+            let encoded = bincode::serialize(&value).unwrap();
+            let decoded = bincode::deserialize(&encoded).unwrap();
+            assert_eq!(value, decoded);
+        }
+
+        #[test]
+        fn test_truncation() {
+            let int_value = 12345678901234567890;
+            let field_value = Goldilocks::from(int_value);
+            let trunc_value = field_value.truncated();
+            assert_eq!(trunc_value, int_value);
+            assert_eq!((-Goldilocks::from(1)).truncated(), 18446744069414584320);
+        }
+
+        #[test]
+        fn test_powers_of_two() {
+            let value = Goldilocks::power_of_two(Goldilocks::SAFE_BITS);
+            assert_eq!(value.truncated(), 1 << 62);
+            assert_eq!(
+                value * Goldilocks::power_of_two_inverse(62),
+                Goldilocks::from(1)
+            );
+        }
+    }
+}
+
+mod ring_64 {
+    use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+    use ff::{Field, PrimeField};
+    use rand::RngCore;
+    use serde::{Deserialize, Serialize};
+    use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, CtOption};
+
+    use crate::MpcField;
+
+    use super::FieldType;
+
+    /// Ring of integers modulo 2^64, used by SPDZ2k protocol. It is not a field: only odd elements
+    /// are invertible, 2^k is not invertible for k > 0 and square roots are not supported,
+    /// so it can be used only with circuits that don't compute inverses.
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(transparent)]
+    pub struct Ring64(u64);
+
+    macro_rules! impl_ring_op {
+        ($op:ident, $op_fn:ident, $op_assign:ident, $op_assign_fn:ident, $wrapping_fn:ident) => {
+            impl $op for Ring64 {
+                type Output = Ring64;
+                fn $op_fn(self, rhs: Self) -> Self::Output {
+                    Ring64(self.0.$wrapping_fn(rhs.0))
+                }
+            }
+
+            impl<'a> $op<&'a Ring64> for Ring64 {
+                type Output = Ring64;
+                fn $op_fn(self, rhs: &'a Ring64) -> Self::Output {
+                    Ring64(self.0.$wrapping_fn(rhs.0))
+                }
+            }
+
+            impl $op_assign for Ring64 {
+                fn $op_assign_fn(&mut self, rhs: Self) {
+                    self.0 = self.0.$wrapping_fn(rhs.0);
+                }
+            }
+
+            impl<'a> $op_assign<&'a Ring64> for Ring64 {
+                fn $op_assign_fn(&mut self, rhs: &'a Ring64) {
+                    self.0 = self.0.$wrapping_fn(rhs.0);
+                }
+            }
+        };
+    }
+
+    impl_ring_op!(Add, add, AddAssign, add_assign, wrapping_add);
+    impl_ring_op!(Sub, sub, SubAssign, sub_assign, wrapping_sub);
+    impl_ring_op!(Mul, mul, MulAssign, mul_assign, wrapping_mul);
+
+    impl Neg for Ring64 {
+        type Output = Ring64;
+        fn neg(self) -> Self::Output {
+            Ring64(self.0.wrapping_neg())
+        }
+    }
+
+    impl From<u64> for Ring64 {
+        fn from(value: u64) -> Self {
+            Ring64(value)
+        }
+    }
+
+    impl ConditionallySelectable for Ring64 {
+        fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+            Ring64(u64::conditional_select(&a.0, &b.0, choice))
+        }
+    }
+
+    impl ConstantTimeEq for Ring64 {
+        fn ct_eq(&self, other: &Self) -> Choice {
+            self.0.ct_eq(&other.0)
+        }
+    }
+
+    impl Field for Ring64 {
+        fn random(mut rng: impl RngCore) -> Self {
+            Ring64(rng.next_u64())
+        }
+
+        fn zero() -> Self {
+            Ring64(0)
+        }
+
+        fn one() -> Self {
+            Ring64(1)
+        }
+
+        fn square(&self) -> Self {
+            *self * *self
+        }
+
+        fn double(&self) -> Self {
+            *self + *self
+        }
+
+        fn invert(&self) -> CtOption<Self> {
+            // Every odd x satisfies x * x = 1 mod 8, and each Newton iteration doubles the number of correct bits.
+            let mut inverse = self.0;
+            for _ in 0..5 {
+                inverse = inverse.wrapping_mul(2u64.wrapping_sub(self.0.wrapping_mul(inverse)));
+            }
+            CtOption::new(Ring64(inverse), self.is_odd())
+        }
+
+        fn sqrt(&self) -> CtOption<Self> {
+            CtOption::new(Self::zero(), Choice::from(0))
+        }
+    }
+
+    impl PrimeField for Ring64 {
+        type Repr = [u8; 8];
+
+        fn from_repr(repr: Self::Repr) -> CtOption<Self> {
+            CtOption::new(Ring64(u64::from_le_bytes(repr)), Choice::from(1))
+        }
+
+        fn to_repr(&self) -> Self::Repr {
+            self.0.to_le_bytes()
+        }
+
+        fn is_odd(&self) -> Choice {
+            Choice::from((self.0 & 1) as u8)
+        }
+
+        const NUM_BITS: u32 = 64;
+        const CAPACITY: u32 = 63;
+
+        /// Group of units is not cyclic, 3 generates its largest cyclic subgroup.
+        fn multiplicative_generator() -> Self {
+            Ring64(3)
+        }
+
+        const S: u32 = 0;
+
+        fn root_of_unity() -> Self {
+            Self::one()
+        }
+    }
+
+    impl MpcField for Ring64 {
+        const FIELD_TYPE: FieldType = FieldType::Ring64;
+        const SAFE_BITS: usize = 63;
+
+        fn power_of_two(k: usize) -> Self {
+            assert!(k <= Self::SAFE_BITS, "Too large power of two");
+            Ring64(1 << k)
+        }
+
+        /// Panics for k > 0, since even elements are not invertible.
+        fn power_of_two_inverse(k: usize) -> Self {
+            assert!(k == 0, "Powers of two are not invertible modulo 2^64");
+            Self::one()
+        }
+
+        fn truncated(&self) -> u64 {
+            self.0
+        }
+
+        fn exact_div_power_of_two(&self, k: usize) -> Self {
+            assert!(k <= Self::SAFE_BITS, "Too large power of two");
+            Ring64(self.0 >> k)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use ff::Field;
+
+        use crate::MpcField;
+
+        use super::Ring64;
+
+        #[test]
+        fn test_serialization() {
+            let value = Ring64::from(12345678901234567890);
+            let encoded = bincode::serialize(&value).unwrap();
+            assert_eq!(encoded.len(), 8);
+            let decoded = bincode::deserialize(&encoded).unwrap();
+            assert_eq!(value, decoded);
+        }
+
+        #[test]
+        fn test_wrapping_arithmetic() {
+            let max = -Ring64::one();
+            assert_eq!(max.truncated(), u64::MAX);
+            assert_eq!(max + Ring64::from(2), Ring64::one());
+            assert_eq!(max * max, Ring64::one());
+            assert_eq!(
+                Ring64::power_of_two(63).double(),
+                Ring64::zero(),
+                "2^64 wraps around"
+            );
+        }
+
+        #[test]
+        fn test_inverse() {
+            for value in [1, 3, 12345678901234567891, u64::MAX] {
+                let value = Ring64::from(value);
+                assert_eq!(value * value.invert().unwrap(), Ring64::one());
+            }
+            assert!(bool::from(Ring64::from(6).invert().is_none()));
+            assert!(bool::from(Ring64::zero().invert().is_none()));
+        }
+
+        #[test]
+        fn test_powers_of_two() {
+            let value = Ring64::from(12345) * Ring64::power_of_two(40);
+            assert_eq!(value.exact_div_power_of_two(40), Ring64::from(12345));
+            assert_eq!(Ring64::power_of_two_inverse(0), Ring64::one());
+        }
+    }
+}
+
+pub use goldilocks::{Goldilocks, GoldilocksRepr};
 pub use mersenne_127::{Mersenne127, Mersenne127Repr};
+pub use mersenne_31::{Mersenne31, Mersenne31Repr};
 pub use mersenne_61::{Mersenne61, Mersenne61Repr};
+pub use ring_64::Ring64;
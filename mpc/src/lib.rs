@@ -1,8 +1,11 @@
 pub mod circuits;
+pub mod estimator;
 pub mod executor;
 pub mod fields;
+pub mod ot;
 pub mod plaintext;
 pub mod spdz;
+pub mod spdz2k;
 pub mod transport;
 
 pub use ff;
@@ -11,8 +14,12 @@ use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
 use async_trait::async_trait;
 
-/// Prime field that can be used in MPC computation.
+/// Prime field that can be used in MPC computation. The only exception is `fields::Ring64`,
+/// which doesn't support inverses of even elements.
 pub trait MpcField: ff::PrimeField {
+    /// Identifier of this field.
+    const FIELD_TYPE: fields::FieldType;
+
     /// Largest k such that 2^(k+1)-2 doesn't overflow.
     const SAFE_BITS: usize;
 
@@ -24,6 +31,11 @@ pub trait MpcField: ff::PrimeField {
 
     /// Convert to u64 by truncating remaining bits.
     fn truncated(&self) -> u64;
+
+    /// Divide integer divisible by 2^k by 2^k. Panics if k > SAFE_BITS.
+    fn exact_div_power_of_two(&self, k: usize) -> Self {
+        *self * Self::power_of_two_inverse(k)
+    }
 }
 
 /// Private share of a field element.
@@ -72,15 +84,66 @@ pub trait MpcDealer: MpcContext {
     /// Random sharing of a secret random triple (a, b, c) that satisfies ab = c.
     fn next_beaver_triple(&mut self) -> (Self::Share, Self::Share, Self::Share);
 
+    /// Random sharing of a secret random pair (a, a^2). By default the dealer has no square pairs,
+    /// and squares are computed using Beaver triples instead.
+    fn next_square_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        None
+    }
+
+    /// Random sharing of a secret random pair (r, r^-1), where r is nonzero. By default the dealer
+    /// has no inverse pairs, and inverses are computed using Beaver triples instead.
+    fn next_inverse_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        None
+    }
+
     /// Random sharing of a secret random N-bit unsigned integer.
     fn next_uint(&mut self, bits: usize) -> Self::Share;
 
+    /// Random sharing of a secret random `Field::SAFE_BITS`-bit unsigned integer X, sharing of X mod 2^k
+    /// and separate sharings of the first k bits of X. By default assembled from random bits.
+    fn next_bit_mask(&mut self, k: usize) -> (Self::Share, Self::Share, Vec<Self::Share>) {
+        assemble_bit_mask(self, k)
+    }
+
+    /// The same as `next_bit_mask`, but returns sharing of X div 2^k instead of X. By default computed from
+    /// `next_bit_mask` using inverse of 2^k, dealers over rings override it with `assemble_split_bit_mask`.
+    fn next_split_bit_mask(&mut self, k: usize) -> (Self::Share, Self::Share, Vec<Self::Share>) {
+        let (mask, low_part, low_bits) = self.next_bit_mask(k);
+        let high_part = (mask - low_part) * Self::Field::power_of_two_inverse(k);
+        (high_part, low_part, low_bits)
+    }
+
     /// Returns true if dealer cannot produce more parameters of some type.
     /// Once this happens, all other methods return undefined values,
     /// and circuit executor aborts before any new communication happens.
     fn is_exhausted(&self) -> bool;
 }
 
+/// Assemble random bit mask of width k from random bits of dealer, as returned by `MpcDealer::next_bit_mask`.
+/// Dealers providing precomputed bit masks use it for widths they have no masks of.
+pub fn assemble_bit_mask<D: MpcDealer + ?Sized>(
+    dealer: &mut D,
+    k: usize,
+) -> (D::Share, D::Share, Vec<D::Share>) {
+    let (high_part, low_part, low_bits) = assemble_split_bit_mask(dealer, k);
+    let mask = high_part * D::Field::power_of_two(k) + low_part;
+    (mask, low_part, low_bits)
+}
+
+/// Assemble random bit mask of width k from random bits of dealer, as returned by `MpcDealer::next_split_bit_mask`.
+pub fn assemble_split_bit_mask<D: MpcDealer + ?Sized>(
+    dealer: &mut D,
+    k: usize,
+) -> (D::Share, D::Share, Vec<D::Share>) {
+    let high_part = dealer.next_uint(D::Field::SAFE_BITS - k);
+    let low_bits: Vec<_> = (0..k).map(|_| dealer.next_uint(1)).collect();
+    let low_part = low_bits
+        .iter()
+        .rev()
+        .fold(D::Share::zero(), |acc, &x| acc.double() + x);
+    (high_part, low_part, low_bits)
+}
+
 /// Low-level interface of sharing-based MPC protocol.
 #[async_trait(?Send)]
 pub trait MpcEngine: MpcContext {
@@ -104,6 +167,17 @@ pub trait MpcEngine: MpcContext {
         requests: Vec<Self::Share>,
     ) -> Result<Vec<Self::Field>, Self::Error>;
 
+    /// Process bundles of partial and private open requests issued in the same round.
+    /// Each private request is a pair of recipient ID and share.
+    /// Returns opened values of partial requests, and opened values of private requests
+    /// addressed to current party with `None` for the rest.
+    /// Warning: Integrity checks may be deferred.
+    async fn process_private_openings_unchecked(
+        &mut self,
+        requests: Vec<Self::Share>,
+        private_requests: Vec<(usize, Self::Share)>,
+    ) -> Result<(Vec<Self::Field>, Vec<Option<Self::Field>>), Self::Error>;
+
     /// Check integrity of everything computed so far.
     async fn check_integrity(&mut self) -> Result<(), Self::Error>;
 }
@@ -0,0 +1,586 @@
+use std::marker::PhantomData;
+
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_POINT,
+    ristretto::{CompressedRistretto, RistrettoPoint},
+    scalar::Scalar,
+};
+use digest::Digest;
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+
+use crate::MpcField;
+
+/// Random number generator used for expanding OT seeds.
+type OtRng = rand::rngs::StdRng;
+
+/// Hashing function used for deriving OT keys.
+type OtDigest = sha3::Sha3_256;
+
+/// Seed obtained from base oblivious transfer.
+pub type OtSeed = [u8; 32];
+
+/// Encoded group element sent in base OT messages.
+pub type OtPoint = [u8; 32];
+
+/// Computational security parameter, i.e. number of base OTs used by OT extension.
+pub const OT_EXTENSION_WIDTH: usize = 128;
+
+/// Number of additional random OTs in each OT extension, which hide choice bits from consistency check.
+/// Equals computational plus statistical security parameter, as required by KOS.
+const KOS_PADDING: usize = OT_EXTENSION_WIDTH + 64;
+
+/// Sender of base oblivious transfers, based on "The Simplest Protocol for Oblivious Transfer" by Chou and Orlandi.
+pub struct BaseOtSender {
+    secret: Scalar,
+    public: RistrettoPoint,
+}
+
+impl BaseOtSender {
+    /// Create sender. Returns sender and its public message.
+    pub fn new(rng: &mut (impl RngCore + CryptoRng)) -> (Self, OtPoint) {
+        let secret = Scalar::random(rng);
+        let public = secret * RISTRETTO_BASEPOINT_POINT;
+        (Self { secret, public }, public.compress().to_bytes())
+    }
+
+    /// Compute pairs of seeds given message of receiver. Returns None if message is malformed.
+    pub fn finish(&self, receiver_msg: &[OtPoint]) -> Option<Vec<(OtSeed, OtSeed)>> {
+        let public = self.public.compress().to_bytes();
+        receiver_msg
+            .iter()
+            .enumerate()
+            .map(|(i, encoded)| {
+                let point = CompressedRistretto(*encoded).decompress()?;
+                let key0 = base_ot_key(i, &public, encoded, self.secret * point);
+                let key1 = base_ot_key(i, &public, encoded, self.secret * (point - self.public));
+                Some((key0, key1))
+            })
+            .collect()
+    }
+}
+
+/// Receive seeds selected by given choice bits from base OT sender.
+/// Returns received seeds and message to sender, or None if sender message is malformed.
+pub fn base_ot_receive(
+    rng: &mut (impl RngCore + CryptoRng),
+    sender_msg: &OtPoint,
+    choices: &[bool],
+) -> Option<(Vec<OtSeed>, Vec<OtPoint>)> {
+    let sender_public = CompressedRistretto(*sender_msg).decompress()?;
+    Some(
+        choices
+            .iter()
+            .enumerate()
+            .map(|(i, &choice)| {
+                let secret = Scalar::random(rng);
+                let mut point = secret * RISTRETTO_BASEPOINT_POINT;
+                if choice {
+                    point += sender_public;
+                }
+                let encoded = point.compress().to_bytes();
+                let key = base_ot_key(i, sender_msg, &encoded, secret * sender_public);
+                (key, encoded)
+            })
+            .unzip(),
+    )
+}
+
+/// Derive key of base OT from shared group element.
+fn base_ot_key(
+    index: usize,
+    sender: &OtPoint,
+    receiver: &OtPoint,
+    shared: RistrettoPoint,
+) -> OtSeed {
+    OtDigest::new()
+        .chain_update((index as u64).to_le_bytes())
+        .chain_update(sender)
+        .chain_update(receiver)
+        .chain_update(shared.compress().as_bytes())
+        .finalize()
+        .into()
+}
+
+/// Sender in correlated oblivious product evaluation (COPE) of MASCOT.
+/// Obtains additive shares of products of its values and fixed secret of receiver.
+/// Requires one base OT (with both seeds known to sender) per bit of field element.
+pub struct CopeSender<T> {
+    rngs: Vec<(OtRng, OtRng)>,
+    phantom: PhantomData<T>,
+}
+
+impl<T: MpcField> CopeSender<T> {
+    /// Create sender from pairs of base OT seeds.
+    pub fn new(seeds: &[(OtSeed, OtSeed)]) -> Self {
+        assert_eq!(seeds.len(), T::NUM_BITS as usize, "Invalid number of seeds");
+        Self {
+            rngs: seeds
+                .iter()
+                .map(|&(seed0, seed1)| (OtRng::from_seed(seed0), OtRng::from_seed(seed1)))
+                .collect(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Compute shares of products of given values and receiver's secret.
+    /// Returns own shares and message for receiver.
+    pub fn extend(&mut self, values: &[T]) -> (Vec<T>, Vec<T>) {
+        let powers = powers_of_two::<T>();
+        let mut message = Vec::with_capacity(values.len() * self.rngs.len());
+        let shares = values
+            .iter()
+            .map(|&value| {
+                let mut share = T::zero();
+                for ((rng0, rng1), &power) in self.rngs.iter_mut().zip(&powers) {
+                    let t0 = T::random(&mut *rng0);
+                    let t1 = T::random(&mut *rng1);
+                    message.push(t0 - t1 + value);
+                    share -= t0 * power;
+                }
+                share
+            })
+            .collect();
+        (shares, message)
+    }
+}
+
+/// Receiver in correlated oblivious product evaluation (COPE) of MASCOT.
+/// Obtains additive shares of products of its fixed secret and values of sender.
+pub struct CopeReceiver<T> {
+    choices: Vec<bool>,
+    rngs: Vec<OtRng>,
+    phantom: PhantomData<T>,
+}
+
+impl<T: MpcField> CopeReceiver<T> {
+    /// Create receiver from base OT seeds, which were chosen by bits of secret.
+    pub fn new(secret: T, seeds: &[OtSeed]) -> Self {
+        assert_eq!(seeds.len(), T::NUM_BITS as usize, "Invalid number of seeds");
+        Self {
+            choices: field_bits(secret),
+            rngs: seeds.iter().map(|&seed| OtRng::from_seed(seed)).collect(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Compute shares of products given sender's message. Panics if message has incorrect length.
+    pub fn extend(&mut self, message: &[T]) -> Vec<T> {
+        assert_eq!(message.len() % self.rngs.len(), 0, "Invalid message length");
+        let powers = powers_of_two::<T>();
+        message
+            .chunks(self.rngs.len())
+            .map(|chunk| {
+                let mut share = T::zero();
+                for (((rng, &choice), &power), &correction) in self
+                    .rngs
+                    .iter_mut()
+                    .zip(&self.choices)
+                    .zip(&powers)
+                    .zip(chunk)
+                {
+                    let t = T::random(&mut *rng);
+                    let q = if choice { t + correction } else { t };
+                    share += q * power;
+                }
+                share
+            })
+            .collect()
+    }
+}
+
+/// Party with choice bits in oblivious linear evaluation based on IKNP OT extension and Gilboa's multiplication.
+/// Obtains additive shares of products of its values and values of sender, which may change between calls.
+/// Base OT seeds are known to this party. Consistency of its choice bits is verified by the check of KOS.
+pub struct OleReceiver<T> {
+    rngs: Vec<(OtRng, OtRng)>,
+    counter: u64,
+    phantom: PhantomData<T>,
+}
+
+/// Pending multiplication started by OLE receiver.
+pub struct OleReceiverState {
+    first_index: u64,
+    choices: Vec<bool>,
+    rows: Vec<u128>,
+}
+
+impl<T: MpcField> OleReceiver<T> {
+    /// Create receiver from pairs of base OT seeds.
+    pub fn new(seeds: &[(OtSeed, OtSeed)]) -> Self {
+        assert_eq!(seeds.len(), OT_EXTENSION_WIDTH, "Invalid number of seeds");
+        Self {
+            rngs: seeds
+                .iter()
+                .map(|&(seed0, seed1)| (OtRng::from_seed(seed0), OtRng::from_seed(seed1)))
+                .collect(),
+            counter: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Start multiplication of given values. Returns state and OT extension message for sender.
+    /// Random padding choices hide the choice bits from the consistency check.
+    pub fn start(&mut self, rng: &mut impl RngCore, values: &[T]) -> (OleReceiverState, Vec<u8>) {
+        let choices: Vec<_> = values
+            .iter()
+            .flat_map(|&x| field_bits(x))
+            .chain((0..KOS_PADDING).map(|_| rng.gen()))
+            .collect();
+        let packed_choices = pack_bits(&choices);
+        let num_bytes = packed_choices.len();
+
+        let mut message = Vec::with_capacity(num_bytes * OT_EXTENSION_WIDTH);
+        let mut columns = Vec::with_capacity(OT_EXTENSION_WIDTH);
+        for (rng0, rng1) in &mut self.rngs {
+            let mut column = vec![0; num_bytes];
+            let mut mask = vec![0; num_bytes];
+            rng0.fill_bytes(&mut column);
+            rng1.fill_bytes(&mut mask);
+            message.extend(
+                column
+                    .iter()
+                    .zip(&mask)
+                    .zip(&packed_choices)
+                    .map(|((t, m), r)| t ^ m ^ r),
+            );
+            columns.push(column);
+        }
+
+        let state = OleReceiverState {
+            first_index: self.counter,
+            rows: transpose(&columns, choices.len()),
+            choices,
+        };
+        self.counter += state.rows.len() as u64;
+        (state, message)
+    }
+
+    /// Answer consistency check of KOS for given challenge of sender.
+    /// Returns combination of choice bits and combination of rows.
+    pub fn check(&self, state: &OleReceiverState, challenge: OtSeed) -> (u128, u128) {
+        let mut coeffs = OtRng::from_seed(challenge);
+        state
+            .rows
+            .iter()
+            .zip(&state.choices)
+            .fold((0, 0), |(x, t), (&row, &choice)| {
+                let coeff: u128 = coeffs.gen();
+                let x = if choice { x ^ coeff } else { x };
+                (x, t ^ gf128_mul(coeff, row))
+            })
+    }
+
+    /// Finish multiplication given sender's corrections. Panics if corrections have incorrect length.
+    pub fn finish(&self, state: OleReceiverState, corrections: &[T]) -> Vec<T> {
+        let num_ots = state.rows.len() - KOS_PADDING;
+        assert_eq!(corrections.len(), num_ots, "Invalid corrections length");
+        let powers = powers_of_two::<T>();
+        let bits = powers.len();
+        (0..num_ots / bits)
+            .map(|product| {
+                let mut share = T::zero();
+                for (j, &power) in powers.iter().enumerate() {
+                    let i = product * bits + j;
+                    let mut y: T = hash_to_field(state.first_index + i as u64, state.rows[i]);
+                    if state.choices[i] {
+                        y += corrections[i];
+                    }
+                    share += y * power;
+                }
+                share
+            })
+            .collect()
+    }
+}
+
+/// Party with values in oblivious linear evaluation based on IKNP OT extension and Gilboa's multiplication.
+/// Base OT seeds were chosen by bits of secret delta.
+pub struct OleSender<T> {
+    delta: u128,
+    rngs: Vec<OtRng>,
+    counter: u64,
+    phantom: PhantomData<T>,
+}
+
+/// Pending multiplication of OLE sender, which waits for consistency check of receiver.
+pub struct OleSenderState {
+    first_index: u64,
+    rows: Vec<u128>,
+}
+
+impl<T: MpcField> OleSender<T> {
+    /// Create sender from base OT seeds chosen by bits of delta.
+    pub fn new(delta: u128, seeds: &[OtSeed]) -> Self {
+        assert_eq!(seeds.len(), OT_EXTENSION_WIDTH, "Invalid number of seeds");
+        Self {
+            delta,
+            rngs: seeds.iter().map(|&seed| OtRng::from_seed(seed)).collect(),
+            counter: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Process OT extension message of receiver for multiplication of given number of values.
+    /// Returns None if message is malformed.
+    pub fn receive(&mut self, num_values: usize, message: &[u8]) -> Option<OleSenderState> {
+        let num_ots = num_values * T::NUM_BITS as usize + KOS_PADDING;
+        let num_bytes = (0..num_ots).step_by(8).len();
+        if message.len() != num_bytes * OT_EXTENSION_WIDTH {
+            return None;
+        }
+
+        let columns: Vec<_> = self
+            .rngs
+            .iter_mut()
+            .zip(message.chunks(num_bytes))
+            .enumerate()
+            .map(|(j, (rng, received))| {
+                let mut column = vec![0; num_bytes];
+                rng.fill_bytes(&mut column);
+                if (self.delta >> j) & 1 == 1 {
+                    for (x, u) in column.iter_mut().zip(received) {
+                        *x ^= u;
+                    }
+                }
+                column
+            })
+            .collect();
+
+        let state = OleSenderState {
+            first_index: self.counter,
+            rows: transpose(&columns, num_ots),
+        };
+        self.counter += num_ots as u64;
+        Some(state)
+    }
+
+    /// Verify answer of receiver to consistency check of KOS with given challenge.
+    /// Fails if receiver didn't use the same choice bits for all base OTs.
+    pub fn check(&self, state: &OleSenderState, challenge: OtSeed, answer: (u128, u128)) -> bool {
+        let mut coeffs = OtRng::from_seed(challenge);
+        let combined = state.rows.iter().fold(0, |acc, &row| {
+            let coeff: u128 = coeffs.gen();
+            acc ^ gf128_mul(coeff, row)
+        });
+        let (x, t) = answer;
+        combined == t ^ gf128_mul(x, self.delta)
+    }
+
+    /// Multiply given values with values of receiver, after its consistency check passed.
+    /// Returns own shares of products and corrections for receiver. Panics if number of values is incorrect.
+    pub fn respond(&self, state: OleSenderState, values: &[T]) -> (Vec<T>, Vec<T>) {
+        let powers = powers_of_two::<T>();
+        let num_ots = values.len() * powers.len();
+        assert_eq!(
+            state.rows.len(),
+            num_ots + KOS_PADDING,
+            "Invalid number of values"
+        );
+
+        let mut corrections = Vec::with_capacity(num_ots);
+        let shares = values
+            .iter()
+            .enumerate()
+            .map(|(product, &value)| {
+                let mut share = T::zero();
+                for (j, &power) in powers.iter().enumerate() {
+                    let i = product * powers.len() + j;
+                    let index = state.first_index + i as u64;
+                    let m0 = hash_to_field::<T>(index, state.rows[i]);
+                    let m1 = hash_to_field::<T>(index, state.rows[i] ^ self.delta);
+                    corrections.push(m0 - m1 + value);
+                    share -= m0 * power;
+                }
+                share
+            })
+            .collect();
+        (shares, corrections)
+    }
+}
+
+/// Multiplication in GF(2^128) modulo x^128 + x^7 + x^2 + x + 1.
+fn gf128_mul(mut a: u128, mut b: u128) -> u128 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        b >>= 1;
+        let carry = a >> 127;
+        a <<= 1;
+        if carry == 1 {
+            a ^= 0x87;
+        }
+    }
+    product
+}
+
+/// Bits of canonical representation of field element, starting from the least significant one.
+pub fn field_bits<T: MpcField>(x: T) -> Vec<bool> {
+    let repr = x.to_repr();
+    (0..T::NUM_BITS as usize)
+        .map(|i| (repr.as_ref()[i / 8] >> (i % 8)) & 1 == 1)
+        .collect()
+}
+
+/// Bits of 128-bit integer, starting from the least significant one.
+pub fn u128_bits(x: u128) -> Vec<bool> {
+    (0..128).map(|i| (x >> i) & 1 == 1).collect()
+}
+
+/// Powers of two up to bit length of field elements.
+fn powers_of_two<T: MpcField>() -> Vec<T> {
+    let mut powers = vec![T::one(); T::NUM_BITS as usize];
+    for i in 1..powers.len() {
+        powers[i] = powers[i - 1].double();
+    }
+    powers
+}
+
+/// Pack bits into bytes, starting from the least significant bit.
+fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, &bit)| acc | ((bit as u8) << i))
+        })
+        .collect()
+}
+
+/// Transpose matrix with OT_EXTENSION_WIDTH columns of packed bits into rows.
+fn transpose(columns: &[Vec<u8>], num_rows: usize) -> Vec<u128> {
+    let mut rows = vec![0; num_rows];
+    for (j, column) in columns.iter().enumerate() {
+        for (i, row) in rows.iter_mut().enumerate() {
+            *row |= (((column[i / 8] >> (i % 8)) & 1) as u128) << j;
+        }
+    }
+    rows
+}
+
+/// Correlation-robust hash of OT extension row into field element.
+fn hash_to_field<T: MpcField>(index: u64, row: u128) -> T {
+    let digest: [u8; 32] = OtDigest::new()
+        .chain_update(index.to_le_bytes())
+        .chain_update(row.to_le_bytes())
+        .finalize()
+        .into();
+
+    // Reduction of 256 random bits is statistically close to uniform for supported fields.
+    let word_shift = T::from(u64::MAX) + T::one();
+    digest.chunks(8).fold(T::zero(), |acc, word| {
+        acc * word_shift + T::from(u64::from_le_bytes(word.try_into().unwrap()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::fields::Mersenne61;
+
+    use super::{
+        base_ot_receive, u128_bits, BaseOtSender, CopeReceiver, CopeSender, OleReceiver, OleSender,
+        OT_EXTENSION_WIDTH,
+    };
+
+    type Fp = Mersenne61;
+
+    #[test]
+    fn test_base_ot() {
+        let mut rng = StdRng::seed_from_u64(123);
+        let choices: Vec<bool> = (0..20).map(|_| rng.gen()).collect();
+        let (sender, sender_msg) = BaseOtSender::new(&mut rng);
+        let (received, receiver_msg) = base_ot_receive(&mut rng, &sender_msg, &choices).unwrap();
+        let seeds = sender.finish(&receiver_msg).unwrap();
+        for ((seed, (seed0, seed1)), choice) in received.iter().zip(seeds).zip(choices) {
+            assert_eq!(*seed, if choice { seed1 } else { seed0 });
+            assert_ne!(*seed, if choice { seed0 } else { seed1 });
+        }
+    }
+
+    #[test]
+    fn test_cope() {
+        let mut rng = StdRng::seed_from_u64(123);
+        let secret = Fp::from(987654321);
+        let seeds: Vec<_> = (0..61).map(|_| (rng.gen(), rng.gen())).collect();
+        let chosen: Vec<_> = super::field_bits(secret)
+            .into_iter()
+            .zip(&seeds)
+            .map(|(bit, &(seed0, seed1))| if bit { seed1 } else { seed0 })
+            .collect();
+
+        let mut sender = CopeSender::new(&seeds);
+        let mut receiver = CopeReceiver::new(secret, &chosen);
+        for round in 0..2 {
+            let values: Vec<Fp> = (0..5).map(|i| Fp::from(i * 1000 + round)).collect();
+            let (sender_shares, message) = sender.extend(&values);
+            let receiver_shares = receiver.extend(&message);
+            for ((value, x), y) in values.iter().zip(sender_shares).zip(receiver_shares) {
+                assert_eq!(x + y, *value * secret);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ole() {
+        let mut rng = StdRng::seed_from_u64(123);
+        let delta: u128 = rng.gen();
+        let seeds: Vec<_> = (0..OT_EXTENSION_WIDTH)
+            .map(|_| (rng.gen(), rng.gen()))
+            .collect();
+        let chosen: Vec<_> = u128_bits(delta)
+            .into_iter()
+            .zip(&seeds)
+            .map(|(bit, &(seed0, seed1))| if bit { seed1 } else { seed0 })
+            .collect();
+
+        let mut receiver = OleReceiver::new(&seeds);
+        let mut sender = OleSender::new(delta, &chosen);
+        for round in 0..2 {
+            let a: Vec<Fp> = (0..5).map(|i| Fp::from(i * 77 + round)).collect();
+            let b: Vec<Fp> = (0..5).map(|i| -Fp::from(i * 13 + 5)).collect();
+            let (state, message) = receiver.start(&mut rng, &a);
+            let sender_state = sender.receive(b.len(), &message).unwrap();
+            let challenge = rng.gen();
+            let answer = receiver.check(&state, challenge);
+            assert!(sender.check(&sender_state, challenge, answer));
+            let (sender_shares, corrections) = sender.respond(sender_state, &b);
+            let receiver_shares = receiver.finish(state, &corrections);
+            for i in 0..a.len() {
+                assert_eq!(sender_shares[i] + receiver_shares[i], a[i] * b[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ole_detects_inconsistent_choices() {
+        let mut rng = StdRng::seed_from_u64(123);
+        let delta: u128 = rng.gen();
+        let seeds: Vec<_> = (0..OT_EXTENSION_WIDTH)
+            .map(|_| (rng.gen(), rng.gen()))
+            .collect();
+        let chosen: Vec<_> = u128_bits(delta)
+            .into_iter()
+            .zip(&seeds)
+            .map(|(bit, &(seed0, seed1))| if bit { seed1 } else { seed0 })
+            .collect();
+
+        let mut receiver = OleReceiver::<Fp>::new(&seeds);
+        let mut sender = OleSender::<Fp>::new(delta, &chosen);
+        let a: Vec<Fp> = (0..5).map(|i| Fp::from(i * 77)).collect();
+        let (state, mut message) = receiver.start(&mut rng, &a);
+
+        // Cheating receiver flips the first choice bit in half of the columns, trying to learn bits of delta.
+        let num_bytes = message.len() / OT_EXTENSION_WIDTH;
+        for column in message.chunks_mut(num_bytes).take(64) {
+            column[0] ^= 1;
+        }
+        let sender_state = sender.receive(a.len(), &message).unwrap();
+        let challenge = rng.gen();
+        let answer = receiver.check(&state, challenge);
+        assert!(!sender.check(&sender_state, challenge, answer));
+    }
+}
@@ -6,7 +6,7 @@ use std::{
 use async_trait::async_trait;
 use rand::{prelude::SmallRng, Rng, SeedableRng};
 
-use crate::{MpcContext, MpcDealer, MpcEngine, MpcField, MpcShare};
+use crate::{fields::random_inverse_pair, MpcContext, MpcDealer, MpcEngine, MpcField, MpcShare};
 
 /// Mock MPC engine that computes result in plain on a single node.
 pub struct PlainMpcEngine<T> {
@@ -66,6 +66,20 @@ impl<T: MpcField> MpcEngine for PlainMpcEngine<T> {
         Ok(requests.iter().map(|r| r.0).collect())
     }
 
+    async fn process_private_openings_unchecked(
+        &mut self,
+        requests: Vec<Self::Share>,
+        private_requests: Vec<(usize, Self::Share)>,
+    ) -> Result<(Vec<Self::Field>, Vec<Option<Self::Field>>), ()> {
+        Ok((
+            requests.iter().map(|r| r.0).collect(),
+            private_requests
+                .iter()
+                .map(|&(id, r)| if id == 0 { Some(r.0) } else { None })
+                .collect(),
+        ))
+    }
+
     async fn check_integrity(&mut self) -> Result<(), Self::Error> {
         // Nothing to do here.
         Ok(())
@@ -83,6 +97,16 @@ impl<T: MpcField> MpcDealer for PlainMpcEngine<T> {
         (PlainShare(a), PlainShare(b), PlainShare(a * b))
     }
 
+    fn next_square_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        let a = Self::Field::random(&mut self.rng);
+        Some((PlainShare(a), PlainShare(a.square())))
+    }
+
+    fn next_inverse_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        let (r, r_inverse) = random_inverse_pair(&mut self.rng);
+        Some((PlainShare(r), PlainShare(r_inverse)))
+    }
+
     fn next_uint(&mut self, bits: usize) -> Self::Share {
         PlainShare((0..bits).fold(Self::Field::zero(), |acc, _| {
             acc.double() + Self::Field::from(self.rng.gen_range(0..=1))
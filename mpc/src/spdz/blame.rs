@@ -0,0 +1,275 @@
+use std::collections::{btree_map::Entry, BTreeMap};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Encoded public key verifying broadcast signatures of a party.
+pub type VerificationKey = [u8; 32];
+
+/// Ed25519 signature of a transcript position or of shares sent to a king.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BroadcastSignature(Signature);
+
+/// Position in broadcast transcript of a party: number of broadcast messages and chained digest of all of them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptPosition {
+    pub count: u64,
+    pub digest: [u8; 32],
+}
+
+/// Transcript position signed by its party, which is forwarded to other parties during blame phase.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BroadcastEvidence {
+    pub party_id: usize,
+    pub position: TranscriptPosition,
+    pub signature: BroadcastSignature,
+}
+
+/// Shares of partial openings signed by the party that sent them to a king,
+/// which are forwarded by the king to other parties during blame phase.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareEvidence<T> {
+    pub party_id: usize,
+    /// Opening round in which shares were sent.
+    pub round: u64,
+    pub shares: Vec<T>,
+    pub signature: BroadcastSignature,
+}
+
+/// Party proven to deviate from the protocol during blame phase.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum Accusation {
+    /// Party signed two different transcripts of the same length.
+    InconsistentBroadcast(usize),
+    /// Party reported evidence with an invalid signature.
+    InvalidSignature(usize),
+}
+
+impl Accusation {
+    fn party_id(&self) -> usize {
+        match *self {
+            Self::InconsistentBroadcast(id) | Self::InvalidSignature(id) => id,
+        }
+    }
+}
+
+/// Signing key of current party and broadcast transcripts of all parties in identifiable-abort mode.
+pub(super) struct BlameState {
+    signing_key: SigningKey,
+    /// Verification keys of all parties, agreed on before the first signed broadcast.
+    pub keys: Option<Vec<VerificationKey>>,
+    /// Position in own broadcast transcript.
+    pub sent: TranscriptPosition,
+    /// Positions in broadcast transcripts of other parties, as received by current party.
+    pub received: Vec<TranscriptPosition>,
+    /// Latest signed positions received from other parties.
+    pub evidence: Vec<Option<BroadcastEvidence>>,
+}
+
+impl BlameState {
+    /// Generate fresh signing key.
+    pub fn new(rng: &mut (impl RngCore + CryptoRng), num_parties: usize) -> Self {
+        Self {
+            signing_key: SigningKey::generate(rng),
+            keys: None,
+            sent: TranscriptPosition::default(),
+            received: vec![TranscriptPosition::default(); num_parties],
+            evidence: vec![None; num_parties],
+        }
+    }
+
+    /// Public key of current party.
+    pub fn verification_key(&self) -> VerificationKey {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    /// Sign position in own transcript.
+    pub fn sign(&self, party_id: usize, position: &TranscriptPosition) -> BroadcastSignature {
+        BroadcastSignature(self.signing_key.sign(&position_bytes(party_id, position)))
+    }
+
+    /// Sign digest of shares sent to given king in given opening round.
+    pub fn sign_shares(
+        &self,
+        party_id: usize,
+        king: usize,
+        round: u64,
+        digest: &[u8; 32],
+    ) -> BroadcastSignature {
+        BroadcastSignature(
+            self.signing_key
+                .sign(&shares_bytes(party_id, king, round, digest)),
+        )
+    }
+}
+
+/// Check signature of transcript position of given party.
+pub(super) fn verify(
+    key: &VerificationKey,
+    party_id: usize,
+    position: &TranscriptPosition,
+    signature: &BroadcastSignature,
+) -> bool {
+    verify_bytes(key, &position_bytes(party_id, position), signature)
+}
+
+/// Check signature of digest of shares sent by given party to a king.
+pub(super) fn verify_shares(
+    key: &VerificationKey,
+    party_id: usize,
+    king: usize,
+    round: u64,
+    digest: &[u8; 32],
+    signature: &BroadcastSignature,
+) -> bool {
+    verify_bytes(key, &shares_bytes(party_id, king, round, digest), signature)
+}
+
+fn verify_bytes(key: &VerificationKey, bytes: &[u8], signature: &BroadcastSignature) -> bool {
+    VerifyingKey::from_bytes(key)
+        .and_then(|key| key.verify_strict(bytes, &signature.0))
+        .is_ok()
+}
+
+/// Encoding of transcript position of given party, which is signed.
+fn position_bytes(party_id: usize, position: &TranscriptPosition) -> Vec<u8> {
+    [
+        &b"position"[..],
+        &(party_id as u64).to_le_bytes(),
+        &position.count.to_le_bytes(),
+        &position.digest,
+    ]
+    .concat()
+}
+
+/// Encoding of digest of shares sent by given party to a king, which is signed.
+fn shares_bytes(party_id: usize, king: usize, round: u64, digest: &[u8; 32]) -> Vec<u8> {
+    [
+        &b"shares"[..],
+        &(party_id as u64).to_le_bytes(),
+        &(king as u64).to_le_bytes(),
+        &round.to_le_bytes(),
+        digest,
+    ]
+    .concat()
+}
+
+/// Find a party proven to deviate from the protocol, given blame reports of parties indexed by reporter ID.
+/// Returns accusation of the party with the lowest ID. Honest parties are never accused.
+pub(super) fn find_cheater(
+    keys: &[VerificationKey],
+    reports: impl IntoIterator<Item = (usize, Vec<BroadcastEvidence>)>,
+) -> Option<Accusation> {
+    let mut positions = vec![BTreeMap::new(); keys.len()];
+    let mut accusations = BTreeMap::new();
+    for (reporter_id, evidence) in reports {
+        for item in evidence {
+            let valid = item.party_id < keys.len()
+                && verify(
+                    &keys[item.party_id],
+                    item.party_id,
+                    &item.position,
+                    &item.signature,
+                );
+            if !valid {
+                accusations
+                    .entry(reporter_id)
+                    .or_insert(Accusation::InvalidSignature(reporter_id));
+                continue;
+            }
+            match positions[item.party_id].entry(item.position.count) {
+                Entry::Vacant(entry) => {
+                    entry.insert(item.position.digest);
+                }
+                Entry::Occupied(entry) => {
+                    if *entry.get() != item.position.digest {
+                        accusations.insert(
+                            item.party_id,
+                            Accusation::InconsistentBroadcast(item.party_id),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    accusations
+        .into_values()
+        .min_by_key(|accusation| accusation.party_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{
+        find_cheater, verify, verify_shares, Accusation, BlameState, BroadcastEvidence,
+        TranscriptPosition,
+    };
+
+    #[test]
+    fn test_find_cheater() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let states: Vec<_> = (0..3).map(|_| BlameState::new(&mut rng, 3)).collect();
+        let keys: Vec<_> = states.iter().map(|x| x.verification_key()).collect();
+        let evidence = |party_id: usize, digest: u8| {
+            let position = TranscriptPosition {
+                count: 5,
+                digest: [digest; 32],
+            };
+            BroadcastEvidence {
+                party_id,
+                position,
+                signature: states[party_id].sign(party_id, &position),
+            }
+        };
+
+        let consistent = evidence(2, 1);
+        assert!(verify(
+            &keys[2],
+            2,
+            &consistent.position,
+            &consistent.signature
+        ));
+        assert!(!verify(
+            &keys[1],
+            1,
+            &consistent.position,
+            &consistent.signature
+        ));
+        assert_eq!(
+            find_cheater(&keys, [(0, vec![consistent]), (1, vec![consistent])]),
+            None
+        );
+
+        let equivocated = evidence(2, 2);
+        assert_eq!(
+            find_cheater(&keys, [(0, vec![consistent]), (1, vec![equivocated])]),
+            Some(Accusation::InconsistentBroadcast(2))
+        );
+
+        let mut forged = consistent;
+        forged.position.digest = [2; 32];
+        assert_eq!(
+            find_cheater(&keys, [(0, vec![consistent]), (1, vec![forged])]),
+            Some(Accusation::InvalidSignature(1))
+        );
+    }
+
+    #[test]
+    fn test_share_signatures() {
+        let state = BlameState::new(&mut StdRng::seed_from_u64(1), 3);
+        let key = state.verification_key();
+        let signature = state.sign_shares(1, 0, 4, &[3; 32]);
+        assert!(verify_shares(&key, 1, 0, 4, &[3; 32], &signature));
+        assert!(!verify_shares(&key, 1, 2, 4, &[3; 32], &signature));
+        assert!(!verify_shares(&key, 1, 0, 5, &[3; 32], &signature));
+        assert!(!verify_shares(&key, 1, 0, 4, &[4; 32], &signature));
+
+        // Signatures of shares are never valid signatures of transcript positions.
+        let position = TranscriptPosition::default();
+        let signature = state.sign(1, &position);
+        assert!(verify(&key, 1, &position, &signature));
+        assert!(!verify_shares(&key, 1, 0, 0, &[0; 32], &signature));
+    }
+}
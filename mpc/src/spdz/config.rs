@@ -0,0 +1,137 @@
+use std::{fmt, ops::Range, str::FromStr};
+
+/// Routing of partial openings between parties.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OpeningStrategy {
+    /// Party 0 sums shares of all values and broadcasts results.
+    SingleKing,
+    /// King summing and broadcasting shares changes in each round.
+    RotatingKing,
+    /// Each batch of values is split into contiguous parts, each summed by a different king.
+    PartitionedKings,
+    /// Every party broadcasts its shares and sums shares of others locally.
+    AllToAll,
+}
+
+impl OpeningStrategy {
+    /// Ranges of values summed by king of each party ID in given opening round,
+    /// or `None` if values are opened all-to-all.
+    pub(crate) fn king_ranges(
+        &self,
+        num_parties: usize,
+        opening_round: usize,
+        values_count: usize,
+    ) -> Option<Vec<Range<usize>>> {
+        let single_king = |king| {
+            (0..num_parties)
+                .map(|id| if id == king { 0..values_count } else { 0..0 })
+                .collect()
+        };
+        match *self {
+            Self::SingleKing => Some(single_king(0)),
+            Self::RotatingKing => Some(single_king(opening_round % num_parties)),
+            Self::PartitionedKings => Some(
+                (0..num_parties)
+                    .map(|id| {
+                        id * values_count / num_parties..(id + 1) * values_count / num_parties
+                    })
+                    .collect(),
+            ),
+            Self::AllToAll => None,
+        }
+    }
+}
+
+impl FromStr for OpeningStrategy {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "king" => Ok(OpeningStrategy::SingleKing),
+            "rotating" => Ok(OpeningStrategy::RotatingKing),
+            "partitioned" => Ok(OpeningStrategy::PartitionedKings),
+            "all-to-all" => Ok(OpeningStrategy::AllToAll),
+            _ => Err("Unsupported opening strategy. Available options: king, rotating, partitioned, all-to-all"),
+        }
+    }
+}
+
+/// Hashing function used for state hashes and commitments.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DigestType {
+    Sha3_256,
+    Sha256,
+    Blake2s256,
+}
+
+impl FromStr for DigestType {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha3" => Ok(DigestType::Sha3_256),
+            "sha256" => Ok(DigestType::Sha256),
+            "blake2" => Ok(DigestType::Blake2s256),
+            _ => Err("Unsupported digest. Available options: sha3, sha256, blake2"),
+        }
+    }
+}
+
+impl fmt::Display for DigestType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Sha3_256 => write!(f, "sha3"),
+            Self::Sha256 => write!(f, "sha256"),
+            Self::Blake2s256 => write!(f, "blake2"),
+        }
+    }
+}
+
+/// Configuration of SPDZ engine. All parties must use the same configuration.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SpdzConfig {
+    /// Threshold of collected partial openings that triggers integrity checking.
+    /// Lower values reduce memory usage at cost of additional rounds.
+    pub batch_check_threshold: usize,
+
+    /// Maximum number of values that can be checked using a single polynomial hash.
+    /// Probability of undetected cheating grows linearly with this value.
+    pub max_batch_check_size: usize,
+
+    /// If set, integrity is also checked after every given number of opening rounds.
+    pub check_every_rounds: Option<usize>,
+
+    /// Hashing function used for state hashes and commitments.
+    pub digest: DigestType,
+
+    /// Routing of partial openings between parties.
+    pub opening_strategy: OpeningStrategy,
+
+    /// Sign broadcast messages and run blame phase after failed checks, identifying parties that sent inconsistent broadcasts.
+    pub identifiable_abort: bool,
+}
+
+impl SpdzConfig {
+    /// Panics if configuration is invalid.
+    pub(crate) fn assert_valid(&self) {
+        assert!(
+            self.max_batch_check_size > 0,
+            "Maximum batch check size must be positive"
+        );
+        assert!(
+            self.check_every_rounds != Some(0),
+            "Integrity check frequency must be positive"
+        );
+    }
+}
+
+impl Default for SpdzConfig {
+    fn default() -> Self {
+        Self {
+            batch_check_threshold: 20000,
+            max_batch_check_size: 40000,
+            check_every_rounds: None,
+            digest: DigestType::Sha3_256,
+            opening_strategy: OpeningStrategy::SingleKing,
+            identifiable_abort: false,
+        }
+    }
+}
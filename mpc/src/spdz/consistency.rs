@@ -0,0 +1,196 @@
+use async_trait::async_trait;
+use digest::Digest;
+
+use crate::{MpcContext, MpcField};
+
+use super::{DigestType, SpdzError};
+
+/// Output of hashing function used by SPDZ implementation.
+pub(crate) type SpdzDigestOutput = [u8; 32];
+
+/// Salt for commitments.
+pub(crate) type CommitmentSalt = [u8; 32];
+
+/// Hashing function used by SPDZ implementation, selected by configuration.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)] // Only a few hashers are alive at once.
+pub(crate) enum SpdzDigest {
+    Sha3_256(sha3::Sha3_256),
+    Sha256(sha2::Sha256),
+    Blake2s256(blake2::Blake2s256),
+}
+
+impl SpdzDigest {
+    /// Create hasher of given type.
+    pub fn new(digest_type: DigestType) -> Self {
+        match digest_type {
+            DigestType::Sha3_256 => Self::Sha3_256(Digest::new()),
+            DigestType::Sha256 => Self::Sha256(Digest::new()),
+            DigestType::Blake2s256 => Self::Blake2s256(Digest::new()),
+        }
+    }
+
+    /// Process input data.
+    pub fn update(&mut self, data: impl AsRef<[u8]>) {
+        match self {
+            Self::Sha3_256(digest) => digest.update(data),
+            Self::Sha256(digest) => digest.update(data),
+            Self::Blake2s256(digest) => digest.update(data),
+        }
+    }
+
+    /// Retrieve result and reset hasher.
+    pub fn finalize_reset(&mut self) -> SpdzDigestOutput {
+        match self {
+            Self::Sha3_256(digest) => digest.finalize_reset().into(),
+            Self::Sha256(digest) => digest.finalize_reset().into(),
+            Self::Blake2s256(digest) => digest.finalize_reset().into(),
+        }
+    }
+}
+
+/// Value that can be committed to and included in state hash.
+pub(crate) trait DigestValue: Clone {
+    /// Process value by hasher.
+    fn update_digest(&self, digest: &mut SpdzDigest);
+}
+
+impl<T: MpcField> DigestValue for T {
+    fn update_digest(&self, digest: &mut SpdzDigest) {
+        digest.update(self.to_repr());
+    }
+}
+
+impl DigestValue for u128 {
+    fn update_digest(&self, digest: &mut SpdzDigest) {
+        digest.update(self.to_le_bytes());
+    }
+}
+
+/// Commit to values with given salt.
+pub(crate) fn commit_values<V: DigestValue>(
+    digest_type: DigestType,
+    values: &[V],
+    salt: CommitmentSalt,
+) -> SpdzDigestOutput {
+    let mut digest = SpdzDigest::new(digest_type);
+    digest.update(salt);
+    for value in values {
+        value.update_digest(&mut digest);
+    }
+    digest.finalize_reset()
+}
+
+/// Protocol message carrying commitments and state hashes of values of type V.
+pub(crate) trait ConsistencyMessage<V>: Sized {
+    /// Commitment to values.
+    fn commitment(hash: SpdzDigestOutput) -> Self;
+
+    /// Values and salt opening a commitment.
+    fn decommitment(values: Vec<V>, salt: CommitmentSalt) -> Self;
+
+    /// State hash compared by all parties.
+    fn state_hash_check(hash: SpdzDigestOutput) -> Self;
+
+    /// Unwrap commitment, if message is one.
+    fn into_commitment(self) -> Option<SpdzDigestOutput>;
+
+    /// Unwrap decommitment, if message is one.
+    fn into_decommitment(self) -> Option<(Vec<V>, CommitmentSalt)>;
+
+    /// Unwrap state hash, if message is a state hash check.
+    fn into_state_hash_check(self) -> Option<SpdzDigestOutput>;
+}
+
+/// Engine which defers checking of broadcast consistency: received broadcasts are included in state hash,
+/// which is compared by all parties during integrity checks. Shared by SPDZ and SPDZ2k engines.
+#[async_trait(?Send)]
+pub(crate) trait ConsistentBroadcast: MpcContext {
+    /// Type of values exchanged with commitments.
+    type Value: DigestValue;
+
+    /// Protocol message type.
+    type Message: ConsistencyMessage<Self::Value>;
+
+    /// Hashing function used for state hashes and commitments.
+    fn digest_type(&self) -> DigestType;
+
+    /// Hash of all broadcasts since the last state hash check.
+    fn state_digest(&mut self) -> &mut SpdzDigest;
+
+    /// Fresh random salt for a commitment.
+    fn commitment_salt(&mut self) -> CommitmentSalt;
+
+    /// Send the same message to all parties and receive messages of all others.
+    async fn broadcast(
+        &mut self,
+        msg: Self::Message,
+    ) -> Result<Vec<(usize, Self::Message)>, SpdzError>;
+
+    /// Check if state hashes of all nodes are the same.
+    async fn check_state_hashes(&mut self) -> Result<(), SpdzError> {
+        let state_hash = self.state_digest().finalize_reset();
+        let received = self
+            .broadcast(Self::Message::state_hash_check(state_hash))
+            .await?;
+        if received
+            .into_iter()
+            .all(|(_, msg)| msg.into_state_hash_check() == Some(state_hash))
+        {
+            Ok(())
+        } else {
+            Err(SpdzError::StateHashMismatch)
+        }
+    }
+
+    /// Commit to values and exchange them. Broadcast consistency checking is deferred.
+    /// Returns values of all parties, indexed by party ID.
+    async fn exchange_with_commitment(
+        &mut self,
+        elems: Vec<Self::Value>,
+    ) -> Result<Vec<Vec<Self::Value>>, SpdzError> {
+        let digest_type = self.digest_type();
+        let own_salt = self.commitment_salt();
+        let own_hash = commit_values(digest_type, &elems, own_salt);
+
+        let received_messages = self.broadcast(Self::Message::commitment(own_hash)).await?;
+
+        let mut all_hashes = vec![Default::default(); self.num_parties()];
+        all_hashes[self.party_id()] = own_hash;
+
+        for (other_id, msg) in received_messages {
+            match msg.into_commitment() {
+                Some(other_hash) => all_hashes[other_id] = other_hash,
+                None => return Err(SpdzError::UnexpectedMessage(other_id)),
+            }
+        }
+
+        // Update state digest, we need to ensure later if broadcasts were consistent.
+        for hash in &all_hashes {
+            self.state_digest().update(hash);
+        }
+
+        let received_messages = self
+            .broadcast(Self::Message::decommitment(elems.clone(), own_salt))
+            .await?;
+
+        let mut all_elems = vec![Vec::new(); self.num_parties()];
+
+        for (other_id, msg) in received_messages {
+            let (other_elems, other_salt) = match msg.into_decommitment() {
+                Some(decommitment) => decommitment,
+                None => return Err(SpdzError::UnexpectedMessage(other_id)),
+            };
+            if all_hashes[other_id] != commit_values(digest_type, &other_elems, other_salt) {
+                return Err(SpdzError::CommitmentHashMismatch(other_id));
+            }
+            if other_elems.len() != elems.len() {
+                return Err(SpdzError::IncorrectNumberOfValues(other_id));
+            }
+            all_elems[other_id] = other_elems;
+        }
+
+        all_elems[self.party_id()] = elems;
+        Ok(all_elems)
+    }
+}
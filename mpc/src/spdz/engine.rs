@@ -1,36 +1,30 @@
-use std::{fmt, mem};
+use std::{collections::BTreeSet, fmt, mem, ops::Range};
 
 use async_trait::async_trait;
-use digest::Digest;
 use futures::{Sink, Stream};
 use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    transport::{MultipartyTransport, TransportError},
+    fields::FieldType,
+    transport::{ChannelError, MultipartyTransport, TransportError},
     MpcContext, MpcEngine, MpcField,
 };
 
-use super::{SpdzDealer, SpdzShare};
+use super::{
+    blame::{
+        self, Accusation, BlameState, BroadcastEvidence, BroadcastSignature, ShareEvidence,
+        TranscriptPosition, VerificationKey,
+    },
+    consistency::{
+        CommitmentSalt, ConsistencyMessage, ConsistentBroadcast, SpdzDigest, SpdzDigestOutput,
+    },
+    DigestType, SpdzConfig, SpdzCursor, SpdzDealer, SpdzSessionId, SpdzShare,
+};
 
 /// Random number generator used by SPDZ implementation.
 type SpdzRng = rand::rngs::StdRng;
 
-/// Hashing function used by SPDZ implementation.
-type SpdzDigest = sha3::Sha3_256;
-
-/// Output of hashing function used by SPDZ implementation.
-type SpdzDigestOutput = [u8; 32];
-
-/// Salt for commitments.
-type CommitmentSalt = [u8; 32];
-
-/// Threshold of collected partial openings that triggers integrity checking.
-const BATCH_CHECK_THRESHOLD: usize = 20000;
-
-/// Maximum number of values that can be checked using a single polynomial hash.
-const MAX_BATCH_CHECK_SIZE: usize = 40000;
-
 /// SPDZ protocol message.
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum SpdzMessage<T> {
@@ -38,11 +32,59 @@ pub enum SpdzMessage<T> {
     SharesExchange(Vec<T>),
     ShareSumExchange(Vec<T>),
     StateHashCheck(SpdzDigestOutput),
+    SessionCheck(Option<SpdzSessionId>, Option<SpdzCursor>, bool),
     Commitment(SpdzDigestOutput),
-    Decommitment(T, CommitmentSalt),
+    Decommitment(Vec<T>, CommitmentSalt),
+    VerificationKey(VerificationKey),
+    VerificationKeysCheck(SpdzDigestOutput),
+    Signed(Box<SpdzMessage<T>>, BroadcastSignature),
+    /// Shares sent to a king in identifiable-abort mode, signed for blame phase.
+    SignedShares(Vec<T>, BroadcastSignature),
+    /// Abort flag of a check in identifiable-abort mode, with the latest signed positions of other parties.
+    BlameReport(bool, Vec<BroadcastEvidence>),
+    /// Own shares of values opened since the last check and signed shares received as a king, sent in blame phase.
+    OpenedShares(Vec<T>, Vec<ShareEvidence<T>>),
+}
+
+impl<T> ConsistencyMessage<T> for SpdzMessage<T> {
+    fn commitment(hash: SpdzDigestOutput) -> Self {
+        Self::Commitment(hash)
+    }
+
+    fn decommitment(values: Vec<T>, salt: CommitmentSalt) -> Self {
+        Self::Decommitment(values, salt)
+    }
+
+    fn state_hash_check(hash: SpdzDigestOutput) -> Self {
+        Self::StateHashCheck(hash)
+    }
+
+    fn into_commitment(self) -> Option<SpdzDigestOutput> {
+        match self {
+            Self::Commitment(hash) => Some(hash),
+            _ => None,
+        }
+    }
+
+    fn into_decommitment(self) -> Option<(Vec<T>, CommitmentSalt)> {
+        match self {
+            Self::Decommitment(values, salt) => Some((values, salt)),
+            _ => None,
+        }
+    }
+
+    fn into_state_hash_check(self) -> Option<SpdzDigestOutput> {
+        match self {
+            Self::StateHashCheck(hash) => Some(hash),
+            _ => None,
+        }
+    }
 }
 
-/// SPDZ error.
+/// SPDZ error. Variants with party ID identify the sender of an invalid message,
+/// while `StateHashMismatch`, `MacCheckFailed`, `PeerAborted` and `VerificationKeysMismatch` cannot be attributed
+/// to any party. In identifiable-abort mode failed checks are followed by a blame phase, which replaces them with
+/// `InconsistentBroadcast`, `InvalidSignature` or `InconsistentShares` if a cheater is found.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SpdzError {
     Transport(TransportError),
@@ -52,6 +94,21 @@ pub enum SpdzError {
     StateHashMismatch,
     MacCheckFailed,
     DealerExhausted,
+    /// Party uses precomputed data from a different preprocessing run.
+    SessionMismatch(usize),
+    /// Party starts at a different position in precomputed data.
+    CursorMismatch(usize),
+    /// Party sent different broadcast messages to different parties, proven by its signatures.
+    InconsistentBroadcast(usize),
+    /// Message or blame report of party has an invalid signature.
+    InvalidSignature(usize),
+    /// Party opened shares in blame phase that differ from shares it signed for a king,
+    /// or, as a king, broadcast sums that differ from the opened shares.
+    InconsistentShares(usize),
+    /// Party reported a failed check, which doesn't prove that it deviated from the protocol.
+    PeerAborted(usize),
+    /// Parties received different verification keys in identifiable-abort mode.
+    VerificationKeysMismatch,
 }
 
 impl fmt::Display for SpdzError {
@@ -68,6 +125,51 @@ impl fmt::Display for SpdzError {
             Self::StateHashMismatch => write!(f, "State hashes do not match"),
             Self::MacCheckFailed => write!(f, "MAC check failed"),
             Self::DealerExhausted => write!(f, "Dealer exhausted"),
+            Self::SessionMismatch(id) => {
+                write!(f, "Party {} uses precomputed data from another session", id)
+            }
+            Self::CursorMismatch(id) => {
+                write!(
+                    f,
+                    "Party {} starts at another position in precomputed data",
+                    id
+                )
+            }
+            Self::InconsistentBroadcast(id) => {
+                write!(f, "Party {} sent inconsistent broadcast messages", id)
+            }
+            Self::InvalidSignature(id) => write!(f, "Invalid signature from {}", id),
+            Self::InconsistentShares(id) => {
+                write!(
+                    f,
+                    "Party {} sent shares inconsistent with opened values",
+                    id
+                )
+            }
+            Self::PeerAborted(id) => write!(f, "Party {} aborted after a failed check", id),
+            Self::VerificationKeysMismatch => write!(f, "Verification keys do not match"),
+        }
+    }
+}
+
+impl SpdzError {
+    /// ID of party that caused the error, if it can be attributed to a single party.
+    pub fn party_id(&self) -> Option<usize> {
+        match *self {
+            Self::Transport(ref err) => Some(err.peer_id()),
+            Self::UnexpectedMessage(id)
+            | Self::IncorrectNumberOfValues(id)
+            | Self::CommitmentHashMismatch(id)
+            | Self::SessionMismatch(id)
+            | Self::CursorMismatch(id)
+            | Self::InconsistentBroadcast(id)
+            | Self::InvalidSignature(id)
+            | Self::InconsistentShares(id) => Some(id),
+            Self::StateHashMismatch
+            | Self::MacCheckFailed
+            | Self::DealerExhausted
+            | Self::PeerAborted(_)
+            | Self::VerificationKeysMismatch => None,
         }
     }
 }
@@ -79,40 +181,107 @@ impl From<TransportError> for SpdzError {
 }
 
 /// Saved opened value for batch MAC checking.
-struct PartiallyOpenedValue<T> {
+struct PartiallyOpenedValue<T, const K: usize> {
     plain_value: T,
-    mac_share: T,
+    /// Own share of the value, opened during blame phase.
+    share: T,
+    mac_share: [T; K],
+}
+
+/// Opening round with kings since the last check, recorded in identifiable-abort mode.
+struct KingOpening<T> {
+    round: u64,
+    /// Index of the first value of this round among values opened since the last check.
+    offset: usize,
+    /// Values summed by each king.
+    ranges: Vec<Range<usize>>,
+    /// Signed shares received from other parties, if current party is a king in this round.
+    received: Vec<ShareEvidence<T>>,
 }
 
-/// SPDZ protocol implementation.
-pub struct SpdzEngine<T, Dealer, Channel> {
+/// SPDZ protocol implementation. Shares are authenticated with K independent MAC keys,
+/// so probability of undetected cheating is roughly (d/|F|)^K, where d is size of checked batch.
+pub struct SpdzEngine<T, Dealer, Channel, const K: usize = 1> {
     dealer: Dealer,
     transport: MultipartyTransport<SpdzMessage<T>, Channel>,
-    opened_values: Vec<PartiallyOpenedValue<T>>,
+    opened_values: Vec<PartiallyOpenedValue<T, K>>,
+    /// Opening rounds with kings since the last check, recorded in identifiable-abort mode.
+    king_openings: Vec<KingOpening<T>>,
     state_digest: SpdzDigest,
     rng: SpdzRng,
+    config: SpdzConfig,
+    opening_round: usize,
+    rounds_since_check: usize,
+    /// Communication rounds so far.
+    num_rounds: usize,
+    /// Session and position in precomputed data at creation and whether the previous run aborted,
+    /// compared with other parties before the first opening.
+    startup_check: Option<(Option<SpdzSessionId>, Option<SpdzCursor>, bool)>,
+    /// Signing keys and transcripts, present in identifiable-abort mode.
+    blame: Option<BlameState>,
 }
 
-impl<T, Dealer, Channel> SpdzEngine<T, Dealer, Channel> {
-    /// Create SPDZ protocol engine.
-    pub fn new(dealer: Dealer, transport: MultipartyTransport<SpdzMessage<T>, Channel>) -> Self {
+impl<T, Dealer, Channel, const K: usize> SpdzEngine<T, Dealer, Channel, K>
+where
+    Dealer: SpdzDealer<K>,
+{
+    /// Create SPDZ protocol engine with given configuration.
+    pub fn new(
+        dealer: Dealer,
+        transport: MultipartyTransport<SpdzMessage<T>, Channel>,
+        config: SpdzConfig,
+    ) -> Self {
+        config.assert_valid();
+        assert!(
+            Dealer::Field::FIELD_TYPE != FieldType::Ring64,
+            "SPDZ requires a prime field, use SPDZ2k engine for Z_2^64"
+        );
+        let (session_id, cursor) = (dealer.session_id(), dealer.cursor());
+        let startup_check = (session_id.is_some() || cursor.is_some()).then_some((
+            session_id,
+            cursor,
+            dealer.resumes_aborted_run(),
+        ));
+
+        let mut rng = SpdzRng::from_entropy();
+        let blame = config
+            .identifiable_abort
+            .then(|| BlameState::new(&mut rng, transport.num_parties()));
+
         Self {
             dealer,
             transport,
             opened_values: Vec::new(),
-            state_digest: SpdzDigest::new(),
-            rng: SpdzRng::from_entropy(),
+            king_openings: Vec::new(),
+            state_digest: SpdzDigest::new(config.digest),
+            rng,
+            config,
+            opening_round: 0,
+            rounds_since_check: 0,
+            num_rounds: 0,
+            startup_check,
+            blame,
         }
     }
+
+    /// Number of communication rounds so far, including input, commitment and integrity-check rounds.
+    pub fn num_rounds(&self) -> usize {
+        self.num_rounds
+    }
+
+    /// Finish computation and return dealer, e.g. to save consumption of precomputed data.
+    pub fn into_dealer(self) -> Dealer {
+        self.dealer
+    }
 }
 
-impl<T, Dealer, Channel> MpcContext for SpdzEngine<T, Dealer, Channel>
+impl<T, Dealer, Channel, const K: usize> MpcContext for SpdzEngine<T, Dealer, Channel, K>
 where
     T: MpcField,
-    Dealer: SpdzDealer<Field = T, Share = SpdzShare<T>>,
+    Dealer: SpdzDealer<K, Field = T, Share = SpdzShare<T, K>>,
 {
     type Field = T;
-    type Share = SpdzShare<T>;
+    type Share = SpdzShare<T, K>;
 
     fn num_parties(&self) -> usize {
         self.transport.num_parties()
@@ -124,10 +293,11 @@ where
 }
 
 #[async_trait(?Send)]
-impl<T, E, Dealer, Channel> MpcEngine for SpdzEngine<T, Dealer, Channel>
+impl<T, E, Dealer, Channel, const K: usize> MpcEngine for SpdzEngine<T, Dealer, Channel, K>
 where
     T: MpcField,
-    Dealer: SpdzDealer<Field = T, Share = SpdzShare<T>>,
+    Dealer: SpdzDealer<K, Field = T, Share = SpdzShare<T, K>>,
+    E: ChannelError,
     Channel: Stream<Item = Result<SpdzMessage<T>, E>> + Sink<SpdzMessage<T>> + Unpin,
 {
     type Dealer = Dealer;
@@ -141,6 +311,7 @@ where
         &mut self,
         inputs: Vec<Self::Field>,
     ) -> Result<Vec<Vec<Self::Share>>, SpdzError> {
+        self.check_startup().await?;
         let (own_shares, own_deltas): (Vec<_>, Vec<_>) = inputs
             .into_iter()
             .map(|x| {
@@ -155,8 +326,7 @@ where
         }
 
         let received_messages = self
-            .transport
-            .exchange_with_all(SpdzMessage::MaskedInputs(own_deltas.clone()))
+            .broadcast(SpdzMessage::MaskedInputs(own_deltas.clone()))
             .await?;
 
         let mut all_shares = vec![Vec::new(); self.num_parties()];
@@ -190,7 +360,8 @@ where
                 self.state_digest.update(x.to_repr());
             }
         }
-        self.check_state_hashes().await?;
+        let result = self.check_state_hashes().await;
+        self.blame_on_failure(result, &[], &[]).await?;
         Ok(all_shares)
     }
 
@@ -198,162 +369,736 @@ where
         &mut self,
         requests: Vec<Self::Share>,
     ) -> Result<Vec<Self::Field>, SpdzError> {
-        let mut values: Vec<_> = requests.iter().map(|x| x.value).collect();
-        let values_count = values.len();
+        self.check_startup().await?;
+        let shares: Vec<_> = requests.iter().map(|x| x.value).collect();
+        let num_parties = self.num_parties();
+        let values_count = shares.len();
 
-        if self.party_id() == 0 {
-            for (other_id, msg) in self.transport.receive_from_all().await? {
-                if let SpdzMessage::SharesExchange(parts) = msg {
-                    if parts.len() != values_count {
-                        return Err(SpdzError::IncorrectNumberOfValues(other_id));
-                    }
-                    for (i, part) in parts.into_iter().enumerate() {
-                        values[i] += part;
-                    }
-                } else {
-                    return Err(SpdzError::UnexpectedMessage(other_id));
-                }
-            }
-            self.transport
-                .send_to_all(SpdzMessage::ShareSumExchange(values.clone()))
-                .await?;
-        } else {
-            self.transport
-                .send_to(0, SpdzMessage::SharesExchange(values))
-                .await?;
-            if let SpdzMessage::ShareSumExchange(sums) = self.transport.receive_from(0).await? {
-                if sums.len() != values_count {
-                    return Err(SpdzError::IncorrectNumberOfValues(0));
-                }
-                values = sums;
-            } else {
-                return Err(SpdzError::UnexpectedMessage(0));
-            }
-        }
+        // Inconsistent sums sent by kings (or shares sent by other parties in all-to-all mode)
+        // lead to different opened values, which are detected by state hash check.
+        let ranges =
+            self.config
+                .opening_strategy
+                .king_ranges(num_parties, self.opening_round, values_count);
+        let values = match ranges {
+            Some(ranges) => self.open_with_kings(shares, &ranges).await?,
+            None => self.open_all_to_all(shares).await?,
+        };
+        self.opening_round += 1;
+        self.rounds_since_check += 1;
 
         // Save opened values for batch MAC and broadcast checking.
         self.opened_values
             .extend(values.iter().zip(requests.iter()).map(|(value, share)| {
                 PartiallyOpenedValue {
                     plain_value: *value,
+                    share: share.value,
                     mac_share: share.mac,
                 }
             }));
 
-        if self.opened_values.len() >= BATCH_CHECK_THRESHOLD {
+        let check_forced = matches!(self.config.check_every_rounds,
+            Some(rounds) if self.rounds_since_check >= rounds);
+        if check_forced || self.opened_values.len() >= self.config.batch_check_threshold {
             self.check_integrity().await?;
         }
         Ok(values)
     }
 
+    async fn process_private_openings_unchecked(
+        &mut self,
+        requests: Vec<Self::Share>,
+        private_requests: Vec<(usize, Self::Share)>,
+    ) -> Result<(Vec<Self::Field>, Vec<Option<Self::Field>>), SpdzError> {
+        // Each share is masked with an output mask of its recipient and opened publicly together with
+        // partial openings, so masked values are covered by the usual batch MAC check.
+        let party_id = self.party_id();
+        let num_requests = requests.len();
+        let (masked_shares, own_masks): (Vec<_>, Vec<_>) = private_requests
+            .into_iter()
+            .map(|(id, share)| {
+                if id == party_id {
+                    let (mask, plain) = self.dealer.next_output_mask_own();
+                    (share + mask, Some(plain))
+                } else {
+                    (share + self.dealer.next_output_mask_for(id), None)
+                }
+            })
+            .unzip();
+
+        if self.dealer.is_exhausted() {
+            return Err(SpdzError::DealerExhausted);
+        }
+
+        let mut values = self
+            .process_openings_unchecked([requests, masked_shares].concat())
+            .await?;
+        let masked_values = values.split_off(num_requests);
+        Ok((
+            values,
+            masked_values
+                .into_iter()
+                .zip(own_masks)
+                .map(|(value, mask)| mask.map(|mask| value - mask))
+                .collect(),
+        ))
+    }
+
     async fn check_integrity(&mut self) -> Result<(), Self::Error> {
+        self.check_startup().await?;
         let opened_values = mem::take(&mut self.opened_values);
+        let king_openings = mem::take(&mut self.king_openings);
+        let result = self.check_integrity_unattributed(&opened_values).await;
+        self.blame_on_failure(result, &opened_values, &king_openings)
+            .await
+    }
+}
 
-        for chunk in opened_values.chunks(MAX_BATCH_CHECK_SIZE) {
-            let root = self.gen_common_random_element().await?;
-
-            let plain_value = polynomial_eval(chunk.iter().map(|x| x.plain_value), root);
-            let mac_share = polynomial_eval(chunk.iter().map(|x| x.mac_share), root);
-            let check_share = mac_share - plain_value * self.dealer().authentication_key_share();
+#[async_trait(?Send)]
+impl<T, E, Dealer, Channel, const K: usize> ConsistentBroadcast
+    for SpdzEngine<T, Dealer, Channel, K>
+where
+    T: MpcField,
+    Dealer: SpdzDealer<K, Field = T, Share = SpdzShare<T, K>>,
+    E: ChannelError,
+    Channel: Stream<Item = Result<SpdzMessage<T>, E>> + Sink<SpdzMessage<T>> + Unpin,
+{
+    type Value = T;
+    type Message = SpdzMessage<T>;
 
-            let shares = self.exchange_with_commitment(check_share).await?;
-            let check_plain = shares.into_iter().fold(T::zero(), |acc, x| acc + x);
+    fn digest_type(&self) -> DigestType {
+        self.config.digest
+    }
 
-            if check_plain != T::zero() {
-                return Err(SpdzError::MacCheckFailed);
-            }
+    fn state_digest(&mut self) -> &mut SpdzDigest {
+        &mut self.state_digest
+    }
 
-            // Ensure broadcasted values were consistent by including their combination in state hash.
-            self.state_digest.update(plain_value.to_repr());
-        }
+    fn commitment_salt(&mut self) -> CommitmentSalt {
+        self.rng.gen()
+    }
 
-        // Check consistency of all broadcasts since last check.
-        self.check_state_hashes().await
+    /// In identifiable-abort mode messages are signed, so inconsistent broadcasts can be proven during blame phase.
+    async fn broadcast(
+        &mut self,
+        msg: SpdzMessage<T>,
+    ) -> Result<Vec<(usize, SpdzMessage<T>)>, SpdzError> {
+        let receive_from = vec![true; self.num_parties()];
+        self.broadcast_with_some(Some(msg), &receive_from).await
     }
 }
 
-impl<T, E, Dealer, Channel> SpdzEngine<T, Dealer, Channel>
+impl<T, E, Dealer, Channel, const K: usize> SpdzEngine<T, Dealer, Channel, K>
 where
     T: MpcField,
-    Dealer: SpdzDealer<Field = T, Share = SpdzShare<T>>,
+    Dealer: SpdzDealer<K, Field = T, Share = SpdzShare<T, K>>,
+    E: ChannelError,
     Channel: Stream<Item = Result<SpdzMessage<T>, E>> + Sink<SpdzMessage<T>> + Unpin,
 {
-    /// Check if state hashes of all nodes are the same.
-    async fn check_state_hashes(&mut self) -> Result<(), SpdzError> {
-        let state_hash = self.state_digest.finalize_reset().into();
-        let msg = SpdzMessage::StateHashCheck(state_hash);
-        let received = self.transport.exchange_with_all(msg.clone()).await?;
-        if received.into_iter().all(|(_, other_msg)| other_msg == msg) {
-            Ok(())
-        } else {
-            Err(SpdzError::StateHashMismatch)
+    /// Check integrity of given opened values and everything broadcast so far, without trying to identify cheaters.
+    /// All batches and state hashes are checked even after a failed MAC check, so that all parties finish it together.
+    async fn check_integrity_unattributed(
+        &mut self,
+        opened_values: &[PartiallyOpenedValue<T, K>],
+    ) -> Result<(), SpdzError> {
+        self.rounds_since_check = 0;
+
+        let mut mac_check_failed = false;
+        for chunk in opened_values.chunks(self.config.max_batch_check_size) {
+            // Each MAC key is checked using independent random point.
+            let roots = self.gen_common_random_elements(K).await?;
+            let auth_key = self.dealer.authentication_key_share();
+
+            let (plain_values, check_shares): (Vec<_>, Vec<_>) = roots
+                .iter()
+                .enumerate()
+                .map(|(i, &root)| {
+                    let plain_value = polynomial_eval(chunk.iter().map(|x| x.plain_value), root);
+                    let mac_share = polynomial_eval(chunk.iter().map(|x| x.mac_share[i]), root);
+                    (plain_value, mac_share - plain_value * auth_key[i])
+                })
+                .unzip();
+
+            let shares = self.exchange_with_commitment(check_shares).await?;
+            mac_check_failed |= (0..K).any(|i| {
+                let check_plain = shares.iter().fold(T::zero(), |acc, x| acc + x[i]);
+                check_plain != T::zero()
+            });
+
+            // Ensure broadcasted values were consistent by including their combinations in state hash.
+            for plain_value in plain_values {
+                self.state_digest.update(plain_value.to_repr());
+            }
+        }
+
+        // Check consistency of all broadcasts since last check.
+        let result = self.check_state_hashes().await;
+        match mac_check_failed {
+            true => result.and(Err(SpdzError::MacCheckFailed)),
+            false => result,
         }
     }
 
-    /// Generate public common random element. Broadcast consistency checking is deferred.
-    async fn gen_common_random_element(&mut self) -> Result<T, SpdzError> {
-        let seed = T::random(&mut self.rng);
-        let all_seeds = self.exchange_with_commitment(seed).await?;
-        Ok(all_seeds.into_iter().fold(T::zero(), |acc, x| acc + x))
+    /// Startup handshake: check that all parties use precomputed data from the same session
+    /// and start at the same position in it, before any data is used. Done only once and only if known by the dealer.
+    /// After an aborted run, parties may be at different positions and all of them skip to the furthest one,
+    /// so that no value is used twice.
+    async fn check_startup(&mut self) -> Result<(), SpdzError> {
+        let (session_id, cursor, aborted) = match self.startup_check.take() {
+            Some(check) => check,
+            None => return Ok(()),
+        };
+        let msg = SpdzMessage::SessionCheck(session_id, cursor.clone(), aborted);
+        let mut any_aborted = aborted;
+        let mut furthest = cursor.clone();
+        let mut mismatch = None;
+        for (other_id, other_msg) in self.broadcast(msg).await? {
+            match other_msg {
+                SpdzMessage::SessionCheck(other_session_id, _, _)
+                    if other_session_id != session_id =>
+                {
+                    return Err(SpdzError::SessionMismatch(other_id))
+                }
+                SpdzMessage::SessionCheck(_, other_cursor, other_aborted) => {
+                    any_aborted |= other_aborted;
+                    if other_cursor != cursor {
+                        mismatch.get_or_insert(other_id);
+                    }
+                    furthest = furthest.zip(other_cursor).and_then(|(a, b)| a.furthest(&b));
+                }
+                _ => return Err(SpdzError::UnexpectedMessage(other_id)),
+            }
+        }
+        match (mismatch, furthest) {
+            (None, _) => Ok(()),
+            (Some(_), Some(furthest)) if any_aborted => {
+                skip_to_cursor(&mut self.dealer, &furthest);
+                match self.dealer.is_exhausted() {
+                    true => Err(SpdzError::DealerExhausted),
+                    false => Ok(()),
+                }
+            }
+            (Some(other_id), _) => Err(SpdzError::CursorMismatch(other_id)),
+        }
     }
 
-    /// Commit to field elements and exchange them. Broadcast consistency checking is deferred.
-    async fn exchange_with_commitment(&mut self, elem: T) -> Result<Vec<T>, SpdzError> {
-        let own_salt: CommitmentSalt = self.rng.gen();
-        let own_hash = commit_value(elem, own_salt);
+    /// Open values by sending shares to kings, which sum them and broadcast results.
+    /// King of party ID `i` is responsible for values in `ranges[i]`.
+    /// In identifiable-abort mode shares are signed and kept by kings as evidence for blame phase.
+    async fn open_with_kings(
+        &mut self,
+        mut values: Vec<T>,
+        ranges: &[Range<usize>],
+    ) -> Result<Vec<T>, SpdzError> {
+        self.exchange_verification_keys().await?;
+        let party_id = self.party_id();
+        let own_range = ranges[party_id].clone();
+        let is_king: Vec<_> = ranges.iter().map(|range| !range.is_empty()).collect();
 
-        let received_messages = self
+        let round = self.opening_round as u64;
+        let messages = ranges
+            .iter()
+            .enumerate()
+            .map(|(id, range)| {
+                (id != party_id && !range.is_empty()).then(|| {
+                    let shares = values[range.clone()].to_vec();
+                    match &self.blame {
+                        Some(state) => {
+                            let digest = shares_digest(self.config.digest, &shares);
+                            let signature = state.sign_shares(party_id, id, round, &digest);
+                            SpdzMessage::SignedShares(shares, signature)
+                        }
+                        None => SpdzMessage::SharesExchange(shares),
+                    }
+                })
+            })
+            .collect();
+        let receive_shares = vec![!own_range.is_empty(); ranges.len()];
+
+        self.num_rounds += 1;
+        let mut received = Vec::new();
+        for (other_id, msg) in self
             .transport
-            .exchange_with_all(SpdzMessage::Commitment(own_hash))
-            .await?;
+            .exchange_with_some(messages, &receive_shares)
+            .await?
+        {
+            let parts = match (msg, &self.blame) {
+                (SpdzMessage::SharesExchange(parts), None) => parts,
+                (SpdzMessage::SignedShares(parts, signature), Some(state)) => {
+                    let digest = shares_digest(self.config.digest, &parts);
+                    let key = &state.keys.as_ref().expect("Keys are exchanged")[other_id];
+                    if !blame::verify_shares(key, other_id, party_id, round, &digest, &signature) {
+                        return Err(SpdzError::InvalidSignature(other_id));
+                    }
+                    received.push(ShareEvidence {
+                        party_id: other_id,
+                        round,
+                        shares: parts.clone(),
+                        signature,
+                    });
+                    parts
+                }
+                _ => return Err(SpdzError::UnexpectedMessage(other_id)),
+            };
+            if parts.len() != own_range.len() {
+                return Err(SpdzError::IncorrectNumberOfValues(other_id));
+            }
+            for (value, part) in values[own_range.clone()].iter_mut().zip(parts) {
+                *value += part;
+            }
+        }
 
-        let mut all_hashes = vec![Default::default(); self.num_parties()];
-        all_hashes[self.party_id()] = own_hash;
+        let own_sums = (!own_range.is_empty())
+            .then(|| SpdzMessage::ShareSumExchange(values[own_range.clone()].to_vec()));
 
-        for (other_id, msg) in received_messages {
-            if let SpdzMessage::Commitment(other_hash) = msg {
-                all_hashes[other_id] = other_hash;
+        for (other_id, msg) in self.broadcast_with_some(own_sums, &is_king).await? {
+            if let SpdzMessage::ShareSumExchange(sums) = msg {
+                let range = ranges[other_id].clone();
+                if sums.len() != range.len() {
+                    return Err(SpdzError::IncorrectNumberOfValues(other_id));
+                }
+                values[range].copy_from_slice(&sums);
             } else {
                 return Err(SpdzError::UnexpectedMessage(other_id));
             }
         }
 
-        // Update state digest, we need to ensure later if broadcasts were consistent.
-        for hash in &all_hashes {
-            self.state_digest.update(hash);
+        if self.blame.is_some() {
+            self.king_openings.push(KingOpening {
+                round,
+                offset: self.opened_values.len(),
+                ranges: ranges.to_vec(),
+                received,
+            });
         }
+        Ok(values)
+    }
 
-        let received_messages = self
+    /// Open values by broadcasting shares to all parties.
+    async fn open_all_to_all(&mut self, mut values: Vec<T>) -> Result<Vec<T>, SpdzError> {
+        let values_count = values.len();
+        for (other_id, msg) in self
+            .broadcast(SpdzMessage::SharesExchange(values.clone()))
+            .await?
+        {
+            if let SpdzMessage::SharesExchange(parts) = msg {
+                if parts.len() != values_count {
+                    return Err(SpdzError::IncorrectNumberOfValues(other_id));
+                }
+                for (value, part) in values.iter_mut().zip(parts) {
+                    *value += part;
+                }
+            } else {
+                return Err(SpdzError::UnexpectedMessage(other_id));
+            }
+        }
+        Ok(values)
+    }
+
+    /// Generate public common random elements. Broadcast consistency checking is deferred.
+    async fn gen_common_random_elements(&mut self, count: usize) -> Result<Vec<T>, SpdzError> {
+        let seeds = (0..count).map(|_| T::random(&mut self.rng)).collect();
+        let all_seeds = self.exchange_with_commitment(seeds).await?;
+        Ok((0..count)
+            .map(|i| all_seeds.iter().fold(T::zero(), |acc, x| acc + x[i]))
+            .collect())
+    }
+
+    /// Optionally send the same message to all parties, and receive messages of parties selected by flags.
+    async fn broadcast_with_some(
+        &mut self,
+        msg: Option<SpdzMessage<T>>,
+        receive_from: &[bool],
+    ) -> Result<Vec<(usize, SpdzMessage<T>)>, SpdzError> {
+        self.exchange_verification_keys().await?;
+        let msg = msg.map(|msg| self.sign_broadcast(msg));
+        self.num_rounds += 1;
+        let received = self
             .transport
-            .exchange_with_all(SpdzMessage::Decommitment(elem, own_salt))
+            .exchange_with_some(vec![msg; self.num_parties()], receive_from)
             .await?;
+        received
+            .into_iter()
+            .map(|(other_id, msg)| Ok((other_id, self.verify_broadcast(other_id, msg)?)))
+            .collect()
+    }
 
-        let mut all_elems = vec![Default::default(); self.num_parties()];
-        all_elems[self.party_id()] = elem;
+    /// Agree on verification keys of all parties before the first signed broadcast.
+    /// Parties that receive different keys abort before any data is shared.
+    async fn exchange_verification_keys(&mut self) -> Result<(), SpdzError> {
+        let own_key = match &self.blame {
+            Some(state) if state.keys.is_none() => state.verification_key(),
+            _ => return Ok(()),
+        };
 
-        for (other_id, msg) in received_messages {
-            if let SpdzMessage::Decommitment(other_elem, other_salt) = msg {
-                let other_hash = commit_value(other_elem, other_salt);
-                if all_hashes[other_id] != other_hash {
-                    return Err(SpdzError::CommitmentHashMismatch(other_id));
-                }
-                all_elems[other_id] = other_elem;
+        let mut keys = vec![own_key; self.num_parties()];
+        self.num_rounds += 2;
+        for (other_id, msg) in self
+            .transport
+            .exchange_with_all(SpdzMessage::VerificationKey(own_key))
+            .await?
+        {
+            if let SpdzMessage::VerificationKey(key) = msg {
+                keys[other_id] = key;
             } else {
                 return Err(SpdzError::UnexpectedMessage(other_id));
             }
         }
 
-        Ok(all_elems)
+        let mut digest = SpdzDigest::new(self.config.digest);
+        for key in &keys {
+            digest.update(key);
+        }
+        let msg = SpdzMessage::VerificationKeysCheck(digest.finalize_reset());
+        let received = self.transport.exchange_with_all(msg.clone()).await?;
+        if received.into_iter().any(|(_, other_msg)| other_msg != msg) {
+            return Err(SpdzError::VerificationKeysMismatch);
+        }
+
+        if let Some(state) = &mut self.blame {
+            state.keys = Some(keys);
+        }
+        Ok(())
+    }
+
+    /// Sign broadcast message in identifiable-abort mode.
+    fn sign_broadcast(&mut self, msg: SpdzMessage<T>) -> SpdzMessage<T> {
+        let party_id = self.party_id();
+        match &mut self.blame {
+            Some(state) => {
+                state.sent = next_position(self.config.digest, &state.sent, &msg);
+                let signature = state.sign(party_id, &state.sent);
+                SpdzMessage::Signed(Box::new(msg), signature)
+            }
+            None => msg,
+        }
+    }
+
+    /// Check signature of broadcast message in identifiable-abort mode and unwrap it.
+    fn verify_broadcast(
+        &mut self,
+        other_id: usize,
+        msg: SpdzMessage<T>,
+    ) -> Result<SpdzMessage<T>, SpdzError> {
+        let state = match &mut self.blame {
+            Some(state) => state,
+            None => return Ok(msg),
+        };
+        let (msg, signature) = match msg {
+            SpdzMessage::Signed(msg, signature)
+                if !matches!(
+                    *msg,
+                    SpdzMessage::Signed(..)
+                        | SpdzMessage::SignedShares(..)
+                        | SpdzMessage::BlameReport(..)
+                        | SpdzMessage::OpenedShares(..)
+                ) =>
+            {
+                (*msg, signature)
+            }
+            _ => return Err(SpdzError::UnexpectedMessage(other_id)),
+        };
+
+        let position = next_position(self.config.digest, &state.received[other_id], &msg);
+        let key = &state.keys.as_ref().expect("Keys are exchanged")[other_id];
+        if !blame::verify(key, other_id, &position, &signature) {
+            return Err(SpdzError::InvalidSignature(other_id));
+        }
+        state.received[other_id] = position;
+        state.evidence[other_id] = Some(BroadcastEvidence {
+            party_id: other_id,
+            position,
+            signature,
+        });
+        Ok(msg)
+    }
+
+    /// In identifiable-abort mode, finish a check with an outcome round, in which all parties send an abort flag
+    /// and the latest signed positions in broadcast transcripts of others. If any party aborted, all of them
+    /// enter blame phase: two different transcripts of the same length signed by the same party prove that
+    /// it sent inconsistent broadcasts. After a failed MAC check, parties also open their shares of values
+    /// opened since the last check, and kings forward signed shares they received, which proves
+    /// inconsistent shares or sums. Returns the original error if no cheater can be proven, e.g. because
+    /// a party modified its own shares consistently, or `PeerAborted` if only other parties detected a failure.
+    async fn blame_on_failure(
+        &mut self,
+        result: Result<(), SpdzError>,
+        opened_values: &[PartiallyOpenedValue<T, K>],
+        king_openings: &[KingOpening<T>],
+    ) -> Result<(), SpdzError> {
+        let err = match result {
+            Ok(()) => None,
+            Err(err @ (SpdzError::MacCheckFailed | SpdzError::StateHashMismatch)) => Some(err),
+            result => return result,
+        };
+        let (keys, own_report): (_, Vec<_>) = match &self.blame {
+            Some(BlameState {
+                keys: Some(keys),
+                evidence,
+                ..
+            }) => (keys.clone(), evidence.iter().flatten().copied().collect()),
+            _ => return err.map_or(Ok(()), Err),
+        };
+
+        self.num_rounds += 1;
+        let received = self
+            .transport
+            .exchange_with_all(SpdzMessage::BlameReport(err.is_some(), own_report.clone()))
+            .await;
+        let received = match received {
+            Ok(received) => received,
+            Err(transport_err) => return Err(err.unwrap_or_else(|| transport_err.into())),
+        };
+        let mut aborted = None;
+        let mut reports = vec![(self.party_id(), own_report)];
+        for (other_id, msg) in received {
+            match msg {
+                SpdzMessage::BlameReport(other_aborted, evidence) => {
+                    if other_aborted {
+                        aborted.get_or_insert(other_id);
+                    }
+                    reports.push((other_id, evidence));
+                }
+                _ => return Err(SpdzError::UnexpectedMessage(other_id)),
+            }
+        }
+        let err = match (err, aborted) {
+            (Some(err), _) => err,
+            (None, Some(other_id)) => SpdzError::PeerAborted(other_id),
+            (None, None) => return Ok(()),
+        };
+
+        // Shares are opened only after a failed MAC check with matching state hashes,
+        // which all honest parties detect together, since they have the same opened values.
+        let share_accusation = match err {
+            SpdzError::MacCheckFailed if !king_openings.is_empty() => {
+                self.open_shares(&keys, opened_values, king_openings)
+                    .await?
+            }
+            _ => None,
+        };
+
+        Err(match blame::find_cheater(&keys, reports) {
+            Some(Accusation::InconsistentBroadcast(id)) => SpdzError::InconsistentBroadcast(id),
+            Some(Accusation::InvalidSignature(id)) => SpdzError::InvalidSignature(id),
+            None => share_accusation.unwrap_or(err),
+        })
+    }
+
+    /// Open own shares of given values and forward signed shares received as a king during blame phase.
+    /// Returns error accusing a party which sent shares or sums inconsistent with opened shares.
+    async fn open_shares(
+        &mut self,
+        keys: &[VerificationKey],
+        values: &[PartiallyOpenedValue<T, K>],
+        openings: &[KingOpening<T>],
+    ) -> Result<Option<SpdzError>, SpdzError> {
+        let own_shares: Vec<_> = values.iter().map(|x| x.share).collect();
+        let own_evidence: Vec<_> = openings
+            .iter()
+            .flat_map(|opening| opening.received.iter().cloned())
+            .collect();
+
+        self.num_rounds += 1;
+        let mut all_shares = vec![Vec::new(); self.num_parties()];
+        let mut all_evidence = vec![Vec::new(); self.num_parties()];
+        for (other_id, msg) in self
+            .transport
+            .exchange_with_all(SpdzMessage::OpenedShares(
+                own_shares.clone(),
+                own_evidence.clone(),
+            ))
+            .await?
+        {
+            match msg {
+                SpdzMessage::OpenedShares(shares, _) if shares.len() != values.len() => {
+                    return Err(SpdzError::IncorrectNumberOfValues(other_id));
+                }
+                SpdzMessage::OpenedShares(shares, evidence) => {
+                    all_shares[other_id] = shares;
+                    all_evidence[other_id] = evidence;
+                }
+                _ => return Err(SpdzError::UnexpectedMessage(other_id)),
+            }
+        }
+        all_shares[self.party_id()] = own_shares;
+        all_evidence[self.party_id()] = own_evidence;
+
+        Ok(find_inconsistent_shares(
+            self.config.digest,
+            keys,
+            values,
+            openings,
+            &all_shares,
+            &all_evidence,
+        )
+        .map(SpdzError::InconsistentShares))
+    }
+}
+
+/// Find a party whose shares or sums are inconsistent with shares opened during blame phase.
+/// Shares and signed evidence forwarded by kings are indexed by party ID. Returns the lowest accused party ID.
+fn find_inconsistent_shares<T: MpcField, const K: usize>(
+    digest_type: DigestType,
+    keys: &[VerificationKey],
+    values: &[PartiallyOpenedValue<T, K>],
+    openings: &[KingOpening<T>],
+    all_shares: &[Vec<T>],
+    all_evidence: &[Vec<ShareEvidence<T>>],
+) -> Option<usize> {
+    let mut accused = BTreeSet::new();
+    for (king, evidence) in all_evidence.iter().enumerate() {
+        for item in evidence {
+            let opening = openings.iter().find(|opening| opening.round == item.round);
+            let (opening, sender) = match (opening, keys.get(item.party_id)) {
+                (Some(opening), Some(_)) => (opening, item.party_id),
+                _ => {
+                    accused.insert(king);
+                    continue;
+                }
+            };
+            let digest = shares_digest(digest_type, &item.shares);
+            let range = &opening.ranges[king];
+            let valid = blame::verify_shares(
+                &keys[sender],
+                sender,
+                king,
+                item.round,
+                &digest,
+                &item.signature,
+            );
+            let opened = all_shares[sender]
+                .get(opening.offset + range.start..opening.offset + range.end)
+                .unwrap_or_default();
+            if !valid {
+                accused.insert(king);
+            } else if item.shares != opened {
+                accused.insert(sender);
+            }
+        }
+    }
+
+    // Sums are checked only if all signed shares match opened ones, so that a king is accused only
+    // if its broadcast sum differs from the sum of shares it received and its own opened share.
+    if accused.is_empty() {
+        for opening in openings {
+            for (king, range) in opening.ranges.iter().enumerate() {
+                for index in range.clone() {
+                    let index = opening.offset + index;
+                    let sum = all_shares.iter().fold(T::zero(), |acc, x| acc + x[index]);
+                    if values.get(index).map(|x| x.plain_value) != Some(sum) {
+                        accused.insert(king);
+                    }
+                }
+            }
+        }
+    }
+    accused.into_iter().next()
+}
+
+/// Position in broadcast transcript after given message.
+fn next_position<T: MpcField>(
+    digest_type: DigestType,
+    position: &TranscriptPosition,
+    msg: &SpdzMessage<T>,
+) -> TranscriptPosition {
+    let mut digest = SpdzDigest::new(digest_type);
+    digest.update(position.digest);
+    // Tags separate messages of different kinds with the same content.
+    let (tag, values): (u8, &[T]) = match msg {
+        SpdzMessage::MaskedInputs(values) => (0, values),
+        SpdzMessage::SharesExchange(values) => (1, values),
+        SpdzMessage::ShareSumExchange(values) => (2, values),
+        SpdzMessage::StateHashCheck(hash) => {
+            digest.update(hash);
+            (3, &[])
+        }
+        SpdzMessage::SessionCheck(session_id, cursor, aborted) => {
+            digest.update(bincode::serialize(&(session_id, cursor, aborted)).unwrap());
+            (4, &[])
+        }
+        SpdzMessage::Commitment(hash) => {
+            digest.update(hash);
+            (5, &[])
+        }
+        SpdzMessage::Decommitment(values, salt) => {
+            digest.update(salt);
+            (6, values)
+        }
+        SpdzMessage::VerificationKey(key) => {
+            digest.update(key);
+            (7, &[])
+        }
+        SpdzMessage::VerificationKeysCheck(hash) => {
+            digest.update(hash);
+            (8, &[])
+        }
+        SpdzMessage::Signed(..)
+        | SpdzMessage::SignedShares(..)
+        | SpdzMessage::BlameReport(..)
+        | SpdzMessage::OpenedShares(..) => {
+            unreachable!("Message cannot be signed")
+        }
+    };
+    digest.update([tag]);
+    digest.update(values.len().to_le_bytes());
+    for value in values {
+        digest.update(value.to_repr());
+    }
+    TranscriptPosition {
+        count: position.count + 1,
+        digest: digest.finalize_reset(),
+    }
+}
+
+/// Consume data from dealer until it reaches given position, which must not be before its current one.
+fn skip_to_cursor<const K: usize>(dealer: &mut impl SpdzDealer<K>, target: &SpdzCursor) {
+    let cursor = dealer.cursor().unwrap_or_default();
+    for _ in cursor.beaver_triples..target.beaver_triples {
+        dealer.next_beaver_triple();
+    }
+    for _ in cursor.square_pairs..target.square_pairs {
+        dealer.next_square_pair();
+    }
+    for _ in cursor.inverse_pairs..target.inverse_pairs {
+        dealer.next_inverse_pair();
+    }
+    for _ in cursor.random_bits..target.random_bits {
+        dealer.next_uint(1);
+    }
+    for (&width, &count) in &target.bit_masks {
+        for _ in cursor.bit_masks.get(&width).copied().unwrap_or(0)..count {
+            dealer.next_bit_mask(width);
+        }
+    }
+    for (id, &count) in target.input_masks.iter().enumerate() {
+        for _ in cursor.input_masks.get(id).copied().unwrap_or(0)..count {
+            if id == dealer.party_id() {
+                dealer.next_input_mask_own();
+            } else {
+                dealer.next_input_mask_for(id);
+            }
+        }
+    }
+    for (id, &count) in target.output_masks.iter().enumerate() {
+        for _ in cursor.output_masks.get(id).copied().unwrap_or(0)..count {
+            if id == dealer.party_id() {
+                dealer.next_output_mask_own();
+            } else {
+                dealer.next_output_mask_for(id);
+            }
+        }
     }
 }
 
-/// Commit to a value with given salt.
-fn commit_value<T: MpcField>(value: T, salt: CommitmentSalt) -> SpdzDigestOutput {
-    SpdzDigest::new()
-        .chain_update(salt)
-        .chain_update(value.to_repr())
-        .finalize()
-        .into()
+/// Digest of shares sent to a king, which is signed in identifiable-abort mode.
+fn shares_digest<T: MpcField>(digest_type: DigestType, shares: &[T]) -> SpdzDigestOutput {
+    let mut digest = SpdzDigest::new(digest_type);
+    digest.update(shares.len().to_le_bytes());
+    for share in shares {
+        digest.update(share.to_repr());
+    }
+    digest.finalize_reset()
 }
 
 // Evaluate polynomial over field.
@@ -366,46 +1111,76 @@ fn polynomial_eval<T: ff::Field>(coeffs: impl IntoIterator<Item = T>, x: T) -> T
 #[cfg(test)]
 mod tests {
     use std::fmt::Debug;
-    use std::pin::Pin;
+    use std::{pin::Pin, time::Duration};
 
-    use futures::{stream::FuturesUnordered, Future, StreamExt};
+    use futures::{future, Future};
+    use tokio::time::Instant;
 
     use crate::{
         circuits::{self, join_circuits_all},
-        executor::{self, MpcExecution},
-        spdz::{FakeSpdzDealer, SpdzShare},
-        transport::{self, BincodeDuplex},
+        executor::{self, MpcExecution, MpcExecutionError},
+        spdz::{
+            testing::{mock_spdz_engines, run_mock_spdz, MockSpdzExecution, MockSpdzField},
+            FakeSpdzDealer, OpeningStrategy, SpdzShare,
+        },
+        transport::{
+            self, inject_faults, no_fault, Fault, FaultyChannel, LinkConditions, MemoryChannel,
+            SimulatedChannel, SimulatedNetwork, TransportError,
+        },
+        MpcContext, MpcEngine, MpcField, MpcShare,
     };
 
-    use super::{SpdzEngine, SpdzMessage};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{
+        next_position, shares_digest, BlameState, DigestType, SpdzConfig, SpdzEngine, SpdzError,
+        SpdzMessage, TranscriptPosition,
+    };
+    use crate::spdz::consistency::commit_values;
 
-    type Fp = crate::fields::Mersenne127;
-    type MockSpdzEngine = SpdzEngine<Fp, FakeSpdzDealer<Fp>, BincodeDuplex<SpdzMessage<Fp>>>;
+    type Fp = MockSpdzField;
 
     async fn run_spdz<F, T>(inputs: Vec<Vec<Fp>>, circuit_fn: F) -> T
     where
         T: PartialEq + Eq + Debug,
         F: Copy
             + Fn(
-                &'_ MpcExecution<MockSpdzEngine>,
+                &'_ MockSpdzExecution,
                 Vec<Vec<SpdzShare<Fp>>>,
             ) -> Pin<Box<dyn Future<Output = T> + '_>>,
     {
-        let num_parties = inputs.len();
-        let channel_matrix = transport::mock_multiparty_channels(num_parties, 512);
-        let futures = FuturesUnordered::new();
-
-        for (party_id, transport) in channel_matrix.into_iter().enumerate() {
-            let dealer = FakeSpdzDealer::new(num_parties, party_id, 123);
-            let engine = MockSpdzEngine::new(dealer, transport);
-            futures.push(executor::run_circuit(engine, &inputs[party_id], circuit_fn));
-        }
-
-        let outputs: Vec<_> = futures.map(|result| result.unwrap()).collect().await;
-        for i in 1..num_parties {
+        let outputs = run_spdz_parties(inputs, circuit_fn).await;
+        for i in 1..outputs.len() {
             assert_eq!(outputs[i], outputs[0], "Mismatched outputs",);
         }
-        outputs.into_iter().next().unwrap().0
+        outputs.into_iter().next().unwrap()
+    }
+
+    async fn run_spdz_parties<F, T>(inputs: Vec<Vec<Fp>>, circuit_fn: F) -> Vec<T>
+    where
+        F: Copy
+            + Fn(
+                &'_ MockSpdzExecution,
+                Vec<Vec<SpdzShare<Fp>>>,
+            ) -> Pin<Box<dyn Future<Output = T> + '_>>,
+    {
+        run_mock_spdz(SpdzConfig::default(), inputs, circuit_fn).await
+    }
+
+    /// Inputs 2, 3 and 4 of three parties.
+    fn product_inputs<T: MpcField>() -> Vec<Vec<T>> {
+        (2..5).map(|x| vec![T::from(x)]).collect()
+    }
+
+    /// Multiply the first inputs of all parties and open the product.
+    fn open_product<E: MpcEngine>(
+        ctx: &'_ MpcExecution<E>,
+        inputs: Vec<Vec<E::Share>>,
+    ) -> Pin<Box<dyn Future<Output = E::Field> + '_>> {
+        Box::pin(async move {
+            let product = circuits::product(ctx, inputs.iter().map(|x| x[0])).await;
+            ctx.open_unchecked(product).await
+        })
     }
 
     #[tokio::test]
@@ -435,4 +1210,658 @@ mod tests {
         .await;
         assert_eq!(outputs, vec![28.into(), 80.into(), 162.into()]);
     }
+
+    #[tokio::test]
+    async fn test_spdz_mid_circuit_inputs() {
+        let output = run_spdz(
+            vec![vec![1.into()], vec![2.into()], vec![3.into()]],
+            |ctx, inputs| {
+                Box::pin(async move {
+                    let (num_parties, party_id) = {
+                        let engine = ctx.engine();
+                        (engine.num_parties(), engine.party_id())
+                    };
+                    let sum = inputs.iter().fold(SpdzShare::zero(), |acc, x| acc + x[0]);
+                    let sum = ctx.open_unchecked(sum).await;
+
+                    let mut second_sum = SpdzShare::zero();
+                    for id in 0..num_parties {
+                        let shares = if id == party_id {
+                            ctx.input_own(vec![sum * Fp::from(id as u64 + 1)]).await
+                        } else {
+                            ctx.input_from(id, 1).await
+                        };
+                        second_sum += shares[0];
+                    }
+                    ctx.open_unchecked(second_sum).await
+                })
+            },
+        )
+        .await;
+        assert_eq!(output, 36.into());
+    }
+
+    #[tokio::test]
+    async fn test_spdz_multiple_mac_keys() {
+        type SmallFp = crate::fields::Mersenne31;
+
+        let engines = mock_spdz_engines::<SmallFp, 3>(3, SpdzConfig::default());
+        let outputs: Vec<_> =
+            executor::run_circuit_for_all_parties(engines, &product_inputs(), open_product)
+                .await
+                .into_iter()
+                .map(|result| result.unwrap().0)
+                .collect();
+        assert_eq!(outputs, vec![SmallFp::from(24); 3]);
+    }
+
+    #[tokio::test]
+    async fn test_spdz_reused_engine() {
+        let engines = mock_spdz_engines::<Fp, 1>(3, SpdzConfig::default());
+        let futures = engines
+            .into_iter()
+            .enumerate()
+            .map(|(party_id, engine)| async move {
+                let inputs = [Fp::from(party_id as u64 + 2)];
+
+                // The first circuit returns a share, which is used by the second one.
+                let (product, _, engine) = executor::run_circuit(engine, &inputs, |ctx, inputs| {
+                    Box::pin(circuits::product(ctx, inputs.into_iter().map(|x| x[0])))
+                })
+                .await
+                .unwrap();
+
+                let (output, _, _) = executor::run_circuit(engine, &inputs, move |ctx, inputs| {
+                    Box::pin(async move {
+                        let sum = inputs.iter().fold(product, |acc, x| acc + x[0]);
+                        ctx.open_unchecked(sum).await
+                    })
+                })
+                .await
+                .unwrap();
+
+                output
+            });
+
+        let outputs = future::join_all(futures).await;
+        assert_eq!(outputs, vec![Fp::from(33); 3]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spdz_simulated_network() {
+        type SimulatedSpdzEngine =
+            SpdzEngine<Fp, FakeSpdzDealer<Fp>, SimulatedChannel<SpdzMessage<Fp>>>;
+
+        // Parties 0 and 1 share a data centre, party 2 is far away.
+        let remote = LinkConditions::with_latency(Duration::from_millis(40));
+        let network = SimulatedNetwork::new(LinkConditions::with_latency(Duration::from_millis(1)))
+            .with_link(0, 2, remote)
+            .with_link(1, 2, remote);
+
+        let start = Instant::now();
+        let engines = transport::simulated_multiparty_channels(3, &network)
+            .into_iter()
+            .enumerate()
+            .map(|(party_id, transport)| {
+                let dealer = FakeSpdzDealer::new(3, party_id, 123);
+                SimulatedSpdzEngine::new(dealer, transport, SpdzConfig::default())
+            })
+            .collect();
+        let outputs: Vec<_> =
+            executor::run_circuit_for_all_parties(engines, &product_inputs(), open_product)
+                .await
+                .into_iter()
+                .map(|result| result.unwrap().0)
+                .collect();
+        assert_eq!(outputs, vec![Fp::from(24); 3]);
+        // Every round waits for the remote link; inputs, two multiplications and opening take at least four.
+        assert!(start.elapsed() >= Duration::from_millis(4 * 40));
+    }
+
+    #[tokio::test]
+    async fn test_spdz_private_openings() {
+        let outputs = run_spdz_parties(
+            vec![vec![1.into()], vec![2.into()], vec![3.into()]],
+            |ctx, inputs| {
+                Box::pin(async move {
+                    let shares = inputs.iter().rev().map(|x| x[0]).collect();
+                    let own_output = ctx.open_to_each(shares).await;
+                    let second_output = ctx.open_to(1, inputs[2][0]).await;
+                    (own_output, second_output)
+                })
+            },
+        )
+        .await;
+        assert_eq!(
+            outputs,
+            vec![
+                (3.into(), None),
+                (2.into(), Some(3.into())),
+                (1.into(), None)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spdz_opening_strategies() {
+        let strategies = [
+            OpeningStrategy::SingleKing,
+            OpeningStrategy::RotatingKing,
+            OpeningStrategy::PartitionedKings,
+            OpeningStrategy::AllToAll,
+        ];
+        for (strategy, identifiable_abort) in strategies
+            .into_iter()
+            .flat_map(|strategy| [(strategy, false), (strategy, true)])
+        {
+            let inputs: Vec<Vec<Fp>> = (1..=4)
+                .map(|i| vec![i.into(), (i + 4).into(), (i + 8).into()])
+                .collect();
+            let config = SpdzConfig {
+                opening_strategy: strategy,
+                identifiable_abort,
+                ..SpdzConfig::default()
+            };
+            let outputs = run_mock_spdz(config, inputs, |ctx, inputs| {
+                Box::pin(async move {
+                    let products = join_circuits_all(
+                        (0..3).map(|i| circuits::product(ctx, inputs.iter().map(move |x| x[i]))),
+                    )
+                    .await;
+                    let private_output = ctx.open_to(3, products[0]).await;
+                    ctx.ensure_integrity();
+                    (ctx.open_unchecked(products[2]).await, private_output)
+                })
+            })
+            .await;
+            assert_eq!(
+                outputs,
+                vec![
+                    (11880.into(), None),
+                    (11880.into(), None),
+                    (11880.into(), None),
+                    (11880.into(), Some(24.into())),
+                ],
+                "Invalid outputs for {:?}",
+                strategy
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spdz_config() {
+        for digest in [
+            DigestType::Sha3_256,
+            DigestType::Sha256,
+            DigestType::Blake2s256,
+        ] {
+            let config = SpdzConfig {
+                batch_check_threshold: 3,
+                max_batch_check_size: 2,
+                check_every_rounds: Some(1),
+                digest,
+                ..SpdzConfig::default()
+            };
+            let engines = mock_spdz_engines::<Fp, 1>(2, config);
+            let results = executor::run_circuit_for_all_parties(
+                engines,
+                &[vec![Fp::from(1), 2.into()], vec![3.into(), 4.into()]],
+                |ctx, inputs| {
+                    Box::pin(async move {
+                        let products = join_circuits_all(
+                            (0..2)
+                                .map(|i| circuits::product(ctx, inputs.iter().map(move |x| x[i]))),
+                        )
+                        .await;
+                        ctx.ensure_integrity();
+                        ctx.open_unchecked(products[0] + products[1]).await
+                    })
+                },
+            )
+            .await;
+            for result in results {
+                let (output, stats, _) = result.unwrap();
+                assert_eq!(output, 11.into(), "Invalid output for {}", digest);
+                // One round of multiplications and one opening, checked before the opening and at the end.
+                assert_eq!(stats.num_openings, 5);
+                assert_eq!(stats.num_rounds, 2);
+                assert_eq!(stats.num_integrity_checks, 2);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spdz_serialized_messages() {
+        // Messages go through bincode, as they do over network, including signed broadcasts.
+        for identifiable_abort in [false, true] {
+            let config = SpdzConfig {
+                check_every_rounds: Some(1),
+                identifiable_abort,
+                ..SpdzConfig::default()
+            };
+            let engines: Vec<_> = transport::mock_multiparty_channels(3, 4096)
+                .into_iter()
+                .enumerate()
+                .map(|(party_id, transport)| {
+                    let dealer = FakeSpdzDealer::<Fp>::new(3, party_id, 123);
+                    SpdzEngine::new(dealer, transport, config)
+                })
+                .collect();
+            let results =
+                executor::run_circuit_for_all_parties(engines, &product_inputs(), |ctx, inputs| {
+                    Box::pin(async move {
+                        let product = circuits::product(ctx, inputs.iter().map(|x| x[0])).await;
+                        (
+                            ctx.open_to(1, product).await,
+                            ctx.open_unchecked(product).await,
+                        )
+                    })
+                })
+                .await;
+            for (party_id, result) in results.into_iter().enumerate() {
+                let (output, _, _) = result.unwrap();
+                let private_output = (party_id == 1).then(|| Fp::from(24));
+                assert_eq!(output, (private_output, Fp::from(24)));
+            }
+        }
+    }
+
+    type FaultySpdzEngine = SpdzEngine<
+        Fp,
+        FakeSpdzDealer<Fp>,
+        FaultyChannel<SpdzMessage<Fp>, MemoryChannel<SpdzMessage<Fp>>>,
+    >;
+
+    /// Run `open_product` for three parties, while party `adversary_id` tampers with messages
+    /// it sends to each peer according to given faults. Returns results of all parties.
+    async fn run_spdz_with_adversary(
+        adversary_id: usize,
+        make_fault: impl FnMut(usize) -> Fault<SpdzMessage<Fp>>,
+    ) -> Vec<Result<Fp, SpdzError>> {
+        run_spdz_with_adversary_config(SpdzConfig::default(), adversary_id, make_fault).await
+    }
+
+    /// Seed of signing key of adversary in identifiable-abort mode, so faults can sign modified messages.
+    const ADVERSARY_KEY_SEED: u64 = 7;
+
+    /// Signing key of adversary in identifiable-abort mode.
+    fn adversary_blame_state() -> BlameState {
+        BlameState::new(&mut StdRng::seed_from_u64(ADVERSARY_KEY_SEED), 3)
+    }
+
+    /// Fault of adversary in identifiable-abort mode, which modifies broadcast messages and signs them again.
+    fn resigning_fault(
+        adversary_id: usize,
+        mut modify: impl FnMut(SpdzMessage<Fp>) -> SpdzMessage<Fp> + Send + 'static,
+    ) -> Fault<SpdzMessage<Fp>> {
+        let state = adversary_blame_state();
+        let mut position = TranscriptPosition::default();
+        Box::new(move |msg| match msg {
+            SpdzMessage::Signed(msg, _) => {
+                let msg = modify(*msg);
+                position = next_position(DigestType::Sha3_256, &position, &msg);
+                let signature = state.sign(adversary_id, &position);
+                vec![SpdzMessage::Signed(Box::new(msg), signature)]
+            }
+            msg => vec![msg],
+        })
+    }
+
+    /// Like `run_spdz_with_adversary`, but with given configuration. In identifiable-abort mode
+    /// the adversary signs broadcasts with the key of `adversary_blame_state`.
+    async fn run_spdz_with_adversary_config(
+        config: SpdzConfig,
+        adversary_id: usize,
+        mut make_fault: impl FnMut(usize) -> Fault<SpdzMessage<Fp>>,
+    ) -> Vec<Result<Fp, SpdzError>> {
+        let engines = transport::memory_multiparty_channels(3)
+            .into_iter()
+            .enumerate()
+            .map(|(party_id, transport)| {
+                let transport = transport.with_timeout(Some(Duration::from_secs(1)));
+                let transport = if party_id == adversary_id {
+                    inject_faults(transport, &mut make_fault)
+                } else {
+                    inject_faults(transport, |_| no_fault())
+                };
+                let dealer = FakeSpdzDealer::new(3, party_id, 123);
+                let mut engine = FaultySpdzEngine::new(dealer, transport, config);
+                if party_id == adversary_id && config.identifiable_abort {
+                    engine.blame = Some(adversary_blame_state());
+                }
+                engine
+            })
+            .collect();
+
+        executor::run_circuit_for_all_parties(engines, &product_inputs(), open_product)
+            .await
+            .into_iter()
+            .map(|result| match result {
+                Ok((output, _, _)) => Ok(output),
+                Err(MpcExecutionError::Engine(err)) => Err(err),
+                Err(err) => panic!("Unexpected execution error: {:?}", err),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_spdz_detects_modified_shares() {
+        let results = run_spdz_with_adversary(2, |_| {
+            Box::new(|msg| match msg {
+                SpdzMessage::SharesExchange(mut shares) => {
+                    shares[0] += Fp::from(1);
+                    vec![SpdzMessage::SharesExchange(shares)]
+                }
+                msg => vec![msg],
+            })
+        })
+        .await;
+        assert_eq!(results[0], Err(SpdzError::MacCheckFailed));
+        assert_eq!(results[1], Err(SpdzError::MacCheckFailed));
+    }
+
+    #[tokio::test]
+    async fn test_spdz_detects_inconsistent_broadcast() {
+        let results = run_spdz_with_adversary(0, |id| match id {
+            2 => Box::new(|msg| match msg {
+                SpdzMessage::MaskedInputs(mut deltas) => {
+                    deltas[0] += Fp::from(1);
+                    vec![SpdzMessage::MaskedInputs(deltas)]
+                }
+                msg => vec![msg],
+            }),
+            _ => no_fault(),
+        })
+        .await;
+        assert_eq!(results[1], Err(SpdzError::StateHashMismatch));
+        assert_eq!(results[2], Err(SpdzError::StateHashMismatch));
+    }
+
+    #[tokio::test]
+    async fn test_spdz_blames_inconsistent_broadcast() {
+        let config = SpdzConfig {
+            identifiable_abort: true,
+            ..SpdzConfig::default()
+        };
+
+        // Inconsistent inputs lead to mismatched state hashes.
+        let results = run_spdz_with_adversary_config(config, 0, |id| match id {
+            2 => resigning_fault(0, |msg| match msg {
+                SpdzMessage::MaskedInputs(mut deltas) => {
+                    deltas[0] += Fp::from(1);
+                    SpdzMessage::MaskedInputs(deltas)
+                }
+                msg => msg,
+            }),
+            _ => no_fault(),
+        })
+        .await;
+        assert_eq!(results[1], Err(SpdzError::InconsistentBroadcast(0)));
+        assert_eq!(results[2], Err(SpdzError::InconsistentBroadcast(0)));
+
+        // Inconsistent sums sent by king lead to failed MAC check.
+        let results = run_spdz_with_adversary_config(config, 0, |id| match id {
+            1 => resigning_fault(0, |msg| match msg {
+                SpdzMessage::ShareSumExchange(mut sums) => {
+                    sums[0] += Fp::from(1);
+                    SpdzMessage::ShareSumExchange(sums)
+                }
+                msg => msg,
+            }),
+            _ => no_fault(),
+        })
+        .await;
+        assert_eq!(results[1], Err(SpdzError::InconsistentBroadcast(0)));
+        assert_eq!(results[2], Err(SpdzError::InconsistentBroadcast(0)));
+    }
+
+    #[tokio::test]
+    async fn test_spdz_detects_invalid_signature() {
+        let config = SpdzConfig {
+            identifiable_abort: true,
+            ..SpdzConfig::default()
+        };
+        let results = run_spdz_with_adversary_config(config, 1, |_| {
+            Box::new(|msg| match msg {
+                SpdzMessage::Signed(msg, signature) => match *msg {
+                    SpdzMessage::MaskedInputs(mut deltas) => {
+                        deltas[0] += Fp::from(1);
+                        let msg = SpdzMessage::MaskedInputs(deltas);
+                        vec![SpdzMessage::Signed(Box::new(msg), signature)]
+                    }
+                    msg => vec![SpdzMessage::Signed(Box::new(msg), signature)],
+                },
+                msg => vec![msg],
+            })
+        })
+        .await;
+        assert_eq!(results[0], Err(SpdzError::InvalidSignature(1)));
+        assert_eq!(results[2], Err(SpdzError::InvalidSignature(1)));
+    }
+
+    /// Fault of adversary in identifiable-abort mode, which modifies shares sent to given king and signs them again.
+    fn resigning_shares_fault(
+        adversary_id: usize,
+        king: usize,
+        mut modify: impl FnMut(u64, &mut Vec<Fp>) + Send + 'static,
+    ) -> Fault<SpdzMessage<Fp>> {
+        let state = adversary_blame_state();
+        let mut round = 0;
+        Box::new(move |msg| match msg {
+            SpdzMessage::SignedShares(mut shares, _) => {
+                modify(round, &mut shares);
+                let digest = shares_digest(DigestType::Sha3_256, &shares);
+                let signature = state.sign_shares(adversary_id, king, round, &digest);
+                round += 1;
+                vec![SpdzMessage::SignedShares(shares, signature)]
+            }
+            msg => vec![msg],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_spdz_blames_modified_shares() {
+        let config = SpdzConfig {
+            identifiable_abort: true,
+            ..SpdzConfig::default()
+        };
+
+        // Shares opened in blame phase differ from shares signed for king.
+        let results = run_spdz_with_adversary_config(config, 2, |id| match id {
+            0 => resigning_shares_fault(2, 0, |round, shares| {
+                if round == 0 {
+                    shares[0] += Fp::from(1);
+                }
+            }),
+            _ => no_fault(),
+        })
+        .await;
+        assert_eq!(results[0], Err(SpdzError::InconsistentShares(2)));
+        assert_eq!(results[1], Err(SpdzError::InconsistentShares(2)));
+
+        // Modified shares which are opened consistently can't be attributed.
+        let results = run_spdz_with_adversary_config(config, 2, |id| {
+            let mut fault = match id {
+                0 => resigning_shares_fault(2, 0, |round, shares| {
+                    if round == 0 {
+                        shares[0] += Fp::from(1);
+                    }
+                }),
+                _ => no_fault(),
+            };
+            Box::new(move |msg| match msg {
+                SpdzMessage::OpenedShares(mut shares, evidence) => {
+                    shares[0] += Fp::from(1);
+                    vec![SpdzMessage::OpenedShares(shares, evidence)]
+                }
+                msg => fault(msg),
+            })
+        })
+        .await;
+        assert_eq!(results[0], Err(SpdzError::MacCheckFailed));
+        assert_eq!(results[1], Err(SpdzError::MacCheckFailed));
+    }
+
+    #[tokio::test]
+    async fn test_spdz_all_parties_enter_blame() {
+        let config = SpdzConfig {
+            identifiable_abort: true,
+            ..SpdzConfig::default()
+        };
+
+        // Only party 2 detects the failure, but party 1 takes part in blame phase too.
+        let results = run_spdz_with_adversary_config(config, 0, |id| match id {
+            2 => resigning_fault(0, |msg| match msg {
+                SpdzMessage::StateHashCheck(mut hash) => {
+                    hash[0] ^= 1;
+                    SpdzMessage::StateHashCheck(hash)
+                }
+                msg => msg,
+            }),
+            _ => no_fault(),
+        })
+        .await;
+        assert_eq!(results[1], Err(SpdzError::InconsistentBroadcast(0)));
+        assert_eq!(results[2], Err(SpdzError::InconsistentBroadcast(0)));
+
+        // Abort without proof of cheating is reported by others.
+        let results = run_spdz_with_adversary_config(config, 0, |_| {
+            Box::new(|msg| match msg {
+                SpdzMessage::BlameReport(_, evidence) => {
+                    vec![SpdzMessage::BlameReport(true, evidence)]
+                }
+                msg => vec![msg],
+            })
+        })
+        .await;
+        assert_eq!(results[1], Err(SpdzError::PeerAborted(0)));
+        assert_eq!(results[2], Err(SpdzError::PeerAborted(0)));
+
+        let results = run_spdz_with_adversary_config(config, 0, |_| {
+            Box::new(|msg| match msg {
+                SpdzMessage::BlameReport(..) => vec![SpdzMessage::StateHashCheck([0; 32])],
+                msg => vec![msg],
+            })
+        })
+        .await;
+        assert_eq!(results[1], Err(SpdzError::UnexpectedMessage(0)));
+        assert_eq!(results[2], Err(SpdzError::UnexpectedMessage(0)));
+    }
+
+    #[tokio::test]
+    async fn test_spdz_detects_inconsistent_verification_keys() {
+        let config = SpdzConfig {
+            identifiable_abort: true,
+            ..SpdzConfig::default()
+        };
+        let results = run_spdz_with_adversary_config(config, 0, |id| match id {
+            2 => Box::new(|msg| match msg {
+                SpdzMessage::VerificationKey(mut key) => {
+                    key[0] ^= 1;
+                    vec![SpdzMessage::VerificationKey(key)]
+                }
+                msg => vec![msg],
+            }),
+            _ => no_fault(),
+        })
+        .await;
+        assert_eq!(results[1], Err(SpdzError::VerificationKeysMismatch));
+        assert_eq!(results[2], Err(SpdzError::VerificationKeysMismatch));
+    }
+
+    #[tokio::test]
+    async fn test_spdz_detects_equivocated_commitment() {
+        // Party 2 gets commitments with a different salt, which are consistent with decommitted values.
+        let results = run_spdz_with_adversary(1, |id| match id {
+            2 => Box::new(|msg| match msg {
+                SpdzMessage::Commitment(_) => vec![],
+                SpdzMessage::Decommitment(values, _) => {
+                    let salt = [7; 32];
+                    let hash = commit_values(SpdzConfig::default().digest, &values, salt);
+                    vec![
+                        SpdzMessage::Commitment(hash),
+                        SpdzMessage::Decommitment(values, salt),
+                    ]
+                }
+                msg => vec![msg],
+            }),
+            _ => no_fault(),
+        })
+        .await;
+        assert_eq!(results[0], Err(SpdzError::StateHashMismatch));
+        assert_eq!(results[2], Err(SpdzError::StateHashMismatch));
+    }
+
+    #[tokio::test]
+    async fn test_spdz_detects_modified_decommitment() {
+        let results = run_spdz_with_adversary(1, |_| {
+            Box::new(|msg| match msg {
+                SpdzMessage::Decommitment(mut values, salt) => {
+                    values[0] += Fp::from(1);
+                    vec![SpdzMessage::Decommitment(values, salt)]
+                }
+                msg => vec![msg],
+            })
+        })
+        .await;
+        assert_eq!(results[0], Err(SpdzError::CommitmentHashMismatch(1)));
+        assert_eq!(results[2], Err(SpdzError::CommitmentHashMismatch(1)));
+    }
+
+    #[tokio::test]
+    async fn test_spdz_detects_incorrect_number_of_values() {
+        let results = run_spdz_with_adversary(2, |_| {
+            Box::new(|msg| match msg {
+                SpdzMessage::SharesExchange(mut shares) => {
+                    shares.push(Fp::from(1));
+                    vec![SpdzMessage::SharesExchange(shares)]
+                }
+                msg => vec![msg],
+            })
+        })
+        .await;
+        assert_eq!(results[0], Err(SpdzError::IncorrectNumberOfValues(2)));
+    }
+
+    #[tokio::test]
+    async fn test_spdz_detects_reordered_messages() {
+        // Commitments are held back and sent after decommitments.
+        let results = run_spdz_with_adversary(1, |_| {
+            let mut held = None;
+            Box::new(move |msg| match msg {
+                SpdzMessage::Commitment(_) => {
+                    held = Some(msg);
+                    vec![]
+                }
+                msg => vec![msg].into_iter().chain(held.take()).collect(),
+            })
+        })
+        .await;
+        assert_eq!(results[0], Err(SpdzError::UnexpectedMessage(1)));
+        assert_eq!(results[2], Err(SpdzError::UnexpectedMessage(1)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spdz_detects_dropped_messages() {
+        // Party 2 stops sending to party 0 at the first state hash check.
+        let results = run_spdz_with_adversary(2, |id| match id {
+            0 => {
+                let mut silent = false;
+                Box::new(move |msg| {
+                    silent |= matches!(msg, SpdzMessage::StateHashCheck(_));
+                    if silent {
+                        vec![]
+                    } else {
+                        vec![msg]
+                    }
+                })
+            }
+            _ => no_fault(),
+        })
+        .await;
+        assert_eq!(
+            results[0],
+            Err(SpdzError::Transport(TransportError::Timeout(2)))
+        );
+    }
 }
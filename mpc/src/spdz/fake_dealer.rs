@@ -1,19 +1,21 @@
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 
-use crate::{MpcContext, MpcDealer, MpcField};
+use crate::{fields::random_inverse_pair, MpcContext, MpcDealer, MpcField};
 
-use super::{SpdzDealer, SpdzShare};
+use super::{SpdzCursor, SpdzDealer, SpdzSessionId, SpdzShare};
 
 /// Insecure dealer for SPDZ protocol that can be used for tests.
-pub struct FakeSpdzDealer<T> {
-    auth_key: FakeAuthKey<T>,
-    beaver_triple_gen: FakeShareGenerator<T>,
-    bits_gen: FakeShareGenerator<T>,
-    input_masks_gen: Vec<FakeShareGenerator<T>>,
+pub struct FakeSpdzDealer<T, const K: usize = 1> {
+    auth_key: FakeAuthKey<T, K>,
+    beaver_triple_gen: FakeShareGenerator<T, K>,
+    bits_gen: FakeShareGenerator<T, K>,
+    input_masks_gen: Vec<FakeShareGenerator<T, K>>,
+    pairs_gen: FakeShareGenerator<T, K>,
+    output_masks_gen: Vec<FakeShareGenerator<T, K>>,
 }
 
-impl<T: MpcField> FakeSpdzDealer<T> {
+impl<T: MpcField, const K: usize> FakeSpdzDealer<T, K> {
     /// Create new instance.
     pub fn new(num_parties: usize, party_id: usize, seed: u8) -> Self {
         let mut rng = SmallRng::from_seed([seed; 32]);
@@ -25,13 +27,17 @@ impl<T: MpcField> FakeSpdzDealer<T> {
             input_masks_gen: (0..num_parties)
                 .map(|_| FakeShareGenerator::new(auth_key, rng.gen()))
                 .collect(),
+            pairs_gen: FakeShareGenerator::new(auth_key, rng.gen()),
+            output_masks_gen: (0..num_parties)
+                .map(|_| FakeShareGenerator::new(auth_key, rng.gen()))
+                .collect(),
         }
     }
 }
 
-impl<T: MpcField> MpcContext for FakeSpdzDealer<T> {
+impl<T: MpcField, const K: usize> MpcContext for FakeSpdzDealer<T, K> {
     type Field = T;
-    type Share = SpdzShare<T>;
+    type Share = SpdzShare<T, K>;
 
     fn num_parties(&self) -> usize {
         self.auth_key.num_parties
@@ -42,7 +48,7 @@ impl<T: MpcField> MpcContext for FakeSpdzDealer<T> {
     }
 }
 
-impl<T: MpcField> MpcDealer for FakeSpdzDealer<T> {
+impl<T: MpcField, const K: usize> MpcDealer for FakeSpdzDealer<T, K> {
     fn share_plain(&self, x: Self::Field) -> Self::Share {
         SpdzShare::from_plain(x, self.auth_key.share_value, self.party_id())
     }
@@ -56,6 +62,20 @@ impl<T: MpcField> MpcDealer for FakeSpdzDealer<T> {
         (a_share, b_share, c_share)
     }
 
+    fn next_square_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        let (a_share, a_plain) = self.pairs_gen.gen_random_authenticated_share();
+        let square_share = self.pairs_gen.gen_authenticated_share(a_plain.square());
+        Some((a_share, square_share))
+    }
+
+    fn next_inverse_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        let (r, r_inverse) = random_inverse_pair(self.pairs_gen.rng());
+        Some((
+            self.pairs_gen.gen_authenticated_share(r),
+            self.pairs_gen.gen_authenticated_share(r_inverse),
+        ))
+    }
+
     fn next_uint(&mut self, bits: usize) -> Self::Share {
         let value = (0..bits).fold(Self::Field::zero(), |acc, _| {
             acc.double() + Self::Field::from(self.bits_gen.rng().gen_range(0..=1))
@@ -68,8 +88,8 @@ impl<T: MpcField> MpcDealer for FakeSpdzDealer<T> {
     }
 }
 
-impl<T: MpcField> SpdzDealer for FakeSpdzDealer<T> {
-    fn authentication_key_share(&self) -> Self::Field {
+impl<T: MpcField, const K: usize> SpdzDealer<K> for FakeSpdzDealer<T, K> {
+    fn authentication_key_share(&self) -> [Self::Field; K] {
         self.auth_key.share_value
     }
 
@@ -80,21 +100,43 @@ impl<T: MpcField> SpdzDealer for FakeSpdzDealer<T> {
     fn next_input_mask_for(&mut self, id: usize) -> Self::Share {
         self.input_masks_gen[id].gen_random_authenticated_share().0
     }
+
+    fn next_output_mask_own(&mut self) -> (Self::Share, Self::Field) {
+        self.output_masks_gen[self.auth_key.party_id].gen_random_authenticated_share()
+    }
+
+    fn next_output_mask_for(&mut self, id: usize) -> Self::Share {
+        self.output_masks_gen[id].gen_random_authenticated_share().0
+    }
+
+    fn cursor(&self) -> Option<SpdzCursor> {
+        None
+    }
+
+    fn session_id(&self) -> Option<SpdzSessionId> {
+        None
+    }
 }
 
-/// Authentication key in plain and its share.
+/// Authentication keys in plain and their shares.
 #[derive(Copy, Clone)]
-struct FakeAuthKey<T> {
+struct FakeAuthKey<T, const K: usize> {
     num_parties: usize,
     party_id: usize,
-    share_value: T,
-    plain_value: T,
+    share_value: [T; K],
+    plain_value: [T; K],
 }
 
-impl<T: MpcField> FakeAuthKey<T> {
-    /// Generate fake authentication key and its share.
+impl<T: MpcField, const K: usize> FakeAuthKey<T, K> {
+    /// Generate fake authentication keys and their shares.
     fn random(rng: &mut impl Rng, party_id: usize, num_parties: usize) -> Self {
-        let (share_value, plain_value) = gen_random_raw_share(rng, party_id, num_parties);
+        let mut share_value = [T::zero(); K];
+        let mut plain_value = [T::zero(); K];
+        for (share, plain) in share_value.iter_mut().zip(&mut plain_value) {
+            let (share_key, plain_key) = gen_random_raw_share(rng, party_id, num_parties);
+            *share = share_key;
+            *plain = plain_key;
+        }
         Self {
             num_parties,
             party_id,
@@ -105,14 +147,14 @@ impl<T: MpcField> FakeAuthKey<T> {
 }
 
 /// Insecure generator of SPDZ-shared values.
-struct FakeShareGenerator<T> {
-    auth_key: FakeAuthKey<T>,
+struct FakeShareGenerator<T, const K: usize> {
+    auth_key: FakeAuthKey<T, K>,
     rng: SmallRng,
 }
 
-impl<T: MpcField> FakeShareGenerator<T> {
+impl<T: MpcField, const K: usize> FakeShareGenerator<T, K> {
     /// Create new generator.
-    fn new(auth_key: FakeAuthKey<T>, seed: [u8; 32]) -> Self {
+    fn new(auth_key: FakeAuthKey<T, K>, seed: [u8; 32]) -> Self {
         Self {
             rng: SmallRng::from_seed(seed),
             auth_key,
@@ -135,15 +177,18 @@ impl<T: MpcField> FakeShareGenerator<T> {
     }
 
     /// Generate local authenticated share of specified value.
-    fn gen_authenticated_share(&mut self, value: T) -> SpdzShare<T> {
+    fn gen_authenticated_share(&mut self, value: T) -> SpdzShare<T, K> {
         SpdzShare {
             value: self.gen_raw_share(value),
-            mac: self.gen_raw_share(value * self.auth_key.plain_value),
+            mac: self
+                .auth_key
+                .plain_value
+                .map(|key| self.gen_raw_share(value * key)),
         }
     }
 
     /// Generate random value and its local authenticated share.
-    fn gen_random_authenticated_share(&mut self) -> (SpdzShare<T>, T) {
+    fn gen_random_authenticated_share(&mut self) -> (SpdzShare<T, K>, T) {
         let value = T::random(&mut self.rng);
         (self.gen_authenticated_share(value), value)
     }
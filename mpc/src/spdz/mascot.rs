@@ -0,0 +1,971 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use digest::Digest;
+use futures::{Sink, Stream};
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ot::{
+        self, BaseOtSender, CopeReceiver, CopeSender, OleReceiver, OleSender, OtPoint, OtSeed,
+        OT_EXTENSION_WIDTH,
+    },
+    transport::{ChannelError, MultipartyTransport, TransportError},
+    MpcDealer, MpcEngine, MpcField, MpcShare,
+};
+
+use super::{
+    PrecomputedSpdzData, PrecomputedSpdzDealer, SpdzConfig, SpdzEngine, SpdzError, SpdzMessage,
+    SpdzShare,
+};
+
+/// Random number generator used by MASCOT implementation.
+type MascotRng = rand::rngs::StdRng;
+
+/// Number of candidate products combined into one triple. Combining hides bits of multiplied values
+/// that a malicious sender could learn by corrupting some of the transfers.
+const COMBINE_SIZE: usize = 3;
+
+/// Maximum number of values multiplied or authenticated at once, which limits sizes of messages.
+const BATCH_SIZE: usize = 1024;
+
+/// MASCOT protocol message.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum MascotMessage<T> {
+    BaseOtSetup(OtPoint),
+    BaseOtChoices(Vec<OtPoint>),
+    OtExtension(Vec<u8>),
+    OtChallenge(OtSeed),
+    OtCheck(u128, u128),
+    Corrections(Vec<T>),
+    Spdz(SpdzMessage<T>),
+}
+
+/// MASCOT error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MascotError {
+    Transport(TransportError),
+    Spdz(SpdzError),
+    UnexpectedMessage(usize),
+    IncorrectNumberOfValues(usize),
+    InvalidBaseOt(usize),
+    OtCheckFailed(usize),
+    SacrificeFailed,
+}
+
+impl fmt::Display for MascotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Transport(ref inner) => inner.fmt(f),
+            Self::Spdz(ref inner) => inner.fmt(f),
+            Self::UnexpectedMessage(id) => write!(f, "Received unexpected message from {}", id),
+            Self::IncorrectNumberOfValues(id) => {
+                write!(f, "Received incorrect number of values from {}", id)
+            }
+            Self::InvalidBaseOt(id) => write!(f, "Received invalid base OT message from {}", id),
+            Self::OtCheckFailed(id) => {
+                write!(f, "Consistency check of OT extension failed for {}", id)
+            }
+            Self::SacrificeFailed => write!(f, "Sacrificed triple is incorrect"),
+        }
+    }
+}
+
+impl From<TransportError> for MascotError {
+    fn from(err: TransportError) -> Self {
+        MascotError::Transport(err)
+    }
+}
+
+impl From<SpdzError> for MascotError {
+    fn from(err: SpdzError) -> Self {
+        MascotError::Spdz(err)
+    }
+}
+
+/// Amounts of data generated by MASCOT protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MascotCounts {
+    pub beaver_triples: usize,
+    pub square_pairs: usize,
+    pub inverse_pairs: usize,
+    pub random_bits: usize,
+    /// Number of bit masks of each width, which are assembled from additional random bits.
+    pub bit_masks: BTreeMap<usize, usize>,
+    /// Number of input masks for each party.
+    pub input_masks: usize,
+    /// Number of output masks for each party.
+    pub output_masks: usize,
+}
+
+/// Generate precomputed data for SPDZ protocol using OT-based MASCOT protocol, so that no party learns the MAC keys.
+/// All parties must request the same amounts of data. Triples are checked by sacrificing,
+/// and all generated sharings are covered by MAC checks of SPDZ engine.
+/// Choice bits of OT extension (IKNP) are verified by consistency check of KOS before they are used.
+pub async fn generate_with_mascot<T, E, Channel, const K: usize>(
+    transport: MultipartyTransport<MascotMessage<T>, Channel>,
+    counts: MascotCounts,
+) -> Result<PrecomputedSpdzData<T, K>, MascotError>
+where
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+    E: ChannelError,
+    Channel: Stream<Item = Result<MascotMessage<T>, E>> + Sink<MascotMessage<T>> + Unpin,
+{
+    let mut rng = MascotRng::from_entropy();
+    let mut mascot: Mascot<T, Channel, K> = Mascot::setup(transport, &mut rng).await?;
+    let num_parties = mascot.transport.num_parties();
+    let party_id = mascot.transport.party_id();
+    let auth_key = mascot.auth_key;
+
+    // Each candidate triple consists of b and 2 * COMBINE_SIZE values of a multiplied by b.
+    // Half of them is combined into output triple, the other half into triple sacrificed to check it.
+    let num_mask_bits: usize = counts.bit_masks.values().sum::<usize>() * T::SAFE_BITS;
+    let num_bits = counts.random_bits + num_mask_bits;
+    let num_pair_factors = counts.square_pairs + 2 * counts.inverse_pairs;
+    let num_products = num_bits + counts.square_pairs + counts.inverse_pairs;
+    let num_triples = counts.beaver_triples + num_products;
+    let num_candidates = 2 * COMBINE_SIZE;
+    let b = random_values(&mut rng, num_triples);
+    let a = random_values(&mut rng, num_triples * num_candidates);
+    let b_repeated: Vec<_> = b
+        .iter()
+        .flat_map(|&x| itertools::repeat_n(x, num_candidates))
+        .collect();
+    let c = mascot.multiply(&mut rng, &a, &b_repeated).await?;
+
+    let bit_candidates = random_values(&mut rng, num_bits);
+    let pair_factors = random_values(&mut rng, num_pair_factors);
+    let capacity = T::CAPACITY as usize;
+    let num_seeds = 256 / capacity + usize::from(256 % capacity != 0);
+    let seeds = random_values(&mut rng, num_seeds);
+
+    let values: Vec<_> = [b, a, c, bit_candidates, pair_factors, seeds].concat();
+    let mut shares = mascot.authenticate(&values, None).await?.into_iter();
+    let b: Vec<_> = shares.by_ref().take(num_triples).collect();
+    let a: Vec<_> = shares.by_ref().take(num_triples * num_candidates).collect();
+    let c: Vec<_> = shares.by_ref().take(num_triples * num_candidates).collect();
+    let bit_candidates: Vec<_> = shares.by_ref().take(num_bits).collect();
+    let pair_factors: Vec<_> = shares.by_ref().take(num_pair_factors).collect();
+    let seeds: Vec<_> = shares.collect();
+
+    // Each party authenticates its own input and output masks, with an additional one to hide values
+    // opened during check.
+    let num_masks = counts.input_masks + counts.output_masks;
+    let mut input_masks = Vec::with_capacity(num_parties);
+    let mut input_masks_plain = Vec::new();
+    let mut output_masks_plain = Vec::new();
+    for owner in 0..num_parties {
+        let values = if owner == party_id {
+            let values = random_values(&mut rng, num_masks + 1);
+            input_masks_plain = values[..counts.input_masks].to_vec();
+            output_masks_plain = values[counts.input_masks..num_masks].to_vec();
+            values
+        } else {
+            vec![T::zero(); num_masks + 1]
+        };
+        input_masks.push(mascot.authenticate(&values, Some(owner)).await?);
+    }
+
+    let mut engine = mascot.into_engine();
+
+    // Everything is authenticated now, so we can toss coins for combining and checking.
+    let seeds = engine.process_openings_unchecked(seeds).await?;
+    engine.check_integrity().await?;
+    let mut coins = {
+        let mut digest = sha3::Sha3_256::new();
+        for seed in seeds {
+            digest.update(seed.to_repr());
+        }
+        MascotRng::from_seed(digest.finalize().into())
+    };
+    let session_id = coins.gen();
+
+    let mut combined = Vec::with_capacity(num_triples);
+    for (i, &b) in b.iter().enumerate() {
+        let candidates = i * num_candidates..(i + 1) * num_candidates;
+        let (a, c) = (&a[candidates.clone()], &c[candidates]);
+        let (a, sacrificed_a) = a.split_at(COMBINE_SIZE);
+        let (c, sacrificed_c) = c.split_at(COMBINE_SIZE);
+        let coeffs = random_values(&mut coins, COMBINE_SIZE);
+        let sacrificed_coeffs = random_values(&mut coins, COMBINE_SIZE);
+        combined.push((
+            (
+                linear_combination(a, &coeffs),
+                b,
+                linear_combination(c, &coeffs),
+            ),
+            (
+                linear_combination(sacrificed_a, &sacrificed_coeffs),
+                linear_combination(sacrificed_c, &sacrificed_coeffs),
+            ),
+        ));
+    }
+
+    // Sacrifice: for triples (a, b, c) and (a', b, c') with the same b, open rho = sa - a'
+    // and check that sc - c' - rho * b = 0.
+    let scales = random_values(&mut coins, num_triples);
+    let rho_shares = combined
+        .iter()
+        .zip(&scales)
+        .map(|(((a, _, _), (sacrificed_a, _)), &scale)| *a * scale - *sacrificed_a)
+        .collect();
+    let rhos = engine.process_openings_unchecked(rho_shares).await?;
+    let check_shares = combined
+        .iter()
+        .zip(&scales)
+        .zip(rhos)
+        .map(|((((_, b, c), (_, sacrificed_c)), &scale), rho)| {
+            *c * scale - *sacrificed_c - *b * rho
+        })
+        .collect();
+    let checks = engine.process_openings_unchecked(check_shares).await?;
+    engine.check_integrity().await?;
+    if checks.into_iter().any(|x| x != T::zero()) {
+        return Err(MascotError::SacrificeFailed);
+    }
+
+    let mut beaver_triples: Vec<_> = combined.into_iter().map(|(triple, _)| triple).collect();
+    let product_triples = beaver_triples.split_off(counts.beaver_triples);
+
+    // Random bits and square pairs need squares of random values, inverse pairs need products r * s.
+    let (square_factors, inverse_factors) = pair_factors.split_at(counts.square_pairs);
+    let factors: Vec<_> = bit_candidates
+        .iter()
+        .chain(square_factors)
+        .map(|&r| (r, r))
+        .chain(inverse_factors.chunks(2).map(|x| (x[0], x[1])))
+        .collect();
+    let masked_shares = factors
+        .iter()
+        .zip(&product_triples)
+        .flat_map(|(&(u, v), &(x, y, _))| [u - x, v - y])
+        .collect();
+    let masked = engine.process_openings_unchecked(masked_shares).await?;
+    let mut products: Vec<_> = product_triples
+        .iter()
+        .zip(masked.chunks(2))
+        .map(|(&(x, y, z), masked)| {
+            let (d, e) = (masked[0], masked[1]);
+            z + y * d + x * e + engine.dealer().share_plain(d * e)
+        })
+        .collect();
+    let inverse_products = products.split_off(num_bits + counts.square_pairs);
+    let square_products = products.split_off(num_bits);
+
+    // Squares for random bits and products for inverse pairs are opened, r and s stay secret.
+    let opened = engine
+        .process_openings_unchecked([products, inverse_products].concat())
+        .await?;
+    engine.check_integrity().await?;
+    let (squares, inverse_products) = opened.split_at(num_bits);
+
+    let square_pairs = square_factors
+        .iter()
+        .copied()
+        .zip(square_products)
+        .collect();
+
+    // Inverse pairs: r^-1 = s * (r * s)^-1.
+    let inverse_pairs = inverse_factors
+        .chunks(2)
+        .zip(inverse_products)
+        .filter_map(|(x, &product)| {
+            // Zero has probability 2/|F|, we just skip it.
+            let product_inverse = Option::<T>::from(product.invert())?;
+            Some((x[0], x[1] * product_inverse))
+        })
+        .collect();
+
+    // Random bits: square random value r and compute (r / sqrt(r^2) + 1) / 2.
+    let one = engine.dealer().share_plain(T::one());
+    let two_inverse = T::from(2).invert().unwrap();
+    let mut random_bits = bit_candidates
+        .into_iter()
+        .zip(squares)
+        .filter_map(|(r, &square)| {
+            // Zero has probability 1/|F|, we just skip it.
+            let root = Option::<T>::from(square.sqrt())?;
+            let root_inverse = Option::<T>::from(root.invert())?;
+            Some((r * root_inverse + one) * two_inverse)
+        });
+
+    // Bit masks are assembled from random bits, remaining bits are output as they are.
+    let mut bit_masks = BTreeMap::new();
+    for (&width, &count) in &counts.bit_masks {
+        let masks = (0..count)
+            .map_while(|_| {
+                let bits: Vec<_> = random_bits.by_ref().take(T::SAFE_BITS).collect();
+                if bits.len() < T::SAFE_BITS {
+                    return None;
+                }
+                let low_bits = bits[..width].to_vec();
+                Some((compose_bits(&bits), compose_bits(&low_bits), low_bits))
+            })
+            .collect();
+        bit_masks.insert(width, masks);
+    }
+    let random_bits = random_bits.collect();
+
+    // Check input masks by opening their random combination, hidden by the additional mask.
+    let check_shares = input_masks
+        .iter()
+        .map(|masks| {
+            let (hiding_mask, masks) = masks.split_last().unwrap();
+            let coeffs = random_values(&mut coins, masks.len());
+            *hiding_mask + linear_combination(masks, &coeffs)
+        })
+        .collect();
+    engine.process_openings_unchecked(check_shares).await?;
+    engine.check_integrity().await?;
+
+    let output_masks = input_masks
+        .iter_mut()
+        .map(|masks| {
+            masks.pop();
+            masks.split_off(counts.input_masks)
+        })
+        .collect();
+
+    Ok(PrecomputedSpdzData {
+        num_parties,
+        party_id,
+        session_id,
+        auth_key,
+        beaver_triples,
+        square_pairs,
+        inverse_pairs,
+        random_bits,
+        bit_masks,
+        input_masks,
+        input_masks_plain,
+        output_masks,
+        output_masks_plain,
+    })
+}
+
+/// OT-based correlations with a single peer.
+struct PeerCorrelations<T> {
+    /// Authentication of own values with MAC key shares of peer.
+    cope_senders: Vec<CopeSender<T>>,
+    /// Authentication of peer's values with own MAC key shares.
+    cope_receivers: Vec<CopeReceiver<T>>,
+    /// Multiplication of own values by values chosen by peer.
+    ole_sender: OleSender<T>,
+    /// Multiplication of own values chosen bit-by-bit with values of peer.
+    ole_receiver: OleReceiver<T>,
+}
+
+/// State of MASCOT protocol before switching to SPDZ engine.
+struct Mascot<T, Channel, const K: usize> {
+    transport: MultipartyTransport<MascotMessage<T>, Channel>,
+    auth_key: [T; K],
+    peers: Vec<Option<PeerCorrelations<T>>>,
+}
+
+impl<T, E, Channel, const K: usize> Mascot<T, Channel, K>
+where
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+    E: ChannelError,
+    Channel: Stream<Item = Result<MascotMessage<T>, E>> + Sink<MascotMessage<T>> + Unpin,
+{
+    /// Choose MAC key shares and set up correlations with all peers using base OTs.
+    async fn setup(
+        mut transport: MultipartyTransport<MascotMessage<T>, Channel>,
+        rng: &mut MascotRng,
+    ) -> Result<Self, MascotError> {
+        let num_parties = transport.num_parties();
+        let party_id = transport.party_id();
+        let bits = T::NUM_BITS as usize;
+
+        let auth_key = [(); K].map(|_| T::random(&mut *rng));
+        let delta: u128 = rng.gen();
+
+        // Choices in base OTs, where this party acts as a receiver.
+        let choices: Vec<_> = ot::u128_bits(delta)
+            .into_iter()
+            .chain(auth_key.iter().flat_map(|&key| ot::field_bits(key)))
+            .collect();
+
+        let (senders, messages): (Vec<_>, Vec<_>) = (0..num_parties)
+            .map(|id| {
+                if id == party_id {
+                    (None, None)
+                } else {
+                    let (sender, msg) = BaseOtSender::new(&mut *rng);
+                    (Some(sender), Some(MascotMessage::BaseOtSetup(msg)))
+                }
+            })
+            .unzip();
+
+        let mut chosen_seeds = vec![Vec::new(); num_parties];
+        let mut replies: Vec<_> = (0..num_parties).map(|_| None).collect();
+        for (id, msg) in exchange(&mut transport, messages).await? {
+            if let MascotMessage::BaseOtSetup(sender_msg) = msg {
+                let (seeds, msg) = ot::base_ot_receive(&mut *rng, &sender_msg, &choices)
+                    .ok_or(MascotError::InvalidBaseOt(id))?;
+                chosen_seeds[id] = seeds;
+                replies[id] = Some(MascotMessage::BaseOtChoices(msg));
+            } else {
+                return Err(MascotError::UnexpectedMessage(id));
+            }
+        }
+
+        let mut peers: Vec<_> = (0..num_parties).map(|_| None).collect();
+        for (id, msg) in exchange(&mut transport, replies).await? {
+            if let MascotMessage::BaseOtChoices(receiver_msg) = msg {
+                if receiver_msg.len() != choices.len() {
+                    return Err(MascotError::IncorrectNumberOfValues(id));
+                }
+                let seed_pairs = senders[id]
+                    .as_ref()
+                    .unwrap()
+                    .finish(&receiver_msg)
+                    .ok_or(MascotError::InvalidBaseOt(id))?;
+                let seeds = &chosen_seeds[id];
+
+                peers[id] = Some(PeerCorrelations {
+                    cope_senders: seed_pairs[OT_EXTENSION_WIDTH..]
+                        .chunks(bits)
+                        .map(CopeSender::new)
+                        .collect(),
+                    cope_receivers: seeds[OT_EXTENSION_WIDTH..]
+                        .chunks(bits)
+                        .zip(auth_key)
+                        .map(|(seeds, key)| CopeReceiver::new(key, seeds))
+                        .collect(),
+                    ole_sender: OleSender::new(delta, &seeds[..OT_EXTENSION_WIDTH]),
+                    ole_receiver: OleReceiver::new(&seed_pairs[..OT_EXTENSION_WIDTH]),
+                });
+            } else {
+                return Err(MascotError::UnexpectedMessage(id));
+            }
+        }
+
+        Ok(Self {
+            transport,
+            auth_key,
+            peers,
+        })
+    }
+
+    /// Compute unauthenticated shares of products of values shared by all parties.
+    async fn multiply(
+        &mut self,
+        rng: &mut MascotRng,
+        a: &[T],
+        b: &[T],
+    ) -> Result<Vec<T>, MascotError> {
+        let mut products = Vec::with_capacity(a.len());
+        for (a, b) in a.chunks(BATCH_SIZE).zip(b.chunks(BATCH_SIZE)) {
+            products.extend(self.multiply_batch(rng, a, b).await?);
+        }
+        Ok(products)
+    }
+
+    /// Compute unauthenticated shares of products of a single batch of values.
+    /// Each peer answers consistency check of its OT extension before receiving corrections.
+    async fn multiply_batch(
+        &mut self,
+        rng: &mut MascotRng,
+        a: &[T],
+        b: &[T],
+    ) -> Result<Vec<T>, MascotError> {
+        let bits = T::NUM_BITS as usize;
+        let num_parties = self.peers.len();
+        let mut products: Vec<_> = a.iter().zip(b).map(|(&x, &y)| x * y).collect();
+
+        let (mut states, messages): (Vec<_>, Vec<_>) = self
+            .peers
+            .iter_mut()
+            .map(|peer| match peer {
+                Some(peer) => {
+                    let (state, msg) = peer.ole_receiver.start(&mut *rng, a);
+                    (Some(state), Some(MascotMessage::OtExtension(msg)))
+                }
+                None => (None, None),
+            })
+            .unzip();
+
+        let mut sender_states: Vec<_> = (0..num_parties).map(|_| None).collect();
+        let mut challenges = vec![[0; 32]; num_parties];
+        let mut replies: Vec<_> = (0..num_parties).map(|_| None).collect();
+        for (id, msg) in exchange(&mut self.transport, messages).await? {
+            if let MascotMessage::OtExtension(ot_msg) = msg {
+                let peer = self.peers[id].as_mut().unwrap();
+                let state = peer
+                    .ole_sender
+                    .receive(b.len(), &ot_msg)
+                    .ok_or(MascotError::IncorrectNumberOfValues(id))?;
+                sender_states[id] = Some(state);
+                challenges[id] = rng.gen();
+                replies[id] = Some(MascotMessage::OtChallenge(challenges[id]));
+            } else {
+                return Err(MascotError::UnexpectedMessage(id));
+            }
+        }
+
+        let mut answers: Vec<_> = (0..num_parties).map(|_| None).collect();
+        for (id, msg) in exchange(&mut self.transport, replies).await? {
+            if let MascotMessage::OtChallenge(challenge) = msg {
+                let peer = self.peers[id].as_ref().unwrap();
+                let (x, t) = peer
+                    .ole_receiver
+                    .check(states[id].as_ref().unwrap(), challenge);
+                answers[id] = Some(MascotMessage::OtCheck(x, t));
+            } else {
+                return Err(MascotError::UnexpectedMessage(id));
+            }
+        }
+
+        let mut corrections: Vec<_> = (0..num_parties).map(|_| None).collect();
+        for (id, msg) in exchange(&mut self.transport, answers).await? {
+            if let MascotMessage::OtCheck(x, t) = msg {
+                let peer = self.peers[id].as_ref().unwrap();
+                let state = sender_states[id].take().unwrap();
+                if !peer.ole_sender.check(&state, challenges[id], (x, t)) {
+                    return Err(MascotError::OtCheckFailed(id));
+                }
+                let (shares, msg) = peer.ole_sender.respond(state, b);
+                add_assign_all(&mut products, shares);
+                corrections[id] = Some(MascotMessage::Corrections(msg));
+            } else {
+                return Err(MascotError::UnexpectedMessage(id));
+            }
+        }
+
+        for (id, msg) in exchange(&mut self.transport, corrections).await? {
+            if let MascotMessage::Corrections(corrections) = msg {
+                if corrections.len() != a.len() * bits {
+                    return Err(MascotError::IncorrectNumberOfValues(id));
+                }
+                let peer = self.peers[id].as_ref().unwrap();
+                let state = states[id].take().unwrap();
+                add_assign_all(&mut products, peer.ole_receiver.finish(state, &corrections));
+            } else {
+                return Err(MascotError::UnexpectedMessage(id));
+            }
+        }
+
+        Ok(products)
+    }
+
+    /// Compute MAC shares of values shared by all parties (or values of a single owner, if given).
+    async fn authenticate(
+        &mut self,
+        values: &[T],
+        owner: Option<usize>,
+    ) -> Result<Vec<SpdzShare<T, K>>, MascotError> {
+        let mut shares = Vec::with_capacity(values.len());
+        for values in values.chunks(BATCH_SIZE) {
+            shares.extend(self.authenticate_batch(values, owner).await?);
+        }
+        Ok(shares)
+    }
+
+    /// Compute MAC shares of a single batch of values.
+    async fn authenticate_batch(
+        &mut self,
+        values: &[T],
+        owner: Option<usize>,
+    ) -> Result<Vec<SpdzShare<T, K>>, MascotError> {
+        let party_id = self.transport.party_id();
+        let message_len = values.len() * T::NUM_BITS as usize;
+        let has_values = |id| owner.is_none() || owner == Some(id);
+
+        let mut macs: Vec<_> = values
+            .iter()
+            .map(|&value| self.auth_key.map(|key| key * value))
+            .collect();
+
+        let messages = self
+            .peers
+            .iter_mut()
+            .map(|peer| match peer {
+                Some(peer) if has_values(party_id) => {
+                    let mut msg = Vec::with_capacity(K * message_len);
+                    for (k, sender) in peer.cope_senders.iter_mut().enumerate() {
+                        let (shares, part) = sender.extend(values);
+                        for (mac, share) in macs.iter_mut().zip(shares) {
+                            mac[k] += share;
+                        }
+                        msg.extend(part);
+                    }
+                    Some(MascotMessage::Corrections(msg))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let receive_from: Vec<_> = (0..self.transport.num_parties()).map(has_values).collect();
+        let received = self
+            .transport
+            .exchange_with_some(messages, &receive_from)
+            .await?;
+
+        for (id, msg) in received {
+            if let MascotMessage::Corrections(msg) = msg {
+                if msg.len() != K * message_len {
+                    return Err(MascotError::IncorrectNumberOfValues(id));
+                }
+                let peer = self.peers[id].as_mut().unwrap();
+                for (k, (receiver, part)) in peer
+                    .cope_receivers
+                    .iter_mut()
+                    .zip(msg.chunks(message_len))
+                    .enumerate()
+                {
+                    for (mac, share) in macs.iter_mut().zip(receiver.extend(part)) {
+                        mac[k] += share;
+                    }
+                }
+            } else {
+                return Err(MascotError::UnexpectedMessage(id));
+            }
+        }
+
+        Ok(values
+            .iter()
+            .zip(macs)
+            .map(|(&value, mac)| SpdzShare { value, mac })
+            .collect())
+    }
+
+    /// Switch connections to SPDZ engine, which is used for opening and checking authenticated values.
+    fn into_engine(self) -> SpdzEngine<T, PrecomputedSpdzDealer<T, K>, SpdzChannel<Channel>, K> {
+        let num_parties = self.transport.num_parties();
+        let party_id = self.transport.party_id();
+        let timeout = self.transport.timeout();
+        let channels = self.transport.into_channels();
+        let transport =
+            MultipartyTransport::new(channels.into_iter().map(|x| x.map(SpdzChannel)), party_id)
+                .with_timeout(timeout);
+
+        let dealer = PrecomputedSpdzDealer::new(PrecomputedSpdzData {
+            num_parties,
+            party_id,
+            auth_key: self.auth_key,
+            ..Default::default()
+        });
+        SpdzEngine::new(dealer, transport, SpdzConfig::default())
+    }
+}
+
+/// Send messages to parties and receive messages from all parties.
+async fn exchange<T, E, Channel>(
+    transport: &mut MultipartyTransport<MascotMessage<T>, Channel>,
+    messages: Vec<Option<MascotMessage<T>>>,
+) -> Result<Vec<(usize, MascotMessage<T>)>, MascotError>
+where
+    T: Clone,
+    E: ChannelError,
+    Channel: Stream<Item = Result<MascotMessage<T>, E>> + Sink<MascotMessage<T>> + Unpin,
+{
+    let receive_from = vec![true; transport.num_parties()];
+    Ok(transport
+        .exchange_with_some(messages, &receive_from)
+        .await?)
+}
+
+/// Channel carrying SPDZ messages wrapped in MASCOT messages.
+struct SpdzChannel<C>(C);
+
+impl<T, E, C> Stream for SpdzChannel<C>
+where
+    C: Stream<Item = Result<MascotMessage<T>, E>> + Unpin,
+{
+    type Item = Result<SpdzMessage<T>, Option<E>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.0).poll_next(cx).map(|item| {
+            item.map(|result| match result {
+                Ok(MascotMessage::Spdz(msg)) => Ok(msg),
+                Ok(_) => Err(None),
+                Err(err) => Err(Some(err)),
+            })
+        })
+    }
+}
+
+impl<T, C> Sink<SpdzMessage<T>> for SpdzChannel<C>
+where
+    C: Sink<MascotMessage<T>> + Unpin,
+{
+    type Error = C::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        Pin::new(&mut self.0).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: SpdzMessage<T>) -> Result<(), C::Error> {
+        Pin::new(&mut self.0).start_send(MascotMessage::Spdz(item))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
+
+/// Generate vector of random field elements.
+fn random_values<T: MpcField>(rng: &mut impl Rng, count: usize) -> Vec<T> {
+    (0..count).map(|_| T::random(&mut *rng)).collect()
+}
+
+/// Compute linear combination of shares with given coefficients.
+fn linear_combination<T: MpcField, const K: usize>(
+    shares: &[SpdzShare<T, K>],
+    coeffs: &[T],
+) -> SpdzShare<T, K> {
+    shares
+        .iter()
+        .zip(coeffs)
+        .fold(Default::default(), |acc, (&share, &coeff)| {
+            acc + share * coeff
+        })
+}
+
+/// Compose sharing of integer from sharings of its bits, starting from the least significant one.
+fn compose_bits<T: MpcField, const K: usize>(bits: &[SpdzShare<T, K>]) -> SpdzShare<T, K> {
+    bits.iter()
+        .rev()
+        .fold(SpdzShare::zero(), |acc, &bit| acc.double() + bit)
+}
+
+/// Add vector of field elements to another one.
+fn add_assign_all<T: MpcField>(target: &mut [T], values: Vec<T>) {
+    for (x, y) in target.iter_mut().zip(values) {
+        *x += y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, time::Duration};
+
+    use ff::Field;
+    use futures::future;
+
+    use crate::{
+        fields::Mersenne61,
+        ot::OT_EXTENSION_WIDTH,
+        transport::{self, inject_faults, no_fault, Fault},
+        MpcField, MpcShare,
+    };
+
+    use super::{
+        generate_with_mascot, MascotCounts, MascotError, MascotMessage, PrecomputedSpdzData,
+        SpdzShare,
+    };
+
+    type Fp = Mersenne61;
+
+    /// Run MASCOT for 3 parties producing a few triples, where party 1 tampers with messages sent to party 0.
+    async fn run_mascot_with_adversary(
+        mut fault: Fault<MascotMessage<Fp>>,
+    ) -> Vec<Result<PrecomputedSpdzData<Fp, 2>, MascotError>> {
+        let counts = MascotCounts {
+            beaver_triples: 2,
+            square_pairs: 0,
+            inverse_pairs: 0,
+            random_bits: 0,
+            bit_masks: BTreeMap::new(),
+            input_masks: 0,
+            output_masks: 0,
+        };
+        let transports: Vec<_> = transport::memory_multiparty_channels(3)
+            .into_iter()
+            .enumerate()
+            .map(|(party_id, transport)| {
+                let transport = transport.with_timeout(Some(Duration::from_secs(1)));
+                inject_faults(transport, |id| {
+                    if party_id == 1 && id == 0 {
+                        std::mem::replace(&mut fault, no_fault())
+                    } else {
+                        no_fault()
+                    }
+                })
+            })
+            .collect();
+        future::join_all(
+            transports
+                .into_iter()
+                .map(|transport| generate_with_mascot::<_, _, _, 2>(transport, counts.clone())),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_mascot_serialized_messages() {
+        // Messages go through bincode, as they do over network.
+        let counts = MascotCounts {
+            beaver_triples: 1,
+            square_pairs: 1,
+            inverse_pairs: 0,
+            random_bits: 1,
+            bit_masks: BTreeMap::new(),
+            input_masks: 1,
+            output_masks: 1,
+        };
+        let channels = transport::mock_multiparty_channels::<MascotMessage<Fp>>(2, 1 << 16);
+        let data: Vec<_> = future::join_all(
+            channels
+                .into_iter()
+                .map(|transport| generate_with_mascot::<_, _, _, 1>(transport, counts.clone())),
+        )
+        .await
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect();
+
+        let open = |share: fn(&PrecomputedSpdzData<Fp, 1>) -> SpdzShare<Fp, 1>| {
+            data.iter().fold(Fp::zero(), |acc, x| acc + share(x).value)
+        };
+        let a = open(|x| x.beaver_triples[0].0);
+        let b = open(|x| x.beaver_triples[0].1);
+        assert_eq!(a * b, open(|x| x.beaver_triples[0].2));
+        assert_eq!(
+            open(|x| x.square_pairs[0].0).square(),
+            open(|x| x.square_pairs[0].1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mascot() {
+        let num_parties = 3;
+        let counts = MascotCounts {
+            beaver_triples: 4,
+            square_pairs: 3,
+            inverse_pairs: 2,
+            random_bits: 5,
+            bit_masks: BTreeMap::from([(3, 2)]),
+            input_masks: 2,
+            output_masks: 3,
+        };
+
+        let channels = transport::memory_multiparty_channels::<MascotMessage<Fp>>(num_parties);
+        let data: Vec<_> = future::join_all(
+            channels
+                .into_iter()
+                .map(|transport| generate_with_mascot::<_, _, _, 2>(transport, counts.clone())),
+        )
+        .await
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect();
+
+        let auth_key = data.iter().fold([Fp::zero(); 2], |acc, x| {
+            [acc[0] + x.auth_key[0], acc[1] + x.auth_key[1]]
+        });
+        let open = |shares: &mut dyn Iterator<Item = SpdzShare<Fp, 2>>| {
+            let sum = shares.fold(SpdzShare::zero(), |acc, x| acc + x);
+            assert_eq!(sum.mac, auth_key.map(|key| key * sum.value), "Invalid MAC");
+            sum.value
+        };
+
+        for i in 0..counts.beaver_triples {
+            let a = open(&mut data.iter().map(|x| x.beaver_triples[i].0));
+            let b = open(&mut data.iter().map(|x| x.beaver_triples[i].1));
+            let c = open(&mut data.iter().map(|x| x.beaver_triples[i].2));
+            assert_eq!(a * b, c);
+        }
+
+        assert_eq!(data[0].square_pairs.len(), counts.square_pairs);
+        for i in 0..counts.square_pairs {
+            let a = open(&mut data.iter().map(|x| x.square_pairs[i].0));
+            let a_squared = open(&mut data.iter().map(|x| x.square_pairs[i].1));
+            assert_eq!(a.square(), a_squared);
+        }
+
+        assert_eq!(data[0].inverse_pairs.len(), counts.inverse_pairs);
+        for i in 0..counts.inverse_pairs {
+            let r = open(&mut data.iter().map(|x| x.inverse_pairs[i].0));
+            let r_inverse = open(&mut data.iter().map(|x| x.inverse_pairs[i].1));
+            assert_eq!(r * r_inverse, Fp::one());
+        }
+
+        assert_eq!(data[0].random_bits.len(), counts.random_bits);
+        for i in 0..counts.random_bits {
+            let bit = open(&mut data.iter().map(|x| x.random_bits[i]));
+            assert!(bit == Fp::zero() || bit == Fp::one());
+        }
+
+        assert_eq!(data[0].bit_masks[&3].len(), 2);
+        for i in 0..2 {
+            let mask = open(&mut data.iter().map(|x| x.bit_masks[&3][i].0));
+            let low_part = open(&mut data.iter().map(|x| x.bit_masks[&3][i].1));
+            let low_bits: Vec<_> = (0..3)
+                .map(|j| open(&mut data.iter().map(|x| x.bit_masks[&3][i].2[j])))
+                .collect();
+            assert!(mask.truncated() < 1 << Fp::SAFE_BITS);
+            assert_eq!(mask.truncated() % 8, low_part.truncated());
+            assert!(low_bits
+                .iter()
+                .all(|&bit| bit == Fp::zero() || bit == Fp::one()));
+            let composed = low_bits
+                .iter()
+                .rev()
+                .fold(0, |acc, &bit| 2 * acc + bit.truncated());
+            assert_eq!(composed, low_part.truncated());
+        }
+
+        for owner in 0..num_parties {
+            for i in 0..counts.input_masks {
+                let mask = open(&mut data.iter().map(|x| x.input_masks[owner][i]));
+                assert_eq!(mask, data[owner].input_masks_plain[i]);
+            }
+            assert_eq!(data[owner].output_masks[owner].len(), counts.output_masks);
+            for i in 0..counts.output_masks {
+                let mask = open(&mut data.iter().map(|x| x.output_masks[owner][i]));
+                assert_eq!(mask, data[owner].output_masks_plain[i]);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mascot_detects_inconsistent_ot_extension() {
+        // Cheating receiver flips its first choice bit in half of the columns, trying to learn bits of delta.
+        let mut tampered = false;
+        let results = run_mascot_with_adversary(Box::new(move |msg| match msg {
+            MascotMessage::OtExtension(mut msg) if !tampered => {
+                tampered = true;
+                let num_bytes = msg.len() / OT_EXTENSION_WIDTH;
+                for column in msg.chunks_mut(num_bytes).take(64) {
+                    column[0] ^= 1;
+                }
+                vec![MascotMessage::OtExtension(msg)]
+            }
+            msg => vec![msg],
+        }))
+        .await;
+        assert_eq!(
+            results[0].as_ref().err(),
+            Some(&MascotError::OtCheckFailed(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mascot_detects_incorrect_products() {
+        // Shifting all multiplication corrections adds the whole factor of party 0 to its product share.
+        let mut tampered = false;
+        let results = run_mascot_with_adversary(Box::new(move |msg| match msg {
+            MascotMessage::Corrections(msg) if !tampered => {
+                tampered = true;
+                let msg = msg.into_iter().map(|x| x + Fp::one()).collect();
+                vec![MascotMessage::Corrections(msg)]
+            }
+            msg => vec![msg],
+        }))
+        .await;
+        for result in results {
+            assert_eq!(result.err(), Some(MascotError::SacrificeFailed));
+        }
+    }
+}
@@ -1,25 +1,72 @@
+mod blame;
+pub use blame::{
+    BroadcastEvidence, BroadcastSignature, ShareEvidence, TranscriptPosition, VerificationKey,
+};
+
+mod config;
+pub use config::{DigestType, OpeningStrategy, SpdzConfig};
+
+pub(crate) mod consistency;
+
 mod engine;
 pub use engine::{SpdzEngine, SpdzError, SpdzMessage};
 
 mod fake_dealer;
 pub use fake_dealer::FakeSpdzDealer;
 
+mod mascot;
+pub use mascot::{generate_with_mascot, MascotCounts, MascotError, MascotMessage};
+
+mod net_dealer;
+pub use net_dealer::{
+    serve_spdz_dealer, NetworkSpdzDealer, SpdzBatch, SpdzDataKind, SpdzDealerMessage,
+    SpdzDealerSetup,
+};
+
 mod precomp_dealer;
-pub use precomp_dealer::{PrecomputedSpdzData, PrecomputedSpdzDealer};
+pub use precomp_dealer::{
+    ConsumptionRecord, PrecomputedSpdzData, PrecomputedSpdzDealer, PrecomputedSpdzFormat,
+    PrecomputedSpdzHeader, SeededShareGenerator, SeededSpdzData, SpdzCursor, SpdzSeed,
+    SpdzSessionId, PRECOMPUTED_SPDZ_VERSION,
+};
 
 mod share;
 pub use share::SpdzShare;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+mod verify;
+pub use verify::{verify_precomputed_data, SpdzDataError};
+
 use crate::MpcDealer;
 
-/// Dealer of precomputed parameters for SPDZ protocol.
-pub trait SpdzDealer: MpcDealer {
-    /// Raw sharing of the authentication key.
-    fn authentication_key_share(&self) -> Self::Field;
+/// Dealer of precomputed parameters for SPDZ protocol with K independent authentication keys.
+pub trait SpdzDealer<const K: usize = 1>: MpcDealer {
+    /// Raw sharings of the authentication keys.
+    fn authentication_key_share(&self) -> [Self::Field; K];
 
     /// Random sharing of a random value with plaintext known to this party.
     fn next_input_mask_own(&mut self) -> (Self::Share, Self::Field);
 
     /// Random sharing of a random value with plaintext known to a specified party.
     fn next_input_mask_for(&mut self, id: usize) -> Self::Share;
+
+    /// Random sharing of a random value with plaintext known to this party, used to open values privately to it.
+    fn next_output_mask_own(&mut self) -> (Self::Share, Self::Field);
+
+    /// Random sharing of a random value with plaintext known to a specified party,
+    /// used to open values privately to that party.
+    fn next_output_mask_for(&mut self, id: usize) -> Self::Share;
+
+    /// Amounts of data consumed so far, if tracked by the dealer. All parties must start at the same position.
+    fn cursor(&self) -> Option<SpdzCursor>;
+
+    /// Identifier of preprocessing run that generated the data, if known. All parties must use the same session.
+    fn session_id(&self) -> Option<SpdzSessionId>;
+
+    /// Returns true if data was used by an aborted run, after which parties may be at different positions.
+    fn resumes_aborted_run(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,909 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    io, iter,
+    sync::{mpsc, Arc, Mutex, Weak},
+    thread,
+};
+
+use futures::{future, stream, Future, Sink, SinkExt, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::{
+    runtime::{Handle, RuntimeFlavor},
+    sync::{mpsc as async_mpsc, oneshot},
+    task,
+};
+use tokio_rustls::rustls::PrivateKey;
+
+use crate::{
+    assemble_bit_mask,
+    fields::FieldType,
+    transport::{self, NetworkConfig, TransportError},
+    MpcContext, MpcDealer, MpcField, MpcShare,
+};
+
+use super::{
+    precomp_dealer::{BitMask, Pair, Triple},
+    share::mac_serde,
+    SpdzCursor, SpdzDealer, SpdzSessionId, SpdzShare,
+};
+
+/// Input or output mask with its plaintext value, which is known only to its owner.
+type InputMask<T, const K: usize> = (SpdzShare<T, K>, Option<T>);
+
+/// Senders of received bit masks of each width, registered by the dealer when it needs a new width.
+type BitMaskSenders<T, const K: usize> = Mutex<HashMap<usize, mpsc::Sender<Vec<BitMask<T, K>>>>>;
+
+/// Kind of precomputed values, which are streamed by dealer service or derived from seeds independently of each other.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum SpdzDataKind {
+    BeaverTriples,
+    SquarePairs,
+    InversePairs,
+    RandomBits,
+    /// Bit masks of given width.
+    BitMasks(usize),
+    /// Input masks of party with given ID.
+    InputMasks(usize),
+    /// Output masks of party with given ID.
+    OutputMasks(usize),
+}
+
+/// Batch of precomputed values of a single kind for one party.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound(
+    serialize = "T: Serialize",
+    deserialize = "T: Deserialize<'de> + Copy + Default"
+))]
+pub enum SpdzBatch<T, const K: usize> {
+    BeaverTriples(Vec<Triple<T, K>>),
+    SquarePairs(Vec<Pair<T, K>>),
+    InversePairs(Vec<Pair<T, K>>),
+    RandomBits(Vec<SpdzShare<T, K>>),
+    BitMasks {
+        width: usize,
+        masks: Vec<BitMask<T, K>>,
+    },
+    /// Input masks of given party, plaintext values are sent only to that party.
+    InputMasks {
+        party_id: usize,
+        masks: Vec<SpdzShare<T, K>>,
+        plain: Vec<T>,
+    },
+    /// Output masks of given party, plaintext values are sent only to that party.
+    OutputMasks {
+        party_id: usize,
+        masks: Vec<SpdzShare<T, K>>,
+        plain: Vec<T>,
+    },
+}
+
+/// Parameters of session, sent by dealer service to each party after connecting.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SpdzDealerSetup<T, const K: usize> {
+    pub field: FieldType,
+    pub num_mac_keys: usize,
+    pub num_parties: usize,
+    pub party_id: usize,
+    pub session_id: SpdzSessionId,
+    #[serde(
+        with = "mac_serde",
+        bound(
+            serialize = "T: Serialize",
+            deserialize = "T: Deserialize<'de> + Copy + Default"
+        )
+    )]
+    pub auth_key: [T; K],
+}
+
+/// Message exchanged between dealer service and party.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound(
+    serialize = "T: Serialize",
+    deserialize = "T: Deserialize<'de> + Copy + Default"
+))]
+pub enum SpdzDealerMessage<T, const K: usize> {
+    Setup(SpdzDealerSetup<T, K>),
+    Request(SpdzDataKind),
+    Batch(SpdzBatch<T, K>),
+}
+
+/// Serve precomputed data of a single session to all parties, given their channels ordered by party ID.
+/// Values of each kind are generated for all parties at once by `generate`, which returns one batch for each party,
+/// and every party receives them in the same order as it requests them. Returns when all parties disconnect.
+pub async fn serve_spdz_dealer<T, E, Channel, F, const K: usize>(
+    channels: Vec<Channel>,
+    session_id: SpdzSessionId,
+    auth_keys: Vec<[T; K]>,
+    mut generate: F,
+) -> Result<(), TransportError>
+where
+    T: MpcField,
+    Channel: Stream<Item = Result<SpdzDealerMessage<T, K>, E>> + Sink<SpdzDealerMessage<T, K>>,
+    F: FnMut(SpdzDataKind) -> Vec<SpdzBatch<T, K>>,
+{
+    let num_parties = channels.len();
+    let (mut sinks, streams): (Vec<_>, Vec<_>) =
+        channels.into_iter().map(|channel| channel.split()).unzip();
+
+    for (party_id, (sink, auth_key)) in sinks.iter_mut().zip(auth_keys).enumerate() {
+        let setup = SpdzDealerSetup {
+            field: T::FIELD_TYPE,
+            num_mac_keys: K,
+            num_parties,
+            party_id,
+            session_id,
+            auth_key,
+        };
+        sink.send(SpdzDealerMessage::Setup(setup))
+            .await
+            .map_err(|_| TransportError::Send(party_id))?;
+    }
+
+    // Batches already generated, but not yet requested by each party.
+    let mut pending: HashMap<SpdzDataKind, Vec<VecDeque<SpdzBatch<T, K>>>> = HashMap::new();
+    let mut requests =
+        stream::select_all(streams.into_iter().enumerate().map(|(party_id, stream)| {
+            // Connection failure is treated as disconnect, parties usually exit without closing it.
+            stream
+                .take_while(|msg| future::ready(msg.is_ok()))
+                .map(move |msg| (party_id, msg))
+        }));
+
+    while let Some((party_id, msg)) = requests.next().await {
+        let kind = match msg {
+            Ok(SpdzDealerMessage::Request(
+                SpdzDataKind::InputMasks(id) | SpdzDataKind::OutputMasks(id),
+            )) if id >= num_parties => return Err(TransportError::Recv(party_id)),
+            Ok(SpdzDealerMessage::Request(SpdzDataKind::BitMasks(width)))
+                if width > T::SAFE_BITS =>
+            {
+                return Err(TransportError::Recv(party_id))
+            }
+            Ok(SpdzDealerMessage::Request(kind)) => kind,
+            _ => return Err(TransportError::Recv(party_id)),
+        };
+
+        let queues = pending
+            .entry(kind)
+            .or_insert_with(|| (0..num_parties).map(|_| VecDeque::new()).collect());
+        if queues[party_id].is_empty() {
+            for (queue, batch) in queues.iter_mut().zip(generate(kind)) {
+                queue.push_back(batch);
+            }
+        }
+
+        let batch = queues[party_id]
+            .pop_front()
+            .expect("Generated batch for each party");
+        // Party can disconnect before its prefetched batch is sent, which is not an error either.
+        let _ = sinks[party_id].send(SpdzDealerMessage::Batch(batch)).await;
+    }
+
+    Ok(())
+}
+
+/// Dealer for SPDZ protocol that receives precomputed data from trusted dealer service on demand.
+/// Connection is handled by a background thread, which fetches the next batch of each kind of values
+/// while the previous one is consumed. Dealer blocks only when a batch hasn't arrived in time.
+pub struct NetworkSpdzDealer<T, const K: usize = 1> {
+    num_parties: usize,
+    party_id: usize,
+    session_id: SpdzSessionId,
+    auth_key: [T; K],
+    requests: async_mpsc::UnboundedSender<SpdzDataKind>,
+    beaver_triples: Prefetched<Triple<T, K>>,
+    square_pairs: Prefetched<Pair<T, K>>,
+    inverse_pairs: Prefetched<Pair<T, K>>,
+    random_bits: Prefetched<SpdzShare<T, K>>,
+    bit_masks: HashMap<usize, Prefetched<BitMask<T, K>>>,
+    bit_mask_senders: Weak<BitMaskSenders<T, K>>,
+    input_masks: Vec<Prefetched<InputMask<T, K>>>,
+    output_masks: Vec<Prefetched<InputMask<T, K>>>,
+    is_exhausted: bool,
+    cursor: SpdzCursor,
+}
+
+impl<T, const K: usize> NetworkSpdzDealer<T, K>
+where
+    T: MpcField + Serialize + DeserializeOwned,
+{
+    /// Connect to dealer service specified in network configuration as party with given ID.
+    pub async fn connect(
+        config: NetworkConfig,
+        private_key: PrivateKey,
+        party_id: usize,
+    ) -> io::Result<Self>
+    where
+        T: Unpin,
+    {
+        Self::from_channel(async move {
+            transport::connect_to_dealer(&config, private_key, party_id).await
+        })
+        .await
+    }
+
+    /// Receive data from dealer service through channel opened by given future, which runs in background thread.
+    pub async fn from_channel<E, Channel, F>(connect: F) -> io::Result<Self>
+    where
+        Channel: Stream<Item = Result<SpdzDealerMessage<T, K>, E>>
+            + Sink<SpdzDealerMessage<T, K>>
+            + Unpin,
+        F: Future<Output = io::Result<Channel>> + Send + 'static,
+    {
+        let (setup_sender, setup_receiver) = oneshot::channel();
+        let (request_sender, request_receiver) = async_mpsc::unbounded_channel();
+
+        thread::spawn(move || {
+            match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => {
+                    runtime.block_on(run_client(connect, setup_sender, request_receiver))
+                }
+                Err(err) => {
+                    let _ = setup_sender.send(Err(err));
+                }
+            }
+        });
+
+        let (setup, receivers) = setup_receiver.await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "Dealer service connection thread failed",
+            )
+        })??;
+
+        Ok(Self {
+            num_parties: setup.num_parties,
+            party_id: setup.party_id,
+            session_id: setup.session_id,
+            auth_key: setup.auth_key,
+            beaver_triples: Prefetched::new(
+                SpdzDataKind::BeaverTriples,
+                &request_sender,
+                receivers.beaver_triples,
+            ),
+            square_pairs: Prefetched::new(
+                SpdzDataKind::SquarePairs,
+                &request_sender,
+                receivers.square_pairs,
+            ),
+            inverse_pairs: Prefetched::new(
+                SpdzDataKind::InversePairs,
+                &request_sender,
+                receivers.inverse_pairs,
+            ),
+            random_bits: Prefetched::new(
+                SpdzDataKind::RandomBits,
+                &request_sender,
+                receivers.random_bits,
+            ),
+            bit_masks: HashMap::new(),
+            bit_mask_senders: receivers.bit_mask_senders,
+            input_masks: receivers
+                .input_masks
+                .into_iter()
+                .enumerate()
+                .map(|(id, receiver)| {
+                    Prefetched::new(SpdzDataKind::InputMasks(id), &request_sender, receiver)
+                })
+                .collect(),
+            output_masks: receivers
+                .output_masks
+                .into_iter()
+                .enumerate()
+                .map(|(id, receiver)| {
+                    Prefetched::new(SpdzDataKind::OutputMasks(id), &request_sender, receiver)
+                })
+                .collect(),
+            requests: request_sender,
+            is_exhausted: false,
+            cursor: SpdzCursor::new(setup.num_parties),
+        })
+    }
+
+    /// Random sharing of a secret random bit.
+    fn next_bit(&mut self) -> SpdzShare<T, K> {
+        if let Some(share) = self.random_bits.next() {
+            self.cursor.random_bits += 1;
+            share
+        } else {
+            self.is_exhausted = true;
+            Default::default()
+        }
+    }
+
+    /// Bit masks of given width, which are requested from dealer service when needed for the first time.
+    fn bit_masks_of_width(&mut self, width: usize) -> &mut Prefetched<BitMask<T, K>> {
+        match self.bit_masks.entry(width) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                // If connection thread has already finished, the sender is dropped and no masks are received.
+                let (sender, receiver) = mpsc::channel();
+                if let Some(senders) = self.bit_mask_senders.upgrade() {
+                    senders.lock().unwrap().insert(width, sender);
+                }
+                entry.insert(Prefetched::new(
+                    SpdzDataKind::BitMasks(width),
+                    &self.requests,
+                    receiver,
+                ))
+            }
+        }
+    }
+}
+
+/// Connect to dealer service, pass received setup to dealer and forward its requests until it's dropped.
+async fn run_client<T, E, Channel, F, const K: usize>(
+    connect: F,
+    setup_sender: oneshot::Sender<io::Result<(SpdzDealerSetup<T, K>, BatchReceivers<T, K>)>>,
+    mut requests: async_mpsc::UnboundedReceiver<SpdzDataKind>,
+) where
+    T: MpcField + Serialize + DeserializeOwned,
+    Channel:
+        Stream<Item = Result<SpdzDealerMessage<T, K>, E>> + Sink<SpdzDealerMessage<T, K>> + Unpin,
+    F: Future<Output = io::Result<Channel>>,
+{
+    let (mut channel, senders) = match receive_setup(connect).await {
+        Ok((channel, setup)) => {
+            let (senders, receivers) = batch_channels(setup.num_parties);
+            if setup_sender.send(Ok((setup, receivers))).is_err() {
+                return;
+            }
+            (channel, senders)
+        }
+        Err(err) => {
+            let _ = setup_sender.send(Err(err));
+            return;
+        }
+    };
+
+    // Dropping senders on failure makes the dealer exhausted once it runs out of received values.
+    loop {
+        tokio::select! {
+            request = requests.recv() => match request {
+                Some(kind) => {
+                    if channel.send(SpdzDealerMessage::Request(kind)).await.is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            },
+            msg = channel.next() => match msg {
+                Some(Ok(SpdzDealerMessage::Batch(batch))) => senders.forward(batch),
+                _ => break,
+            },
+        }
+    }
+}
+
+/// Open channel to dealer service and receive setup of session.
+async fn receive_setup<T, E, Channel, F, const K: usize>(
+    connect: F,
+) -> io::Result<(Channel, SpdzDealerSetup<T, K>)>
+where
+    T: MpcField,
+    Channel: Stream<Item = Result<SpdzDealerMessage<T, K>, E>> + Unpin,
+    F: Future<Output = io::Result<Channel>>,
+{
+    let mut channel = connect.await?;
+    let setup = match channel.next().await {
+        Some(Ok(SpdzDealerMessage::Setup(setup))) => setup,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid setup received from dealer service",
+            ))
+        }
+    };
+
+    if setup.field != T::FIELD_TYPE || setup.num_mac_keys != K {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Dealer service serves field {} with {} MAC keys, expected field {} with {} MAC keys",
+                setup.field, setup.num_mac_keys, T::FIELD_TYPE, K,
+            ),
+        ));
+    }
+    if setup.party_id >= setup.num_parties {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid party ID received from dealer service",
+        ));
+    }
+    Ok((channel, setup))
+}
+
+/// Senders of received batches to the dealer, one for each kind of values.
+struct BatchSenders<T, const K: usize> {
+    beaver_triples: mpsc::Sender<Vec<Triple<T, K>>>,
+    square_pairs: mpsc::Sender<Vec<Pair<T, K>>>,
+    inverse_pairs: mpsc::Sender<Vec<Pair<T, K>>>,
+    random_bits: mpsc::Sender<Vec<SpdzShare<T, K>>>,
+    bit_masks: Arc<BitMaskSenders<T, K>>,
+    input_masks: Vec<mpsc::Sender<Vec<InputMask<T, K>>>>,
+    output_masks: Vec<mpsc::Sender<Vec<InputMask<T, K>>>>,
+}
+
+/// Receivers of batches owned by the dealer.
+struct BatchReceivers<T, const K: usize> {
+    beaver_triples: mpsc::Receiver<Vec<Triple<T, K>>>,
+    square_pairs: mpsc::Receiver<Vec<Pair<T, K>>>,
+    inverse_pairs: mpsc::Receiver<Vec<Pair<T, K>>>,
+    random_bits: mpsc::Receiver<Vec<SpdzShare<T, K>>>,
+    bit_mask_senders: Weak<BitMaskSenders<T, K>>,
+    input_masks: Vec<mpsc::Receiver<Vec<InputMask<T, K>>>>,
+    output_masks: Vec<mpsc::Receiver<Vec<InputMask<T, K>>>>,
+}
+
+/// Create channels for batches of all kinds.
+fn batch_channels<T, const K: usize>(
+    num_parties: usize,
+) -> (BatchSenders<T, K>, BatchReceivers<T, K>) {
+    let (triples_sender, triples_receiver) = mpsc::channel();
+    let (squares_sender, squares_receiver) = mpsc::channel();
+    let (inverses_sender, inverses_receiver) = mpsc::channel();
+    let (bits_sender, bits_receiver) = mpsc::channel();
+    let (masks_senders, masks_receivers) = (0..num_parties).map(|_| mpsc::channel()).unzip();
+    let (output_masks_senders, output_masks_receivers) =
+        (0..num_parties).map(|_| mpsc::channel()).unzip();
+    let bit_mask_senders = Arc::new(Mutex::new(HashMap::new()));
+    (
+        BatchSenders {
+            beaver_triples: triples_sender,
+            square_pairs: squares_sender,
+            inverse_pairs: inverses_sender,
+            random_bits: bits_sender,
+            bit_masks: bit_mask_senders.clone(),
+            input_masks: masks_senders,
+            output_masks: output_masks_senders,
+        },
+        BatchReceivers {
+            beaver_triples: triples_receiver,
+            square_pairs: squares_receiver,
+            inverse_pairs: inverses_receiver,
+            random_bits: bits_receiver,
+            bit_mask_senders: Arc::downgrade(&bit_mask_senders),
+            input_masks: masks_receivers,
+            output_masks: output_masks_receivers,
+        },
+    )
+}
+
+impl<T: Copy, const K: usize> BatchSenders<T, K> {
+    /// Pass batch to the dealer. Batches are ignored once the dealer is dropped.
+    fn forward(&self, batch: SpdzBatch<T, K>) {
+        match batch {
+            SpdzBatch::BeaverTriples(triples) => {
+                let _ = self.beaver_triples.send(triples);
+            }
+            SpdzBatch::SquarePairs(pairs) => {
+                let _ = self.square_pairs.send(pairs);
+            }
+            SpdzBatch::InversePairs(pairs) => {
+                let _ = self.inverse_pairs.send(pairs);
+            }
+            SpdzBatch::RandomBits(bits) => {
+                let _ = self.random_bits.send(bits);
+            }
+            SpdzBatch::BitMasks { width, masks } => {
+                if let Some(sender) = self.bit_masks.lock().unwrap().get(&width) {
+                    let _ = sender.send(masks);
+                }
+            }
+            SpdzBatch::InputMasks {
+                party_id,
+                masks,
+                plain,
+            } => forward_masks(self.input_masks.get(party_id), masks, plain),
+            SpdzBatch::OutputMasks {
+                party_id,
+                masks,
+                plain,
+            } => forward_masks(self.output_masks.get(party_id), masks, plain),
+        }
+    }
+}
+
+/// Pass masks of a party to the dealer, paired with their plaintext values if they are own masks.
+fn forward_masks<T: Copy, const K: usize>(
+    sender: Option<&mpsc::Sender<Vec<InputMask<T, K>>>>,
+    masks: Vec<SpdzShare<T, K>>,
+    plain: Vec<T>,
+) {
+    if let Some(sender) = sender {
+        let plain = plain.into_iter().map(Some).chain(iter::repeat(None));
+        let _ = sender.send(masks.into_iter().zip(plain).collect());
+    }
+}
+
+/// Values of a single kind received from dealer service. The next batch is requested as soon as the previous one
+/// starts being consumed. Iteration ends when connection to dealer service fails or it sends an empty batch.
+struct Prefetched<V> {
+    kind: SpdzDataKind,
+    requests: async_mpsc::UnboundedSender<SpdzDataKind>,
+    receiver: mpsc::Receiver<Vec<V>>,
+    current: std::vec::IntoIter<V>,
+    requested: bool,
+    received: bool,
+    finished: bool,
+}
+
+impl<V> Prefetched<V> {
+    /// Create receiver of values, which sends requests for their batches through given channel.
+    fn new(
+        kind: SpdzDataKind,
+        requests: &async_mpsc::UnboundedSender<SpdzDataKind>,
+        receiver: mpsc::Receiver<Vec<V>>,
+    ) -> Self {
+        Self {
+            kind,
+            requests: requests.clone(),
+            receiver,
+            current: Vec::new().into_iter(),
+            requested: false,
+            received: false,
+            finished: false,
+        }
+    }
+
+    /// Returns true if dealer service doesn't provide values of this kind at all.
+    fn is_unavailable(&self) -> bool {
+        self.finished && !self.received
+    }
+}
+
+impl<V> Iterator for Prefetched<V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        loop {
+            if let Some(value) = self.current.next() {
+                return Some(value);
+            }
+            if self.finished {
+                return None;
+            }
+            if !self.requested {
+                self.requests.send(self.kind).ok()?;
+                self.requested = true;
+            }
+            let batch = self.receive()?;
+            if batch.is_empty() {
+                self.finished = true;
+                return None;
+            }
+            self.received = true;
+            self.current = batch.into_iter();
+            self.requests.send(self.kind).ok()?;
+        }
+    }
+}
+
+impl<V> Prefetched<V> {
+    /// Wait for the next batch. Dealer is called synchronously from async code, so on multi-threaded runtime
+    /// the worker thread is handed over to other tasks while waiting. Other runtimes are blocked, but batches
+    /// still arrive because connection is handled by its own thread.
+    fn receive(&self) -> Option<Vec<V>> {
+        match self.receiver.try_recv() {
+            Ok(batch) => return Some(batch),
+            Err(mpsc::TryRecvError::Disconnected) => return None,
+            Err(mpsc::TryRecvError::Empty) => {}
+        }
+        match Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                task::block_in_place(|| self.receiver.recv().ok())
+            }
+            _ => self.receiver.recv().ok(),
+        }
+    }
+}
+
+impl<T: MpcField, const K: usize> MpcContext for NetworkSpdzDealer<T, K> {
+    type Field = T;
+    type Share = SpdzShare<T, K>;
+
+    fn num_parties(&self) -> usize {
+        self.num_parties
+    }
+
+    fn party_id(&self) -> usize {
+        self.party_id
+    }
+}
+
+impl<T, const K: usize> MpcDealer for NetworkSpdzDealer<T, K>
+where
+    T: MpcField + Serialize + DeserializeOwned,
+{
+    fn share_plain(&self, x: Self::Field) -> Self::Share {
+        SpdzShare::from_plain(x, self.auth_key, self.party_id())
+    }
+
+    fn next_beaver_triple(&mut self) -> (Self::Share, Self::Share, Self::Share) {
+        if let Some(triple) = self.beaver_triples.next() {
+            self.cursor.beaver_triples += 1;
+            triple
+        } else {
+            self.is_exhausted = true;
+            (Default::default(), Default::default(), Default::default())
+        }
+    }
+
+    fn next_square_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        if let Some(pair) = self.square_pairs.next() {
+            self.cursor.square_pairs += 1;
+            Some(pair)
+        } else if self.square_pairs.is_unavailable() {
+            None
+        } else {
+            self.is_exhausted = true;
+            Some((Default::default(), Default::default()))
+        }
+    }
+
+    fn next_inverse_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        if let Some(pair) = self.inverse_pairs.next() {
+            self.cursor.inverse_pairs += 1;
+            Some(pair)
+        } else if self.inverse_pairs.is_unavailable() {
+            None
+        } else {
+            self.is_exhausted = true;
+            Some((Default::default(), Default::default()))
+        }
+    }
+
+    fn next_uint(&mut self, bits: usize) -> Self::Share {
+        (0..bits).fold(Self::Share::zero(), |acc, _| acc.double() + self.next_bit())
+    }
+
+    fn next_bit_mask(&mut self, k: usize) -> (Self::Share, Self::Share, Vec<Self::Share>) {
+        let masks = self.bit_masks_of_width(k);
+        if let Some(mask) = masks.next() {
+            *self.cursor.bit_masks.entry(k).or_default() += 1;
+            mask
+        } else if masks.is_unavailable() {
+            // Dealer service has no masks of this width, but masks that run out aren't replaced by random bits.
+            assemble_bit_mask(self, k)
+        } else {
+            self.is_exhausted = true;
+            (
+                Default::default(),
+                Default::default(),
+                vec![Default::default(); k],
+            )
+        }
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.is_exhausted
+    }
+}
+
+impl<T, const K: usize> SpdzDealer<K> for NetworkSpdzDealer<T, K>
+where
+    T: MpcField + Serialize + DeserializeOwned,
+{
+    fn authentication_key_share(&self) -> [Self::Field; K] {
+        self.auth_key
+    }
+
+    fn next_input_mask_own(&mut self) -> (Self::Share, Self::Field) {
+        let id = self.party_id();
+        match self.input_masks[id].next() {
+            Some((mask, Some(plain))) => {
+                self.cursor.input_masks[id] += 1;
+                (mask, plain)
+            }
+            _ => {
+                self.is_exhausted = true;
+                (Default::default(), Default::default())
+            }
+        }
+    }
+
+    fn next_input_mask_for(&mut self, id: usize) -> Self::Share {
+        if id == self.party_id() {
+            panic!("Tried to get own mask as third-party mask");
+        }
+        if let Some((mask, _)) = self.input_masks[id].next() {
+            self.cursor.input_masks[id] += 1;
+            mask
+        } else {
+            self.is_exhausted = true;
+            Default::default()
+        }
+    }
+
+    fn next_output_mask_own(&mut self) -> (Self::Share, Self::Field) {
+        let id = self.party_id();
+        match self.output_masks[id].next() {
+            Some((mask, Some(plain))) => {
+                self.cursor.output_masks[id] += 1;
+                (mask, plain)
+            }
+            _ => {
+                self.is_exhausted = true;
+                (Default::default(), Default::default())
+            }
+        }
+    }
+
+    fn next_output_mask_for(&mut self, id: usize) -> Self::Share {
+        if id == self.party_id() {
+            panic!("Tried to get own mask as third-party mask");
+        }
+        if let Some((mask, _)) = self.output_masks[id].next() {
+            self.cursor.output_masks[id] += 1;
+            mask
+        } else {
+            self.is_exhausted = true;
+            Default::default()
+        }
+    }
+
+    fn cursor(&self) -> Option<SpdzCursor> {
+        Some(self.cursor.clone())
+    }
+
+    fn session_id(&self) -> Option<SpdzSessionId> {
+        Some(self.session_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+
+    use crate::{
+        circuits::{self, IntShare, WrappedShare},
+        executor,
+        fields::Mersenne61,
+        spdz::{FakeSpdzDealer, SpdzConfig, SpdzEngine},
+        transport, MpcContext, MpcDealer,
+    };
+
+    use super::{
+        serve_spdz_dealer, NetworkSpdzDealer, SpdzBatch, SpdzDataKind, SpdzDealer,
+        SpdzDealerMessage,
+    };
+
+    type Fp = Mersenne61;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_network_dealer() {
+        let num_parties = 3;
+        let batch_size = 1;
+
+        let (server_channels, client_channels): (Vec<_>, Vec<_>) = (0..num_parties)
+            .map(|_| transport::bincode_duplex::<SpdzDealerMessage<Fp, 1>>(512))
+            .unzip();
+
+        let server = std::thread::spawn(move || {
+            let mut sources: Vec<_> = (0..num_parties)
+                .map(|id| FakeSpdzDealer::<Fp>::new(num_parties, id, 123))
+                .collect();
+            let auth_keys = sources
+                .iter()
+                .map(|x| x.authentication_key_share())
+                .collect();
+            let generate = move |kind| {
+                sources
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(id, source)| match kind {
+                        SpdzDataKind::BeaverTriples => SpdzBatch::BeaverTriples(
+                            (0..batch_size)
+                                .map(|_| source.next_beaver_triple())
+                                .collect(),
+                        ),
+                        SpdzDataKind::SquarePairs => SpdzBatch::SquarePairs(
+                            (0..batch_size)
+                                .map(|_| source.next_square_pair().unwrap())
+                                .collect(),
+                        ),
+                        SpdzDataKind::InversePairs => SpdzBatch::InversePairs(
+                            (0..batch_size)
+                                .map(|_| source.next_inverse_pair().unwrap())
+                                .collect(),
+                        ),
+                        SpdzDataKind::RandomBits => SpdzBatch::RandomBits(
+                            (0..batch_size).map(|_| source.next_uint(1)).collect(),
+                        ),
+                        SpdzDataKind::BitMasks(width) => SpdzBatch::BitMasks {
+                            width,
+                            masks: (0..batch_size)
+                                .map(|_| source.next_bit_mask(width))
+                                .collect(),
+                        },
+                        SpdzDataKind::InputMasks(party_id) if party_id == id => {
+                            let (masks, plain) = (0..batch_size)
+                                .map(|_| source.next_input_mask_own())
+                                .unzip();
+                            SpdzBatch::InputMasks {
+                                party_id,
+                                masks,
+                                plain,
+                            }
+                        }
+                        SpdzDataKind::InputMasks(party_id) => SpdzBatch::InputMasks {
+                            party_id,
+                            masks: (0..batch_size)
+                                .map(|_| source.next_input_mask_for(party_id))
+                                .collect(),
+                            plain: Vec::new(),
+                        },
+                        SpdzDataKind::OutputMasks(party_id) if party_id == id => {
+                            let (masks, plain) = (0..batch_size)
+                                .map(|_| source.next_output_mask_own())
+                                .unzip();
+                            SpdzBatch::OutputMasks {
+                                party_id,
+                                masks,
+                                plain,
+                            }
+                        }
+                        SpdzDataKind::OutputMasks(party_id) => SpdzBatch::OutputMasks {
+                            party_id,
+                            masks: (0..batch_size)
+                                .map(|_| source.next_output_mask_for(party_id))
+                                .collect(),
+                            plain: Vec::new(),
+                        },
+                    })
+                    .collect()
+            };
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            runtime.block_on(serve_spdz_dealer(
+                server_channels,
+                [9; 16],
+                auth_keys,
+                generate,
+            ))
+        });
+
+        let channel_matrix = transport::memory_multiparty_channels(num_parties);
+        let outputs = future::join_all(channel_matrix.into_iter().zip(client_channels).map(
+            |(transport, channel)| async move {
+                let dealer: NetworkSpdzDealer<Fp> =
+                    NetworkSpdzDealer::from_channel(async move { Ok(channel) })
+                        .await
+                        .unwrap();
+                assert_eq!(dealer.session_id(), Some([9; 16]));
+
+                let party_id = dealer.party_id();
+                let engine = SpdzEngine::new(dealer, transport, SpdzConfig::default());
+                let inputs = [Fp::from(party_id as u64 + 2)];
+                executor::run_circuit(engine, &inputs, |ctx, inputs| {
+                    Box::pin(async move {
+                        let product = circuits::product(ctx, inputs.iter().map(|x| x[0])).await;
+                        let is_positive =
+                            IntShare::<_, 8>::wrap(product).greater_than_zero(ctx).await;
+                        let square = circuits::square(ctx, product).await;
+                        let inverse = circuits::inverse(ctx, product).await;
+                        (
+                            ctx.open_unchecked(product).await,
+                            ctx.open_unchecked(square).await,
+                            ctx.open_unchecked(inverse * Fp::from(24)).await,
+                            is_positive.open_unchecked(ctx).await,
+                            ctx.open_to(1, product).await.is_some() == (party_id == 1),
+                        )
+                    })
+                })
+                .await
+                .unwrap()
+                .0
+            },
+        ))
+        .await;
+
+        let expected = (Fp::from(24), Fp::from(576), Fp::from(1), true, true);
+        assert_eq!(outputs, vec![expected; num_parties]);
+        assert_eq!(server.join().unwrap(), Ok(()));
+    }
+}
@@ -1,75 +1,973 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
-    io::{self, BufReader, BufWriter},
-    path::Path,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    iter,
+    marker::PhantomData,
+    path::{Path, PathBuf},
 };
 
-use serde::{Deserialize, Serialize};
+use digest::Digest;
+use rand::SeedableRng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::{MpcContext, MpcDealer, MpcField, MpcShare};
+use crate::{assemble_bit_mask, fields::FieldType, MpcContext, MpcDealer, MpcField, MpcShare};
 
-use super::{SpdzDealer, SpdzShare};
+use super::{share::mac_serde, SpdzDataKind, SpdzDealer, SpdzShare};
 
-/// Precomputed data for SPDZ protocol.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct PrecomputedSpdzData<T> {
+/// Current version of format of files with precomputed data.
+pub const PRECOMPUTED_SPDZ_VERSION: u32 = 7;
+
+/// Magic bytes at the beginning of files with precomputed data.
+const PRECOMPUTED_SPDZ_MAGIC: [u8; 4] = *b"SPDZ";
+
+/// Random identifier of a single preprocessing run, shared by data of all parties.
+pub type SpdzSessionId = [u8; 16];
+
+/// Seed from which shares of a party are derived in compressed precomputed data.
+pub type SpdzSeed = [u8; 32];
+
+/// Random number generator expanding seeds of compressed precomputed data.
+/// Its output is part of the file format, so it must stay the same across platforms and versions of dependencies.
+type SeedRng = rand_chacha::ChaCha20Rng;
+
+/// Beaver triple of SPDZ shares.
+pub(super) type Triple<T, const K: usize> = (SpdzShare<T, K>, SpdzShare<T, K>, SpdzShare<T, K>);
+
+/// Square pair (a, a^2) or inverse pair (r, r^-1) of SPDZ shares.
+pub(super) type Pair<T, const K: usize> = (SpdzShare<T, K>, SpdzShare<T, K>);
+
+/// Random bit mask of SPDZ shares: random `Field::SAFE_BITS`-bit integer, its remainder modulo 2^k
+/// and its first k bits, where k is the width of the mask.
+pub(super) type BitMask<T, const K: usize> =
+    (SpdzShare<T, K>, SpdzShare<T, K>, Vec<SpdzShare<T, K>>);
+
+/// Layout of file with precomputed data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PrecomputedSpdzFormat {
+    /// All data serialized at once and loaded into memory as a whole.
+    Monolithic,
+    /// Data split into sections, which are read incrementally as they are consumed.
+    Streaming,
+    /// Shares derived from a seed as they are consumed (party 0 holds corrections in another format).
+    Seeded,
+}
+
+/// Header of file with precomputed data for SPDZ protocol.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PrecomputedSpdzHeader {
+    pub version: u32,
+    pub field: FieldType,
+    pub num_mac_keys: usize,
     pub num_parties: usize,
     pub party_id: usize,
-    pub auth_key: T,
-    pub beaver_triples: Vec<(SpdzShare<T>, SpdzShare<T>, SpdzShare<T>)>,
-    pub random_bits: Vec<SpdzShare<T>>,
-    pub input_masks: Vec<Vec<SpdzShare<T>>>,
+    pub session_id: SpdzSessionId,
+    pub format: PrecomputedSpdzFormat,
+}
+
+impl PrecomputedSpdzHeader {
+    /// Read header of file with precomputed data.
+    pub fn load_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::read_from(&mut BufReader::new(file))
+    }
+
+    /// Read header and check that it has supported version.
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != PRECOMPUTED_SPDZ_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a file with precomputed SPDZ data (or it was generated by an older version)",
+            ));
+        }
+
+        let header: Self = bincode::deserialize_from(reader).map_err(bincode_error)?;
+        if header.version != PRECOMPUTED_SPDZ_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Unsupported version {} of precomputed data, expected {}",
+                    header.version, PRECOMPUTED_SPDZ_VERSION
+                ),
+            ));
+        }
+        if header.party_id >= header.num_parties {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid party ID in precomputed data",
+            ));
+        }
+        Ok(header)
+    }
+
+    /// Write header preceded by magic bytes.
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&PRECOMPUTED_SPDZ_MAGIC)?;
+        bincode::serialize_into(writer, self).map_err(bincode_error)
+    }
+}
+
+/// Numbers of values consumed from each kind of precomputed data.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SpdzCursor {
+    pub beaver_triples: usize,
+    pub square_pairs: usize,
+    pub inverse_pairs: usize,
+    pub random_bits: usize,
+    /// Consumed bit masks of each width.
+    pub bit_masks: BTreeMap<usize, usize>,
+    /// Consumed input masks of each party.
+    pub input_masks: Vec<usize>,
+    /// Consumed output masks of each party.
+    pub output_masks: Vec<usize>,
+}
+
+impl SpdzCursor {
+    /// Cursor at the beginning of data for given number of parties.
+    pub fn new(num_parties: usize) -> Self {
+        Self {
+            input_masks: vec![0; num_parties],
+            output_masks: vec![0; num_parties],
+            ..Default::default()
+        }
+    }
+
+    /// Position after both cursors, i.e. the larger amount of each kind of data.
+    /// Returns None if cursors are for different numbers of parties.
+    pub fn furthest(&self, other: &Self) -> Option<Self> {
+        if self.input_masks.len() != other.input_masks.len()
+            || self.output_masks.len() != other.output_masks.len()
+        {
+            return None;
+        }
+        let max_each = |a: &[usize], b: &[usize]| a.iter().zip(b).map(|(a, b)| *a.max(b)).collect();
+        let mut bit_masks = self.bit_masks.clone();
+        for (&width, &count) in &other.bit_masks {
+            let consumed = bit_masks.entry(width).or_default();
+            *consumed = count.max(*consumed);
+        }
+        Some(Self {
+            beaver_triples: self.beaver_triples.max(other.beaver_triples),
+            square_pairs: self.square_pairs.max(other.square_pairs),
+            inverse_pairs: self.inverse_pairs.max(other.inverse_pairs),
+            random_bits: self.random_bits.max(other.random_bits),
+            bit_masks,
+            input_masks: max_each(&self.input_masks, &other.input_masks),
+            output_masks: max_each(&self.output_masks, &other.output_masks),
+        })
+    }
+}
+
+/// Consumption record stored next to file with precomputed data.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ConsumptionRecord {
+    /// Session of data, record of replaced data is ignored.
+    pub session_id: SpdzSessionId,
+    pub cursor: SpdzCursor,
+    /// Set while data is used, so that data of interrupted runs is never reused.
+    pub in_progress: bool,
+    /// Set if the last run aborted. Parties may have consumed different amounts of data then,
+    /// so the next run continues after the furthest position of all parties.
+    #[serde(default)]
+    pub aborted: bool,
+}
+
+impl ConsumptionRecord {
+    /// Load consumption record of file with precomputed data, if there is any.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Option<Self>> {
+        match File::open(record_path(path.as_ref())) {
+            Ok(file) => Ok(Some(serde_json::from_reader(BufReader::new(file))?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Path of consumption record for file with precomputed data.
+fn record_path(path: &Path) -> PathBuf {
+    let mut record_path = path.as_os_str().to_owned();
+    record_path.push(".cursor");
+    record_path.into()
+}
+
+/// Remove consumption record of overwritten file with precomputed data.
+fn remove_record(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(record_path(path)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Precomputed data for SPDZ protocol with K authentication keys.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PrecomputedSpdzData<T, const K: usize = 1> {
+    pub num_parties: usize,
+    pub party_id: usize,
+    pub session_id: SpdzSessionId,
+    #[serde(
+        with = "mac_serde",
+        bound(deserialize = "T: Deserialize<'de> + Copy + Default")
+    )]
+    pub auth_key: [T; K],
+    pub beaver_triples: Vec<(SpdzShare<T, K>, SpdzShare<T, K>, SpdzShare<T, K>)>,
+    pub square_pairs: Vec<(SpdzShare<T, K>, SpdzShare<T, K>)>,
+    pub inverse_pairs: Vec<(SpdzShare<T, K>, SpdzShare<T, K>)>,
+    pub random_bits: Vec<SpdzShare<T, K>>,
+    /// Random bit masks of each width.
+    pub bit_masks: BTreeMap<usize, Vec<BitMask<T, K>>>,
+    pub input_masks: Vec<Vec<SpdzShare<T, K>>>,
     pub input_masks_plain: Vec<T>,
+    /// Masks of values opened privately to each party.
+    pub output_masks: Vec<Vec<SpdzShare<T, K>>>,
+    pub output_masks_plain: Vec<T>,
+}
+
+impl<T: Copy + Default, const K: usize> Default for PrecomputedSpdzData<T, K> {
+    fn default() -> Self {
+        Self {
+            num_parties: 0,
+            party_id: 0,
+            session_id: Default::default(),
+            auth_key: [T::default(); K],
+            beaver_triples: Vec::new(),
+            square_pairs: Vec::new(),
+            inverse_pairs: Vec::new(),
+            random_bits: Vec::new(),
+            bit_masks: BTreeMap::new(),
+            input_masks: Vec::new(),
+            input_masks_plain: Vec::new(),
+            output_masks: Vec::new(),
+            output_masks_plain: Vec::new(),
+        }
+    }
 }
 
-impl<T> PrecomputedSpdzData<T>
+impl<T, const K: usize> PrecomputedSpdzData<T, K>
 where
-    T: Serialize + for<'a> Deserialize<'a>,
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
 {
-    /// Load precomputed data from file.
+    /// Header describing this data stored in given format.
+    pub fn header(&self, format: PrecomputedSpdzFormat) -> PrecomputedSpdzHeader {
+        PrecomputedSpdzHeader {
+            version: PRECOMPUTED_SPDZ_VERSION,
+            field: T::FIELD_TYPE,
+            num_mac_keys: K,
+            num_parties: self.num_parties,
+            party_id: self.party_id,
+            session_id: self.session_id,
+            format,
+        }
+    }
+
+    /// Load precomputed data from file in any format. Fails if data was generated for different field or number of MAC keys.
     pub fn load_file(path: impl AsRef<Path>) -> io::Result<Self> {
         let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        bincode::deserialize_from(reader).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+        let mut reader = BufReader::new(file);
+
+        let header = read_header::<T, K>(&mut reader)?;
+        match header.format {
+            PrecomputedSpdzFormat::Monolithic => read_monolithic(&header, reader),
+            PrecomputedSpdzFormat::Seeded => Ok(read_seeded(&header, reader)?.expand()),
+            PrecomputedSpdzFormat::Streaming => {
+                // Sections are stored one after another, so they can be simply read in order.
+                let index: StreamingIndex<T, K> = read_index(&header, &mut reader)?;
+                Ok(Self {
+                    num_parties: header.num_parties,
+                    party_id: header.party_id,
+                    session_id: header.session_id,
+                    auth_key: index.auth_key,
+                    beaver_triples: bincode::deserialize_from(&mut reader)
+                        .map_err(bincode_error)?,
+                    random_bits: bincode::deserialize_from(&mut reader).map_err(bincode_error)?,
+                    square_pairs: bincode::deserialize_from(&mut reader).map_err(bincode_error)?,
+                    inverse_pairs: bincode::deserialize_from(&mut reader).map_err(bincode_error)?,
+                    input_masks: (0..header.num_parties)
+                        .map(|_| bincode::deserialize_from(&mut reader))
+                        .collect::<Result<_, _>>()
+                        .map_err(bincode_error)?,
+                    input_masks_plain: bincode::deserialize_from(&mut reader)
+                        .map_err(bincode_error)?,
+                    output_masks: (0..header.num_parties)
+                        .map(|_| bincode::deserialize_from(&mut reader))
+                        .collect::<Result<_, _>>()
+                        .map_err(bincode_error)?,
+                    output_masks_plain: bincode::deserialize_from(&mut reader)
+                        .map_err(bincode_error)?,
+                    bit_masks: index
+                        .bit_mask_widths
+                        .iter()
+                        .map(|&width| Ok((width, bincode::deserialize_from(&mut reader)?)))
+                        .collect::<Result<_, _>>()
+                        .map_err(bincode_error)?,
+                })
+            }
+        }
     }
 
-    /// Save precomputed data to file.
+    /// Save precomputed data to file in monolithic format. Resets consumption record of the file.
     pub fn save_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        remove_record(path.as_ref())?;
         let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        bincode::serialize_into(writer, self)
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+        let mut writer = BufWriter::new(file);
+        self.header(PrecomputedSpdzFormat::Monolithic)
+            .write_to(&mut writer)?;
+        bincode::serialize_into(writer, self).map_err(bincode_error)
+    }
+
+    /// Save precomputed data to file in streaming format, which can be consumed without loading it into memory.
+    /// Resets consumption record of the file.
+    pub fn save_file_streaming(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        remove_record(path.as_ref())?;
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+        self.header(PrecomputedSpdzFormat::Streaming)
+            .write_to(&mut writer)?;
+
+        let mut section_sizes = vec![
+            bincode::serialized_size(&self.beaver_triples),
+            bincode::serialized_size(&self.random_bits),
+            bincode::serialized_size(&self.square_pairs),
+            bincode::serialized_size(&self.inverse_pairs),
+        ];
+        section_sizes.extend(self.input_masks.iter().map(bincode::serialized_size));
+        section_sizes.push(bincode::serialized_size(&self.input_masks_plain));
+        section_sizes.extend(self.output_masks.iter().map(bincode::serialized_size));
+        section_sizes.push(bincode::serialized_size(&self.output_masks_plain));
+        section_sizes.extend(self.bit_masks.values().map(bincode::serialized_size));
+
+        let index = StreamingIndex {
+            auth_key: self.auth_key,
+            bit_mask_widths: self.bit_masks.keys().copied().collect(),
+            section_sizes: section_sizes
+                .into_iter()
+                .collect::<Result<_, _>>()
+                .map_err(bincode_error)?,
+        };
+        bincode::serialize_into(&mut writer, &index).map_err(bincode_error)?;
+
+        bincode::serialize_into(&mut writer, &self.beaver_triples).map_err(bincode_error)?;
+        bincode::serialize_into(&mut writer, &self.random_bits).map_err(bincode_error)?;
+        bincode::serialize_into(&mut writer, &self.square_pairs).map_err(bincode_error)?;
+        bincode::serialize_into(&mut writer, &self.inverse_pairs).map_err(bincode_error)?;
+        for masks in &self.input_masks {
+            bincode::serialize_into(&mut writer, masks).map_err(bincode_error)?;
+        }
+        bincode::serialize_into(&mut writer, &self.input_masks_plain).map_err(bincode_error)?;
+        for masks in &self.output_masks {
+            bincode::serialize_into(&mut writer, masks).map_err(bincode_error)?;
+        }
+        bincode::serialize_into(&mut writer, &self.output_masks_plain).map_err(bincode_error)?;
+        for masks in self.bit_masks.values() {
+            bincode::serialize_into(&mut writer, masks).map_err(bincode_error)?;
+        }
+        Ok(())
     }
 }
 
+/// Description of data stored in streaming format, followed by sections.
+#[derive(Deserialize, Serialize)]
+struct StreamingIndex<T, const K: usize> {
+    #[serde(
+        with = "mac_serde",
+        bound(
+            serialize = "T: Serialize",
+            deserialize = "T: Deserialize<'de> + Copy + Default"
+        )
+    )]
+    auth_key: [T; K],
+    /// Widths of stored bit masks, in order of their sections.
+    bit_mask_widths: Vec<usize>,
+    /// Sizes of sections in bytes: beaver triples, random bits, square pairs, inverse pairs,
+    /// input masks of each party, plain input masks, output masks of each party, plain output masks
+    /// and bit masks of each width.
+    section_sizes: Vec<u64>,
+}
+
+/// Compressed precomputed data of a party other than 0, whose shares are derived from a seed.
+/// Data of party 0 contains corrections, so that shares of all parties add up to the shared values.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SeededSpdzData<T, const K: usize = 1> {
+    pub num_parties: usize,
+    pub party_id: usize,
+    pub session_id: SpdzSessionId,
+    #[serde(
+        with = "mac_serde",
+        bound(deserialize = "T: Deserialize<'de> + Copy + Default")
+    )]
+    pub auth_key: [T; K],
+    pub seed: SpdzSeed,
+    pub num_beaver_triples: usize,
+    pub num_square_pairs: usize,
+    pub num_inverse_pairs: usize,
+    pub num_random_bits: usize,
+    /// Number of bit masks of each width.
+    pub num_bit_masks: BTreeMap<usize, usize>,
+    /// Number of input masks of each party.
+    pub num_input_masks: Vec<usize>,
+    pub input_masks_plain: Vec<T>,
+    /// Number of output masks of each party.
+    pub num_output_masks: Vec<usize>,
+    pub output_masks_plain: Vec<T>,
+}
+
+impl<T, const K: usize> SeededSpdzData<T, K>
+where
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
+{
+    /// Header describing this data.
+    pub fn header(&self) -> PrecomputedSpdzHeader {
+        PrecomputedSpdzHeader {
+            version: PRECOMPUTED_SPDZ_VERSION,
+            field: T::FIELD_TYPE,
+            num_mac_keys: K,
+            num_parties: self.num_parties,
+            party_id: self.party_id,
+            session_id: self.session_id,
+            format: PrecomputedSpdzFormat::Seeded,
+        }
+    }
+
+    /// Save compressed data to file. Resets consumption record of the file.
+    pub fn save_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        remove_record(path.as_ref())?;
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+        self.header().write_to(&mut writer)?;
+        bincode::serialize_into(writer, self).map_err(bincode_error)
+    }
+
+    /// Derive all shares from the seed.
+    pub fn expand(self) -> PrecomputedSpdzData<T, K> {
+        PrecomputedSpdzData {
+            num_parties: self.num_parties,
+            party_id: self.party_id,
+            session_id: self.session_id,
+            auth_key: self.auth_key,
+            beaver_triples: seeded_triples(&self.seed, self.num_beaver_triples).collect(),
+            square_pairs: seeded_pairs(&self.seed, SpdzDataKind::SquarePairs)
+                .take(self.num_square_pairs)
+                .collect(),
+            inverse_pairs: seeded_pairs(&self.seed, SpdzDataKind::InversePairs)
+                .take(self.num_inverse_pairs)
+                .collect(),
+            random_bits: SeededShareGenerator::new(&self.seed, SpdzDataKind::RandomBits)
+                .take(self.num_random_bits)
+                .collect(),
+            bit_masks: self
+                .num_bit_masks
+                .iter()
+                .map(|(&width, &count)| {
+                    (width, seeded_bit_masks(&self.seed, width, count).collect())
+                })
+                .collect(),
+            input_masks: self
+                .num_input_masks
+                .iter()
+                .enumerate()
+                .map(|(id, &count)| {
+                    SeededShareGenerator::new(&self.seed, SpdzDataKind::InputMasks(id))
+                        .take(count)
+                        .collect()
+                })
+                .collect(),
+            input_masks_plain: self.input_masks_plain,
+            output_masks: self
+                .num_output_masks
+                .iter()
+                .enumerate()
+                .map(|(id, &count)| {
+                    SeededShareGenerator::new(&self.seed, SpdzDataKind::OutputMasks(id))
+                        .take(count)
+                        .collect()
+                })
+                .collect(),
+            output_masks_plain: self.output_masks_plain,
+        }
+    }
+}
+
+/// Generator of shares of a single kind derived from a seed, independently of other kinds.
+/// Dealer uses the same generators to compute corrections for party 0.
+pub struct SeededShareGenerator<T, const K: usize> {
+    rng: SeedRng,
+    _phantom: PhantomData<T>,
+}
+
+impl<T, const K: usize> SeededShareGenerator<T, K> {
+    /// Create generator of shares of given kind.
+    pub fn new(seed: &SpdzSeed, kind: SpdzDataKind) -> Self {
+        let mut digest = sha3::Sha3_256::new();
+        digest.update(seed);
+        digest.update(bincode::serialize(&kind).unwrap());
+        Self {
+            rng: SeedRng::from_seed(digest.finalize().into()),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: MpcField, const K: usize> Iterator for SeededShareGenerator<T, K> {
+    type Item = SpdzShare<T, K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = T::random(&mut self.rng);
+        let mut mac = [T::zero(); K];
+        for x in &mut mac {
+            *x = T::random(&mut self.rng);
+        }
+        Some(SpdzShare { value, mac })
+    }
+}
+
+/// Beaver triples derived from a seed.
+fn seeded_triples<T: MpcField, const K: usize>(
+    seed: &SpdzSeed,
+    count: usize,
+) -> impl Iterator<Item = Triple<T, K>> + Send {
+    let mut shares = SeededShareGenerator::new(seed, SpdzDataKind::BeaverTriples);
+    (0..count).map(move |_| {
+        let a = shares.next().unwrap();
+        let b = shares.next().unwrap();
+        let c = shares.next().unwrap();
+        (a, b, c)
+    })
+}
+
+/// Pairs of given kind derived from a seed.
+fn seeded_pairs<T: MpcField, const K: usize>(
+    seed: &SpdzSeed,
+    kind: SpdzDataKind,
+) -> impl Iterator<Item = Pair<T, K>> + Send {
+    let mut shares = SeededShareGenerator::new(seed, kind);
+    iter::from_fn(move || Some((shares.next()?, shares.next()?)))
+}
+
+/// Bit masks of given width derived from a seed.
+fn seeded_bit_masks<T: MpcField, const K: usize>(
+    seed: &SpdzSeed,
+    width: usize,
+    count: usize,
+) -> impl Iterator<Item = BitMask<T, K>> + Send {
+    let mut shares = SeededShareGenerator::new(seed, SpdzDataKind::BitMasks(width));
+    (0..count).map(move |_| {
+        let mask = shares.next().unwrap();
+        let low_part = shares.next().unwrap();
+        let low_bits = shares.by_ref().take(width).collect();
+        (mask, low_part, low_bits)
+    })
+}
+
+/// Read header of file with precomputed data and check that it matches expected field and number of MAC keys.
+fn read_header<T: MpcField, const K: usize>(
+    reader: &mut impl Read,
+) -> io::Result<PrecomputedSpdzHeader> {
+    let header = PrecomputedSpdzHeader::read_from(reader)?;
+    if header.field != T::FIELD_TYPE || header.num_mac_keys != K {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Precomputed data was generated for field {} with {} MAC keys, expected field {} with {} MAC keys",
+                header.field, header.num_mac_keys, T::FIELD_TYPE, K,
+            ),
+        ));
+    }
+    Ok(header)
+}
+
+/// Read data in monolithic format and check that it matches its header.
+fn read_monolithic<T, const K: usize>(
+    header: &PrecomputedSpdzHeader,
+    reader: impl Read,
+) -> io::Result<PrecomputedSpdzData<T, K>>
+where
+    T: MpcField + for<'a> Deserialize<'a>,
+{
+    let data: PrecomputedSpdzData<T, K> =
+        bincode::deserialize_from(reader).map_err(bincode_error)?;
+    if data.num_parties != header.num_parties
+        || data.party_id != header.party_id
+        || data.session_id != header.session_id
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Header doesn't match precomputed data",
+        ));
+    }
+    Ok(data)
+}
+
+/// Read data in seeded format and check that it matches its header.
+fn read_seeded<T, const K: usize>(
+    header: &PrecomputedSpdzHeader,
+    reader: impl Read,
+) -> io::Result<SeededSpdzData<T, K>>
+where
+    T: MpcField + for<'a> Deserialize<'a>,
+{
+    let data: SeededSpdzData<T, K> = bincode::deserialize_from(reader).map_err(bincode_error)?;
+    if data.num_parties != header.num_parties
+        || data.party_id != header.party_id
+        || data.session_id != header.session_id
+        || data.num_input_masks.len() != header.num_parties
+        || data.num_output_masks.len() != header.num_parties
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Header doesn't match precomputed data",
+        ));
+    }
+    Ok(data)
+}
+
+/// Read index of data in streaming format and check that it describes all sections.
+fn read_index<T, const K: usize>(
+    header: &PrecomputedSpdzHeader,
+    reader: &mut impl Read,
+) -> io::Result<StreamingIndex<T, K>>
+where
+    T: MpcField + for<'a> Deserialize<'a>,
+{
+    let index: StreamingIndex<T, K> = bincode::deserialize_from(reader).map_err(bincode_error)?;
+    if index.section_sizes.len() != 2 * header.num_parties + 6 + index.bit_mask_widths.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid number of sections in precomputed data",
+        ));
+    }
+    Ok(index)
+}
+
+/// Convert serialization error to IO error.
+fn bincode_error(err: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
 /// Dealer for SPDZ protocol that serves precomputed data.
-pub struct PrecomputedSpdzDealer<T> {
-    data: PrecomputedSpdzData<T>,
+pub struct PrecomputedSpdzDealer<T, const K: usize = 1> {
+    num_parties: usize,
+    party_id: usize,
+    session_id: SpdzSessionId,
+    auth_key: [T; K],
+    beaver_triples: Section<Triple<T, K>>,
+    /// Square pairs, if data has any. Otherwise squares are computed using Beaver triples.
+    square_pairs: Option<Section<Pair<T, K>>>,
+    /// Inverse pairs, if data has any. Otherwise inverses are computed using Beaver triples.
+    inverse_pairs: Option<Section<Pair<T, K>>>,
+    random_bits: Section<SpdzShare<T, K>>,
+    bit_masks: BTreeMap<usize, Section<BitMask<T, K>>>,
+    input_masks: Vec<Section<SpdzShare<T, K>>>,
+    input_masks_plain: Section<T>,
+    output_masks: Vec<Section<SpdzShare<T, K>>>,
+    output_masks_plain: Section<T>,
     is_exhausted: bool,
+    cursor: SpdzCursor,
+    record_path: Option<PathBuf>,
+    resumes_aborted_run: bool,
 }
 
-impl<T> PrecomputedSpdzDealer<T>
+impl<T, const K: usize> PrecomputedSpdzDealer<T, K>
 where
-    T: Serialize + for<'a> Deserialize<'a>,
+    T: MpcField + Serialize + for<'a> Deserialize<'a>,
 {
     /// Create new dealer given precomputed data.
-    pub fn new(data: PrecomputedSpdzData<T>) -> Self {
+    pub fn new(data: PrecomputedSpdzData<T, K>) -> Self {
         Self {
-            data,
+            num_parties: data.num_parties,
+            party_id: data.party_id,
+            session_id: data.session_id,
+            auth_key: data.auth_key,
+            beaver_triples: Section::Memory(data.beaver_triples.into_iter()),
+            square_pairs: (!data.square_pairs.is_empty())
+                .then(|| Section::Memory(data.square_pairs.into_iter())),
+            inverse_pairs: (!data.inverse_pairs.is_empty())
+                .then(|| Section::Memory(data.inverse_pairs.into_iter())),
+            random_bits: Section::Memory(data.random_bits.into_iter()),
+            bit_masks: data
+                .bit_masks
+                .into_iter()
+                .map(|(width, masks)| (width, Section::Memory(masks.into_iter())))
+                .collect(),
+            input_masks: data
+                .input_masks
+                .into_iter()
+                .map(|masks| Section::Memory(masks.into_iter()))
+                .collect(),
+            input_masks_plain: Section::Memory(data.input_masks_plain.into_iter()),
+            output_masks: data
+                .output_masks
+                .into_iter()
+                .map(|masks| Section::Memory(masks.into_iter()))
+                .collect(),
+            output_masks_plain: Section::Memory(data.output_masks_plain.into_iter()),
             is_exhausted: false,
+            cursor: SpdzCursor::new(data.num_parties),
+            record_path: None,
+            resumes_aborted_run: false,
         }
     }
 
-    /// Create new dealer from file with precomputed data.
+    /// Create new dealer from file with precomputed data. Data in streaming format is read as it is consumed.
+    /// Consumption is recorded in a file next to the data (with `.cursor` suffix) when the dealer is closed
+    /// or dropped, so that values are never reused. Fails if data was left in use by an interrupted run.
     pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
-        Ok(Self::new(PrecomputedSpdzData::load_file(path)?))
+        let path = path.as_ref();
+        let mut dealer = Self::open_file(path)?;
+
+        if let Some(record) = ConsumptionRecord::load(path)? {
+            if record.session_id == dealer.session_id {
+                if record.in_progress {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Precomputed data was used by an interrupted run and must be regenerated",
+                    ));
+                }
+                dealer.skip_consumed(record.cursor)?;
+                dealer.resumes_aborted_run = record.aborted;
+            }
+        }
+
+        dealer.record_path = Some(record_path(path));
+        dealer.save_record(true, false)?;
+        Ok(dealer)
+    }
+
+    /// Open file with precomputed data without consumption tracking.
+    fn open_file(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(file);
+
+        let header = read_header::<T, K>(&mut reader)?;
+        match header.format {
+            PrecomputedSpdzFormat::Monolithic => Ok(Self::new(read_monolithic(&header, reader)?)),
+            PrecomputedSpdzFormat::Seeded => Ok(Self::from_seeded(read_seeded(&header, reader)?)),
+            PrecomputedSpdzFormat::Streaming => {
+                let index: StreamingIndex<T, K> = read_index(&header, &mut reader)?;
+                let mut offsets = Vec::with_capacity(index.section_sizes.len());
+                let mut offset = reader.stream_position()?;
+                for size in &index.section_sizes {
+                    offsets.push(offset);
+                    offset += size;
+                }
+                let (input_masks_offsets, other_offsets) =
+                    offsets[4..].split_at(header.num_parties);
+                let (input_masks_plain_offset, other_offsets) = other_offsets.split_at(1);
+                let (output_masks_offsets, other_offsets) =
+                    other_offsets.split_at(header.num_parties);
+                let (output_masks_plain_offset, bit_masks_offsets) = other_offsets.split_at(1);
+                let square_pairs = FileSection::open(path, offsets[2])?;
+                let inverse_pairs = FileSection::open(path, offsets[3])?;
+
+                Ok(Self {
+                    num_parties: header.num_parties,
+                    party_id: header.party_id,
+                    session_id: header.session_id,
+                    auth_key: index.auth_key,
+                    beaver_triples: Section::File(FileSection::open(path, offsets[0])?),
+                    random_bits: Section::File(FileSection::open(path, offsets[1])?),
+                    square_pairs: (square_pairs.remaining > 0).then(|| Section::File(square_pairs)),
+                    inverse_pairs: (inverse_pairs.remaining > 0)
+                        .then(|| Section::File(inverse_pairs)),
+                    bit_masks: index
+                        .bit_mask_widths
+                        .iter()
+                        .zip(bit_masks_offsets)
+                        .map(|(&width, &offset)| {
+                            Ok((width, Section::File(FileSection::open(path, offset)?)))
+                        })
+                        .collect::<io::Result<_>>()?,
+                    input_masks: input_masks_offsets
+                        .iter()
+                        .map(|&offset| Ok(Section::File(FileSection::open(path, offset)?)))
+                        .collect::<io::Result<_>>()?,
+                    input_masks_plain: Section::File(FileSection::open(
+                        path,
+                        input_masks_plain_offset[0],
+                    )?),
+                    output_masks: output_masks_offsets
+                        .iter()
+                        .map(|&offset| Ok(Section::File(FileSection::open(path, offset)?)))
+                        .collect::<io::Result<_>>()?,
+                    output_masks_plain: Section::File(FileSection::open(
+                        path,
+                        output_masks_plain_offset[0],
+                    )?),
+                    is_exhausted: false,
+                    cursor: SpdzCursor::new(header.num_parties),
+                    record_path: None,
+                    resumes_aborted_run: false,
+                })
+            }
+        }
+    }
+
+    /// Create dealer deriving shares from a seed as they are consumed.
+    fn from_seeded(data: SeededSpdzData<T, K>) -> Self {
+        let seed = data.seed;
+        Self {
+            num_parties: data.num_parties,
+            party_id: data.party_id,
+            session_id: data.session_id,
+            auth_key: data.auth_key,
+            beaver_triples: Section::Seeded(Box::new(seeded_triples(
+                &seed,
+                data.num_beaver_triples,
+            ))),
+            square_pairs: (data.num_square_pairs > 0).then(|| {
+                Section::Seeded(Box::new(
+                    seeded_pairs(&seed, SpdzDataKind::SquarePairs).take(data.num_square_pairs),
+                ))
+            }),
+            inverse_pairs: (data.num_inverse_pairs > 0).then(|| {
+                Section::Seeded(Box::new(
+                    seeded_pairs(&seed, SpdzDataKind::InversePairs).take(data.num_inverse_pairs),
+                ))
+            }),
+            random_bits: Section::Seeded(Box::new(
+                SeededShareGenerator::new(&seed, SpdzDataKind::RandomBits)
+                    .take(data.num_random_bits),
+            )),
+            bit_masks: data
+                .num_bit_masks
+                .iter()
+                .map(|(&width, &count)| {
+                    let masks: Box<dyn Iterator<Item = _> + Send> =
+                        Box::new(seeded_bit_masks(&seed, width, count));
+                    (width, Section::Seeded(masks))
+                })
+                .collect(),
+            input_masks: data
+                .num_input_masks
+                .iter()
+                .enumerate()
+                .map(|(id, &count)| {
+                    Section::Seeded(Box::new(
+                        SeededShareGenerator::new(&seed, SpdzDataKind::InputMasks(id)).take(count),
+                    ))
+                })
+                .collect(),
+            input_masks_plain: Section::Memory(data.input_masks_plain.into_iter()),
+            output_masks: data
+                .num_output_masks
+                .iter()
+                .enumerate()
+                .map(|(id, &count)| {
+                    Section::Seeded(Box::new(
+                        SeededShareGenerator::new(&seed, SpdzDataKind::OutputMasks(id)).take(count),
+                    ))
+                })
+                .collect(),
+            output_masks_plain: Section::Memory(data.output_masks_plain.into_iter()),
+            is_exhausted: false,
+            cursor: SpdzCursor::new(data.num_parties),
+            record_path: None,
+            resumes_aborted_run: false,
+        }
+    }
+
+    /// Skip values consumed by previous runs.
+    fn skip_consumed(&mut self, cursor: SpdzCursor) -> io::Result<()> {
+        let invalid_cursor = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Consumption record doesn't match precomputed data",
+            )
+        };
+        if cursor.input_masks.len() != self.num_parties
+            || cursor.output_masks.len() != self.num_parties
+        {
+            return Err(invalid_cursor());
+        }
+
+        let own_masks = cursor.input_masks[self.party_id];
+        let own_output_masks = cursor.output_masks[self.party_id];
+        let all_skipped = skip(&mut self.beaver_triples, cursor.beaver_triples)
+            && match &mut self.square_pairs {
+                Some(pairs) => skip(pairs, cursor.square_pairs),
+                None => cursor.square_pairs == 0,
+            }
+            && match &mut self.inverse_pairs {
+                Some(pairs) => skip(pairs, cursor.inverse_pairs),
+                None => cursor.inverse_pairs == 0,
+            }
+            && skip(&mut self.random_bits, cursor.random_bits)
+            && skip(&mut self.input_masks_plain, own_masks)
+            && skip(&mut self.output_masks_plain, own_output_masks)
+            && cursor
+                .bit_masks
+                .iter()
+                .all(|(width, &count)| match self.bit_masks.get_mut(width) {
+                    Some(masks) => skip(masks, count),
+                    None => count == 0,
+                })
+            && self
+                .input_masks
+                .iter_mut()
+                .zip(&cursor.input_masks)
+                .all(|(masks, &count)| skip(masks, count))
+            && self
+                .output_masks
+                .iter_mut()
+                .zip(&cursor.output_masks)
+                .all(|(masks, &count)| skip(masks, count));
+        if !all_skipped {
+            return Err(invalid_cursor());
+        }
+
+        self.cursor = cursor;
+        Ok(())
     }
 }
 
-impl<T: MpcField> PrecomputedSpdzDealer<T> {
+impl<T, const K: usize> PrecomputedSpdzDealer<T, K> {
+    /// Save consumption record, if consumption is tracked.
+    fn save_record(&self, in_progress: bool, aborted: bool) -> io::Result<()> {
+        if let Some(path) = &self.record_path {
+            let record = ConsumptionRecord {
+                session_id: self.session_id,
+                cursor: self.cursor.clone(),
+                in_progress,
+                aborted,
+            };
+            let writer = BufWriter::new(File::create(path)?);
+            serde_json::to_writer(writer, &record)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, const K: usize> PrecomputedSpdzDealer<T, K> {
+    /// Save consumption record after computation, so that the next run continues after consumed data.
+    /// If saving fails, the record stays marked as in progress and data won't be used again.
+    pub fn close(mut self) -> io::Result<()> {
+        let result = self.save_record(false, false);
+        self.record_path = None;
+        result
+    }
+}
+
+impl<T, const K: usize> Drop for PrecomputedSpdzDealer<T, K> {
+    fn drop(&mut self) {
+        // Dealers of failed runs are dropped without closing, their errors can't be reported anymore.
+        // Other parties may have consumed more data before the abort, so the next run has to catch up with them.
+        let _ = self.save_record(false, true);
+    }
+}
+
+/// Skip given number of values. Returns false if there are not enough values.
+fn skip<V: DeserializeOwned>(section: &mut Section<V>, count: usize) -> bool {
+    section.take(count).count() == count
+}
+
+impl<T, const K: usize> PrecomputedSpdzDealer<T, K>
+where
+    T: MpcField + for<'a> Deserialize<'a>,
+{
     /// Random sharing of a secret random bit.
-    fn next_bit(&mut self) -> SpdzShare<T> {
-        if let Some(share) = self.data.random_bits.pop() {
+    fn next_bit(&mut self) -> SpdzShare<T, K> {
+        if let Some(share) = self.random_bits.next() {
+            self.cursor.random_bits += 1;
             share
         } else {
             self.is_exhausted = true;
@@ -78,26 +976,30 @@ impl<T: MpcField> PrecomputedSpdzDealer<T> {
     }
 }
 
-impl<T: MpcField> MpcContext for PrecomputedSpdzDealer<T> {
+impl<T: MpcField, const K: usize> MpcContext for PrecomputedSpdzDealer<T, K> {
     type Field = T;
-    type Share = SpdzShare<T>;
+    type Share = SpdzShare<T, K>;
 
     fn num_parties(&self) -> usize {
-        self.data.num_parties
+        self.num_parties
     }
 
     fn party_id(&self) -> usize {
-        self.data.party_id
+        self.party_id
     }
 }
 
-impl<T: MpcField> MpcDealer for PrecomputedSpdzDealer<T> {
+impl<T, const K: usize> MpcDealer for PrecomputedSpdzDealer<T, K>
+where
+    T: MpcField + for<'a> Deserialize<'a>,
+{
     fn share_plain(&self, x: Self::Field) -> Self::Share {
-        SpdzShare::from_plain(x, self.data.auth_key, self.party_id())
+        SpdzShare::from_plain(x, self.auth_key, self.party_id())
     }
 
     fn next_beaver_triple(&mut self) -> (Self::Share, Self::Share, Self::Share) {
-        if let Some(triple) = self.data.beaver_triples.pop() {
+        if let Some(triple) = self.beaver_triples.next() {
+            self.cursor.beaver_triples += 1;
             triple
         } else {
             self.is_exhausted = true;
@@ -105,24 +1007,69 @@ impl<T: MpcField> MpcDealer for PrecomputedSpdzDealer<T> {
         }
     }
 
+    fn next_square_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        if let Some(pair) = self.square_pairs.as_mut()?.next() {
+            self.cursor.square_pairs += 1;
+            Some(pair)
+        } else {
+            self.is_exhausted = true;
+            Some((Default::default(), Default::default()))
+        }
+    }
+
+    fn next_inverse_pair(&mut self) -> Option<(Self::Share, Self::Share)> {
+        if let Some(pair) = self.inverse_pairs.as_mut()?.next() {
+            self.cursor.inverse_pairs += 1;
+            Some(pair)
+        } else {
+            self.is_exhausted = true;
+            Some((Default::default(), Default::default()))
+        }
+    }
+
     fn next_uint(&mut self, bits: usize) -> Self::Share {
         (0..bits).fold(Self::Share::zero(), |acc, _| acc.double() + self.next_bit())
     }
 
+    fn next_bit_mask(&mut self, k: usize) -> (Self::Share, Self::Share, Vec<Self::Share>) {
+        let masks = match self.bit_masks.get_mut(&k) {
+            Some(masks) => masks,
+            // Data has no masks of this width, but masks that run out aren't replaced by random bits.
+            None => return assemble_bit_mask(self, k),
+        };
+        if let Some(mask) = masks.next() {
+            *self.cursor.bit_masks.entry(k).or_default() += 1;
+            mask
+        } else {
+            self.is_exhausted = true;
+            (
+                Default::default(),
+                Default::default(),
+                vec![Default::default(); k],
+            )
+        }
+    }
+
     fn is_exhausted(&self) -> bool {
         self.is_exhausted
     }
 }
 
-impl<T: MpcField> SpdzDealer for PrecomputedSpdzDealer<T> {
-    fn authentication_key_share(&self) -> Self::Field {
-        self.data.auth_key
+impl<T, const K: usize> SpdzDealer<K> for PrecomputedSpdzDealer<T, K>
+where
+    T: MpcField + for<'a> Deserialize<'a>,
+{
+    fn authentication_key_share(&self) -> [Self::Field; K] {
+        self.auth_key
     }
 
     fn next_input_mask_own(&mut self) -> (Self::Share, Self::Field) {
         let id = self.party_id();
-        if let Some(mask) = self.data.input_masks[id].pop() {
-            (mask, self.data.input_masks_plain.pop().unwrap())
+        if let (Some(mask), Some(plain)) =
+            (self.input_masks[id].next(), self.input_masks_plain.next())
+        {
+            self.cursor.input_masks[id] += 1;
+            (mask, plain)
         } else {
             self.is_exhausted = true;
             (Default::default(), Default::default())
@@ -133,11 +1080,670 @@ impl<T: MpcField> SpdzDealer for PrecomputedSpdzDealer<T> {
         if id == self.party_id() {
             panic!("Tried to get own mask as third-party mask");
         }
-        if let Some(mask) = self.data.input_masks[id].pop() {
+        if let Some(mask) = self.input_masks[id].next() {
+            self.cursor.input_masks[id] += 1;
+            mask
+        } else {
+            self.is_exhausted = true;
+            Default::default()
+        }
+    }
+
+    fn next_output_mask_own(&mut self) -> (Self::Share, Self::Field) {
+        let id = self.party_id();
+        if let (Some(mask), Some(plain)) =
+            (self.output_masks[id].next(), self.output_masks_plain.next())
+        {
+            self.cursor.output_masks[id] += 1;
+            (mask, plain)
+        } else {
+            self.is_exhausted = true;
+            (Default::default(), Default::default())
+        }
+    }
+
+    fn next_output_mask_for(&mut self, id: usize) -> Self::Share {
+        if id == self.party_id() {
+            panic!("Tried to get own mask as third-party mask");
+        }
+        if let Some(mask) = self.output_masks[id].next() {
+            self.cursor.output_masks[id] += 1;
             mask
         } else {
             self.is_exhausted = true;
             Default::default()
         }
     }
+
+    fn cursor(&self) -> Option<SpdzCursor> {
+        Some(self.cursor.clone())
+    }
+
+    fn session_id(&self) -> Option<SpdzSessionId> {
+        Some(self.session_id)
+    }
+
+    fn resumes_aborted_run(&self) -> bool {
+        self.resumes_aborted_run
+    }
+}
+
+/// Precomputed values of a single kind, either kept in memory, read from file or derived from seed on demand.
+enum Section<V> {
+    Memory(std::vec::IntoIter<V>),
+    File(FileSection<V>),
+    Seeded(Box<dyn Iterator<Item = V> + Send>),
+}
+
+impl<V: DeserializeOwned> Iterator for Section<V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        match self {
+            Section::Memory(values) => values.next(),
+            Section::File(section) => section.next(),
+            Section::Seeded(values) => values.next(),
+        }
+    }
+}
+
+/// Section of file with precomputed data, deserialized value by value.
+/// Values are consumed once and in order, so a buffered reader is enough; memory-mapping the file
+/// wouldn't save any copies, and reading a mapped file that is truncated meanwhile is undefined behavior.
+/// A read error (e.g. a truncated file) ends the section, so the dealer reports that it is exhausted.
+struct FileSection<V> {
+    reader: BufReader<File>,
+    remaining: u64,
+    _phantom: PhantomData<V>,
+}
+
+impl<V> FileSection<V> {
+    /// Open section starting at given offset of file.
+    fn open(path: &Path, offset: u64) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::new(file);
+        let remaining = bincode::deserialize_from(&mut reader).map_err(bincode_error)?;
+        Ok(Self {
+            reader,
+            remaining,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<V: DeserializeOwned> Iterator for FileSection<V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match bincode::deserialize_from(&mut self.reader) {
+            Ok(value) => {
+                self.remaining -= 1;
+                Some(value)
+            }
+            Err(_) => {
+                self.remaining = 0;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use futures::future;
+
+    use crate::{
+        circuits, executor,
+        fields::{FieldType, Mersenne127, Mersenne61},
+        spdz::{FakeSpdzDealer, SpdzConfig, SpdzDataKind, SpdzEngine, SpdzError, SpdzShare},
+        transport, MpcDealer, MpcEngine, MpcField,
+    };
+
+    use super::{
+        PrecomputedSpdzData, PrecomputedSpdzDealer, PrecomputedSpdzFormat, PrecomputedSpdzHeader,
+        SeededShareGenerator, SeededSpdzData, SpdzCursor, SpdzDealer, PRECOMPUTED_SPDZ_VERSION,
+    };
+
+    #[test]
+    fn test_field_check() {
+        let path = std::env::temp_dir().join(format!("precomp-test-{}.bin", std::process::id()));
+        let data = PrecomputedSpdzData::<Mersenne61, 2> {
+            num_parties: 2,
+            input_masks_plain: vec![123.into()],
+            ..Default::default()
+        };
+        data.save_file(&path).unwrap();
+
+        let header = PrecomputedSpdzHeader::load_file(&path).unwrap();
+        assert_eq!(header.version, PRECOMPUTED_SPDZ_VERSION);
+        assert_eq!(header.field, FieldType::Mersenne61);
+        assert_eq!(header.num_mac_keys, 2);
+        assert_eq!(header.num_parties, 2);
+        assert_eq!(header.party_id, 0);
+
+        let loaded = PrecomputedSpdzData::<Mersenne61, 2>::load_file(&path).unwrap();
+        assert_eq!(loaded.input_masks_plain, data.input_masks_plain);
+
+        assert!(PrecomputedSpdzData::<Mersenne61, 1>::load_file(&path).is_err());
+        assert!(PrecomputedSpdzData::<Mersenne127, 2>::load_file(&path).is_err());
+
+        std::fs::write(&path, b"garbage").unwrap();
+        assert!(PrecomputedSpdzHeader::load_file(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_streaming_format() {
+        let path =
+            std::env::temp_dir().join(format!("precomp-streaming-test-{}.bin", std::process::id()));
+        let share = |x: u64| SpdzShare {
+            value: Mersenne61::from(x),
+            mac: [Mersenne61::from(x * 7)],
+        };
+        let data = PrecomputedSpdzData::<Mersenne61> {
+            num_parties: 2,
+            party_id: 1,
+            session_id: [7; 16],
+            auth_key: [5.into()],
+            beaver_triples: (0..3)
+                .map(|x| (share(x), share(x + 1), share(x + 2)))
+                .collect(),
+            square_pairs: vec![(share(3), share(9))],
+            inverse_pairs: vec![(share(4), share(5)), (share(6), share(7))],
+            random_bits: (0..5).map(share).collect(),
+            bit_masks: BTreeMap::from([
+                (1, vec![(share(30), share(1), vec![share(1)])]),
+                (2, vec![(share(40), share(0), vec![share(0); 2]); 2]),
+            ]),
+            input_masks: vec![vec![share(10)], vec![share(20), share(21)]],
+            input_masks_plain: vec![20.into(), 21.into()],
+            output_masks: vec![vec![share(50), share(51)], vec![share(60)]],
+            output_masks_plain: vec![60.into()],
+        };
+        data.save_file_streaming(&path).unwrap();
+
+        let header = PrecomputedSpdzHeader::load_file(&path).unwrap();
+        assert_eq!(header.format, PrecomputedSpdzFormat::Streaming);
+        assert_eq!(header.session_id, [7; 16]);
+
+        let loaded = PrecomputedSpdzData::<Mersenne61>::load_file(&path).unwrap();
+        assert_eq!(loaded.party_id, 1);
+        assert_eq!(loaded.input_masks_plain, data.input_masks_plain);
+        assert_eq!(loaded.input_masks[1][1].value, data.input_masks[1][1].value);
+        assert_eq!(
+            loaded.output_masks[0][1].value,
+            data.output_masks[0][1].value
+        );
+        assert_eq!(loaded.output_masks_plain, data.output_masks_plain);
+
+        let consume = |dealer: &mut PrecomputedSpdzDealer<Mersenne61>| {
+            let mut values = Vec::new();
+            for _ in 0..3 {
+                let (a, b, c) = dealer.next_beaver_triple();
+                values.extend([a.value, b.value, c.value]);
+            }
+            values.push(dealer.next_uint(5).value);
+            let (a, a_squared) = dealer.next_square_pair().unwrap();
+            values.extend([a.value, a_squared.value]);
+            for _ in 0..2 {
+                let (r, r_inverse) = dealer.next_inverse_pair().unwrap();
+                values.extend([r.value, r_inverse.value]);
+            }
+            for width in [2, 1, 2] {
+                let (mask, low_part, low_bits) = dealer.next_bit_mask(width);
+                values.extend([mask.value, low_part.value]);
+                values.extend(low_bits.iter().map(|bit| bit.value));
+            }
+            values.push(dealer.next_input_mask_for(0).value);
+            for _ in 0..2 {
+                let (mask, plain) = dealer.next_input_mask_own();
+                values.extend([mask.value, mask.mac[0], plain]);
+            }
+            let (mask, plain) = dealer.next_output_mask_own();
+            values.extend([mask.value, plain]);
+            for _ in 0..2 {
+                values.push(dealer.next_output_mask_for(0).value);
+            }
+            assert!(!dealer.is_exhausted());
+            dealer.next_beaver_triple();
+            assert!(dealer.is_exhausted());
+            values
+        };
+
+        let from_file = consume(&mut PrecomputedSpdzDealer::from_file(&path).unwrap());
+        let from_memory = consume(&mut PrecomputedSpdzDealer::new(data));
+        assert_eq!(from_file, from_memory);
+
+        // Truncating the last section (bit masks of width 2) makes the dealer exhausted instead of panicking.
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(file.metadata().unwrap().len() - 1).unwrap();
+        let mut dealer = PrecomputedSpdzDealer::<Mersenne61>::open_file(&path).unwrap();
+        dealer.next_bit_mask(2);
+        assert!(!dealer.is_exhausted());
+        dealer.next_bit_mask(2);
+        assert!(dealer.is_exhausted());
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(super::record_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn test_missing_data_fallback() {
+        let share = |x: u64| SpdzShare {
+            value: Mersenne61::from(x),
+            mac: [Mersenne61::from(x * 7)],
+        };
+        let mut dealer = PrecomputedSpdzDealer::new(PrecomputedSpdzData::<Mersenne61> {
+            num_parties: 2,
+            party_id: 1,
+            random_bits: (0..Mersenne61::SAFE_BITS as u64).map(share).collect(),
+            bit_masks: BTreeMap::from([(1, vec![(share(30), share(1), vec![share(1)])])]),
+            ..Default::default()
+        });
+
+        // Squares and inverses use Beaver triples instead of missing square and inverse pairs.
+        assert!(dealer.next_square_pair().is_none());
+        assert!(dealer.next_inverse_pair().is_none());
+        assert!(!dealer.is_exhausted());
+
+        // Masks of a width missing in data are assembled from random bits.
+        let (_, low_part, low_bits) = dealer.next_bit_mask(2);
+        assert!(!dealer.is_exhausted());
+        assert_eq!(dealer.cursor().unwrap().random_bits, Mersenne61::SAFE_BITS);
+        assert_eq!(
+            low_part.value,
+            low_bits[0].value + low_bits[1].value * Mersenne61::from(2)
+        );
+
+        // Masks of a width present in data are never replaced by random bits.
+        dealer.next_bit_mask(1);
+        assert!(!dealer.is_exhausted());
+        dealer.next_bit_mask(1);
+        assert!(dealer.is_exhausted());
+    }
+
+    #[tokio::test]
+    async fn test_square_and_inverse_without_pairs() {
+        // Consistent data of both parties is taken from fake dealers, without any square or inverse pairs.
+        let channels = transport::memory_multiparty_channels(2);
+        let outputs = future::join_all(channels.into_iter().enumerate().map(
+            |(party_id, transport)| {
+                let mut fake = FakeSpdzDealer::<Mersenne61>::new(2, party_id, 7);
+                let (input_masks, input_masks_plain) = if party_id == 0 {
+                    let (mask, plain) = fake.next_input_mask_own();
+                    (vec![vec![mask], Vec::new()], vec![plain])
+                } else {
+                    (
+                        vec![vec![fake.next_input_mask_for(0)], Vec::new()],
+                        Vec::new(),
+                    )
+                };
+                let data = PrecomputedSpdzData {
+                    num_parties: 2,
+                    party_id,
+                    auth_key: fake.authentication_key_share(),
+                    beaver_triples: (0..2).map(|_| fake.next_beaver_triple()).collect(),
+                    input_masks,
+                    input_masks_plain,
+                    ..Default::default()
+                };
+                let engine: SpdzEngine<_, _, _> = SpdzEngine::new(
+                    PrecomputedSpdzDealer::new(data),
+                    transport,
+                    SpdzConfig::default(),
+                );
+                let inputs = if party_id == 0 {
+                    vec![Mersenne61::from(5)]
+                } else {
+                    Vec::new()
+                };
+                async move {
+                    let (outputs, _, engine) =
+                        executor::run_circuit(engine, &inputs, |ctx, inputs| {
+                            Box::pin(async move {
+                                let x = inputs[0][0];
+                                let square = circuits::square(ctx, x).await;
+                                let inverse = circuits::inverse(ctx, x).await;
+                                (
+                                    ctx.open_unchecked(square).await,
+                                    ctx.open_unchecked(inverse * Mersenne61::from(5)).await,
+                                )
+                            })
+                        })
+                        .await
+                        .unwrap();
+                    let dealer = engine.into_dealer();
+                    assert!(!dealer.is_exhausted());
+                    assert_eq!(dealer.cursor().unwrap().beaver_triples, 2);
+                    outputs
+                }
+            },
+        ))
+        .await;
+        assert_eq!(
+            outputs,
+            vec![(Mersenne61::from(25), Mersenne61::from(1)); 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_seeded_format() {
+        let path =
+            std::env::temp_dir().join(format!("precomp-seeded-test-{}.bin", std::process::id()));
+        let seed = [3; 32];
+        let seeded = SeededSpdzData::<Mersenne61> {
+            num_parties: 2,
+            party_id: 1,
+            session_id: [4; 16],
+            auth_key: [6.into()],
+            seed,
+            num_beaver_triples: 2,
+            num_square_pairs: 3,
+            num_inverse_pairs: 0,
+            num_random_bits: 0,
+            num_bit_masks: BTreeMap::from([(2, 1)]),
+            num_input_masks: vec![1, 1],
+            input_masks_plain: vec![21.into()],
+            num_output_masks: vec![0, 2],
+            output_masks_plain: vec![22.into(), 23.into()],
+        };
+        seeded.save_file(&path).unwrap();
+
+        // Expansion of seeds is part of the file format and must not depend on platform or dependencies.
+        let mut shares =
+            SeededShareGenerator::<Mersenne61, 1>::new(&seed, SpdzDataKind::BeaverTriples);
+        assert_eq!(
+            shares.next().unwrap().value.truncated(),
+            1513263572382088940
+        );
+
+        let header = PrecomputedSpdzHeader::load_file(&path).unwrap();
+        assert_eq!(header.format, PrecomputedSpdzFormat::Seeded);
+        let expanded = PrecomputedSpdzData::<Mersenne61>::load_file(&path).unwrap();
+        assert_eq!(expanded.beaver_triples.len(), 2);
+        assert_eq!(expanded.square_pairs.len(), 3);
+        assert_eq!(expanded.input_masks[0].len(), 1);
+        assert_eq!(expanded.output_masks[1].len(), 2);
+        assert_eq!(expanded.bit_masks[&2][0].2.len(), 2);
+
+        // Party 0 holds corrections of shares derived from the seed of party 1.
+        let correct = |x: u64, share: SpdzShare<Mersenne61>| {
+            let mut correction = SpdzShare::from_plain(x.into(), [11.into()], 0);
+            correction -= share;
+            correction
+        };
+        let mut triple_shares = SeededShareGenerator::new(&seed, SpdzDataKind::BeaverTriples);
+        let mut triple_share = || triple_shares.next().unwrap();
+        let beaver_triples = [(2, 3), (4, 5)]
+            .into_iter()
+            .map(|(a, b)| {
+                (
+                    correct(a, triple_share()),
+                    correct(b, triple_share()),
+                    correct(a * b, triple_share()),
+                )
+            })
+            .collect();
+        let input_masks = [(0, 10), (1, 21)]
+            .into_iter()
+            .map(|(id, plain)| {
+                let mut shares = SeededShareGenerator::new(&seed, SpdzDataKind::InputMasks(id));
+                vec![correct(plain, shares.next().unwrap())]
+            })
+            .collect();
+        let corrections = PrecomputedSpdzData::<Mersenne61> {
+            num_parties: 2,
+            party_id: 0,
+            session_id: [4; 16],
+            auth_key: [5.into()],
+            beaver_triples,
+            input_masks,
+            input_masks_plain: vec![10.into()],
+            ..Default::default()
+        };
+
+        let channels = transport::memory_multiparty_channels(2);
+        let outputs = future::join_all(channels.into_iter().enumerate().map(
+            |(party_id, transport)| {
+                let dealer = if party_id == 0 {
+                    PrecomputedSpdzDealer::new(corrections.clone())
+                } else {
+                    PrecomputedSpdzDealer::from_file(&path).unwrap()
+                };
+                async move {
+                    let engine: SpdzEngine<_, _, _> =
+                        SpdzEngine::new(dealer, transport, SpdzConfig::default());
+                    let inputs = [Mersenne61::from(party_id as u64 + 7)];
+                    executor::run_circuit(engine, &inputs, |ctx, inputs| {
+                        Box::pin(async move {
+                            let product = circuits::product(ctx, inputs.iter().map(|x| x[0])).await;
+                            ctx.open_unchecked(product).await
+                        })
+                    })
+                    .await
+                    .unwrap()
+                    .0
+                }
+            },
+        ))
+        .await;
+        assert_eq!(outputs, vec![Mersenne61::from(56); 2]);
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(super::record_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn test_consumption_tracking() {
+        let path =
+            std::env::temp_dir().join(format!("precomp-tracking-test-{}.bin", std::process::id()));
+        let share = |x: u64| SpdzShare {
+            value: Mersenne61::from(x),
+            mac: [Mersenne61::from(x * 7)],
+        };
+        let data = PrecomputedSpdzData::<Mersenne61> {
+            num_parties: 2,
+            party_id: 0,
+            beaver_triples: (0..3).map(|x| (share(x), share(x), share(x))).collect(),
+            input_masks: vec![vec![share(10), share(11)], Vec::new()],
+            input_masks_plain: vec![10.into(), 11.into()],
+            ..Default::default()
+        };
+        data.save_file(&path).unwrap();
+
+        let mut dealer = PrecomputedSpdzDealer::<Mersenne61>::from_file(&path).unwrap();
+        dealer.next_beaver_triple();
+        dealer.next_beaver_triple();
+        dealer.next_input_mask_own();
+        dealer.close().unwrap();
+
+        let mut dealer = PrecomputedSpdzDealer::<Mersenne61>::from_file(&path).unwrap();
+        let expected_cursor = SpdzCursor {
+            beaver_triples: 2,
+            square_pairs: 0,
+            inverse_pairs: 0,
+            random_bits: 0,
+            bit_masks: BTreeMap::new(),
+            input_masks: vec![1, 0],
+            output_masks: vec![0, 0],
+        };
+        assert_eq!(dealer.cursor(), Some(expected_cursor));
+        assert_eq!(dealer.next_beaver_triple().0.value, 2.into());
+        assert_eq!(dealer.next_input_mask_own().1, 11.into());
+        dealer.next_beaver_triple();
+        assert!(dealer.is_exhausted());
+
+        // Dealer which is not dropped properly leaves its data unusable.
+        std::mem::forget(dealer);
+        assert!(PrecomputedSpdzDealer::<Mersenne61>::from_file(&path).is_err());
+
+        // Data from another session replacing the file doesn't inherit its record.
+        let other_path = path.with_extension("other");
+        let other_data = PrecomputedSpdzData {
+            session_id: [1; 16],
+            ..data.clone()
+        };
+        other_data.save_file(&other_path).unwrap();
+        std::fs::rename(&other_path, &path).unwrap();
+        let dealer = PrecomputedSpdzDealer::<Mersenne61>::from_file(&path).unwrap();
+        assert_eq!(dealer.cursor().unwrap().beaver_triples, 0);
+        drop(dealer);
+
+        // Regenerated data can be used again.
+        data.save_file(&path).unwrap();
+        let dealer = PrecomputedSpdzDealer::<Mersenne61>::from_file(&path).unwrap();
+        assert_eq!(dealer.cursor().unwrap().beaver_triples, 0);
+        drop(dealer);
+
+        // Failure to save the record is reported when closing the dealer.
+        let dealer = PrecomputedSpdzDealer::<Mersenne61>::from_file(&path).unwrap();
+        let record_path = super::record_path(&path);
+        std::fs::remove_file(&record_path).unwrap();
+        std::fs::create_dir(&record_path).unwrap();
+        assert!(dealer.close().is_err());
+
+        std::fs::remove_dir(&record_path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_abort() {
+        let paths: Vec<_> = (0..2)
+            .map(|id| {
+                std::env::temp_dir().join(format!(
+                    "precomp-resume-test-{}-{}.bin",
+                    std::process::id(),
+                    id
+                ))
+            })
+            .collect();
+        // Zero sharings are valid for any MAC key, so all parties can share the same data.
+        for (party_id, path) in paths.iter().enumerate() {
+            let data = PrecomputedSpdzData::<Mersenne61> {
+                num_parties: 2,
+                party_id,
+                beaver_triples: vec![Default::default(); 2],
+                input_masks: vec![vec![Default::default(); 2]; 2],
+                input_masks_plain: vec![0.into(); 2],
+                ..Default::default()
+            };
+            data.save_file(path).unwrap();
+        }
+
+        // Party 1 leaves the circuit before its multiplication, after party 0 has used a triple for it.
+        let channels = transport::memory_multiparty_channels(2);
+        let results = future::join_all(channels.into_iter().zip(&paths).enumerate().map(
+            |(party_id, (transport, path))| async move {
+                let dealer = PrecomputedSpdzDealer::<Mersenne61>::from_file(path).unwrap();
+                let engine: SpdzEngine<_, _, _> =
+                    SpdzEngine::new(dealer, transport, SpdzConfig::default());
+                let inputs = [Mersenne61::from(1)];
+                executor::run_circuit(engine, &inputs, |ctx, inputs| {
+                    Box::pin(async move {
+                        if party_id == 0 {
+                            circuits::product(ctx, inputs.iter().map(|x| x[0])).await;
+                        }
+                    })
+                })
+                .await
+                .map(|_| ())
+            },
+        ))
+        .await;
+        assert!(results.iter().all(|result| result.is_err()));
+
+        // Both parties continue after the triple used by party 0.
+        let channels = transport::memory_multiparty_channels(2);
+        let cursors = future::join_all(channels.into_iter().zip(&paths).enumerate().map(
+            |(party_id, (transport, path))| async move {
+                let dealer = PrecomputedSpdzDealer::<Mersenne61>::from_file(path).unwrap();
+                let engine: SpdzEngine<_, _, _> =
+                    SpdzEngine::new(dealer, transport, SpdzConfig::default());
+                let inputs = [Mersenne61::from(party_id as u64 + 7)];
+                let (output, _, engine) = executor::run_circuit(engine, &inputs, |ctx, inputs| {
+                    Box::pin(async move {
+                        let product = circuits::product(ctx, inputs.iter().map(|x| x[0])).await;
+                        ctx.open_unchecked(product).await
+                    })
+                })
+                .await
+                .unwrap();
+                assert_eq!(output, Mersenne61::from(56));
+                let dealer = engine.into_dealer();
+                let cursor = dealer.cursor().unwrap();
+                dealer.close().unwrap();
+                cursor
+            },
+        ))
+        .await;
+        assert_eq!(cursors[0], cursors[1]);
+        assert_eq!(cursors[0].beaver_triples, 2);
+
+        for path in paths {
+            std::fs::remove_file(&path).unwrap();
+            std::fs::remove_file(super::record_path(&path)).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_and_cursor_mismatch() {
+        // Zero sharings are valid for any MAC key, so dealers of all parties can share the same data.
+        let make_dealer = |party_id, session_id, skipped_triples| {
+            let mut dealer = PrecomputedSpdzDealer::new(PrecomputedSpdzData::<Mersenne61> {
+                num_parties: 2,
+                party_id,
+                session_id,
+                beaver_triples: vec![Default::default(); 2],
+                input_masks: vec![Vec::new(); 2],
+                ..Default::default()
+            });
+            for _ in 0..skipped_triples {
+                dealer.next_beaver_triple();
+            }
+            dealer
+        };
+
+        let cases = [
+            ([0; 16], 0, [Ok(()), Ok(())]),
+            (
+                [0; 16],
+                1,
+                [
+                    Err(SpdzError::CursorMismatch(1)),
+                    Err(SpdzError::CursorMismatch(0)),
+                ],
+            ),
+            (
+                [1; 16],
+                0,
+                [
+                    Err(SpdzError::SessionMismatch(1)),
+                    Err(SpdzError::SessionMismatch(0)),
+                ],
+            ),
+        ];
+
+        for (other_session_id, other_skipped_triples, expected) in cases {
+            let channels = transport::memory_multiparty_channels(2);
+            let results = future::join_all(channels.into_iter().enumerate().map(
+                |(party_id, transport)| async move {
+                    let dealer = if party_id == 0 {
+                        make_dealer(party_id, [0; 16], 0)
+                    } else {
+                        make_dealer(party_id, other_session_id, other_skipped_triples)
+                    };
+                    let mut engine: SpdzEngine<_, _, _> =
+                        SpdzEngine::new(dealer, transport, SpdzConfig::default());
+                    engine.check_integrity().await
+                },
+            ))
+            .await;
+            assert_eq!(results, expected);
+        }
+    }
 }
@@ -5,103 +5,188 @@ use serde::{Deserialize, Serialize};
 use crate::{MpcField, MpcShare};
 
 /// Private share of a field element in SPDZ protocol.
+/// Value is authenticated with K independent MAC keys, which increases security for small fields.
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
-pub struct SpdzShare<T> {
+pub struct SpdzShare<T, const K: usize = 1> {
     pub value: T,
-    pub mac: T,
+    #[serde(
+        with = "mac_serde",
+        bound(deserialize = "T: Deserialize<'de> + Copy + Default")
+    )]
+    pub mac: [T; K],
 }
 
-impl<T: MpcField> SpdzShare<T> {
-    /// Make SPDZ share for given plain value, authorization key share and party ID.
-    pub fn from_plain(value: T, auth_key: T, party_id: usize) -> Self {
+impl<T: MpcField, const K: usize> SpdzShare<T, K> {
+    /// Make SPDZ share for given plain value, authorization key shares and party ID.
+    pub fn from_plain(value: T, auth_key: [T; K], party_id: usize) -> Self {
         Self {
             value: if party_id == 0 { value } else { T::zero() },
-            mac: value * auth_key,
+            mac: auth_key.map(|key| value * key),
         }
     }
 }
 
-impl<T: MpcField> MpcShare for SpdzShare<T> {
+impl<T: MpcField, const K: usize> MpcShare for SpdzShare<T, K> {
     type Field = T;
 
     fn zero() -> Self {
         SpdzShare {
             value: T::zero(),
-            mac: T::zero(),
+            mac: [T::zero(); K],
         }
     }
 
     fn double(&self) -> Self {
         SpdzShare {
             value: self.value.double(),
-            mac: self.mac.double(),
+            mac: self.mac.map(|x| x.double()),
         }
     }
 }
 
-impl<T: MpcField> Default for SpdzShare<T> {
+impl<T: MpcField, const K: usize> Default for SpdzShare<T, K> {
     fn default() -> Self {
         Self::zero()
     }
 }
 
-impl<T: MpcField> Add for SpdzShare<T> {
-    type Output = SpdzShare<T>;
-    fn add(self, rhs: Self) -> Self::Output {
-        SpdzShare {
-            value: self.value + rhs.value,
-            mac: self.mac + rhs.mac,
-        }
+impl<T: MpcField, const K: usize> Add for SpdzShare<T, K> {
+    type Output = SpdzShare<T, K>;
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
     }
 }
 
-impl<T: MpcField> Sub for SpdzShare<T> {
-    type Output = SpdzShare<T>;
-    fn sub(self, rhs: Self) -> Self::Output {
-        SpdzShare {
-            value: self.value - rhs.value,
-            mac: self.mac - rhs.mac,
-        }
+impl<T: MpcField, const K: usize> Sub for SpdzShare<T, K> {
+    type Output = SpdzShare<T, K>;
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self -= rhs;
+        self
     }
 }
 
-impl<T: MpcField> Neg for SpdzShare<T> {
-    type Output = SpdzShare<T>;
+impl<T: MpcField, const K: usize> Neg for SpdzShare<T, K> {
+    type Output = SpdzShare<T, K>;
     fn neg(self) -> Self::Output {
         SpdzShare {
             value: -self.value,
-            mac: -self.mac,
+            mac: self.mac.map(|x| -x),
         }
     }
 }
 
-impl<T: MpcField> Mul<T> for SpdzShare<T> {
-    type Output = SpdzShare<T>;
-    fn mul(self, rhs: T) -> Self::Output {
-        SpdzShare {
-            value: self.value * rhs,
-            mac: self.mac * rhs,
-        }
+impl<T: MpcField, const K: usize> Mul<T> for SpdzShare<T, K> {
+    type Output = SpdzShare<T, K>;
+    fn mul(mut self, rhs: T) -> Self::Output {
+        self *= rhs;
+        self
     }
 }
 
-impl<T: MpcField> AddAssign for SpdzShare<T> {
+impl<T: MpcField, const K: usize> AddAssign for SpdzShare<T, K> {
     fn add_assign(&mut self, rhs: Self) {
         self.value += rhs.value;
-        self.mac += rhs.mac;
+        for (x, y) in self.mac.iter_mut().zip(rhs.mac) {
+            *x += y;
+        }
     }
 }
 
-impl<T: MpcField> SubAssign for SpdzShare<T> {
+impl<T: MpcField, const K: usize> SubAssign for SpdzShare<T, K> {
     fn sub_assign(&mut self, rhs: Self) {
         self.value -= rhs.value;
-        self.mac -= rhs.mac;
+        for (x, y) in self.mac.iter_mut().zip(rhs.mac) {
+            *x -= y;
+        }
     }
 }
 
-impl<T: MpcField> MulAssign<T> for SpdzShare<T> {
+impl<T: MpcField, const K: usize> MulAssign<T> for SpdzShare<T, K> {
     fn mul_assign(&mut self, rhs: T) {
         self.value *= rhs;
-        self.mac *= rhs;
+        for x in &mut self.mac {
+            *x *= rhs;
+        }
+    }
+}
+
+/// Serialization of MAC arrays as tuples. For K = 1 encoding is the same as for a single element.
+pub(crate) mod mac_serde {
+    use std::{fmt, marker::PhantomData};
+
+    use serde::{
+        de::{self, SeqAccess, Visitor},
+        ser::SerializeTuple,
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    pub fn serialize<S, T, const K: usize>(mac: &[T; K], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        let mut tuple = serializer.serialize_tuple(K)?;
+        for x in mac {
+            tuple.serialize_element(x)?;
+        }
+        tuple.end()
+    }
+
+    pub fn deserialize<'de, D, T, const K: usize>(deserializer: D) -> Result<[T; K], D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de> + Copy + Default,
+    {
+        struct MacVisitor<T, const K: usize>(PhantomData<T>);
+
+        impl<'de, T, const K: usize> Visitor<'de> for MacVisitor<T, K>
+        where
+            T: Deserialize<'de> + Copy + Default,
+        {
+            type Value = [T; K];
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "array of {} MAC shares", K)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut mac = [T::default(); K];
+                for (i, x) in mac.iter_mut().enumerate() {
+                    *x = seq
+                        .next_element()?
+                        .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+                }
+                Ok(mac)
+            }
+        }
+
+        deserializer.deserialize_tuple(K, MacVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fields::Mersenne61;
+
+    use super::SpdzShare;
+
+    #[test]
+    fn test_serialization_compatibility() {
+        let share = SpdzShare {
+            value: Mersenne61::from(123),
+            mac: [Mersenne61::from(456)],
+        };
+        let encoded = bincode::serialize(&share).unwrap();
+        let expected = bincode::serialize(&(share.value, share.mac[0])).unwrap();
+        assert_eq!(encoded, expected);
+
+        let share = SpdzShare {
+            value: Mersenne61::from(123),
+            mac: [Mersenne61::from(456), Mersenne61::from(789)],
+        };
+        let encoded = bincode::serialize(&share).unwrap();
+        let decoded: SpdzShare<Mersenne61, 2> = bincode::deserialize(&encoded).unwrap();
+        assert_eq!((decoded.value, decoded.mac), (share.value, share.mac));
     }
 }
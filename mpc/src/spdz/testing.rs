@@ -0,0 +1,58 @@
+use std::{future::Future, pin::Pin};
+
+use crate::{
+    executor::{run_circuit_for_all_parties, MpcExecution},
+    transport::{memory_multiparty_channels, MemoryChannel},
+    MpcField,
+};
+
+use super::{FakeSpdzDealer, SpdzConfig, SpdzEngine, SpdzMessage, SpdzShare};
+
+/// Field for SPDZ tests.
+pub type MockSpdzField = crate::fields::Mersenne127;
+
+/// SPDZ engine with fake dealer, connected to other parties with in-memory channels.
+pub type MockSpdzEngine<T = MockSpdzField, const K: usize = 1> =
+    SpdzEngine<T, FakeSpdzDealer<T, K>, MemoryChannel<SpdzMessage<T>>, K>;
+
+/// Execution context for SPDZ tests.
+pub type MockSpdzExecution<T = MockSpdzField, const K: usize = 1> =
+    MpcExecution<MockSpdzEngine<T, K>>;
+
+/// Create connected engines of all parties with fake dealers.
+pub fn mock_spdz_engines<T: MpcField, const K: usize>(
+    num_parties: usize,
+    config: SpdzConfig,
+) -> Vec<MockSpdzEngine<T, K>> {
+    memory_multiparty_channels(num_parties)
+        .into_iter()
+        .enumerate()
+        .map(|(party_id, transport)| {
+            let dealer = FakeSpdzDealer::new(num_parties, party_id, 123);
+            SpdzEngine::new(dealer, transport, config)
+        })
+        .collect()
+}
+
+/// Run circuit for all parties with mock SPDZ engines using a single MAC key, given inputs of each party.
+/// Returns outputs of all parties, panics if any party fails.
+pub async fn run_mock_spdz<T, F, R>(
+    config: SpdzConfig,
+    inputs: Vec<Vec<T>>,
+    circuit_fn: F,
+) -> Vec<R>
+where
+    T: MpcField,
+    F: Copy
+        + Fn(
+            &'_ MockSpdzExecution<T>,
+            Vec<Vec<SpdzShare<T>>>,
+        ) -> Pin<Box<dyn Future<Output = R> + '_>>,
+{
+    let engines = mock_spdz_engines(inputs.len(), config);
+    run_circuit_for_all_parties(engines, &inputs, circuit_fn)
+        .await
+        .into_iter()
+        .map(|result| result.unwrap().0)
+        .collect()
+}
@@ -0,0 +1,375 @@
+use std::{collections::BTreeSet, fmt};
+
+use crate::{MpcField, MpcShare};
+
+use super::{PrecomputedSpdzData, SpdzDataKind, SpdzShare};
+
+/// Inconsistency found in precomputed data of all parties.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpdzDataError {
+    /// Data of given number of parties was expected.
+    NumberOfParties(usize),
+    /// Data at given position belongs to a different party.
+    PartyId(usize),
+    /// Data of given party belongs to a different session.
+    SessionMismatch(usize),
+    /// Parties have different numbers of values of given kind.
+    CountMismatch(SpdzDataKind),
+    /// Value of given kind at given index has an invalid MAC.
+    InvalidMac(SpdzDataKind, usize),
+    /// Value of given kind at given index doesn't satisfy its relation (e.g. `a * b = c` for triples).
+    InvalidValue(SpdzDataKind, usize),
+}
+
+impl fmt::Display for SpdzDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::NumberOfParties(count) => write!(f, "Expected data of {} parties", count),
+            Self::PartyId(index) => write!(f, "Data at position {} has wrong party ID", index),
+            Self::SessionMismatch(id) => {
+                write!(f, "Data of party {} belongs to a different session", id)
+            }
+            Self::CountMismatch(kind) => {
+                write!(f, "Parties have different numbers of {:?}", kind)
+            }
+            Self::InvalidMac(kind, index) => write!(f, "Invalid MAC of {:?} #{}", kind, index),
+            Self::InvalidValue(kind, index) => write!(f, "Invalid value of {:?} #{}", kind, index),
+        }
+    }
+}
+
+impl std::error::Error for SpdzDataError {}
+
+/// Check that precomputed data of all parties (ordered by party ID) is consistent. Shares of each value
+/// are opened with the MAC key reconstructed from its shares, and values are checked to be valid triples,
+/// pairs, bits, bit masks, and input and output masks with plaintexts known to their owners.
+/// Data derived from seeds must be expanded first.
+pub fn verify_precomputed_data<T: MpcField, const K: usize>(
+    data: &[PrecomputedSpdzData<T, K>],
+) -> Result<(), SpdzDataError> {
+    let num_parties = data.len();
+    if num_parties == 0 {
+        return Err(SpdzDataError::NumberOfParties(0));
+    }
+    for (id, party_data) in data.iter().enumerate() {
+        if party_data.num_parties != num_parties
+            || party_data.input_masks.len() != num_parties
+            || party_data.output_masks.len() != num_parties
+        {
+            return Err(SpdzDataError::NumberOfParties(party_data.num_parties));
+        }
+        if party_data.party_id != id {
+            return Err(SpdzDataError::PartyId(id));
+        }
+        if party_data.session_id != data[0].session_id {
+            return Err(SpdzDataError::SessionMismatch(id));
+        }
+    }
+
+    let mut auth_key = [T::zero(); K];
+    for party_data in data {
+        for (key, key_share) in auth_key.iter_mut().zip(party_data.auth_key) {
+            *key += key_share;
+        }
+    }
+    let verifier = Verifier { data, auth_key };
+
+    verifier.check_each(
+        SpdzDataKind::BeaverTriples,
+        |x| &x.beaver_triples,
+        |_, triple| Some(triple.open(|x| x.0)? * triple.open(|x| x.1)? == triple.open(|x| x.2)?),
+    )?;
+    verifier.check_each(
+        SpdzDataKind::SquarePairs,
+        |x| &x.square_pairs,
+        |_, pair| Some(pair.open(|x| x.0)?.square() == pair.open(|x| x.1)?),
+    )?;
+    verifier.check_each(
+        SpdzDataKind::InversePairs,
+        |x| &x.inverse_pairs,
+        |_, pair| Some(pair.open(|x| x.0)? * pair.open(|x| x.1)? == T::one()),
+    )?;
+    verifier.check_each(
+        SpdzDataKind::RandomBits,
+        |x| &x.random_bits,
+        |_, bit| Some(is_bit(bit.open(|&x| x)?)),
+    )?;
+
+    let widths: BTreeSet<usize> = data
+        .iter()
+        .flat_map(|x| x.bit_masks.keys())
+        .copied()
+        .collect();
+    for width in widths {
+        let kind = SpdzDataKind::BitMasks(width);
+        if data.iter().any(|x| !x.bit_masks.contains_key(&width)) {
+            return Err(SpdzDataError::CountMismatch(kind));
+        }
+        verifier.check_each(
+            kind,
+            |x| &x.bit_masks[&width],
+            |_, bit_mask| {
+                let mask = bit_mask.open(|x| x.0)?;
+                let low_part = bit_mask.open(|x| x.1)?;
+                if width > T::SAFE_BITS || bit_mask.values.iter().any(|x| x.2.len() != width) {
+                    return Some(false);
+                }
+                let mut composed = T::zero();
+                for i in (0..width).rev() {
+                    let bit = bit_mask.open(|x| x.2[i])?;
+                    if !is_bit(bit) {
+                        return Some(false);
+                    }
+                    composed = composed.double() + bit;
+                }
+                // Mask is a SAFE_BITS-bit integer, so its part above the low bits must be small.
+                let high_part = (mask - low_part) * T::power_of_two_inverse(width);
+                Some(composed == low_part && num_bits(&high_part) <= T::SAFE_BITS - width)
+            },
+        )?;
+    }
+
+    for (owner, owner_data) in data.iter().enumerate() {
+        let kind = SpdzDataKind::InputMasks(owner);
+        let plain = &owner_data.input_masks_plain;
+        if plain.len() != owner_data.input_masks[owner].len() {
+            return Err(SpdzDataError::CountMismatch(kind));
+        }
+        verifier.check_each(
+            kind,
+            |x| &x.input_masks[owner],
+            |index, mask| Some(mask.open(|&x| x)? == plain[index]),
+        )?;
+
+        let kind = SpdzDataKind::OutputMasks(owner);
+        let plain = &owner_data.output_masks_plain;
+        if plain.len() != owner_data.output_masks[owner].len() {
+            return Err(SpdzDataError::CountMismatch(kind));
+        }
+        verifier.check_each(
+            kind,
+            |x| &x.output_masks[owner],
+            |index, mask| Some(mask.open(|&x| x)? == plain[index]),
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns true if value is 0 or 1.
+fn is_bit<T: MpcField>(value: T) -> bool {
+    value == T::zero() || value == T::one()
+}
+
+/// Number of bits of integer representing field element (all fields use little endian representation).
+fn num_bits<T: MpcField>(value: &T) -> usize {
+    let repr = value.to_repr();
+    let bytes = repr.as_ref();
+    match bytes.iter().rposition(|&x| x != 0) {
+        Some(i) => 8 * i + (8 - bytes[i].leading_zeros() as usize),
+        None => 0,
+    }
+}
+
+/// Checker of values in precomputed data of all parties, which knows the reconstructed MAC key.
+struct Verifier<'a, T, const K: usize> {
+    data: &'a [PrecomputedSpdzData<T, K>],
+    auth_key: [T; K],
+}
+
+impl<'a, T: MpcField, const K: usize> Verifier<'a, T, K> {
+    /// Check each value of given kind, which is selected from data of all parties. Check function gets index
+    /// of the value and its opening, and returns None if a MAC is invalid or false if the value is invalid.
+    fn check_each<V: 'a>(
+        &self,
+        kind: SpdzDataKind,
+        select: impl Fn(&'a PrecomputedSpdzData<T, K>) -> &'a Vec<V>,
+        mut check: impl FnMut(usize, &Opening<'a, T, V, K>) -> Option<bool>,
+    ) -> Result<(), SpdzDataError> {
+        let values: Vec<_> = self.data.iter().map(select).collect();
+        if values.iter().any(|x| x.len() != values[0].len()) {
+            return Err(SpdzDataError::CountMismatch(kind));
+        }
+
+        for index in 0..values[0].len() {
+            let opening = Opening {
+                values: values.iter().map(|x| &x[index]).collect(),
+                auth_key: self.auth_key,
+            };
+            match check(index, &opening) {
+                Some(true) => {}
+                Some(false) => return Err(SpdzDataError::InvalidValue(kind, index)),
+                None => return Err(SpdzDataError::InvalidMac(kind, index)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shares of a single value (or a tuple of values) held by all parties.
+struct Opening<'a, T, V, const K: usize> {
+    values: Vec<&'a V>,
+    auth_key: [T; K],
+}
+
+impl<'a, T: MpcField, V, const K: usize> Opening<'a, T, V, K> {
+    /// Open shared value selected from the tuple. Returns None if its MAC is invalid.
+    fn open(&self, share: impl Fn(&V) -> SpdzShare<T, K>) -> Option<T> {
+        let sum = self
+            .values
+            .iter()
+            .fold(SpdzShare::zero(), |acc, &x| acc + share(x));
+        if sum.mac == self.auth_key.map(|key| key * sum.value) {
+            Some(sum.value)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ff::Field;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{fields::Mersenne61, spdz::PrecomputedSpdzData};
+
+    use super::{verify_precomputed_data, SpdzDataError, SpdzDataKind, SpdzShare};
+
+    type Fp = Mersenne61;
+
+    /// MAC keys reconstructed from key shares of both parties.
+    fn auth_key() -> [Fp; 2] {
+        [10.into(), 16.into()]
+    }
+
+    /// Sharing of a plain value, which shifts shared value by it when added to share of party 0.
+    fn plain(value: u64) -> SpdzShare<Fp, 2> {
+        SpdzShare::from_plain(value.into(), auth_key(), 0)
+    }
+
+    /// Data of two parties with a single value of each kind.
+    fn valid_data() -> Vec<PrecomputedSpdzData<Fp, 2>> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let share = |value: Fp| {
+            let other = SpdzShare {
+                value: Fp::random(&mut rng),
+                mac: [Fp::random(&mut rng), Fp::random(&mut rng)],
+            };
+            [SpdzShare::from_plain(value, auth_key(), 0) - other, other]
+        };
+
+        let values = [
+            6.into(),
+            7.into(),
+            42.into(),
+            36.into(),
+            2.into(),
+            Fp::from(2).invert().unwrap(),
+            1.into(),
+            ((5 << 2) + 2).into(),
+            2.into(),
+            0.into(),
+            1.into(),
+            123.into(),
+            45.into(),
+        ];
+        let shares: Vec<_> = values.into_iter().map(share).collect();
+        let auth_keys = [[3.into(), 5.into()], [7.into(), 11.into()]];
+
+        (0..2)
+            .map(|id| {
+                let x = |i: usize| shares[i][id];
+                PrecomputedSpdzData {
+                    num_parties: 2,
+                    party_id: id,
+                    session_id: [1; 16],
+                    auth_key: auth_keys[id],
+                    beaver_triples: vec![(x(0), x(1), x(2))],
+                    square_pairs: vec![(x(0), x(3))],
+                    inverse_pairs: vec![(x(4), x(5))],
+                    random_bits: vec![x(6)],
+                    bit_masks: BTreeMap::from([(2, vec![(x(7), x(8), vec![x(9), x(10)])])]),
+                    input_masks: vec![vec![], vec![x(11)]],
+                    input_masks_plain: if id == 1 { vec![123.into()] } else { vec![] },
+                    output_masks: vec![vec![x(12)], vec![]],
+                    output_masks_plain: if id == 0 { vec![45.into()] } else { vec![] },
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_verify_valid_data() {
+        assert_eq!(verify_precomputed_data(&valid_data()), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_invalid_data() {
+        let mut data = valid_data();
+        data[1].beaver_triples[0].2.value += Fp::one();
+        assert_eq!(
+            verify_precomputed_data(&data),
+            Err(SpdzDataError::InvalidMac(SpdzDataKind::BeaverTriples, 0))
+        );
+
+        let mut data = valid_data();
+        data[0].beaver_triples[0].2 += plain(1);
+        assert_eq!(
+            verify_precomputed_data(&data),
+            Err(SpdzDataError::InvalidValue(SpdzDataKind::BeaverTriples, 0))
+        );
+
+        let mut data = valid_data();
+        data[0].random_bits[0] += plain(1);
+        assert_eq!(
+            verify_precomputed_data(&data),
+            Err(SpdzDataError::InvalidValue(SpdzDataKind::RandomBits, 0))
+        );
+
+        let mut data = valid_data();
+        data[0].bit_masks.get_mut(&2).unwrap()[0].0 += plain(1);
+        assert_eq!(
+            verify_precomputed_data(&data),
+            Err(SpdzDataError::InvalidValue(SpdzDataKind::BitMasks(2), 0))
+        );
+
+        let mut data = valid_data();
+        data[1].input_masks_plain[0] += Fp::one();
+        assert_eq!(
+            verify_precomputed_data(&data),
+            Err(SpdzDataError::InvalidValue(SpdzDataKind::InputMasks(1), 0))
+        );
+
+        let mut data = valid_data();
+        data[0].output_masks_plain[0] += Fp::one();
+        assert_eq!(
+            verify_precomputed_data(&data),
+            Err(SpdzDataError::InvalidValue(SpdzDataKind::OutputMasks(0), 0))
+        );
+    }
+
+    #[test]
+    fn test_verify_inconsistent_parties() {
+        let mut data = valid_data();
+        data[1].random_bits.clear();
+        assert_eq!(
+            verify_precomputed_data(&data),
+            Err(SpdzDataError::CountMismatch(SpdzDataKind::RandomBits))
+        );
+
+        let mut data = valid_data();
+        data[1].session_id = [2; 16];
+        assert_eq!(
+            verify_precomputed_data(&data),
+            Err(SpdzDataError::SessionMismatch(1))
+        );
+
+        let data = valid_data();
+        assert_eq!(
+            verify_precomputed_data(&data[..1]),
+            Err(SpdzDataError::NumberOfParties(2))
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{assemble_split_bit_mask, fields::Ring64, MpcContext, MpcDealer};
+
+use super::Spdz2kShare;
+
+/// Dealer of precomputed parameters for SPDZ2k protocol.
+/// All sharings are authenticated modulo 2^128 with a 64-bit global key, and Beaver triples satisfy ab = c
+/// modulo 2^128. Implementations must override `MpcDealer::next_split_bit_mask`, e.g. with
+/// `assemble_split_bit_mask`, since its default implementation needs inverses of powers of two.
+pub trait Spdz2kDealer: MpcDealer<Field = Ring64, Share = Spdz2kShare> {
+    /// Raw sharing of the authentication key. Each share is smaller than 2^64.
+    fn authentication_key_share(&self) -> u128;
+
+    /// Random sharing of a random value with plaintext known to this party.
+    fn next_input_mask_own(&mut self) -> (Spdz2kShare, Ring64);
+
+    /// Random sharing of a random value with plaintext known to a specified party.
+    fn next_input_mask_for(&mut self, id: usize) -> Spdz2kShare;
+
+    /// Random sharing of a random value with plaintext known to this party, used to open values privately to it.
+    fn next_output_mask_own(&mut self) -> (Spdz2kShare, Ring64);
+
+    /// Random sharing of a random value with plaintext known to a specified party,
+    /// used to open values privately to that party.
+    fn next_output_mask_for(&mut self, id: usize) -> Spdz2kShare;
+
+    /// Random sharing of a secret random element of Z_2^128, which hides upper bits of values during MAC checks.
+    fn next_check_mask(&mut self) -> Spdz2kShare;
+}
+
+/// Insecure dealer for SPDZ2k protocol that can be used for tests.
+pub struct FakeSpdz2kDealer {
+    num_parties: usize,
+    party_id: usize,
+    key_share: u128,
+    beaver_triple_gen: FakeShareGenerator,
+    bits_gen: FakeShareGenerator,
+    input_masks_gen: Vec<FakeShareGenerator>,
+    check_masks_gen: FakeShareGenerator,
+    output_masks_gen: Vec<FakeShareGenerator>,
+}
+
+impl FakeSpdz2kDealer {
+    /// Create new instance.
+    pub fn new(num_parties: usize, party_id: usize, seed: u8) -> Self {
+        let mut rng = SmallRng::from_seed([seed; 32]);
+        let key_shares: Vec<u128> = (0..num_parties).map(|_| rng.gen::<u64>().into()).collect();
+        let key = key_shares.iter().fold(0u128, |acc, x| acc.wrapping_add(*x));
+        let mut new_gen = || FakeShareGenerator {
+            num_parties,
+            party_id,
+            key,
+            rng: SmallRng::from_seed(rng.gen()),
+        };
+        Self {
+            num_parties,
+            party_id,
+            key_share: key_shares[party_id],
+            beaver_triple_gen: new_gen(),
+            bits_gen: new_gen(),
+            input_masks_gen: (0..num_parties).map(|_| new_gen()).collect(),
+            check_masks_gen: new_gen(),
+            output_masks_gen: (0..num_parties).map(|_| new_gen()).collect(),
+        }
+    }
+}
+
+impl MpcContext for FakeSpdz2kDealer {
+    type Field = Ring64;
+    type Share = Spdz2kShare;
+
+    fn num_parties(&self) -> usize {
+        self.num_parties
+    }
+
+    fn party_id(&self) -> usize {
+        self.party_id
+    }
+}
+
+impl MpcDealer for FakeSpdz2kDealer {
+    fn share_plain(&self, x: Ring64) -> Spdz2kShare {
+        Spdz2kShare::from_plain(x, self.key_share, self.party_id)
+    }
+
+    fn next_beaver_triple(&mut self) -> (Spdz2kShare, Spdz2kShare, Spdz2kShare) {
+        let (a_share, a_plain) = self.beaver_triple_gen.gen_random_authenticated_share();
+        let (b_share, b_plain) = self.beaver_triple_gen.gen_random_authenticated_share();
+        let c_share = self
+            .beaver_triple_gen
+            .gen_authenticated_share(a_plain.wrapping_mul(b_plain));
+        (a_share, b_share, c_share)
+    }
+
+    fn next_uint(&mut self, bits: usize) -> Spdz2kShare {
+        let value = match bits {
+            0 => 0,
+            _ => self.bits_gen.rng.gen::<u64>() >> (64 - bits),
+        };
+        self.bits_gen.gen_authenticated_share(value.into())
+    }
+
+    fn next_split_bit_mask(&mut self, k: usize) -> (Spdz2kShare, Spdz2kShare, Vec<Spdz2kShare>) {
+        assemble_split_bit_mask(self, k)
+    }
+
+    fn is_exhausted(&self) -> bool {
+        false
+    }
+}
+
+impl Spdz2kDealer for FakeSpdz2kDealer {
+    fn authentication_key_share(&self) -> u128 {
+        self.key_share
+    }
+
+    fn next_input_mask_own(&mut self) -> (Spdz2kShare, Ring64) {
+        let (share, plain) = self.input_masks_gen[self.party_id].gen_random_authenticated_share();
+        (share, Ring64::from(plain as u64))
+    }
+
+    fn next_input_mask_for(&mut self, id: usize) -> Spdz2kShare {
+        self.input_masks_gen[id].gen_random_authenticated_share().0
+    }
+
+    fn next_output_mask_own(&mut self) -> (Spdz2kShare, Ring64) {
+        let (share, plain) = self.output_masks_gen[self.party_id].gen_random_authenticated_share();
+        (share, Ring64::from(plain as u64))
+    }
+
+    fn next_output_mask_for(&mut self, id: usize) -> Spdz2kShare {
+        self.output_masks_gen[id].gen_random_authenticated_share().0
+    }
+
+    fn next_check_mask(&mut self) -> Spdz2kShare {
+        self.check_masks_gen.gen_random_authenticated_share().0
+    }
+}
+
+/// Insecure generator of SPDZ2k-shared values.
+struct FakeShareGenerator {
+    num_parties: usize,
+    party_id: usize,
+    key: u128,
+    rng: SmallRng,
+}
+
+impl FakeShareGenerator {
+    /// Generate local unauthenticated share of specified value.
+    fn gen_raw_share(&mut self, value: u128) -> u128 {
+        let shares: Vec<u128> = (1..self.num_parties).map(|_| self.rng.gen()).collect();
+        if self.party_id == 0 {
+            shares.iter().fold(value, |acc, x| acc.wrapping_sub(*x))
+        } else {
+            shares[self.party_id - 1]
+        }
+    }
+
+    /// Generate local authenticated share of specified value.
+    fn gen_authenticated_share(&mut self, value: u128) -> Spdz2kShare {
+        Spdz2kShare {
+            value: self.gen_raw_share(value),
+            mac: self.gen_raw_share(value.wrapping_mul(self.key)),
+        }
+    }
+
+    /// Generate random value and its local authenticated share.
+    fn gen_random_authenticated_share(&mut self) -> (Spdz2kShare, u128) {
+        let value = self.rng.gen();
+        (self.gen_authenticated_share(value), value)
+    }
+}
@@ -0,0 +1,821 @@
+use std::{mem, ops::Range};
+
+use async_trait::async_trait;
+use ff::{Field, PrimeField};
+use futures::{Sink, Stream};
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fields::Ring64,
+    spdz::{
+        consistency::{
+            CommitmentSalt, ConsistencyMessage, ConsistentBroadcast, SpdzDigest, SpdzDigestOutput,
+        },
+        DigestType, SpdzConfig, SpdzError,
+    },
+    transport::{ChannelError, MultipartyTransport},
+    MpcContext, MpcEngine, MpcField, MpcShare,
+};
+
+use super::{Spdz2kDealer, Spdz2kShare};
+
+/// Random number generator used by SPDZ2k implementation.
+type Spdz2kRng = rand::rngs::StdRng;
+
+/// SPDZ2k protocol message.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum Spdz2kMessage {
+    MaskedInputs(Vec<Ring64>),
+    SharesExchange(Vec<Ring64>),
+    ShareSumExchange(Vec<Ring64>),
+    CheckValueExchange(u128),
+    StateHashCheck(SpdzDigestOutput),
+    Commitment(SpdzDigestOutput),
+    Decommitment(Vec<u128>, CommitmentSalt),
+}
+
+impl ConsistencyMessage<u128> for Spdz2kMessage {
+    fn commitment(hash: SpdzDigestOutput) -> Self {
+        Self::Commitment(hash)
+    }
+
+    fn decommitment(values: Vec<u128>, salt: CommitmentSalt) -> Self {
+        Self::Decommitment(values, salt)
+    }
+
+    fn state_hash_check(hash: SpdzDigestOutput) -> Self {
+        Self::StateHashCheck(hash)
+    }
+
+    fn into_commitment(self) -> Option<SpdzDigestOutput> {
+        match self {
+            Self::Commitment(hash) => Some(hash),
+            _ => None,
+        }
+    }
+
+    fn into_decommitment(self) -> Option<(Vec<u128>, CommitmentSalt)> {
+        match self {
+            Self::Decommitment(values, salt) => Some((values, salt)),
+            _ => None,
+        }
+    }
+
+    fn into_state_hash_check(self) -> Option<SpdzDigestOutput> {
+        match self {
+            Self::StateHashCheck(hash) => Some(hash),
+            _ => None,
+        }
+    }
+}
+
+/// SPDZ2k protocol implementation [8] over Z_2^64 with MACs modulo 2^128 and a 64-bit key,
+/// so probability of undetected cheating is roughly 2^-57 for each MAC check.
+/// Partial openings reveal only the lowest 64 bits of shares, upper bits are checked with MACs.
+/// Implements `MpcEngine` with `Ring64` values, so circuits that don't compute inverses can run on it.
+/// Configuration is shared with SPDZ engine, except for identifiable abort, which is not supported.
+pub struct Spdz2kEngine<Dealer, Channel> {
+    dealer: Dealer,
+    transport: MultipartyTransport<Spdz2kMessage, Channel>,
+    opened_values: Vec<(Ring64, Spdz2kShare)>,
+    state_digest: SpdzDigest,
+    rng: Spdz2kRng,
+    config: SpdzConfig,
+    opening_round: usize,
+    rounds_since_check: usize,
+}
+
+impl<Dealer: Spdz2kDealer, Channel> Spdz2kEngine<Dealer, Channel> {
+    /// Create SPDZ2k protocol engine with given configuration.
+    pub fn new(
+        dealer: Dealer,
+        transport: MultipartyTransport<Spdz2kMessage, Channel>,
+        config: SpdzConfig,
+    ) -> Self {
+        config.assert_valid();
+        assert!(
+            !config.identifiable_abort,
+            "SPDZ2k engine doesn't support identifiable abort"
+        );
+        Self {
+            dealer,
+            transport,
+            opened_values: Vec::new(),
+            state_digest: SpdzDigest::new(config.digest),
+            rng: Spdz2kRng::from_entropy(),
+            config,
+            opening_round: 0,
+            rounds_since_check: 0,
+        }
+    }
+}
+
+impl<Dealer: Spdz2kDealer, Channel> MpcContext for Spdz2kEngine<Dealer, Channel> {
+    type Field = Ring64;
+    type Share = Spdz2kShare;
+
+    fn num_parties(&self) -> usize {
+        self.transport.num_parties()
+    }
+
+    fn party_id(&self) -> usize {
+        self.transport.party_id()
+    }
+}
+
+#[async_trait(?Send)]
+impl<Dealer, Channel, E> ConsistentBroadcast for Spdz2kEngine<Dealer, Channel>
+where
+    Dealer: Spdz2kDealer,
+    E: ChannelError,
+    Channel: Stream<Item = Result<Spdz2kMessage, E>> + Sink<Spdz2kMessage> + Unpin,
+{
+    type Value = u128;
+    type Message = Spdz2kMessage;
+
+    fn digest_type(&self) -> DigestType {
+        self.config.digest
+    }
+
+    fn state_digest(&mut self) -> &mut SpdzDigest {
+        &mut self.state_digest
+    }
+
+    fn commitment_salt(&mut self) -> CommitmentSalt {
+        self.rng.gen()
+    }
+
+    async fn broadcast(
+        &mut self,
+        msg: Spdz2kMessage,
+    ) -> Result<Vec<(usize, Spdz2kMessage)>, SpdzError> {
+        Ok(self.transport.exchange_with_all(msg).await?)
+    }
+}
+
+#[async_trait(?Send)]
+impl<Dealer, Channel, E> MpcEngine for Spdz2kEngine<Dealer, Channel>
+where
+    Dealer: Spdz2kDealer,
+    E: ChannelError,
+    Channel: Stream<Item = Result<Spdz2kMessage, E>> + Sink<Spdz2kMessage> + Unpin,
+{
+    type Dealer = Dealer;
+    type Error = SpdzError;
+
+    fn dealer(&mut self) -> &mut Self::Dealer {
+        &mut self.dealer
+    }
+
+    async fn process_inputs(
+        &mut self,
+        inputs: Vec<Ring64>,
+    ) -> Result<Vec<Vec<Spdz2kShare>>, SpdzError> {
+        let (own_shares, own_deltas): (Vec<_>, Vec<_>) = inputs
+            .into_iter()
+            .map(|x| {
+                let (share, plain) = self.dealer.next_input_mask_own();
+                let delta = x - plain;
+                (share + self.dealer.share_plain(delta), delta)
+            })
+            .unzip();
+
+        if self.dealer.is_exhausted() {
+            return Err(SpdzError::DealerExhausted);
+        }
+
+        let received_messages = self
+            .broadcast(Spdz2kMessage::MaskedInputs(own_deltas.clone()))
+            .await?;
+
+        let mut all_shares = vec![Vec::new(); self.num_parties()];
+        let mut all_deltas = vec![Vec::new(); self.num_parties()];
+        all_shares[self.party_id()] = own_shares;
+        all_deltas[self.party_id()] = own_deltas;
+
+        for (other_id, msg) in received_messages {
+            if let Spdz2kMessage::MaskedInputs(deltas) = msg {
+                all_shares[other_id] = deltas
+                    .iter()
+                    .map(|&delta| {
+                        self.dealer.next_input_mask_for(other_id) + self.dealer.share_plain(delta)
+                    })
+                    .collect();
+                all_deltas[other_id] = deltas;
+            } else {
+                return Err(SpdzError::UnexpectedMessage(other_id));
+            }
+        }
+
+        if self.dealer.is_exhausted() {
+            return Err(SpdzError::DealerExhausted);
+        }
+
+        for deltas in all_deltas {
+            self.state_digest.update(deltas.len().to_le_bytes());
+            for x in deltas {
+                self.state_digest.update(x.to_repr());
+            }
+        }
+        self.check_state_hashes().await?;
+        Ok(all_shares)
+    }
+
+    /// Values are opened modulo 2^64 from the lowest 64 bits of shares.
+    async fn process_openings_unchecked(
+        &mut self,
+        requests: Vec<Spdz2kShare>,
+    ) -> Result<Vec<Ring64>, SpdzError> {
+        let shares: Vec<_> = requests
+            .iter()
+            .map(|x| Ring64::from(x.value as u64))
+            .collect();
+        let ranges = self.config.opening_strategy.king_ranges(
+            self.num_parties(),
+            self.opening_round,
+            shares.len(),
+        );
+
+        // Inconsistent sums sent by kings (or shares sent by other parties in all-to-all mode)
+        // lead to different opened values, which are detected by state hash check.
+        let values = match ranges {
+            Some(ranges) => self.open_with_kings(shares, &ranges).await?,
+            None => self.open_all_to_all(shares).await?,
+        };
+        self.opening_round += 1;
+        self.rounds_since_check += 1;
+        self.opened_values
+            .extend(values.iter().copied().zip(requests));
+
+        let check_forced = matches!(self.config.check_every_rounds,
+            Some(rounds) if self.rounds_since_check >= rounds);
+        if check_forced || self.opened_values.len() >= self.config.batch_check_threshold {
+            self.check_integrity().await?;
+        }
+        Ok(values)
+    }
+
+    async fn process_private_openings_unchecked(
+        &mut self,
+        requests: Vec<Spdz2kShare>,
+        private_requests: Vec<(usize, Spdz2kShare)>,
+    ) -> Result<(Vec<Ring64>, Vec<Option<Ring64>>), SpdzError> {
+        // Each share is masked with an output mask of its recipient and opened publicly together with
+        // partial openings, so masked values are covered by the usual MAC check.
+        let party_id = self.party_id();
+        let num_requests = requests.len();
+        let (masked_shares, own_masks): (Vec<_>, Vec<_>) = private_requests
+            .into_iter()
+            .map(|(id, share)| {
+                if id == party_id {
+                    let (mask, plain) = self.dealer.next_output_mask_own();
+                    (share + mask, Some(plain))
+                } else {
+                    (share + self.dealer.next_output_mask_for(id), None)
+                }
+            })
+            .unzip();
+
+        if self.dealer.is_exhausted() {
+            return Err(SpdzError::DealerExhausted);
+        }
+
+        let mut values = self
+            .process_openings_unchecked([requests, masked_shares].concat())
+            .await?;
+        let masked_values = values.split_off(num_requests);
+        Ok((
+            values,
+            masked_values
+                .into_iter()
+                .zip(own_masks)
+                .map(|(value, mask)| mask.map(|mask| value - mask))
+                .collect(),
+        ))
+    }
+
+    /// All batches and state hashes are checked even after a failed MAC check, so that all parties finish it together.
+    async fn check_integrity(&mut self) -> Result<(), SpdzError> {
+        self.rounds_since_check = 0;
+        let opened_values = mem::take(&mut self.opened_values);
+
+        let mut mac_check_failed = false;
+        for chunk in opened_values.chunks(self.config.max_batch_check_size) {
+            mac_check_failed |= !self.check_macs(chunk).await?;
+        }
+
+        // Check consistency of all broadcasts since last check.
+        let result = self.check_state_hashes().await;
+        match mac_check_failed {
+            true => result.and(Err(SpdzError::MacCheckFailed)),
+            false => result,
+        }
+    }
+}
+
+impl<Dealer, Channel, E> Spdz2kEngine<Dealer, Channel>
+where
+    Dealer: Spdz2kDealer,
+    E: ChannelError,
+    Channel: Stream<Item = Result<Spdz2kMessage, E>> + Sink<Spdz2kMessage> + Unpin,
+{
+    /// Multiply pairs of shares with Beaver triples in a single round.
+    pub async fn mul(
+        &mut self,
+        pairs: Vec<(Spdz2kShare, Spdz2kShare)>,
+    ) -> Result<Vec<Spdz2kShare>, SpdzError> {
+        let triples: Vec<_> = pairs
+            .iter()
+            .map(|_| self.dealer.next_beaver_triple())
+            .collect();
+        if self.dealer.is_exhausted() {
+            return Err(SpdzError::DealerExhausted);
+        }
+
+        let masked = pairs
+            .iter()
+            .zip(&triples)
+            .flat_map(|(&(x, y), &(a, b, _))| [x - a, y - b])
+            .collect();
+        let opened = self.process_openings_unchecked(masked).await?;
+
+        Ok(opened
+            .chunks(2)
+            .zip(triples)
+            .map(|(eps_delta, (a, b, c))| {
+                let (eps, delta) = (eps_delta[0], eps_delta[1]);
+                c + b * eps + a * delta + self.dealer.share_plain(eps * delta)
+            })
+            .collect())
+    }
+
+    /// Test if values interpreted as 64-bit signed integers are less than zero.
+    /// Result is exact and leaks nothing, since values are masked with uniformly random 64-bit integers.
+    pub async fn less_than_zero(
+        &mut self,
+        values: Vec<Spdz2kShare>,
+    ) -> Result<Vec<Spdz2kShare>, SpdzError> {
+        let mask_bits: Vec<Vec<_>> = values
+            .iter()
+            .map(|_| (0..64).map(|_| self.dealer.next_uint(1)).collect())
+            .collect();
+        if self.dealer.is_exhausted() {
+            return Err(SpdzError::DealerExhausted);
+        }
+
+        let masked = values
+            .iter()
+            .zip(&mask_bits)
+            .map(|(&x, bits)| {
+                x + bits
+                    .iter()
+                    .rev()
+                    .fold(Spdz2kShare::zero(), |acc, &bit| acc.double() + bit)
+            })
+            .collect();
+        let masked: Vec<_> = self
+            .process_openings_unchecked(masked)
+            .await?
+            .iter()
+            .map(|c| c.truncated())
+            .collect();
+
+        // Borrow of subtraction of the lowest 63 bits of mask from the opened value is [c' < r'].
+        // Each bit is mapped into a pair ([c_i < r_i], [c_i = r_i]) and pairs are folded in binary-tree fashion.
+        let one = self.dealer.share_plain(Ring64::one());
+        let mut segments: Vec<Vec<_>> = masked
+            .iter()
+            .zip(&mask_bits)
+            .map(|(&c, bits)| {
+                bits[..63]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &bit)| {
+                        if (c >> i) & 1 == 0 {
+                            (bit, one - bit)
+                        } else {
+                            (Spdz2kShare::zero(), bit)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        while segments[..].iter().any(|x| x.len() > 1) {
+            // Less significant segment (lt_L, eq_L) and more significant (lt_H, eq_H) give
+            // lt = lt_H + eq_H * lt_L and eq = eq_H * eq_L.
+            let products = self
+                .mul(
+                    segments
+                        .iter()
+                        .flat_map(|x| x.chunks_exact(2))
+                        .flat_map(|pair| [(pair[1].1, pair[0].0), (pair[1].1, pair[0].1)])
+                        .collect(),
+                )
+                .await?;
+            let mut products = products.chunks(2);
+            for segment in &mut segments {
+                *segment = segment
+                    .chunks(2)
+                    .map(|pair| match *pair {
+                        [_, (high_lt, _)] => {
+                            let product = products.next().unwrap();
+                            (high_lt + product[0], product[1])
+                        }
+                        [single] => single,
+                        _ => unreachable!(),
+                    })
+                    .collect();
+            }
+        }
+
+        // The highest bit of value is c_63 xor r_63 xor borrow.
+        let borrows = self
+            .mul(
+                segments
+                    .iter()
+                    .zip(&mask_bits)
+                    .map(|(segment, bits)| (segment[0].0, bits[63]))
+                    .collect(),
+            )
+            .await?;
+        Ok(segments
+            .iter()
+            .zip(&mask_bits)
+            .zip(borrows)
+            .zip(masked)
+            .map(|(((segment, bits), product), c)| {
+                let mask_xor_borrow = segment[0].0 + bits[63] - product.double();
+                if c >> 63 == 0 {
+                    mask_xor_borrow
+                } else {
+                    one - mask_xor_borrow
+                }
+            })
+            .collect())
+    }
+
+    /// Test if lhs < rhs for pairs of 64-bit signed integers, whose difference doesn't overflow.
+    pub async fn less(
+        &mut self,
+        pairs: Vec<(Spdz2kShare, Spdz2kShare)>,
+    ) -> Result<Vec<Spdz2kShare>, SpdzError> {
+        self.less_than_zero(pairs.into_iter().map(|(x, y)| x - y).collect())
+            .await
+    }
+
+    /// Open values by sending shares to kings, which sum them and broadcast results.
+    /// King of party ID `i` is responsible for values in `ranges[i]`.
+    async fn open_with_kings(
+        &mut self,
+        mut values: Vec<Ring64>,
+        ranges: &[Range<usize>],
+    ) -> Result<Vec<Ring64>, SpdzError> {
+        let party_id = self.party_id();
+        let own_range = ranges[party_id].clone();
+        let is_king: Vec<_> = ranges.iter().map(|range| !range.is_empty()).collect();
+
+        let messages = ranges
+            .iter()
+            .enumerate()
+            .map(|(id, range)| {
+                (id != party_id && !range.is_empty())
+                    .then(|| Spdz2kMessage::SharesExchange(values[range.clone()].to_vec()))
+            })
+            .collect();
+        let receive_shares = vec![!own_range.is_empty(); ranges.len()];
+        for (other_id, msg) in self
+            .transport
+            .exchange_with_some(messages, &receive_shares)
+            .await?
+        {
+            if let Spdz2kMessage::SharesExchange(parts) = msg {
+                if parts.len() != own_range.len() {
+                    return Err(SpdzError::IncorrectNumberOfValues(other_id));
+                }
+                for (value, part) in values[own_range.clone()].iter_mut().zip(parts) {
+                    *value += part;
+                }
+            } else {
+                return Err(SpdzError::UnexpectedMessage(other_id));
+            }
+        }
+
+        let own_sums = (!own_range.is_empty())
+            .then(|| Spdz2kMessage::ShareSumExchange(values[own_range].to_vec()));
+        for (other_id, msg) in self
+            .transport
+            .exchange_with_some(vec![own_sums; ranges.len()], &is_king)
+            .await?
+        {
+            if let Spdz2kMessage::ShareSumExchange(sums) = msg {
+                let range = ranges[other_id].clone();
+                if sums.len() != range.len() {
+                    return Err(SpdzError::IncorrectNumberOfValues(other_id));
+                }
+                values[range].copy_from_slice(&sums);
+            } else {
+                return Err(SpdzError::UnexpectedMessage(other_id));
+            }
+        }
+        Ok(values)
+    }
+
+    /// Open values by broadcasting shares to all parties.
+    async fn open_all_to_all(&mut self, mut values: Vec<Ring64>) -> Result<Vec<Ring64>, SpdzError> {
+        let values_count = values.len();
+        for (other_id, msg) in self
+            .broadcast(Spdz2kMessage::SharesExchange(values.clone()))
+            .await?
+        {
+            if let Spdz2kMessage::SharesExchange(parts) = msg {
+                if parts.len() != values_count {
+                    return Err(SpdzError::IncorrectNumberOfValues(other_id));
+                }
+                for (value, part) in values.iter_mut().zip(parts) {
+                    *value += part;
+                }
+            } else {
+                return Err(SpdzError::UnexpectedMessage(other_id));
+            }
+        }
+        Ok(values)
+    }
+
+    /// Check MACs of a batch of opened values. Returns false if check failed.
+    async fn check_macs(
+        &mut self,
+        opened_values: &[(Ring64, Spdz2kShare)],
+    ) -> Result<bool, SpdzError> {
+        let own_seed = self.rng.gen();
+        let seeds = self.exchange_with_commitment(vec![own_seed]).await?;
+        let seed = seeds.iter().fold(0u128, |acc, x| acc.wrapping_add(x[0]));
+
+        // Combine opened values with random 64-bit coefficients. Upper bits of the combination are not
+        // opened yet, so they are masked with a random multiple of 2^64 before they are revealed.
+        let (plain_value, check_share) = opened_values.iter().enumerate().fold(
+            (Ring64::zero(), Spdz2kShare::zero()),
+            |(plain_acc, share_acc), (i, &(plain, share))| {
+                let coeff = check_coefficient(self.config.digest, seed, i);
+                (plain_acc + plain * coeff, share_acc + share * coeff)
+            },
+        );
+        let mask = self.dealer.next_check_mask();
+        if self.dealer.is_exhausted() {
+            return Err(SpdzError::DealerExhausted);
+        }
+        let check_share = Spdz2kShare {
+            value: check_share.value.wrapping_add(mask.value << 64),
+            mac: check_share.mac.wrapping_add(mask.mac << 64),
+        };
+
+        let mut check_value = check_share.value;
+        for (other_id, msg) in self
+            .broadcast(Spdz2kMessage::CheckValueExchange(check_share.value))
+            .await?
+        {
+            if let Spdz2kMessage::CheckValueExchange(part) = msg {
+                check_value = check_value.wrapping_add(part);
+            } else {
+                return Err(SpdzError::UnexpectedMessage(other_id));
+            }
+        }
+        // Ensure broadcasted values were consistent by including their combination in state hash.
+        self.state_digest.update(plain_value.to_repr());
+        self.state_digest.update(check_value.to_le_bytes());
+
+        let mac_difference = check_share.mac.wrapping_sub(
+            self.dealer
+                .authentication_key_share()
+                .wrapping_mul(check_value),
+        );
+        let differences = self.exchange_with_commitment(vec![mac_difference]).await?;
+        let mac_sum = differences
+            .iter()
+            .fold(0u128, |acc, x| acc.wrapping_add(x[0]));
+        Ok(check_value as u64 == plain_value.truncated() && mac_sum == 0)
+    }
+}
+
+/// Random coefficient of i-th opened value in MAC check, derived from common seed.
+fn check_coefficient(digest_type: DigestType, seed: u128, i: usize) -> Ring64 {
+    let mut digest = SpdzDigest::new(digest_type);
+    digest.update(seed.to_le_bytes());
+    digest.update((i as u64).to_le_bytes());
+    Ring64::from(u64::from_le_bytes(
+        digest.finalize_reset()[..8].try_into().unwrap(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::future;
+
+    use crate::{
+        circuits::{IntShare, WrappedShare},
+        executor::{run_circuit_for_all_parties, MpcExecutionError},
+        fields::Ring64,
+        join_circuits,
+        spdz::{OpeningStrategy, SpdzConfig, SpdzError},
+        spdz2k::{testing::run_mock_spdz2k, FakeSpdz2kDealer, Spdz2kShare},
+        transport::{
+            inject_faults, memory_multiparty_channels, no_fault, Fault, FaultyChannel,
+            MemoryChannel,
+        },
+        MpcContext, MpcEngine, MpcField,
+    };
+
+    use super::{Spdz2kEngine, Spdz2kMessage};
+
+    type MockSpdz2kEngine =
+        Spdz2kEngine<FakeSpdz2kDealer, FaultyChannel<Spdz2kMessage, MemoryChannel<Spdz2kMessage>>>;
+
+    /// Create connected engines of all parties with fake dealers,
+    /// where party `adversary_id` tampers with messages it sends according to given faults.
+    fn mock_spdz2k_engines(
+        num_parties: usize,
+        adversary_id: usize,
+        mut make_fault: impl FnMut(usize) -> Fault<Spdz2kMessage>,
+    ) -> Vec<MockSpdz2kEngine> {
+        memory_multiparty_channels(num_parties)
+            .into_iter()
+            .enumerate()
+            .map(|(party_id, transport)| {
+                let transport = transport.with_timeout(Some(Duration::from_secs(1)));
+                let transport = if party_id == adversary_id {
+                    inject_faults(transport, &mut make_fault)
+                } else {
+                    inject_faults(transport, |_| no_fault())
+                };
+                let dealer = FakeSpdz2kDealer::new(num_parties, party_id, 123);
+                Spdz2kEngine::new(dealer, transport, SpdzConfig::default())
+            })
+            .collect()
+    }
+
+    /// Provide inputs of each party, open the product of the first inputs of all parties, and signed comparisons
+    /// of the first input of party 0 with all inputs of party 1. Shares of party `corrupt_id` are shifted by 2^64.
+    async fn run_spdz2k(
+        engines: Vec<MockSpdz2kEngine>,
+        inputs: Vec<Vec<i64>>,
+        corrupt_id: Option<usize>,
+    ) -> Vec<Result<(u64, Vec<u64>), SpdzError>> {
+        future::join_all(engines.into_iter().map(|mut engine| {
+            let own_inputs = inputs[engine.party_id()]
+                .iter()
+                .map(|&x| Ring64::from(x as u64))
+                .collect();
+            async move {
+                let shares = engine.process_inputs(own_inputs).await?;
+                let mut product = shares[0][0];
+                for party_shares in &shares[1..] {
+                    product = engine.mul(vec![(product, party_shares[0])]).await?[0];
+                }
+                if corrupt_id == Some(engine.party_id()) {
+                    product.value = product.value.wrapping_add(1 << 64);
+                }
+                let pairs = shares[1].iter().map(|&x| (shares[0][0], x)).collect();
+                let less = engine.less(pairs).await?;
+                let opened: Vec<_> = engine
+                    .process_openings_unchecked([vec![product], less].concat())
+                    .await?
+                    .iter()
+                    .map(|x| x.truncated())
+                    .collect();
+                engine.check_integrity().await?;
+                Ok((opened[0], opened[1..].to_vec()))
+            }
+        }))
+        .await
+    }
+
+    #[test]
+    fn test_spdz2k_share_arithmetic() {
+        let x = Spdz2kShare::from_plain(Ring64::from(5), 7, 0);
+        let y = Spdz2kShare::from_plain(Ring64::from(u64::MAX), 7, 0);
+        assert_eq!((x + y).value as u64, 4);
+        assert_eq!((x - y * Ring64::from(2)).value as u64, 7);
+        assert_eq!((x + y).mac, 7 * (5 + u64::MAX as u128));
+        assert_eq!((-x).mac.wrapping_add(x.mac), 0);
+        assert_eq!(Spdz2kShare::from_plain(Ring64::from(5), 7, 1).value, 0);
+    }
+
+    #[tokio::test]
+    async fn test_spdz2k() {
+        let inputs = vec![
+            vec![1 << 40, 0],
+            vec![(1 << 30) + 1, -5, i64::MIN / 2, (1 << 62) - 1, 1 << 40],
+            vec![-3],
+        ];
+        let results = run_spdz2k(mock_spdz2k_engines(3, 0, |_| no_fault()), inputs, None).await;
+        for result in results {
+            let (product, less) = result.unwrap();
+            // Product wraps around modulo 2^64, comparisons are exact for all 64-bit values.
+            assert_eq!(product, (-3i64 << 40) as u64);
+            assert_eq!(less, vec![0, 0, 0, 1, 0]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spdz2k_detects_modified_shares() {
+        let engines = mock_spdz2k_engines(3, 2, |_| {
+            Box::new(|msg| match msg {
+                Spdz2kMessage::SharesExchange(mut shares) => {
+                    shares[0] += Ring64::from(1);
+                    vec![Spdz2kMessage::SharesExchange(shares)]
+                }
+                msg => vec![msg],
+            })
+        });
+        let results = run_spdz2k(engines, vec![vec![2], vec![3], vec![4]], None).await;
+        assert_eq!(results[0], Err(SpdzError::MacCheckFailed));
+        assert_eq!(results[1], Err(SpdzError::MacCheckFailed));
+    }
+
+    #[tokio::test]
+    async fn test_spdz2k_detects_modified_upper_bits() {
+        // Modified upper bits of a share do not change the opened value, but are caught by MAC check.
+        let engines = mock_spdz2k_engines(3, 0, |_| no_fault());
+        let results = run_spdz2k(engines, vec![vec![2], vec![3], vec![4]], Some(1)).await;
+        for result in results {
+            assert_eq!(result, Err(SpdzError::MacCheckFailed));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spdz2k_circuits() {
+        // Integer circuits need no inverses, so they run on SPDZ2k with every opening strategy.
+        for opening_strategy in [
+            OpeningStrategy::SingleKing,
+            OpeningStrategy::RotatingKing,
+            OpeningStrategy::PartitionedKings,
+            OpeningStrategy::AllToAll,
+        ] {
+            let config = SpdzConfig {
+                opening_strategy,
+                check_every_rounds: Some(3),
+                max_batch_check_size: 5,
+                ..SpdzConfig::default()
+            };
+            let inputs = vec![vec![Ring64::from(1000)], vec![-Ring64::from(77)], vec![]];
+            let outputs = run_mock_spdz2k(config, inputs, |ctx, inputs| {
+                Box::pin(async move {
+                    let x = IntShare::<_, 16>::wrap(inputs[0][0]);
+                    let y = IntShare::<_, 16>::wrap(inputs[1][0]);
+                    let (product, quotient, remainder, less, equal) = join_circuits!(
+                        x.mul(ctx, y),
+                        y.div_power_of_two(ctx, 3),
+                        y.mod_power_of_two(ctx, 3),
+                        y.less(ctx, x),
+                        x.equal(ctx, y)
+                    );
+                    ctx.ensure_integrity();
+                    let private = ctx.open_to(2, product.raw()).await;
+                    (
+                        quotient.open_unchecked(ctx).await,
+                        remainder.open_unchecked(ctx).await,
+                        less.open_unchecked(ctx).await,
+                        equal.open_unchecked(ctx).await,
+                        private.map(|x| x.truncated() as i64),
+                    )
+                })
+            })
+            .await;
+            for (party_id, output) in outputs.into_iter().enumerate() {
+                let private = (party_id == 2).then_some(-77000);
+                assert_eq!(output, (-10, 3, true, false, private));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spdz2k_circuits_detect_modified_shares() {
+        let engines = mock_spdz2k_engines(3, 1, |_| {
+            Box::new(|msg| match msg {
+                Spdz2kMessage::SharesExchange(mut shares) => {
+                    shares[0] += Ring64::from(1);
+                    vec![Spdz2kMessage::SharesExchange(shares)]
+                }
+                msg => vec![msg],
+            })
+        });
+        let inputs = vec![vec![Ring64::from(5)], vec![Ring64::from(7)], vec![]];
+        let results = run_circuit_for_all_parties(engines, &inputs, |ctx, inputs| {
+            Box::pin(async move {
+                let x = IntShare::<_, 16>::wrap(inputs[0][0]);
+                let y = IntShare::<_, 16>::wrap(inputs[1][0]);
+                x.less(ctx, y).await.open_unchecked(ctx).await
+            })
+        })
+        .await;
+        for result in results {
+            assert!(matches!(
+                result,
+                Err(MpcExecutionError::Engine(SpdzError::MacCheckFailed))
+            ));
+        }
+    }
+}
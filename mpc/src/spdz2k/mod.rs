@@ -0,0 +1,11 @@
+mod dealer;
+pub use dealer::{FakeSpdz2kDealer, Spdz2kDealer};
+
+mod engine;
+pub use engine::{Spdz2kEngine, Spdz2kMessage};
+
+mod share;
+pub use share::Spdz2kShare;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
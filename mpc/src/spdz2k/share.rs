@@ -0,0 +1,92 @@
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{fields::Ring64, MpcField, MpcShare};
+
+/// Private share of an element of Z_2^64 in SPDZ2k protocol.
+/// Value and MAC are stored modulo 2^128, only the lowest 64 bits of the value are meaningful.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spdz2kShare {
+    pub value: u128,
+    pub mac: u128,
+}
+
+impl Spdz2kShare {
+    /// Make SPDZ2k share for given plain value, authorization key share and party ID.
+    pub fn from_plain(value: Ring64, auth_key: u128, party_id: usize) -> Self {
+        let value = u128::from(value.truncated());
+        Self {
+            value: if party_id == 0 { value } else { 0 },
+            mac: auth_key.wrapping_mul(value),
+        }
+    }
+}
+
+impl MpcShare for Spdz2kShare {
+    type Field = Ring64;
+
+    fn zero() -> Self {
+        Self::default()
+    }
+
+    fn double(&self) -> Self {
+        *self + *self
+    }
+}
+
+impl Add for Spdz2kShare {
+    type Output = Spdz2kShare;
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl Sub for Spdz2kShare {
+    type Output = Spdz2kShare;
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self -= rhs;
+        self
+    }
+}
+
+impl Neg for Spdz2kShare {
+    type Output = Spdz2kShare;
+    fn neg(self) -> Self::Output {
+        Spdz2kShare {
+            value: self.value.wrapping_neg(),
+            mac: self.mac.wrapping_neg(),
+        }
+    }
+}
+
+impl Mul<Ring64> for Spdz2kShare {
+    type Output = Spdz2kShare;
+    fn mul(mut self, rhs: Ring64) -> Self::Output {
+        self *= rhs;
+        self
+    }
+}
+
+impl AddAssign for Spdz2kShare {
+    fn add_assign(&mut self, rhs: Self) {
+        self.value = self.value.wrapping_add(rhs.value);
+        self.mac = self.mac.wrapping_add(rhs.mac);
+    }
+}
+
+impl SubAssign for Spdz2kShare {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.value = self.value.wrapping_sub(rhs.value);
+        self.mac = self.mac.wrapping_sub(rhs.mac);
+    }
+}
+
+impl MulAssign<Ring64> for Spdz2kShare {
+    fn mul_assign(&mut self, rhs: Ring64) {
+        let rhs = u128::from(rhs.truncated());
+        self.value = self.value.wrapping_mul(rhs);
+        self.mac = self.mac.wrapping_mul(rhs);
+    }
+}
@@ -0,0 +1,47 @@
+use std::{future::Future, pin::Pin};
+
+use crate::{
+    executor::{run_circuit_for_all_parties, MpcExecution},
+    fields::Ring64,
+    spdz::SpdzConfig,
+    transport::{memory_multiparty_channels, MemoryChannel},
+};
+
+use super::{FakeSpdz2kDealer, Spdz2kEngine, Spdz2kMessage, Spdz2kShare};
+
+/// SPDZ2k engine with fake dealer, connected to other parties with in-memory channels.
+pub type MockSpdz2kEngine = Spdz2kEngine<FakeSpdz2kDealer, MemoryChannel<Spdz2kMessage>>;
+
+/// Execution context for SPDZ2k tests.
+pub type MockSpdz2kExecution = MpcExecution<MockSpdz2kEngine>;
+
+/// Create connected engines of all parties with fake dealers.
+pub fn mock_spdz2k_engines(num_parties: usize, config: SpdzConfig) -> Vec<MockSpdz2kEngine> {
+    memory_multiparty_channels(num_parties)
+        .into_iter()
+        .enumerate()
+        .map(|(party_id, transport)| {
+            let dealer = FakeSpdz2kDealer::new(num_parties, party_id, 123);
+            Spdz2kEngine::new(dealer, transport, config)
+        })
+        .collect()
+}
+
+/// Run circuit for all parties with mock SPDZ2k engines, given inputs of each party.
+/// Returns outputs of all parties, panics if any party fails.
+pub async fn run_mock_spdz2k<F, R>(
+    config: SpdzConfig,
+    inputs: Vec<Vec<Ring64>>,
+    circuit_fn: F,
+) -> Vec<R>
+where
+    F: Copy
+        + Fn(&'_ MockSpdz2kExecution, Vec<Vec<Spdz2kShare>>) -> Pin<Box<dyn Future<Output = R> + '_>>,
+{
+    let engines = mock_spdz2k_engines(inputs.len(), config);
+    run_circuit_for_all_parties(engines, &inputs, circuit_fn)
+        .await
+        .into_iter()
+        .map(|result| result.unwrap().0)
+        .collect()
+}
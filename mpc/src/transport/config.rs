@@ -3,36 +3,152 @@ use std::{
     io::{self, BufReader},
     net::SocketAddr,
     path::Path,
+    time::Duration,
 };
 
 use rustls_pemfile::Item;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use tokio_rustls::rustls::{Certificate, PrivateKey};
 
+use crate::fields::FieldType;
+
 /// Configuration of networked multi-party transport.
 #[derive(Clone, Debug)]
 pub struct NetworkConfig {
     pub parties: Vec<NetworkPartyConfig>,
+    pub field: Option<FieldType>,
+    /// Trusted dealer service, which streams precomputed data to parties.
+    pub dealer: Option<NetworkPartyConfig>,
+    pub timeouts: NetworkTimeouts,
+}
+
+/// Timeouts of networked transport, None disables a timeout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NetworkTimeouts {
+    /// Maximum time of establishing connections with all peers, including retries of refused connections.
+    pub connection: Option<Duration>,
+    /// Maximum time of a single send or receive operation with a peer.
+    pub operation: Option<Duration>,
+    /// Interval of heartbeats sent over idle connections.
+    pub heartbeat_interval: Option<Duration>,
+    /// Time after which a peer that sends neither messages nor heartbeats is considered dead.
+    pub peer_timeout: Option<Duration>,
+}
+
+impl Default for NetworkTimeouts {
+    fn default() -> Self {
+        Self {
+            connection: Some(Duration::from_secs(300)),
+            operation: Some(Duration::from_secs(300)),
+            heartbeat_interval: Some(Duration::from_secs(5)),
+            peer_timeout: Some(Duration::from_secs(30)),
+        }
+    }
 }
 
 /// Details about party in networked multiparty protocol.
 #[derive(Clone, Debug)]
 pub struct NetworkPartyConfig {
-    pub address: SocketAddr,
-    pub certificate: Certificate,
+    /// Human-readable name used in logs.
+    pub name: Option<String>,
+    /// Host name or IP address with port. Host names are resolved when connecting.
+    pub address: String,
+    /// Local address to listen on, if it differs from the advertised one (e.g. behind NAT or in a container).
+    pub bind: Option<SocketAddr>,
+    pub certificate: PartyCertificate,
+}
+
+/// SHA-256 fingerprint of DER-encoded certificate.
+pub type CertificateFingerprint = [u8; 32];
+
+/// Certificate of a party, either loaded from PEM file or pinned by fingerprint.
+/// Parties pinned by fingerprint can only be authenticated, their full certificate is needed to act as them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PartyCertificate {
+    Full(Certificate),
+    Fingerprint(CertificateFingerprint),
+}
+
+impl PartyCertificate {
+    /// Fingerprint of the certificate.
+    pub fn fingerprint(&self) -> CertificateFingerprint {
+        match self {
+            Self::Full(cert) => certificate_fingerprint(cert),
+            Self::Fingerprint(fingerprint) => *fingerprint,
+        }
+    }
+}
+
+impl NetworkPartyConfig {
+    /// Provide full certificate of party pinned by fingerprint, e.g. own certificate needed for TLS handshakes.
+    pub fn set_certificate(&mut self, certificate: Certificate) -> io::Result<()> {
+        if certificate_fingerprint(&certificate) != self.certificate.fingerprint() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Certificate doesn't match configuration",
+            ));
+        }
+        self.certificate = PartyCertificate::Full(certificate);
+        Ok(())
+    }
+
+    /// Address to listen on for incoming connections.
+    pub fn listen_address(&self) -> String {
+        match self.bind {
+            Some(bind) => bind.to_string(),
+            None => self.address.clone(),
+        }
+    }
 }
 
 /// Raw parsed JSON configuration file.
 #[derive(Clone, Debug, Deserialize)]
 struct RawNetworkConfig {
     parties: Vec<RawNetworkPartyConfig>,
+    #[serde(default)]
+    field: Option<String>,
+    #[serde(default)]
+    dealer: Option<RawNetworkPartyConfig>,
+    #[serde(default)]
+    timeouts: RawNetworkTimeouts,
 }
 
-/// Raw parsed JSON party configuration file.
+/// Raw parsed JSON timeouts in seconds. Missing timeouts have default values, null disables them.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct RawNetworkTimeouts {
+    connection: Option<f64>,
+    operation: Option<f64>,
+    heartbeat_interval: Option<f64>,
+    peer_timeout: Option<f64>,
+}
+
+impl Default for RawNetworkTimeouts {
+    fn default() -> Self {
+        let defaults = NetworkTimeouts::default();
+        let secs = |x: Option<Duration>| x.map(|x| x.as_secs_f64());
+        Self {
+            connection: secs(defaults.connection),
+            operation: secs(defaults.operation),
+            heartbeat_interval: secs(defaults.heartbeat_interval),
+            peer_timeout: secs(defaults.peer_timeout),
+        }
+    }
+}
+
+/// Raw parsed JSON party configuration file. At least one of certificate path and fingerprint is required.
 #[derive(Clone, Debug, Deserialize)]
 pub struct RawNetworkPartyConfig {
-    address: SocketAddr,
-    certificate: String,
+    #[serde(default)]
+    name: Option<String>,
+    address: String,
+    #[serde(default)]
+    bind: Option<SocketAddr>,
+    #[serde(default)]
+    certificate: Option<String>,
+    #[serde(default)]
+    fingerprint: Option<String>,
 }
 
 impl NetworkConfig {
@@ -47,14 +163,39 @@ impl NetworkConfig {
         let reader = BufReader::new(file);
         let raw: RawNetworkConfig = serde_json::from_reader(reader)?;
 
+        let field = raw
+            .field
+            .map(|x| x.parse())
+            .transpose()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
         Ok(NetworkConfig {
             parties: raw
                 .parties
                 .into_iter()
                 .map(|x| parse_raw_party_config(parent_dir, x))
                 .collect::<Result<_, _>>()?,
+            field,
+            dealer: raw
+                .dealer
+                .map(|x| parse_raw_party_config(parent_dir, x))
+                .transpose()?,
+            timeouts: parse_raw_timeouts(raw.timeouts)?,
         })
     }
+
+    /// Description of party with given ID for logs, including its name and address if known.
+    pub fn describe_party(&self, id: usize) -> String {
+        match self.parties.get(id) {
+            Some(NetworkPartyConfig {
+                name: Some(name),
+                address,
+                ..
+            }) => format!("{} ({}, {})", id, name, address),
+            Some(party) => format!("{} ({})", id, party.address),
+            None => id.to_string(),
+        }
+    }
 }
 
 /// Parse raw party configuration.
@@ -62,9 +203,87 @@ fn parse_raw_party_config(
     parent_dir: &Path,
     raw: RawNetworkPartyConfig,
 ) -> io::Result<NetworkPartyConfig> {
+    let invalid_data = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+    if !is_valid_address(&raw.address) {
+        return Err(invalid_data("Invalid address, expected host:port"));
+    }
+
+    let fingerprint = raw
+        .fingerprint
+        .map(|x| {
+            parse_fingerprint(&x).ok_or_else(|| invalid_data("Invalid certificate fingerprint"))
+        })
+        .transpose()?;
+    let certificate = match (raw.certificate, fingerprint) {
+        (Some(path), fingerprint) => {
+            let cert = load_certificate(parent_dir.join(path))?;
+            if matches!(fingerprint, Some(x) if x != certificate_fingerprint(&cert)) {
+                return Err(invalid_data("Certificate doesn't match fingerprint"));
+            }
+            PartyCertificate::Full(cert)
+        }
+        (None, Some(fingerprint)) => PartyCertificate::Fingerprint(fingerprint),
+        (None, None) => return Err(invalid_data("Certificate or fingerprint is required")),
+    };
+
     Ok(NetworkPartyConfig {
+        name: raw.name,
         address: raw.address,
-        certificate: load_certificate(parent_dir.join(raw.certificate))?,
+        bind: raw.bind,
+        certificate,
+    })
+}
+
+/// Check that address consists of host (name or IP address) and port.
+fn is_valid_address(address: &str) -> bool {
+    match address.rsplit_once(':') {
+        Some((host, port)) => !host.is_empty() && port.parse::<u16>().is_ok(),
+        None => false,
+    }
+}
+
+/// Parse hex-encoded SHA-256 fingerprint. Bytes may be separated by colons, as printed by OpenSSL.
+fn parse_fingerprint(hex: &str) -> Option<CertificateFingerprint> {
+    let digits: Vec<_> = hex
+        .chars()
+        .filter(|&c| c != ':')
+        .map(|c| c.to_digit(16))
+        .collect::<Option<_>>()?;
+    if digits.len() != 64 {
+        return None;
+    }
+    let mut fingerprint = [0; 32];
+    for (byte, pair) in fingerprint.iter_mut().zip(digits.chunks(2)) {
+        *byte = (pair[0] * 16 + pair[1]) as u8;
+    }
+    Some(fingerprint)
+}
+
+/// Compute SHA-256 fingerprint of certificate.
+pub fn certificate_fingerprint(cert: &Certificate) -> CertificateFingerprint {
+    Sha256::digest(&cert.0).into()
+}
+
+/// Parse raw timeouts.
+fn parse_raw_timeouts(raw: RawNetworkTimeouts) -> io::Result<NetworkTimeouts> {
+    let duration = |secs: Option<f64>| {
+        secs.map(|secs| {
+            if secs.is_finite() && secs > 0.0 {
+                Ok(Duration::from_secs_f64(secs))
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid timeout",
+                ))
+            }
+        })
+        .transpose()
+    };
+    Ok(NetworkTimeouts {
+        connection: duration(raw.connection)?,
+        operation: duration(raw.operation)?,
+        heartbeat_interval: duration(raw.heartbeat_interval)?,
+        peer_timeout: duration(raw.peer_timeout)?,
     })
 }
 
@@ -89,3 +308,56 @@ pub fn load_private_key(path: impl AsRef<Path>) -> io::Result<PrivateKey> {
         Err(io::Error::new(io::ErrorKind::Other, "Invalid certificate"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{parse_fingerprint, parse_raw_party_config, PartyCertificate};
+
+    #[test]
+    fn test_parse_fingerprint() {
+        let hex = "00:1f:2E:3d".to_owned() + &"ff".repeat(28);
+        let mut expected = [0xff; 32];
+        expected[..4].copy_from_slice(&[0x00, 0x1f, 0x2e, 0x3d]);
+        assert_eq!(parse_fingerprint(&hex), Some(expected));
+        assert_eq!(parse_fingerprint(&hex.replace(':', "")), Some(expected));
+        assert_eq!(parse_fingerprint(&hex[3..]), None);
+        assert_eq!(parse_fingerprint(&hex.replace('f', "g")), None);
+    }
+
+    #[test]
+    fn test_parse_party_config() {
+        let parse = |json: &str| {
+            let raw = serde_json::from_str(json).unwrap();
+            parse_raw_party_config(Path::new("."), raw)
+        };
+        let fingerprint = "ab".repeat(32);
+
+        let party = parse(&format!(
+            r#"{{"name": "bob", "address": "bob.example.com:5000", "bind": "0.0.0.0:5001", "fingerprint": "{}"}}"#,
+            fingerprint
+        ))
+        .unwrap();
+        assert_eq!(party.name.as_deref(), Some("bob"));
+        assert_eq!(party.address, "bob.example.com:5000");
+        assert_eq!(party.listen_address(), "0.0.0.0:5001");
+        assert_eq!(party.certificate, PartyCertificate::Fingerprint([0xab; 32]));
+
+        let party = parse(&format!(
+            r#"{{"address": "[::1]:5000", "fingerprint": "{}"}}"#,
+            fingerprint
+        ))
+        .unwrap();
+        assert_eq!(party.listen_address(), "[::1]:5000");
+
+        for address in ["bob.example.com", ":5000", "bob.example.com:port"] {
+            let json = format!(
+                r#"{{"address": "{}", "fingerprint": "{}"}}"#,
+                address, fingerprint
+            );
+            assert!(parse(&json).is_err(), "{} is invalid", address);
+        }
+        assert!(parse(r#"{"address": "127.0.0.1:5000"}"#).is_err());
+    }
+}
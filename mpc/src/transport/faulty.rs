@@ -0,0 +1,156 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{ready, Sink, Stream};
+
+use super::MultipartyTransport;
+
+/// Function deciding what is actually sent in place of each outgoing message.
+/// Returning no messages drops the original one, returning several injects extra messages.
+/// Closures can keep state, e.g. to hold a message back and release it later out of order.
+pub type Fault<T> = Box<dyn FnMut(T) -> Vec<T> + Send>;
+
+/// Fault of an honest party, which sends all messages unchanged.
+pub fn no_fault<T>() -> Fault<T> {
+    Box::new(|msg| vec![msg])
+}
+
+/// Channel wrapper which tampers with outgoing messages, simulating a malicious party in tests.
+/// Incoming messages are passed through unchanged.
+pub struct FaultyChannel<T, C> {
+    inner: C,
+    fault: Fault<T>,
+    /// Tampered messages waiting to be sent over inner channel.
+    queue: VecDeque<T>,
+}
+
+impl<T, C> FaultyChannel<T, C> {
+    /// Wrap channel, applying given fault to every sent message.
+    pub fn new(inner: C, fault: Fault<T>) -> Self {
+        Self {
+            inner,
+            fault,
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl<T, C> FaultyChannel<T, C>
+where
+    T: Unpin,
+    C: Sink<T> + Unpin,
+{
+    /// Pass queued messages to inner channel.
+    fn poll_send_queue(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        while !self.queue.is_empty() {
+            ready!(Pin::new(&mut self.inner).poll_ready(cx))?;
+            let msg = self.queue.pop_front().unwrap();
+            Pin::new(&mut self.inner).start_send(msg)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: Unpin, C: Stream + Unpin> Stream for FaultyChannel<T, C> {
+    type Item = C::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().inner).poll_next(cx)
+    }
+}
+
+impl<T, C> Sink<T> for FaultyChannel<T, C>
+where
+    T: Unpin,
+    C: Sink<T> + Unpin,
+{
+    type Error = C::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_send_queue(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        this.queue.extend((this.fault)(item));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_send_queue(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_send_queue(cx))?;
+        Pin::new(&mut this.inner).poll_close(cx)
+    }
+}
+
+/// Wrap all channels of a transport with faults created for each peer ID, keeping its timeout.
+/// Different faults for different peers let a party equivocate, e.g. send inconsistent broadcasts.
+pub fn inject_faults<T, C>(
+    transport: MultipartyTransport<T, C>,
+    mut make_fault: impl FnMut(usize) -> Fault<T>,
+) -> MultipartyTransport<T, FaultyChannel<T, C>>
+where
+    T: Unpin,
+    C: Stream + Sink<T> + Unpin,
+{
+    let party_id = transport.party_id();
+    let timeout = transport.timeout();
+    let channels = transport
+        .into_channels()
+        .into_iter()
+        .enumerate()
+        .map(|(id, channel)| channel.map(|channel| FaultyChannel::new(channel, make_fault(id))));
+    MultipartyTransport::new(channels, party_id).with_timeout(timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inject_faults, no_fault, Fault};
+    use crate::transport::mock_multiparty_channels;
+
+    #[tokio::test]
+    async fn test_faults() {
+        let mut channels = mock_multiparty_channels::<u32>(3, 64);
+        let mut second = channels.pop().unwrap();
+        let mut first = channels.pop().unwrap();
+        let mut transport = inject_faults(channels.pop().unwrap(), |id| -> Fault<u32> {
+            match id {
+                1 => Box::new(|msg| if msg == 2 { vec![] } else { vec![msg + 10] }),
+                // Swap the first two messages.
+                _ => {
+                    let mut held = None;
+                    Box::new(move |msg| match held.take() {
+                        Some(prev) => vec![msg, prev],
+                        None if msg == 1 => {
+                            held = Some(msg);
+                            vec![]
+                        }
+                        None => vec![msg],
+                    })
+                }
+            }
+        });
+
+        for msg in 1..=3 {
+            transport.send_to_all(msg).await.unwrap();
+        }
+        assert_eq!(first.receive_from(0).await, Ok(11));
+        assert_eq!(first.receive_from(0).await, Ok(13));
+        assert_eq!(second.receive_from(0).await, Ok(2));
+        assert_eq!(second.receive_from(0).await, Ok(1));
+        assert_eq!(second.receive_from(0).await, Ok(3));
+
+        let mut honest = inject_faults(first, |_| no_fault());
+        honest.send_to(0, 4).await.unwrap();
+        assert_eq!(transport.receive_from(1).await, Ok(4));
+    }
+}
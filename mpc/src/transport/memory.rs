@@ -0,0 +1,106 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Sink, Stream, StreamExt,
+};
+
+use super::{multiparty::connect_all_parties, MultipartyTransport};
+
+/// In-process channel moving typed messages between tasks without serialization.
+/// Errors match those of byte stream channels, so it can replace them in tests.
+pub struct MemoryChannel<T> {
+    sender: UnboundedSender<T>,
+    receiver: UnboundedReceiver<T>,
+}
+
+impl<T> MemoryChannel<T> {
+    /// Create connected pair of channels.
+    pub fn pair() -> (Self, Self) {
+        let (a_sender, b_receiver) = mpsc::unbounded();
+        let (b_sender, a_receiver) = mpsc::unbounded();
+        (
+            Self {
+                sender: a_sender,
+                receiver: a_receiver,
+            },
+            Self {
+                sender: b_sender,
+                receiver: b_receiver,
+            },
+        )
+    }
+}
+
+impl<T> Stream for MemoryChannel<T> {
+    type Item = Result<T, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx).map(|x| x.map(Ok))
+    }
+}
+
+impl<T> Sink<T> for MemoryChannel<T> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        self.sender
+            .unbounded_send(item)
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.sender.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Create in-process channels for testing multiparty protocols, which pass messages without serialization.
+pub fn memory_multiparty_channels<T>(
+    num_parties: usize,
+) -> Vec<MultipartyTransport<T, MemoryChannel<T>>> {
+    connect_all_parties(num_parties, |_, _| MemoryChannel::pair())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::memory_multiparty_channels;
+    use crate::transport::TransportError;
+
+    #[tokio::test]
+    async fn test_memory_channels() {
+        let mut channels = memory_multiparty_channels::<Vec<u32>>(3);
+        let mut second = channels.pop().unwrap();
+        let mut first = channels.pop().unwrap();
+        let mut transport = channels.pop().unwrap();
+
+        first.send_to(0, vec![1, 2]).await.unwrap();
+        second.send_to(0, vec![3]).await.unwrap();
+        let received = transport.exchange_with_all(vec![4]).await.unwrap();
+        assert_eq!(received, vec![(1, vec![1, 2]), (2, vec![3])]);
+        assert_eq!(first.receive_from(0).await, Ok(vec![4]));
+
+        drop(transport);
+        assert_eq!(
+            second.receive_from(0).await,
+            Ok(vec![4]),
+            "Messages sent before closing are delivered"
+        );
+        assert_eq!(
+            second.receive_from(0).await,
+            Err(TransportError::PeerClosed(0))
+        );
+    }
+}
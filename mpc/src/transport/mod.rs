@@ -1,13 +1,22 @@
 mod config;
 pub use config::*;
 
+mod faulty;
+pub use faulty::*;
+
+mod memory;
+pub use memory::*;
+
 mod multiparty;
 pub use multiparty::*;
 
 mod networking;
 pub use networking::*;
 
-use std::fmt;
+mod simulated;
+pub use simulated::*;
+
+use std::{fmt, io};
 
 use tokio::io::{AsyncRead, AsyncWrite, DuplexStream};
 use tokio_serde::formats::Bincode;
@@ -18,6 +27,12 @@ use tokio_util::codec::LengthDelimitedCodec;
 pub enum TransportError {
     Send(usize),
     Recv(usize),
+    /// Peer didn't respond in time.
+    Timeout(usize),
+    /// Peer closed connection.
+    PeerClosed(usize),
+    /// Connection to peer couldn't be authenticated.
+    HandshakeFailed(usize),
 }
 
 impl fmt::Display for TransportError {
@@ -25,6 +40,59 @@ impl fmt::Display for TransportError {
         match *self {
             Self::Send(id) => write!(f, "Error while sending message to {}", id),
             Self::Recv(id) => write!(f, "Error while receiving message from {}", id),
+            Self::Timeout(id) => write!(f, "Party {} is not responding", id),
+            Self::PeerClosed(id) => write!(f, "Party {} closed connection", id),
+            Self::HandshakeFailed(id) => write!(f, "Handshake with party {} failed", id),
+        }
+    }
+}
+
+impl TransportError {
+    /// ID of peer whose connection failed.
+    pub fn peer_id(&self) -> usize {
+        match *self {
+            Self::Send(id)
+            | Self::Recv(id)
+            | Self::Timeout(id)
+            | Self::PeerClosed(id)
+            | Self::HandshakeFailed(id) => id,
+        }
+    }
+
+    /// Transport error wrapped in I/O error, e.g. returned when establishing connections.
+    pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+        err.get_ref().and_then(|err| err.downcast_ref())
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+/// Error of channel stream, which can be classified as a transport error.
+pub trait ChannelError {
+    /// Transport error corresponding to this error of channel with given peer.
+    fn transport_error(&self, peer_id: usize) -> TransportError;
+}
+
+impl ChannelError for io::Error {
+    fn transport_error(&self, peer_id: usize) -> TransportError {
+        match self.kind() {
+            io::ErrorKind::TimedOut => TransportError::Timeout(peer_id),
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe => TransportError::PeerClosed(peer_id),
+            _ => TransportError::Recv(peer_id),
+        }
+    }
+}
+
+/// Channels carrying only some kinds of messages (e.g. wrapped in messages of another protocol) signal
+/// unexpected messages with None.
+impl<E: ChannelError> ChannelError for Option<E> {
+    fn transport_error(&self, peer_id: usize) -> TransportError {
+        match self {
+            Some(err) => err.transport_error(peer_id),
+            None => TransportError::Recv(peer_id),
         }
     }
 }
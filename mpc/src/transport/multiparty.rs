@@ -1,10 +1,12 @@
+use std::{future::Future, time::Duration};
+
 use futures::{
     stream::{SplitSink, SplitStream},
     FutureExt, Sink, SinkExt, Stream, StreamExt, TryFutureExt,
 };
 use serde::{de::DeserializeOwned, Serialize};
 
-use super::{bincode_duplex, BincodeDuplex, TransportError};
+use super::{bincode_duplex, BincodeDuplex, ChannelError, TransportError};
 
 /// Halves of split channel.
 type ChannelHalves<C, T> = (SplitSink<C, T>, SplitStream<C>);
@@ -13,6 +15,8 @@ type ChannelHalves<C, T> = (SplitSink<C, T>, SplitStream<C>);
 pub struct MultipartyTransport<T, Channel> {
     channels: Vec<Option<ChannelHalves<Channel, T>>>,
     party_id: usize,
+    /// Maximum duration of each operation with a single peer.
+    timeout: Option<Duration>,
 }
 
 impl<T, Channel> MultipartyTransport<T, Channel>
@@ -29,7 +33,30 @@ where
                 panic!("Channel missing for party {}", j);
             }
         }
-        Self { channels, party_id }
+        Self {
+            channels,
+            party_id,
+            timeout: None,
+        }
+    }
+
+    /// Limit duration of each send or receive operation with a single peer, operations wait forever by default.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl<T, Channel> MultipartyTransport<T, Channel>
+where
+    Channel: Stream + Sink<T> + Unpin,
+{
+    /// Recover connections wrapped by this transport, e.g. to use them for a different protocol.
+    pub fn into_channels(self) -> Vec<Option<Channel>> {
+        self.channels
+            .into_iter()
+            .map(|x| x.map(|(sink, stream)| stream.reunite(sink).unwrap()))
+            .collect()
     }
 }
 
@@ -43,11 +70,17 @@ impl<T, Channel> MultipartyTransport<T, Channel> {
     pub fn party_id(&self) -> usize {
         self.party_id
     }
+
+    /// Timeout of operations with a single peer.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
 }
 
 impl<T, E, Channel> MultipartyTransport<T, Channel>
 where
     T: Clone,
+    E: ChannelError,
     Channel: Stream<Item = Result<T, E>> + Sink<T> + Unpin,
 {
     /// Send message to party with given ID.
@@ -56,9 +89,10 @@ where
             panic!("Cannot send message on loopback");
         }
         let (sink, _) = self.channels[other_id].as_mut().unwrap();
-        sink.send(msg)
-            .await
-            .map_err(|_| TransportError::Send(other_id))
+        let send_future = sink
+            .send(msg)
+            .map(|x| x.map_err(|_| TransportError::Send(other_id)));
+        with_timeout(self.timeout, other_id, send_future).await
     }
 
     /// Receive message from party wit given ID.
@@ -67,14 +101,13 @@ where
             panic!("Cannot receive message on loopback");
         }
         let (_, stream) = self.channels[other_id].as_mut().unwrap();
-        match stream.next().await {
-            Some(Ok(msg)) => Ok(msg),
-            _ => Err(TransportError::Recv(other_id)),
-        }
+        let recv_future = stream.next().map(|raw| received(other_id, raw));
+        with_timeout(self.timeout, other_id, recv_future).await
     }
 
     /// Send message to all parties.
     pub async fn send_to_all(&mut self, msg: T) -> Result<(), TransportError> {
+        let timeout = self.timeout;
         futures::future::try_join_all(
             self.channels
                 .iter_mut()
@@ -82,8 +115,10 @@ where
                 .filter(|(id, _)| *id != self.party_id)
                 .map(|(id, channel)| {
                     let (sink, _) = channel.as_mut().unwrap();
-                    sink.send(msg.clone())
-                        .then(move |x| async move { x.map_err(|_| TransportError::Send(id)) })
+                    let send_future = sink
+                        .send(msg.clone())
+                        .map(move |x| x.map_err(|_| TransportError::Send(id)));
+                    with_timeout(timeout, id, send_future)
                 }),
         )
         .await
@@ -92,6 +127,7 @@ where
 
     /// Receive messages from all parties.
     pub async fn receive_from_all(&mut self) -> Result<Vec<(usize, T)>, TransportError> {
+        let timeout = self.timeout;
         futures::future::try_join_all(
             self.channels
                 .iter_mut()
@@ -99,12 +135,10 @@ where
                 .filter(|(id, _)| *id != self.party_id)
                 .map(|(id, channel)| {
                     let (_, stream) = channel.as_mut().unwrap();
-                    stream.next().then(move |raw| async move {
-                        match raw {
-                            Some(Ok(msg)) => Ok((id, msg)),
-                            _ => Err(TransportError::Recv(id)),
-                        }
-                    })
+                    let recv_future = stream
+                        .next()
+                        .map(move |raw| received(id, raw).map(|msg| (id, msg)));
+                    with_timeout(timeout, id, recv_future)
                 }),
         )
         .await
@@ -112,6 +146,7 @@ where
 
     /// Concurrently send and receive messages from all parties.
     pub async fn exchange_with_all(&mut self, msg: T) -> Result<Vec<(usize, T)>, TransportError> {
+        let timeout = self.timeout;
         futures::future::try_join_all(
             self.channels
                 .iter_mut()
@@ -121,19 +156,81 @@ where
                     let (sink, stream) = channel.as_mut().unwrap();
                     let send_future = sink
                         .send(msg.clone())
-                        .then(move |x| async move { x.map_err(|_| TransportError::Send(id)) });
-                    let recv_future = stream.next().then(move |raw| async move {
-                        match raw {
-                            Some(Ok(msg)) => Ok((id, msg)),
-                            _ => Err(TransportError::Recv(id)),
-                        }
-                    });
-                    futures::future::try_join(send_future, recv_future)
-                        .and_then(|(_, received_msg)| async { Ok(received_msg) })
+                        .map(move |x| x.map_err(|_| TransportError::Send(id)));
+                    let recv_future = stream
+                        .next()
+                        .map(move |raw| received(id, raw).map(|msg| (id, msg)));
+                    let exchange_future = futures::future::try_join(send_future, recv_future)
+                        .and_then(|(_, received_msg)| async { Ok(received_msg) });
+                    with_timeout(timeout, id, exchange_future)
                 }),
         )
         .await
     }
+
+    /// Concurrently send messages to some parties and receive messages from some parties.
+    /// Outgoing messages and receive flags are indexed by party ID; entries for current party are ignored.
+    pub async fn exchange_with_some(
+        &mut self,
+        messages: Vec<Option<T>>,
+        receive_from: &[bool],
+    ) -> Result<Vec<(usize, T)>, TransportError> {
+        let timeout = self.timeout;
+        let received = futures::future::try_join_all(
+            self.channels
+                .iter_mut()
+                .zip(messages)
+                .enumerate()
+                .filter(|(id, _)| *id != self.party_id)
+                .map(|(id, (channel, msg))| {
+                    let (sink, stream) = channel.as_mut().unwrap();
+                    let should_receive = receive_from[id];
+                    let send_future = async move {
+                        match msg {
+                            Some(msg) => sink.send(msg).await.map_err(|_| TransportError::Send(id)),
+                            None => Ok(()),
+                        }
+                    };
+                    let recv_future = async move {
+                        if !should_receive {
+                            return Ok(None);
+                        }
+                        received(id, stream.next().await).map(|msg| Some((id, msg)))
+                    };
+                    let exchange_future = futures::future::try_join(send_future, recv_future)
+                        .and_then(|(_, received_msg)| async { Ok(received_msg) });
+                    with_timeout(timeout, id, exchange_future)
+                }),
+        )
+        .await?;
+        Ok(received.into_iter().flatten().collect())
+    }
+}
+
+/// Convert message received from given peer (None if the stream ended) to result.
+fn received<T, E: ChannelError>(
+    peer_id: usize,
+    raw: Option<Result<T, E>>,
+) -> Result<T, TransportError> {
+    match raw {
+        Some(Ok(msg)) => Ok(msg),
+        Some(Err(err)) => Err(err.transport_error(peer_id)),
+        None => Err(TransportError::PeerClosed(peer_id)),
+    }
+}
+
+/// Run operation with given peer, failing with timeout error if it doesn't finish in time.
+async fn with_timeout<R>(
+    timeout: Option<Duration>,
+    peer_id: usize,
+    operation: impl Future<Output = Result<R, TransportError>>,
+) -> Result<R, TransportError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, operation)
+            .await
+            .unwrap_or(Err(TransportError::Timeout(peer_id))),
+        None => operation.await,
+    }
 }
 
 /// Create in-process channels for testing multiparty protocols.
@@ -143,6 +240,17 @@ pub fn mock_multiparty_channels<T>(
 ) -> Vec<MultipartyTransport<T, BincodeDuplex<T>>>
 where
     T: Clone + Serialize + DeserializeOwned + Unpin,
+{
+    connect_all_parties(num_parties, |_, _| bincode_duplex::<T>(max_buf_size))
+}
+
+/// Connect every pair of parties (i, j), where j < i, with ends of a channel created by given function.
+pub(super) fn connect_all_parties<T, Channel>(
+    num_parties: usize,
+    mut create_channel: impl FnMut(usize, usize) -> (Channel, Channel),
+) -> Vec<MultipartyTransport<T, Channel>>
+where
+    Channel: Stream + Sink<T>,
 {
     let mut matrix: Vec<Vec<_>> = (0..num_parties)
         .map(|_| (0..num_parties).map(|_| None).collect())
@@ -150,7 +258,7 @@ where
 
     for i in 0..num_parties {
         for j in 0..i {
-            let (a, b) = bincode_duplex::<T>(max_buf_size);
+            let (a, b) = create_channel(i, j);
             matrix[i][j] = Some(a);
             matrix[j][i] = Some(b);
         }
@@ -162,3 +270,46 @@ where
         .map(|(id, row)| MultipartyTransport::new(row, id))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{mock_multiparty_channels, TransportError};
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let mut channels = mock_multiparty_channels::<u32>(3, 64);
+        let silent = channels.pop().unwrap();
+        let mut other = channels.pop().unwrap();
+        let mut transport = channels
+            .pop()
+            .unwrap()
+            .with_timeout(Some(Duration::from_millis(50)));
+
+        other.send_to(0, 1).await.unwrap();
+        assert_eq!(transport.receive_from(1).await, Ok(1));
+        other.send_to(0, 2).await.unwrap();
+        assert_eq!(
+            transport.receive_from_all().await,
+            Err(TransportError::Timeout(2))
+        );
+        other.send_to(0, 3).await.unwrap();
+        assert_eq!(
+            transport.exchange_with_all(4).await,
+            Err(TransportError::Timeout(2))
+        );
+        drop(silent);
+    }
+
+    #[tokio::test]
+    async fn test_peer_closed() {
+        let mut channels = mock_multiparty_channels::<u32>(2, 64);
+        drop(channels.pop());
+        let mut transport = channels.pop().unwrap();
+        assert_eq!(
+            transport.receive_from(1).await,
+            Err(TransportError::PeerClosed(1))
+        );
+    }
+}
@@ -1,22 +1,38 @@
-use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
 
-use futures::{future, stream::FuturesUnordered, StreamExt};
-use serde::{de::DeserializeOwned, Serialize};
+use futures::{
+    channel::{mpsc, oneshot},
+    future,
+    stream::FuturesUnordered,
+    FutureExt, Sink, SinkExt, Stream, StreamExt, TryFutureExt,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     net::{TcpListener, TcpStream},
+    time::Instant,
 };
 use tokio_rustls::{
     rustls::{
-        server::AllowAnyAuthenticatedClient, Certificate, ClientConfig, PrivateKey, RootCertStore,
-        ServerConfig,
+        client::{ServerCertVerified, ServerCertVerifier},
+        server::{AllowAnyAuthenticatedClient, ClientCertVerified, ClientCertVerifier},
+        Certificate, ClientConfig, DistinguishedNames, Error as TlsError, PrivateKey,
+        RootCertStore, ServerConfig, ServerName,
     },
     TlsAcceptor, TlsConnector, TlsStream,
 };
 
 use super::{
-    wrap_channel_with_bincode, BincodeStreamSink, MultipartyTransport, NetworkConfig,
-    NetworkPartyConfig,
+    certificate_fingerprint, wrap_channel_with_bincode, CertificateFingerprint,
+    MultipartyTransport, NetworkConfig, NetworkPartyConfig, NetworkTimeouts, PartyCertificate,
+    TransportError,
 };
 
 /// Virtual domain name for TLS certificates.
@@ -25,11 +41,164 @@ const VIRTUAL_DOMAIN_FOR_TLS: &str = "mpc";
 /// Delay in milliseconds after which connection to peer is retried.
 const CONNECTION_RETRY_DELAY: u64 = 1000;
 
+/// Number of received messages buffered by network channel before they are consumed.
+const RECEIVE_BUFFER_SIZE: usize = 16;
+
 /// Public certificate and its private key.
 type PrivateCert = (Certificate, PrivateKey);
 
-/// Bincode-encoded and TLS-encrypted TCP connection.
-pub type NetChannel<T> = BincodeStreamSink<T, TlsStream<TcpStream>>;
+/// Message sent over network connection, interleaved with heartbeats while the connection is idle.
+#[derive(Serialize, Deserialize)]
+enum NetFrame<T> {
+    Message(T),
+    Heartbeat,
+}
+
+/// Bincode-encoded connection (TLS-encrypted TCP connection in networked transport). Background tasks send
+/// heartbeats while no messages are sent and fail the connection with a timeout error when the peer stays silent.
+/// Sent messages are flushed once they are written to the connection.
+pub struct NetChannel<T> {
+    sender: mpsc::Sender<(T, oneshot::Sender<()>)>,
+    /// Acknowledgement of writing the last sent message.
+    last_written: Option<oneshot::Receiver<()>>,
+    receiver: mpsc::Receiver<io::Result<T>>,
+}
+
+impl<T> NetChannel<T>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    /// Wrap connection to peer, which uses heartbeats with given interval and timeout.
+    pub fn new<C>(connection: C, timeouts: &NetworkTimeouts) -> Self
+    where
+        C: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (sink, stream) = wrap_channel_with_bincode::<NetFrame<T>, _>(connection).split();
+        // Messages are written one by one, so only one of them is waiting for the writer at a time.
+        let (sender, outgoing) = mpsc::channel(0);
+        let (incoming, receiver) = mpsc::channel(RECEIVE_BUFFER_SIZE);
+        tokio::spawn(write_frames(sink, outgoing, timeouts.heartbeat_interval));
+        tokio::spawn(read_frames(stream, incoming, timeouts.peer_timeout));
+        Self {
+            sender,
+            last_written: None,
+            receiver,
+        }
+    }
+}
+
+impl<T> Stream for NetChannel<T> {
+    type Item = io::Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+impl<T> Sink<T> for NetChannel<T> {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.sender.poll_ready(cx).map_err(|_| connection_closed())
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> io::Result<()> {
+        let (ack, written) = oneshot::channel();
+        self.sender
+            .start_send((item, ack))
+            .map_err(|_| connection_closed())?;
+        self.last_written = Some(written);
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(written) = &mut self.last_written {
+            futures::ready!(written.poll_unpin(cx)).map_err(|_| connection_closed())?;
+            self.last_written = None;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        futures::ready!(self.as_mut().poll_flush(cx))?;
+        self.sender.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Error of sending over connection whose writer has failed.
+fn connection_closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "Connection closed")
+}
+
+/// Write messages to connection and acknowledge them, sending heartbeats when there are no messages.
+/// Closes the connection when the channel is dropped.
+async fn write_frames<T, S>(
+    mut sink: S,
+    mut outgoing: mpsc::Receiver<(T, oneshot::Sender<()>)>,
+    heartbeat_interval: Option<Duration>,
+) where
+    S: Sink<NetFrame<T>> + Unpin,
+{
+    loop {
+        let next = match heartbeat_interval {
+            Some(interval) => tokio::time::timeout(interval, outgoing.next()).await,
+            None => Ok(outgoing.next().await),
+        };
+        match next {
+            Ok(Some((msg, ack))) => {
+                if sink.send(NetFrame::Message(msg)).await.is_err() {
+                    break;
+                }
+                let _ = ack.send(());
+            }
+            Ok(None) => {
+                let _ = sink.close().await;
+                break;
+            }
+            Err(_) => {
+                if sink.send(NetFrame::Heartbeat).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Read messages from connection, skipping heartbeats. Fails with timeout error if nothing is received
+/// for a given time. Stops when the channel is dropped.
+async fn read_frames<T, S>(
+    mut stream: S,
+    mut incoming: mpsc::Sender<io::Result<T>>,
+    peer_timeout: Option<Duration>,
+) where
+    S: Stream<Item = io::Result<NetFrame<T>>> + Unpin,
+{
+    loop {
+        let frame = match peer_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, stream.next())
+                .await
+                .unwrap_or_else(|_| {
+                    Some(Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "Peer stopped sending heartbeats",
+                    )))
+                }),
+            None => stream.next().await,
+        };
+        let result = match frame {
+            Some(Ok(NetFrame::Message(msg))) => Ok(msg),
+            Some(Ok(NetFrame::Heartbeat)) if incoming.is_closed() => break,
+            Some(Ok(NetFrame::Heartbeat)) => continue,
+            Some(Err(err)) => Err(err),
+            None => break,
+        };
+        let failed = result.is_err();
+        if incoming.send(result).await.is_err() || failed {
+            break;
+        }
+    }
+}
 
 /// Establish network connections for multiparty protocol.
 pub async fn connect_multiparty<T>(
@@ -38,21 +207,27 @@ pub async fn connect_multiparty<T>(
     party_id: usize,
 ) -> io::Result<MultipartyTransport<T, NetChannel<T>>>
 where
-    T: Serialize + DeserializeOwned,
+    T: Serialize + DeserializeOwned + Send + 'static,
 {
     let this_party = &config.parties[party_id];
-    let private_cert = (this_party.certificate.clone(), private_key);
+    let private_cert = (own_certificate(this_party)?, private_key);
+    let deadline = connection_deadline(&config.timeouts);
 
     let listen_for = listen_for_parties(
         &config.parties[..party_id],
         &private_cert,
-        this_party.address,
+        this_party.listen_address(),
+        deadline,
     );
 
     let connect_to = future::try_join_all(
         config.parties[party_id + 1..]
             .iter()
-            .map(|config| connect_to_party(config, &private_cert, party_id)),
+            .zip(party_id + 1..)
+            .map(|(config, other_id)| {
+                connect_to_party(config, &private_cert, party_id, deadline)
+                    .map_err(move |err| connection_error(other_id, err))
+            }),
     );
 
     let (listen_for, connect_to) = futures::try_join!(listen_for, connect_to)?;
@@ -62,16 +237,111 @@ where
         .map(Some)
         .chain(std::iter::once(None))
         .chain(connect_to.into_iter().map(Some))
-        .map(|x| x.map(wrap_channel_with_bincode));
+        .map(|x| x.map(|x| NetChannel::new(x, &config.timeouts)));
+
+    Ok(MultipartyTransport::new(channels, party_id).with_timeout(config.timeouts.operation))
+}
+
+/// Accept connections from all parties to trusted dealer service.
+pub async fn accept_dealer_clients<T>(
+    config: &NetworkConfig,
+    private_key: PrivateKey,
+) -> io::Result<Vec<NetChannel<T>>>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    let dealer = dealer_config(config)?;
+    let private_cert = (own_certificate(dealer)?, private_key);
+    let deadline = connection_deadline(&config.timeouts);
+    let channels = listen_for_parties(
+        &config.parties,
+        &private_cert,
+        dealer.listen_address(),
+        deadline,
+    )
+    .await?;
+    Ok(channels
+        .into_iter()
+        .map(|x| NetChannel::new(x, &config.timeouts))
+        .collect())
+}
+
+/// Connect to trusted dealer service as party with given ID.
+pub async fn connect_to_dealer<T>(
+    config: &NetworkConfig,
+    private_key: PrivateKey,
+    party_id: usize,
+) -> io::Result<NetChannel<T>>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    let dealer = dealer_config(config)?;
+    let private_cert = (own_certificate(&config.parties[party_id])?, private_key);
+    let deadline = connection_deadline(&config.timeouts);
+    let channel = connect_to_party(dealer, &private_cert, party_id, deadline).await?;
+    Ok(NetChannel::new(channel, &config.timeouts))
+}
+
+/// Get details about trusted dealer service.
+fn dealer_config(config: &NetworkConfig) -> io::Result<&NetworkPartyConfig> {
+    config.dealer.as_ref().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Dealer service is not configured",
+        )
+    })
+}
+
+/// Get full certificate of this party, which is needed to authenticate it to others.
+fn own_certificate(config: &NetworkPartyConfig) -> io::Result<Certificate> {
+    match &config.certificate {
+        PartyCertificate::Full(cert) => Ok(cert.clone()),
+        PartyCertificate::Fingerprint(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Own certificate is required, configuration only contains its fingerprint",
+        )),
+    }
+}
 
-    Ok(MultipartyTransport::new(channels, party_id))
+/// Time until which connections have to be established.
+fn connection_deadline(timeouts: &NetworkTimeouts) -> Option<Instant> {
+    timeouts.connection.map(|timeout| Instant::now() + timeout)
 }
 
-/// Listen for incoming connections from parties with lower IDs.
+/// Attribute error of connecting to peer with given ID, keeping its kind.
+fn connection_error(peer_id: usize, err: io::Error) -> io::Error {
+    let transport_error = match err.kind() {
+        io::ErrorKind::TimedOut => TransportError::Timeout(peer_id),
+        _ => TransportError::HandshakeFailed(peer_id),
+    };
+    io::Error::new(err.kind(), transport_error)
+}
+
+/// Run connection future until given deadline.
+async fn until_deadline<R>(
+    deadline: Option<Instant>,
+    connect: impl Future<Output = io::Result<R>>,
+) -> io::Result<R> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, connect)
+            .await
+            .unwrap_or_else(|_| {
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "Connection timed out",
+                ))
+            }),
+        None => connect.await,
+    }
+}
+
+/// Listen for incoming connections from parties with lower IDs. Incoming connections that fail
+/// are ignored, but they are reported if the party doesn't connect successfully until the deadline.
 async fn listen_for_parties(
     parties: &[NetworkPartyConfig],
     private_cert: &PrivateCert,
-    addr: SocketAddr,
+    addr: String,
+    deadline: Option<Instant>,
 ) -> io::Result<Vec<TlsStream<TcpStream>>> {
     if parties.is_empty() {
         return Ok(Vec::new());
@@ -80,6 +350,9 @@ async fn listen_for_parties(
     let listener = TcpListener::bind(addr).await?;
     let mut futures = FuturesUnordered::new();
     let mut connected_parties: Vec<_> = parties.iter().map(|_| None).collect();
+    let mut failed_parties = vec![false; parties.len()];
+    let timed_out = until_deadline(deadline, future::pending::<io::Result<()>>());
+    tokio::pin!(timed_out);
 
     loop {
         tokio::select! {
@@ -88,15 +361,25 @@ async fn listen_for_parties(
                 futures.push(accept_party(parties, private_cert, socket));
             },
             tmp = futures.next(), if !futures.is_empty() => {
-                if let Some(Ok((socket, id))) = tmp {
-                    if connected_parties[id].is_none() {
+                match tmp {
+                    Some(Ok((id, Ok(socket)))) if connected_parties[id].is_none() => {
                         connected_parties[id] = Some(socket);
                         if connected_parties.iter().all(|x| x.is_some()) {
                             break;
                         }
                     }
+                    Some(Ok((id, Err(_)))) => failed_parties[id] = true,
+                    _ => {}
                 }
             },
+            Err(err) = &mut timed_out => {
+                let id = connected_parties.iter().position(|x| x.is_none()).unwrap();
+                let err = match failed_parties[id] {
+                    true => io::Error::new(io::ErrorKind::InvalidData, "Handshake failed"),
+                    false => err,
+                };
+                return Err(connection_error(id, err));
+            },
         }
     }
 
@@ -106,55 +389,63 @@ async fn listen_for_parties(
         .collect())
 }
 
-/// Process incoming connection from party.
+/// Process incoming connection from party. Returns ID of the party and result of its TLS handshake.
 async fn accept_party(
     parties: &[NetworkPartyConfig],
     private_cert: &PrivateCert,
     mut socket: TcpStream,
-) -> io::Result<(TlsStream<TcpStream>, usize)> {
+) -> io::Result<(usize, io::Result<TlsStream<TcpStream>>)> {
     let party_id = socket.read_u32().await? as usize;
     if party_id >= parties.len() {
         return Err(io::Error::new(io::ErrorKind::Other, "Invalid party ID"));
     }
 
     let other_cert = parties[party_id].certificate.clone();
-    let tls_socket = wrap_tls_server(socket, other_cert, private_cert.clone()).await?;
-    Ok((tls_socket, party_id))
+    let tls_socket = wrap_tls_server(socket, other_cert, private_cert.clone()).await;
+    Ok((party_id, tls_socket))
 }
 
-/// Connect to party with higher ID.
+/// Connect to party with higher ID (or to dealer service), retrying refused connections until the deadline.
 async fn connect_to_party(
     other_party: &NetworkPartyConfig,
     private_cert: &PrivateCert,
     this_party_id: usize,
+    deadline: Option<Instant>,
 ) -> io::Result<TlsStream<TcpStream>> {
-    let mut socket = loop {
-        match TcpStream::connect(other_party.address).await {
-            Ok(socket) => break socket,
-            _ => tokio::time::sleep(Duration::from_millis(CONNECTION_RETRY_DELAY)).await,
-        }
-    };
+    until_deadline(deadline, async {
+        let mut socket = loop {
+            // Host name is resolved on each attempt, as it may not be resolvable before the peer starts.
+            match TcpStream::connect(other_party.address.as_str()).await {
+                Ok(socket) => break socket,
+                _ => tokio::time::sleep(Duration::from_millis(CONNECTION_RETRY_DELAY)).await,
+            }
+        };
 
-    socket.write_u32(this_party_id as u32).await?;
-    socket.flush().await?;
+        socket.write_u32(this_party_id as u32).await?;
+        socket.flush().await?;
 
-    let other_cert = other_party.certificate.clone();
-    wrap_tls_client(socket, other_cert, private_cert.clone()).await
+        let other_cert = other_party.certificate.clone();
+        wrap_tls_client(socket, other_cert, private_cert.clone()).await
+    })
+    .await
 }
 
 /// Wrap TCP client socket with TLS layer. Authenticates both sides using specified certificates.
 async fn wrap_tls_client(
     socket: TcpStream,
-    other_cert: Certificate,
+    other_cert: PartyCertificate,
     private_cert: PrivateCert,
 ) -> io::Result<TlsStream<TcpStream>> {
-    let root_cert_store = root_cert_store_from_cert(other_cert).await?;
-
-    let tls_config = ClientConfig::builder()
-        .with_safe_defaults()
-        .with_root_certificates(root_cert_store)
-        .with_single_cert(vec![private_cert.0], private_cert.1)
-        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let builder = ClientConfig::builder().with_safe_defaults();
+    let tls_config = match other_cert {
+        PartyCertificate::Full(cert) => builder
+            .with_root_certificates(root_cert_store_from_cert(cert).await?)
+            .with_single_cert(vec![private_cert.0], private_cert.1),
+        PartyCertificate::Fingerprint(fingerprint) => builder
+            .with_custom_certificate_verifier(Arc::new(FingerprintVerifier(fingerprint)))
+            .with_single_cert(vec![private_cert.0], private_cert.1),
+    }
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
 
     let connector = TlsConnector::from(Arc::new(tls_config));
     let domain = VIRTUAL_DOMAIN_FOR_TLS.try_into().unwrap();
@@ -164,11 +455,15 @@ async fn wrap_tls_client(
 /// Wrap incoming TCP connection with TLS layer. Authenticates both sides using specified certificates.
 async fn wrap_tls_server(
     socket: TcpStream,
-    other_cert: Certificate,
+    other_cert: PartyCertificate,
     private_cert: PrivateCert,
 ) -> io::Result<TlsStream<TcpStream>> {
-    let root_cert_store = root_cert_store_from_cert(other_cert).await?;
-    let client_cert_verifier = AllowAnyAuthenticatedClient::new(root_cert_store);
+    let client_cert_verifier: Arc<dyn ClientCertVerifier> = match other_cert {
+        PartyCertificate::Full(cert) => {
+            AllowAnyAuthenticatedClient::new(root_cert_store_from_cert(cert).await?)
+        }
+        PartyCertificate::Fingerprint(fingerprint) => Arc::new(FingerprintVerifier(fingerprint)),
+    };
 
     let tls_config = ServerConfig::builder()
         .with_safe_defaults()
@@ -188,3 +483,249 @@ async fn root_cert_store_from_cert(cert: Certificate) -> io::Result<RootCertStor
         .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
     Ok(store)
 }
+
+/// Verifier of peer certificates pinned by fingerprint. The certificate is trusted if its fingerprint matches,
+/// handshake signatures are still checked against it.
+struct FingerprintVerifier(CertificateFingerprint);
+
+impl FingerprintVerifier {
+    fn verify(&self, cert: &Certificate) -> Result<(), TlsError> {
+        if certificate_fingerprint(cert) == self.0 {
+            Ok(())
+        } else {
+            Err(TlsError::InvalidCertificateData(
+                "Certificate doesn't match pinned fingerprint".into(),
+            ))
+        }
+    }
+}
+
+impl ServerCertVerifier for FingerprintVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, TlsError> {
+        self.verify(end_entity)?;
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+impl ClientCertVerifier for FingerprintVerifier {
+    fn client_auth_root_subjects(&self) -> Option<DistinguishedNames> {
+        // Certificate is still requested, just without hints about accepted issuers.
+        Some(DistinguishedNames::new())
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, TlsError> {
+        self.verify(end_entity)?;
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, path::PathBuf, time::Duration};
+
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls::{Certificate, PrivateKey};
+
+    use super::{
+        certificate_fingerprint, connect_multiparty, CertificateFingerprint, MultipartyTransport,
+        NetChannel, NetworkConfig, NetworkPartyConfig, NetworkTimeouts, PartyCertificate,
+        TransportError,
+    };
+    use crate::transport::{load_certificate, load_private_key};
+
+    /// Timeouts with heartbeats sent every 10 ms, if enabled.
+    fn timeouts(heartbeats: bool) -> NetworkTimeouts {
+        NetworkTimeouts {
+            heartbeat_interval: heartbeats.then(|| Duration::from_millis(10)),
+            peer_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_heartbeats() {
+        let (a, b) = tokio::io::duplex(1024);
+        let mut a = NetChannel::<u32>::new(a, &timeouts(true));
+        let mut b = NetChannel::<u32>::new(b, &timeouts(true));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        a.send(1).await.unwrap();
+        assert_eq!(b.next().await.unwrap().unwrap(), 1);
+        b.send(2).await.unwrap();
+        assert_eq!(a.next().await.unwrap().unwrap(), 2);
+
+        drop(a);
+        assert!(b.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_silent_peer() {
+        let (a, b) = tokio::io::duplex(1024);
+        let _a = NetChannel::<u32>::new(a, &timeouts(false));
+        let mut b = NetChannel::<u32>::new(b, &timeouts(true));
+
+        let err = b.next().await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(b.next().await.is_none());
+    }
+
+    /// Certificate and private key generated with `openssl.cnf`, see `prepare-test-env.py`.
+    fn test_certificate(name: &str) -> (Certificate, PrivateKey) {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/transport/testdata");
+        (
+            load_certificate(dir.join(format!("{}.pem", name))).unwrap(),
+            load_private_key(dir.join(format!("{}.key", name))).unwrap(),
+        )
+    }
+
+    /// Port that is currently free on localhost.
+    async fn free_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    /// Configuration of two parties as seen by party with given ID, which pins the other party by fingerprint.
+    fn two_party_config(
+        own_id: usize,
+        certificates: &[Certificate; 2],
+        other_fingerprint: CertificateFingerprint,
+        parties: [(String, Option<&str>); 2],
+    ) -> NetworkConfig {
+        let parties = parties
+            .into_iter()
+            .enumerate()
+            .map(|(id, (address, bind))| NetworkPartyConfig {
+                name: None,
+                address,
+                bind: bind.map(|bind| bind.parse().unwrap()),
+                certificate: match id == own_id {
+                    true => PartyCertificate::Full(certificates[id].clone()),
+                    false => PartyCertificate::Fingerprint(other_fingerprint),
+                },
+            })
+            .collect();
+        NetworkConfig {
+            parties,
+            field: None,
+            dealer: None,
+            timeouts: NetworkTimeouts {
+                connection: Some(Duration::from_secs(2)),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Connect two parties concurrently.
+    async fn connect_two_parties(
+        configs: [NetworkConfig; 2],
+        keys: [PrivateKey; 2],
+    ) -> [io::Result<MultipartyTransport<u32, NetChannel<u32>>>; 2] {
+        let [config0, config1] = configs;
+        let [key0, key1] = keys;
+        let (party0, party1) = futures::join!(
+            connect_multiparty(&config0, key0, 0),
+            connect_multiparty(&config1, key1, 1),
+        );
+        [party0, party1]
+    }
+
+    /// Send a message from party 0 to party 1.
+    async fn send_message(
+        party0: MultipartyTransport<u32, NetChannel<u32>>,
+        party1: MultipartyTransport<u32, NetChannel<u32>>,
+    ) {
+        let mut to1 = party0.into_channels().remove(1).unwrap();
+        let mut from0 = party1.into_channels().remove(0).unwrap();
+        to1.send(7).await.unwrap();
+        assert_eq!(from0.next().await.unwrap().unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_fingerprints() {
+        let (cert0, key0) = test_certificate("party0");
+        let (cert1, key1) = test_certificate("party1");
+        let certificates = [cert0, cert1];
+        let fingerprints = [
+            certificate_fingerprint(&certificates[0]),
+            certificate_fingerprint(&certificates[1]),
+        ];
+        let address = format!("127.0.0.1:{}", free_port().await);
+        let parties = || [("127.0.0.1:1".to_owned(), None), (address.clone(), None)];
+
+        let configs = [
+            two_party_config(0, &certificates, fingerprints[1], parties()),
+            two_party_config(1, &certificates, fingerprints[0], parties()),
+        ];
+        let [party0, party1] = connect_two_parties(configs, [key0, key1]).await;
+        send_message(party0.unwrap(), party1.unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_mismatch() {
+        let (cert0, key0) = test_certificate("party0");
+        let (cert1, key1) = test_certificate("party1");
+        let certificates = [cert0, cert1];
+        let address = format!("127.0.0.1:{}", free_port().await);
+        let parties = || [("127.0.0.1:1".to_owned(), None), (address.clone(), None)];
+
+        // Party 0 pins its own certificate instead of the certificate of party 1.
+        let own_fingerprint = certificate_fingerprint(&certificates[0]);
+        let configs = [
+            two_party_config(0, &certificates, own_fingerprint, parties()),
+            two_party_config(1, &certificates, own_fingerprint, parties()),
+        ];
+        let [party0, party1] = connect_two_parties(configs, [key0, key1]).await;
+        let err0 = party0.err().unwrap();
+        let err1 = party1.err().unwrap();
+        assert_eq!(
+            TransportError::from_io_error(&err0),
+            Some(&TransportError::HandshakeFailed(1))
+        );
+        assert_eq!(
+            TransportError::from_io_error(&err1),
+            Some(&TransportError::HandshakeFailed(0))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_to_host_name_with_bind_address() {
+        let (cert0, key0) = test_certificate("party0");
+        let (cert1, key1) = test_certificate("party1");
+        let certificates = [cert0, cert1];
+        let fingerprints = [
+            certificate_fingerprint(&certificates[0]),
+            certificate_fingerprint(&certificates[1]),
+        ];
+        // Party 1 advertises a host name and listens on all interfaces.
+        let port = free_port().await;
+        let bind = format!("0.0.0.0:{}", port);
+        let parties = || {
+            [
+                ("localhost:1".to_owned(), None),
+                (format!("localhost:{}", port), Some(bind.as_str())),
+            ]
+        };
+
+        let configs = [
+            two_party_config(0, &certificates, fingerprints[1], parties()),
+            two_party_config(1, &certificates, fingerprints[0], parties()),
+        ];
+        assert_eq!(configs[1].parties[1].listen_address(), bind);
+        let [party0, party1] = connect_two_parties(configs, [key0, key1]).await;
+        send_message(party0.unwrap(), party1.unwrap()).await;
+    }
+}
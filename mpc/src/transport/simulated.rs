@@ -0,0 +1,290 @@
+use std::{
+    collections::HashMap,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    ready, Future, Sink, Stream, StreamExt,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Serialize;
+use tokio::time::{Instant, Sleep};
+
+use super::{multiparty::connect_all_parties, MultipartyTransport};
+
+/// Conditions of a simulated link between two parties, applied in both directions.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkConditions {
+    /// One-way delay of every message.
+    pub latency: Duration,
+    /// Upper bound of random delay added to latency of each message.
+    pub jitter: Duration,
+    /// Throughput in bytes per second, unlimited if None.
+    pub bandwidth: Option<u64>,
+}
+
+impl LinkConditions {
+    /// Link with given latency, no jitter and unlimited bandwidth.
+    pub fn with_latency(latency: Duration) -> Self {
+        Self {
+            latency,
+            ..Self::default()
+        }
+    }
+
+    /// Time needed to push message of given size through the link.
+    fn transmission_time(&self, size: u64) -> Duration {
+        match self.bandwidth {
+            Some(bandwidth) => Duration::from_secs_f64(size as f64 / bandwidth as f64),
+            None => Duration::ZERO,
+        }
+    }
+}
+
+/// Description of simulated network between parties.
+#[derive(Clone, Debug, Default)]
+pub struct SimulatedNetwork {
+    default_link: LinkConditions,
+    links: HashMap<(usize, usize), LinkConditions>,
+    seed: u64,
+}
+
+impl SimulatedNetwork {
+    /// Network where all links have given conditions.
+    pub fn new(default_link: LinkConditions) -> Self {
+        Self {
+            default_link,
+            ..Self::default()
+        }
+    }
+
+    /// Override conditions of link between parties a and b.
+    pub fn with_link(mut self, a: usize, b: usize, conditions: LinkConditions) -> Self {
+        self.links.insert((a.min(b), a.max(b)), conditions);
+        self
+    }
+
+    /// Set seed of random generator used for jitter.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Conditions of link between parties a and b.
+    pub fn link(&self, a: usize, b: usize) -> LinkConditions {
+        self.links
+            .get(&(a.min(b), a.max(b)))
+            .copied()
+            .unwrap_or(self.default_link)
+    }
+}
+
+/// Message in flight, with time it reaches the receiver.
+type Delivery<T> = (Instant, T);
+
+/// In-process channel delivering messages according to conditions of simulated link.
+/// Delays are driven by tokio time, so simulations can run with paused clock.
+pub struct SimulatedChannel<T> {
+    sender: UnboundedSender<Delivery<T>>,
+    receiver: UnboundedReceiver<Delivery<T>>,
+    conditions: LinkConditions,
+    rng: StdRng,
+    /// Time when all messages sent so far leave the sender.
+    link_free_at: Instant,
+    /// Arrival time of last sent message, links deliver messages in order.
+    last_arrival: Instant,
+    /// Received message waiting for its arrival time.
+    pending: Option<(Pin<Box<Sleep>>, T)>,
+}
+
+impl<T> SimulatedChannel<T> {
+    fn new(
+        sender: UnboundedSender<Delivery<T>>,
+        receiver: UnboundedReceiver<Delivery<T>>,
+        conditions: LinkConditions,
+        seed: u64,
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            sender,
+            receiver,
+            conditions,
+            rng: StdRng::seed_from_u64(seed),
+            link_free_at: now,
+            last_arrival: now,
+            pending: None,
+        }
+    }
+
+    /// Create connected pair of channels over link with given conditions.
+    pub fn pair(conditions: LinkConditions, seed: u64) -> (Self, Self) {
+        let (a_sender, b_receiver) = mpsc::unbounded();
+        let (b_sender, a_receiver) = mpsc::unbounded();
+        (
+            Self::new(a_sender, a_receiver, conditions, seed),
+            Self::new(b_sender, b_receiver, conditions, seed.wrapping_add(1)),
+        )
+    }
+
+    fn arrival_time(&mut self, size: u64) -> Instant {
+        let start = self.link_free_at.max(Instant::now());
+        self.link_free_at = start + self.conditions.transmission_time(size);
+        let jitter = match self.conditions.jitter.as_nanos() as u64 {
+            0 => Duration::ZERO,
+            max_jitter => Duration::from_nanos(self.rng.gen_range(0..=max_jitter)),
+        };
+        let arrival = self.link_free_at + self.conditions.latency + jitter;
+        self.last_arrival = self.last_arrival.max(arrival);
+        self.last_arrival
+    }
+}
+
+impl<T: Unpin> Stream for SimulatedChannel<T> {
+    type Item = Result<T, io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some((delay, _)) = this.pending.as_mut() {
+                ready!(delay.as_mut().poll(cx));
+                let (_, msg) = this.pending.take().unwrap();
+                return Poll::Ready(Some(Ok(msg)));
+            }
+            match ready!(this.receiver.poll_next_unpin(cx)) {
+                Some((arrival, msg)) => {
+                    this.pending = Some((Box::pin(tokio::time::sleep_until(arrival)), msg))
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl<T: Serialize + Unpin> Sink<T> for SimulatedChannel<T> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let size = bincode::serialized_size(&item)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let arrival = this.arrival_time(size);
+        this.sender
+            .unbounded_send((arrival, item))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().sender.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Create in-process channels between parties, which deliver messages as given simulated network would.
+pub fn simulated_multiparty_channels<T>(
+    num_parties: usize,
+    network: &SimulatedNetwork,
+) -> Vec<MultipartyTransport<T, SimulatedChannel<T>>>
+where
+    T: Clone + Serialize + Unpin,
+{
+    connect_all_parties(num_parties, |i, j| {
+        let seed = network.seed.wrapping_add(2 * (i * num_parties + j) as u64);
+        SimulatedChannel::pair(network.link(i, j), seed)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{simulated_multiparty_channels, LinkConditions, SimulatedNetwork};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_latency() {
+        let network =
+            SimulatedNetwork::new(LinkConditions::with_latency(Duration::from_millis(10)))
+                .with_link(
+                    2,
+                    0,
+                    LinkConditions::with_latency(Duration::from_millis(80)),
+                );
+        let mut channels = simulated_multiparty_channels::<u32>(3, &network);
+        let mut far = channels.pop().unwrap();
+        let mut near = channels.pop().unwrap();
+        let mut transport = channels.pop().unwrap();
+
+        let start = Instant::now();
+        near.send_to(0, 1).await.unwrap();
+        far.send_to(0, 2).await.unwrap();
+        assert_eq!(transport.receive_from(1).await, Ok(1));
+        assert_eq!(start.elapsed(), Duration::from_millis(10));
+        assert_eq!(transport.receive_from(2).await, Ok(2));
+        assert_eq!(start.elapsed(), Duration::from_millis(80));
+
+        let start = Instant::now();
+        far.send_to(0, 3).await.unwrap();
+        assert_eq!(transport.receive_from(2).await, Ok(3));
+        transport.send_to(2, 4).await.unwrap();
+        assert_eq!(far.receive_from(0).await, Ok(4));
+        assert_eq!(start.elapsed(), Duration::from_millis(160));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bandwidth() {
+        let conditions = LinkConditions {
+            latency: Duration::from_millis(5),
+            bandwidth: Some(1000),
+            ..LinkConditions::default()
+        };
+        let mut channels =
+            simulated_multiparty_channels::<Vec<u8>>(2, &SimulatedNetwork::new(conditions));
+        let mut receiver = channels.pop().unwrap();
+        let mut sender = channels.pop().unwrap();
+
+        // Bincode prefixes vectors with 8-byte length, so each message takes 100 bytes.
+        let start = Instant::now();
+        sender.send_to(1, vec![0; 92]).await.unwrap();
+        sender.send_to(1, vec![1; 92]).await.unwrap();
+        assert_eq!(receiver.receive_from(0).await, Ok(vec![0; 92]));
+        assert_eq!(start.elapsed(), Duration::from_millis(105));
+        assert_eq!(receiver.receive_from(0).await, Ok(vec![1; 92]));
+        assert_eq!(start.elapsed(), Duration::from_millis(205));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_jitter_keeps_order() {
+        let conditions = LinkConditions {
+            latency: Duration::from_millis(20),
+            jitter: Duration::from_millis(30),
+            bandwidth: None,
+        };
+        let network = SimulatedNetwork::new(conditions).with_seed(7);
+        let mut channels = simulated_multiparty_channels::<u32>(2, &network);
+        let mut receiver = channels.pop().unwrap();
+        let mut sender = channels.pop().unwrap();
+
+        let start = Instant::now();
+        for i in 0..20 {
+            sender.send_to(1, i).await.unwrap();
+        }
+        for i in 0..20 {
+            assert_eq!(receiver.receive_from(0).await, Ok(i));
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(20) && elapsed <= Duration::from_millis(50));
+    }
+}
@@ -76,13 +76,13 @@ impl<T: MpcShare, const N: usize> FlowNetwork<T, N> {
     where
         E: MpcEngine<Share = T>,
     {
-        let cost_bound = self.total_cost_bound(ctx).await;
+        let cost_bound = ctx.profile("cost bound", self.total_cost_bound(ctx)).await;
         let mut state = FlowState::new(ctx, self, cost_bound);
         state.normalize_source_and_sink(source, sink);
         for _ in 0..flow_limit {
             state.augment().await?;
         }
-        Ok(state.into_flow_matrix().await)
+        Ok(ctx.profile("flow matrix", state.into_flow_matrix()).await)
     }
 
     /// Get strict bound on cost of the most expensive path. Returns sum of costs on existing edges.
@@ -185,9 +185,19 @@ impl<'a, E: MpcEngine, const N: usize> FlowState<'a, E, N> {
 
     /// Improve flow by 1 along the cheapest augmenting path from source vertex 0 to sink vertex 1.
     async fn augment(&mut self) -> Result<(), FlowError> {
-        self.permute_randomly().await;
+        let ctx = self.ctx;
+        ctx.profile("permute", self.permute_randomly()).await;
         self.reset_vertices();
 
+        let processing_order = ctx.profile("dijkstra", self.dijkstra()).await?;
+        ctx.profile("invert path", self.invert_shortest_path(&processing_order))
+            .await;
+        self.update_potential();
+        Ok(())
+    }
+
+    /// Run Dijkstra algorithm from source vertex 0 and return processing order of vertices.
+    async fn dijkstra(&mut self) -> Result<Vec<usize>, FlowError> {
         let mut processing_order = vec![0];
         self.vertices[0].distance = IntShare::zero();
         self.vertices[0].processed = true;
@@ -201,9 +211,7 @@ impl<'a, E: MpcEngine, const N: usize> FlowState<'a, E, N> {
         }
 
         processing_order.push(1);
-        self.invert_shortest_path(&processing_order).await;
-        self.update_potential();
-        Ok(())
+        Ok(processing_order)
     }
 
     /// Permute randomly all vertices from 2 to n-1 (0 is source, 1 is sink). Original adjacency matrix is left alone.
@@ -3,22 +3,63 @@
 import argparse
 import json
 import os
+import subprocess
 
 parser = argparse.ArgumentParser(description='Generate SPDZ parameters for testing.')
 parser.add_argument('--dir', type=str, default='test-env', help='test-env directory')
 parser.add_argument('--beaver-triples', type=int, default=1000000, help='number of beaver triples to be generated')
+parser.add_argument('--square-pairs', type=int, default=100000, help='number of square pairs to be generated')
+parser.add_argument('--inverse-pairs', type=int, default=1000, help='number of inverse pairs to be generated')
 parser.add_argument('--random-bits', type=int, default=1000000, help='number of random bits to be generated')
+parser.add_argument('--bit-masks', type=str, nargs='*', default=['32:100000'], help='numbers of random bit masks of given widths to be generated (width:count)')
 parser.add_argument('--input-masks', type=int, default=100, help='number of input masks to be generated')
+parser.add_argument('--output-masks', type=int, default=100, help='number of output masks (for private openings) to be generated')
+parser.add_argument('--mac-keys', type=int, default=1, help='number of independent MAC keys (1-4)')
+parser.add_argument('--mascot', action='store_true', help='generate data jointly by all parties using MASCOT protocol instead of a trusted dealer')
+parser.add_argument('--streaming', action='store_true', help='write data in streaming format, which is read incrementally by matcher')
+parser.add_argument('--compressed', action='store_true', help='derive data of parties other than 0 from seeds (trusted dealer only)')
+parser.add_argument('--matching-dims', type=int, help='generate exactly what matcher needs for preference vectors of given length')
 args = parser.parse_args()
 
 with open(f'{args.dir}/common/config.json', 'r') as config_file:
     config = json.load(config_file)
     num_parties = len(config['parties'])
+    field = config.get('field', '127')
 
 if os.system('cargo build --release -p dealer') != 0:
     exit(1)
 
-os.system(
-    f'./target/release/dealer --parties {num_parties} --output "{args.dir}/node#/precomp.bin" ' +
-    f'--beaver-triples {args.beaver_triples} --random-bits {args.random_bits} --input-masks {args.input_masks}'
-)
+if args.matching_dims is None:
+    amounts = f'--beaver-triples {args.beaver_triples} --square-pairs {args.square_pairs} --inverse-pairs {args.inverse_pairs} '
+    amounts += f'--random-bits {args.random_bits} --input-masks {args.input_masks} --output-masks {args.output_masks}'
+    amounts += ''.join(f' --bit-masks {bit_masks}' for bit_masks in args.bit_masks)
+else:
+    amounts = f'--estimate-for-matching {args.matching_dims}'
+
+if args.streaming:
+    amounts += ' --streaming'
+
+if args.compressed:
+    if args.mascot:
+        print('Compressed data can be generated only by a trusted dealer')
+        exit(1)
+    amounts += ' --compressed'
+
+if args.mascot:
+    processes = [
+        subprocess.Popen(
+            f'./target/release/dealer generate --mac-keys {args.mac_keys} ' +
+            f'--output "{args.dir}/node{party_id}/precomp.bin" ' + amounts + ' ' +
+            f'mascot --config "{args.dir}/common/config.json" --id {party_id} ' +
+            f'--private-key "{args.dir}/node{party_id}/private.key"',
+            shell=True
+        )
+        for party_id in range(num_parties)
+    ]
+    if any([process.wait() != 0 for process in processes]):
+        exit(1)
+else:
+    os.system(
+        f'./target/release/dealer generate --parties {num_parties} --field {field} --mac-keys {args.mac_keys} ' +
+        f'--output "{args.dir}/node#/precomp.bin" ' + amounts
+    )
@@ -9,6 +9,8 @@ parser.add_argument('--dir', type=str, default='test-env', help='target director
 parser.add_argument('--parties', type=int, default=16, help='number of parties')
 parser.add_argument('--address', type=str, default='127.0.0.1', help='address on which all parties listen')
 parser.add_argument('--base-port', type=int, default=5000, help='port of the first party')
+parser.add_argument('--dealer-port', type=int, default=4999, help='port of the dealer service')
+parser.add_argument('--field', type=str, default='127', help='field used by protocol (31, 61, 127 or goldilocks)')
 args = parser.parse_args()
 
 if os.path.isdir(args.dir):
@@ -18,23 +20,34 @@ if os.path.isdir(args.dir):
 os.mkdir(args.dir)
 os.mkdir(f'{args.dir}/common')
 
-config = {'parties': []}
+config = {
+    'field': args.field,
+    'parties': [],
+    'dealer': {'name': 'dealer', 'address': f'{args.address}:{args.dealer_port}', 'certificate': 'dealer.pem'},
+}
 
-for i in range(args.parties):
-    config['parties'].append({
-        'address': f'{args.address}:{args.base_port + i}',
-        'certificate': f'node{i}.pem',
-    })
 
-    os.mkdir(f'{args.dir}/node{i}')
+def generate_certificate(name):
+    os.mkdir(f'{args.dir}/{name}')
 
     status = os.system(
-        f'openssl req -newkey rsa:2048 -nodes -keyout "{args.dir}/node{i}/private.key" -x509 -days 365 ' +
-        f'-out "{args.dir}/common/node{i}.pem" -subj "/" -config openssl.cnf -extensions v3_req'
+        f'openssl req -newkey rsa:2048 -nodes -keyout "{args.dir}/{name}/private.key" -x509 -days 365 ' +
+        f'-out "{args.dir}/common/{name}.pem" -subj "/" -config openssl.cnf -extensions v3_req'
     )
 
     if status != 0:
         exit(1)
 
+
+for i in range(args.parties):
+    config['parties'].append({
+        'name': f'node{i}',
+        'address': f'{args.address}:{args.base_port + i}',
+        'certificate': f'node{i}.pem',
+    })
+    generate_certificate(f'node{i}')
+
+generate_certificate('dealer')
+
 with open(f'{args.dir}/common/config.json', 'w') as config_file:
     json.dump(config, config_file, indent=4)
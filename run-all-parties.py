@@ -19,6 +19,7 @@ def redirect_to_stdout(party_id, stream):
 
 parser = argparse.ArgumentParser(description='Run all nodes using generated configuration.')
 parser.add_argument('--dir', type=str, default='test-env', help='test-env directory')
+parser.add_argument('--dealer-service', action='store_true', help='receive SPDZ parameters from running dealer service')
 args = parser.parse_args()
 
 config_path = f'{args.dir}/common/config.json'
@@ -31,15 +32,15 @@ if os.system('cargo build --release -p matcher') != 0:
     exit(1)
 
 for party_id in range(num_parties):
+    precomp = [] if args.dealer_service else ['--precomp', f'{args.dir}/node{party_id}/precomp.bin']
     process = subprocess.Popen(
         [
             './target/release/matcher',
             '--config', f'{config_path}',
             '--id', f'{party_id}',
             '--private-key', f'{args.dir}/node{party_id}/private.key',
-            '--precomp', f'{args.dir}/node{party_id}/precomp.bin',
             '--preferences', f'{party_id},{party_id+1},{party_id+5}'
-        ],
+        ] + precomp,
         stdout=subprocess.PIPE,
         stderr=subprocess.PIPE,
         bufsize=2048
@@ -7,8 +7,11 @@ parser = argparse.ArgumentParser(description='Run a single node using generated
 parser.add_argument('--dir', type=str, default='test-env', help='test-env directory')
 parser.add_argument('--id', type=int, required=True, help='party ID')
 parser.add_argument('--preferences', type=str, required=True, help='preference vector')
+parser.add_argument('--dealer-service', action='store_true', help='receive SPDZ parameters from running dealer service')
 args = parser.parse_args()
 
+precomp = '' if args.dealer_service else f'--precomp {args.dir}/node{args.id}/precomp.bin '
+
 if os.system('cargo build --release -p matcher') != 0:
     exit(1)
 
@@ -17,6 +20,6 @@ os.system(
     f'--config {args.dir}/common/config.json ' +
     f'--id {args.id} ' +
     f'--private-key {args.dir}/node{args.id}/private.key ' +
-    f'--precomp {args.dir}/node{args.id}/precomp.bin ' +
+    precomp +
     f'--preferences {args.preferences}'
 )